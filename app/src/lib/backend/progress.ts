@@ -0,0 +1,24 @@
+import { listen } from '$lib/backend/ipc';
+
+export type Stage = 'counting' | 'compressing' | 'writing' | 'receiving' | 'resolving';
+
+export type Progress =
+	| { kind: 'objects'; stage: Stage; current: number; total: number | null }
+	| { kind: 'bytes'; transferred: number }
+	| { kind: 'refUpdate'; refname: string; error: string | null };
+
+export type ProgressEvent = {
+	projectId: string;
+	remote: string;
+	direction: 'fetch' | 'push';
+	progress: Progress;
+};
+
+export function subscribeToProgress(
+	projectId: string,
+	callback: (progress: ProgressEvent) => void
+) {
+	return listen<ProgressEvent>('git_progress', (event) => {
+		if (event.payload.projectId === projectId) callback(event.payload);
+	});
+}
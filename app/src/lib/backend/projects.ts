@@ -13,6 +13,7 @@ export type KeyType =
 	| 'generated'
 	| 'gitCredentialsHelper'
 	| 'local'
+	| 'sshAgent'
 	| 'systemExecutable';
 export type LocalKey = {
 	local: { private_key_path: string };
@@ -20,6 +21,8 @@ export type LocalKey = {
 
 export type Key = Exclude<KeyType, 'local'> | LocalKey;
 
+export type RemoteBackend = 'git2' | 'gitCli' | 'gix';
+
 export class Project {
 	id!: string;
 	title!: string;
@@ -27,6 +30,7 @@ export class Project {
 	path!: string;
 	api?: CloudProject & { sync: boolean };
 	preferred_key!: Key;
+	remote_backend: RemoteBackend | undefined;
 	ok_with_force_push!: boolean;
 	omit_certificate_check: boolean | undefined;
 	use_diff_context: boolean | undefined;
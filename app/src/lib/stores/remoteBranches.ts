@@ -44,10 +44,21 @@ export class RemoteBranchService {
 	}
 }
 
-async function listRemoteBranches(projectId: string): Promise<RemoteBranch[]> {
+// Criteria the back end applies to remote branches, all of which have to match
+export type RemoteBranchFilter = {
+	remote?: string;
+	author?: string;
+	maxAgeDays?: number;
+	merged?: boolean;
+};
+
+async function listRemoteBranches(
+	projectId: string,
+	filter?: RemoteBranchFilter
+): Promise<RemoteBranch[]> {
 	const branches = plainToInstance(
 		RemoteBranch,
-		await invoke<any[]>('list_remote_branches', { projectId })
+		await invoke<any[]>('list_remote_branches', { projectId, filter })
 	);
 
 	return branches;
@@ -4,7 +4,7 @@ import * as toasts from '$lib/utils/toasts';
 import posthog from 'posthog-js';
 import type { RemoteBranchService } from '$lib/stores/remoteBranches';
 import type { BaseBranchService } from './baseBranch';
-import type { Branch, Hunk, LocalFile } from './types';
+import type { Branch, Hunk, LocalFile, SkippedFile } from './types';
 import type { VirtualBranchService } from './virtualBranch';
 
 export class BranchController {
@@ -185,6 +185,15 @@ export class BranchController {
 		}
 	}
 
+	/**
+	 * Files too large to diff have no hunks, so they only go into a branch that claims all of them.
+	 */
+	async assignSkippedFile(branch: Branch, file: SkippedFile) {
+		if (!file.newPath) return;
+		const ownership = `${file.newPath}:0-0`;
+		await this.updateBranchOwnership(branch.id, (ownership + '\n' + branch.ownership).trim());
+	}
+
 	async pushBranch(branchId: string, withForce: boolean): Promise<Branch | undefined> {
 		try {
 			await invoke<void>('push_virtual_branch', {
@@ -10,7 +10,11 @@ export type ChangeType =
 	/// Entry does not exist in new version
 	| 'deleted'
 	/// Entry content changed between old and new
-	| 'modified';
+	| 'modified'
+	/// Entry was moved from another path, and may have changed along the way
+	| 'renamed'
+	/// Entry was copied from another path, which still exists, and may have changed
+	| 'copied';
 
 export class Hunk {
 	id!: string;
@@ -29,6 +33,13 @@ export class Hunk {
 	new_lines!: number;
 }
 
+export class SubmoduleChange {
+	// Unset if the submodule was added.
+	oldId?: string;
+	// Unset if the submodule was removed.
+	newId?: string;
+}
+
 export class HunkLock {
 	branchId!: string;
 	commitId!: string;
@@ -51,6 +62,8 @@ export class LocalFile {
 	content!: string;
 	binary!: boolean;
 	large!: boolean;
+	// Set if the file is a submodule, whose change is the commit it points to.
+	submodule?: SubmoduleChange;
 
 	get filename(): string {
 		const parts = this.path.split('/');
@@ -94,6 +107,16 @@ export class VirtualBranches {
 	skippedFiles!: SkippedFile[];
 }
 
+export type PullRequest = {
+	number: number;
+	title: string;
+	url: string;
+	state: 'open' | 'closed' | 'merged';
+	draft: boolean;
+	review: 'pending' | 'approved' | 'changesRequested';
+	checks: 'none' | 'pending' | 'success' | 'failure';
+};
+
 export class Branch {
 	id!: string;
 	name!: string;
@@ -123,6 +146,10 @@ export class Branch {
 	updatedAt!: Date;
 	// Indicates that branch is default target for new changes
 	selectedForChanges!: boolean;
+	// The id of the branch this branch is stacked on, if any
+	parent?: string;
+	// The pull request opened for the upstream, as last seen on the forge
+	pullRequest?: PullRequest;
 
 	get localCommits() {
 		return this.commits.filter((c) => c.status == 'local');
@@ -284,6 +311,11 @@ export class RemoteBranch {
 	upstream?: string;
 	lastCommitTimestampMs?: number | undefined;
 	lastCommitAuthor?: string | undefined;
+	// Compared to the fetched target, only when listing remote branches
+	ahead!: number;
+	behind!: number;
+	isMerged!: boolean;
+	virtualBranchId?: string;
 
 	get displayName(): string {
 		return this.name.replace('refs/remotes/', '').replace('origin/', '').replace('refs/heads/', '');
@@ -324,12 +356,18 @@ export class RemoteBranchData {
 	}
 }
 
+export type ForgeKind = 'gitHub' | 'gitLab' | 'gitea' | 'bitbucket';
+
 export class BaseBranch {
 	branchName!: string;
 	remoteName!: string;
 	remoteUrl!: string;
+	// The forge hosting the remote, detected from its host or configured for self-hosted ones
+	forge?: ForgeKind;
+	webUrl?: string;
 	pushRemoteName!: string;
 	pushRemoteUrl!: string;
+	pushWebUrl?: string;
 	baseSha!: string;
 	currentSha!: string;
 	behind!: number;
@@ -348,11 +386,11 @@ export class BaseBranch {
 	}
 
 	get pushRepoBaseUrl(): string {
-		return this.cleanUrl(this.pushRemoteUrl);
+		return this.pushWebUrl ?? this.cleanUrl(this.pushRemoteUrl);
 	}
 
 	get repoBaseUrl(): string {
-		return this.cleanUrl(this.remoteUrl);
+		return this.webUrl ?? this.cleanUrl(this.remoteUrl);
 	}
 
 	// turn a git remote url into a web url (github, gitlab, bitbucket, etc)
@@ -396,7 +434,10 @@ export class BaseBranch {
 		if (this.isBitBucket) {
 			return `${this.repoBaseUrl}/branch/${branchName}?dest=${baseBranchName}`;
 		}
-		// The following branch path is good for at least Gitlab and Github:
+		if (this.isGitlab) {
+			return `${this.repoBaseUrl}/-/compare/${baseBranchName}...${branchName}`;
+		}
+		// The following branch path is good for at least Gitea, Forgejo and Github:
 		return `${this.repoBaseUrl}/compare/${baseBranchName}...${branchName}`;
 	}
 
@@ -413,14 +454,14 @@ export class BaseBranch {
 	}
 
 	private get isGitHub(): boolean {
-		return this.repoBaseUrl.includes('github.com');
+		return this.forge === 'gitHub';
 	}
 
 	private get isBitBucket(): boolean {
-		return this.repoBaseUrl.includes('bitbucket.org');
+		return this.forge === 'bitbucket';
 	}
 
 	private get isGitlab(): boolean {
-		return this.repoBaseUrl.includes('gitlab.com');
+		return this.forge === 'gitLab';
 	}
 }
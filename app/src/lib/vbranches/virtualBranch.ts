@@ -1,4 +1,4 @@
-import { Branch, VirtualBranches } from './types';
+import { Branch, VirtualBranches, type SkippedFile } from './types';
 import { invoke, listen } from '$lib/backend/ipc';
 import { observableToStore } from '$lib/rxjs/store';
 import * as toasts from '$lib/utils/toasts';
@@ -26,6 +26,8 @@ export class VirtualBranchService {
 	stashedBranches$: Observable<Branch[] | undefined>;
 	activeBranches$: Observable<Branch[] | undefined>;
 	branchesError = writable<any>();
+	/** Changed files too large to diff, which no branch claimed yet. */
+	skippedFiles = writable<SkippedFile[]>([]);
 	private reload$ = new BehaviorSubject<void>(undefined);
 	private fresh$ = new Subject<void>();
 
@@ -39,14 +41,16 @@ export class VirtualBranchService {
 				gbBranchActive
 					? concat(
 							from(listVirtualBranches({ projectId })),
-							new Observable<Branch[]>((subscriber) => {
-								return subscribeToVirtualBranches(projectId, (branches) =>
-									subscriber.next(branches)
+							new Observable<VirtualBranches>((subscriber) => {
+								return subscribeToVirtualBranches(projectId, (virtualBranches) =>
+									subscriber.next(virtualBranches)
 								);
 							})
 						)
-					: of([])
+					: of(plainToInstance(VirtualBranches, { branches: [], skippedFiles: [] }))
 			),
+			tap((virtualBranches) => this.skippedFiles.set(virtualBranches.skippedFiles)),
+			map((virtualBranches) => virtualBranches.branches),
 			tap((branches) => {
 				branches.forEach((branch) => {
 					branch.files.sort((a) => (a.conflicted ? -1 : 0));
@@ -100,13 +104,15 @@ export class VirtualBranchService {
 	}
 }
 
-function subscribeToVirtualBranches(projectId: string, callback: (branches: Branch[]) => void) {
+function subscribeToVirtualBranches(
+	projectId: string,
+	callback: (virtualBranches: VirtualBranches) => void
+) {
 	return listen<any>(`project://${projectId}/virtual-branches`, (event) =>
-		callback(plainToInstance(VirtualBranches, event.payload).branches)
+		callback(plainToInstance(VirtualBranches, event.payload))
 	);
 }
 
-export async function listVirtualBranches(params: { projectId: string }): Promise<Branch[]> {
-	return plainToInstance(VirtualBranches, await invoke<any>('list_virtual_branches', params))
-		.branches;
+export async function listVirtualBranches(params: { projectId: string }): Promise<VirtualBranches> {
+	return plainToInstance(VirtualBranches, await invoke<any>('list_virtual_branches', params));
 }
@@ -0,0 +1,132 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use gitbutler_core::{
+    git, keys,
+    project_repository::remote_backend::{Direction, Progress, ProgressEvent, Stage},
+    projects, users, virtual_branches,
+};
+use serde::Serialize;
+
+pub mod snapshot;
+pub mod vbranch;
+
+/// How results are presented to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Human,
+    Json,
+}
+
+impl Output {
+    pub fn from_flag(json: bool) -> Self {
+        if json {
+            Output::Json
+        } else {
+            Output::Human
+        }
+    }
+
+    /// Print `value` as JSON, or call `human` to print it for people to read.
+    pub fn print<T: Serialize>(self, value: &T, human: impl FnOnce(&T)) -> Result<()> {
+        match self {
+            Output::Json => println!("{}", serde_json::to_string_pretty(value)?),
+            Output::Human => human(value),
+        }
+        Ok(())
+    }
+}
+
+/// The state needed to drive the virtual branch controller without the app running.
+///
+/// Project metadata (the project list, keys and user) is kept in its own data directory,
+/// and the project is registered there the first time the CLI is used on a repository.
+pub struct App {
+    pub project: projects::Project,
+    pub controller: virtual_branches::Controller,
+}
+
+impl App {
+    pub fn open(repo_dir: &str, data_dir: Option<&String>) -> Result<Self> {
+        let repo_dir = std::fs::canonicalize(repo_dir)
+            .with_context(|| format!("failed to find repository at {repo_dir}"))?;
+        let data_dir = data_dir.map_or_else(
+            || project_from_path(&repo_dir).gb_dir().join("cli"),
+            PathBuf::from,
+        );
+
+        let users = users::Controller::from_path(&data_dir);
+        let keys = keys::Controller::from_path(&data_dir);
+        let projects = projects::Controller::from_path(&data_dir);
+        let helper = git::credentials::Helper::new(
+            keys.clone(),
+            users.clone(),
+            std::env::var_os("HOME").map(PathBuf::from),
+        );
+
+        let project = match projects
+            .list()?
+            .into_iter()
+            .find(|project| project.path == repo_dir)
+        {
+            Some(project) => project,
+            None => projects.add(&repo_dir).context("failed to add project")?,
+        };
+
+        let controller = virtual_branches::Controller::new(projects, users, keys, helper)
+            .with_progress(Arc::new(print_progress));
+        Ok(Self {
+            project,
+            controller,
+        })
+    }
+}
+
+pub fn project_from_path(repo_dir: impl AsRef<Path>) -> projects::Project {
+    projects::Project {
+        path: repo_dir.as_ref().to_path_buf(),
+        enable_snapshots: Some(true),
+        ..Default::default()
+    }
+}
+
+/// Show how far a fetch or push got on stderr, so it doesn't mix with the results on stdout.
+fn print_progress(event: ProgressEvent) {
+    let direction = match event.direction {
+        Direction::Fetch => "fetch",
+        Direction::Push => "push",
+    };
+    match event.progress {
+        Progress::Objects {
+            stage,
+            current,
+            total,
+        } => {
+            let stage = match stage {
+                Stage::Counting => "counting",
+                Stage::Compressing => "compressing",
+                Stage::Writing => "writing",
+                Stage::Receiving => "receiving",
+                Stage::Resolving => "resolving",
+            };
+            match total {
+                Some(total) => eprintln!("{direction} {}: {stage} {current}/{total}", event.remote),
+                None => eprintln!("{direction} {}: {stage} {current}", event.remote),
+            }
+        }
+        Progress::Bytes { transferred } => {
+            eprintln!("{direction} {}: {transferred} bytes", event.remote);
+        }
+        Progress::RefUpdate {
+            refname,
+            error: None,
+        } => eprintln!("{direction} {}: updated {refname}", event.remote),
+        Progress::RefUpdate {
+            refname,
+            error: Some(error),
+        } => eprintln!("{direction} {}: rejected {refname} ({error})", event.remote),
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use gitbutler_core::{git::diff::FileDiff, snapshots::snapshot::Oplog};
+
+use super::{project_from_path, Output};
+
+pub fn list(repo_dir: &str, output: Output) -> Result<()> {
+    let project = project_from_path(repo_dir);
+    let snapshots = project.list_snapshots(100, None)?;
+    output.print(&snapshots, |snapshots| {
+        for snapshot in snapshots {
+            let ts = chrono::DateTime::from_timestamp(snapshot.created_at / 1000, 0);
+            let details = &snapshot.details;
+            if let (Some(ts), Some(details)) = (ts, details) {
+                println!("{} {} {}", ts, snapshot.id, details.operation);
+            }
+        }
+    })
+}
+
+pub fn restore(repo_dir: &str, snapshot_id: &str, paths: &[PathBuf], output: Output) -> Result<()> {
+    let project = project_from_path(repo_dir);
+    let snapshot = if paths.is_empty() {
+        project.restore_snapshot(snapshot_id.to_owned())?
+    } else {
+        project.restore_snapshot_paths(snapshot_id.to_owned(), paths)?
+    };
+    output.print(&snapshot, |snapshot| {
+        if let Some(snapshot) = snapshot {
+            println!("the previous state is kept in snapshot {snapshot}");
+        }
+    })
+}
+
+pub fn diff(repo_dir: &str, from: &str, to: &str, output: Output) -> Result<()> {
+    let project = project_from_path(repo_dir);
+    let diff = project.diff_snapshots(from.to_owned(), to.to_owned())?;
+    output.print(&diff, |diff| {
+        let mut paths = diff.files.keys().collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            print_file_diff(&path.display().to_string(), &diff.files[path]);
+        }
+        if let Some(virtual_branches) = &diff.virtual_branches {
+            print_file_diff("virtual_branches.toml", virtual_branches);
+        }
+    })
+}
+
+pub fn compact(repo_dir: &str, output: Output) -> Result<()> {
+    let project = project_from_path(repo_dir);
+    let dropped = project.compact_snapshots()?;
+    output.print(&dropped, |dropped| println!("dropped {dropped} snapshots"))
+}
+
+pub fn undo(repo_dir: &str, output: Output) -> Result<()> {
+    let project = project_from_path(repo_dir);
+    let snapshot = project.undo()?;
+    output.print(&snapshot, |snapshot| match snapshot {
+        Some(snapshot) => println!("{snapshot}"),
+        None => println!("nothing to undo"),
+    })
+}
+
+pub fn redo(repo_dir: &str, output: Output) -> Result<()> {
+    let project = project_from_path(repo_dir);
+    let snapshot = project.redo()?;
+    output.print(&snapshot, |snapshot| match snapshot {
+        Some(snapshot) => println!("{snapshot}"),
+        None => println!("nothing to redo"),
+    })
+}
+
+fn print_file_diff(name: &str, file: &FileDiff) {
+    println!("--- {name}");
+    if file.binary || file.skipped {
+        println!("binary or large file changed");
+    }
+    for hunk in file.hunks.iter().filter(|hunk| !hunk.binary) {
+        print!("{}", hunk.diff_lines);
+    }
+}
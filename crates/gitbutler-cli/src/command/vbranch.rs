@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Context, Result};
+use gitbutler_core::{
+    git,
+    virtual_branches::{
+        branch::{BranchCreateRequest, BranchId, BranchOwnershipClaims, BranchUpdateRequest},
+        rebase::RebaseOutcome,
+        PushOptions, VirtualBranches,
+    },
+};
+use serde::Serialize;
+
+use super::{App, Output};
+
+/// What is printed for operations that affect a single branch.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BranchResult {
+    branch_id: BranchId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_id: Option<git::Oid>,
+    /// How rewriting the history of the branch ended, for operations that do.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rebase: Option<RebaseOutcome>,
+}
+
+impl BranchResult {
+    fn branch(branch_id: BranchId) -> Self {
+        Self {
+            branch_id,
+            commit_id: None,
+            rebase: None,
+        }
+    }
+
+    fn rebased(branch_id: BranchId, outcome: RebaseOutcome) -> Self {
+        Self {
+            rebase: Some(outcome),
+            ..Self::branch(branch_id)
+        }
+    }
+}
+
+pub async fn list(app: &App, pull_requests: bool, output: Output) -> Result<()> {
+    if pull_requests {
+        let errors = app
+            .controller
+            .refresh_pull_requests(&app.project.id)
+            .await?;
+        for (branch_id, error) in errors {
+            eprintln!("failed to refresh the pull request of {branch_id}: {error:#}");
+        }
+    }
+    let (mut branches, skipped_files) = app
+        .controller
+        .list_virtual_branches(&app.project.id)
+        .await?;
+    branches.sort_by_key(|branch| branch.order);
+    let branches = VirtualBranches {
+        branches,
+        skipped_files,
+    };
+    output.print(&branches, |branches| {
+        for branch in &branches.branches {
+            println!(
+                "{} {}{} ({} files, {} commits){}{}",
+                branch.id,
+                branch.name,
+                if branch.active { "" } else { " [unapplied]" },
+                branch.files.len(),
+                branch.commits.len(),
+                if branch.selected_for_changes {
+                    " *"
+                } else {
+                    ""
+                },
+                branch
+                    .pull_request
+                    .as_ref()
+                    .map(|pr| format!(
+                        " #{} {:?}, review {:?}, checks {:?}",
+                        pr.number, pr.state, pr.review, pr.checks
+                    ))
+                    .unwrap_or_default(),
+            );
+        }
+    })
+}
+
+pub async fn create(
+    app: &App,
+    name: Option<&String>,
+    parent: Option<&String>,
+    output: Output,
+) -> Result<()> {
+    let parent = parent
+        .map(|parent| resolve_branch(app, parent))
+        .transpose()?;
+    let branch_id = app
+        .controller
+        .create_virtual_branch(
+            &app.project.id,
+            &BranchCreateRequest {
+                name: name.cloned(),
+                parent,
+                ..Default::default()
+            },
+        )
+        .await?;
+    print_branch(output, BranchResult::branch(branch_id))
+}
+
+pub async fn apply(app: &App, branch: &str, output: Output) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    app.controller
+        .apply_virtual_branch(&app.project.id, &branch_id)
+        .await?;
+    print_branch(output, BranchResult::branch(branch_id))
+}
+
+pub async fn unapply(app: &App, branch: &str, output: Output) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    app.controller
+        .unapply_virtual_branch(&app.project.id, &branch_id)
+        .await?;
+    print_branch(output, BranchResult::branch(branch_id))
+}
+
+pub async fn delete(app: &App, branch: &str, output: Output) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    app.controller
+        .delete_virtual_branch(&app.project.id, &branch_id)
+        .await?;
+    print_branch(output, BranchResult::branch(branch_id))
+}
+
+pub async fn commit(
+    app: &App,
+    branch: &str,
+    message: &str,
+    run_hooks: bool,
+    output: Output,
+) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    let commit_id = app
+        .controller
+        .create_commit(&app.project.id, &branch_id, message, None, run_hooks)
+        .await?;
+    print_branch(
+        output,
+        BranchResult {
+            commit_id: Some(commit_id),
+            ..BranchResult::branch(branch_id)
+        },
+    )
+}
+
+pub async fn push(
+    app: &App,
+    branch: &str,
+    with_force: bool,
+    dry_run: bool,
+    output: Output,
+) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    let options = PushOptions {
+        with_force,
+        dry_run,
+    };
+    let report = app
+        .controller
+        .push_virtual_branch_with_options(&app.project.id, &branch_id, &options, None)
+        .await?;
+    if !dry_run {
+        return print_branch(output, BranchResult::branch(branch_id));
+    }
+    output.print(&report, |report| {
+        for hook_output in &report.hook_output {
+            print!("{hook_output}");
+        }
+        for pushed in &report.refs {
+            match pushed.old_head {
+                Some(old_head) => println!(
+                    "{} {} {}..{}",
+                    pushed.branch_id, pushed.remote_ref, old_head, pushed.new_head
+                ),
+                None => println!(
+                    "{} {} (new) {}",
+                    pushed.branch_id, pushed.remote_ref, pushed.new_head
+                ),
+            }
+        }
+    })
+}
+
+pub async fn fetch(app: &App, output: Output) -> Result<()> {
+    let base_branch = app
+        .controller
+        .fetch_from_target(&app.project.id, None)
+        .await?;
+    output.print(&base_branch, |base_branch| {
+        println!(
+            "{}/{} {} ({} behind)",
+            base_branch.remote_name,
+            base_branch.branch_name,
+            base_branch.current_sha,
+            base_branch.behind
+        );
+    })
+}
+
+pub async fn update_base(app: &App, output: Output) -> Result<()> {
+    app.controller.update_base_branch(&app.project.id).await?;
+    let base_branch = app.controller.get_base_branch_data(&app.project.id).await?;
+    output.print(&base_branch, |base_branch| {
+        if let Some(base_branch) = base_branch {
+            println!(
+                "{}/{} {}",
+                base_branch.remote_name, base_branch.branch_name, base_branch.base_sha
+            );
+        }
+    })
+}
+
+pub async fn move_hunk(app: &App, ownership: &str, to: &str, output: Output) -> Result<()> {
+    let branch_id = resolve_branch(app, to)?;
+    let claims: BranchOwnershipClaims = ownership
+        .parse()
+        .with_context(|| format!("invalid ownership: {ownership}"))?;
+    // the update replaces all claims of the branch, so keep what it already owns
+    let mut ownership = app
+        .project
+        .virtual_branches()
+        .get_branch(&branch_id)
+        .with_context(|| format!("failed to read branch {branch_id}"))?
+        .ownership;
+    for claim in claims.claims {
+        ownership.put(claim);
+    }
+    app.controller
+        .update_virtual_branch(
+            &app.project.id,
+            BranchUpdateRequest {
+                id: branch_id,
+                ownership: Some(ownership),
+                ..Default::default()
+            },
+        )
+        .await?;
+    print_branch(output, BranchResult::branch(branch_id))
+}
+
+pub async fn squash(app: &App, branch: &str, commit: &str, output: Output) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    let commit_oid = parse_commit(commit)?;
+    let outcome = app
+        .controller
+        .squash(&app.project.id, &branch_id, commit_oid)
+        .await?;
+    print_branch(output, BranchResult::rebased(branch_id, outcome))
+}
+
+pub async fn reorder(
+    app: &App,
+    branch: &str,
+    commit: &str,
+    offset: i32,
+    output: Output,
+) -> Result<()> {
+    let branch_id = resolve_branch(app, branch)?;
+    let commit_oid = parse_commit(commit)?;
+    let outcome = app
+        .controller
+        .reorder_commit(&app.project.id, &branch_id, commit_oid, offset)
+        .await?;
+    print_branch(output, BranchResult::rebased(branch_id, outcome))
+}
+
+fn print_branch(output: Output, result: BranchResult) -> Result<()> {
+    output.print(&result, |result| {
+        match result.commit_id {
+            Some(commit_id) => println!("{} {}", result.branch_id, commit_id),
+            None => println!("{}", result.branch_id),
+        }
+        if result.rebase == Some(RebaseOutcome::Stopped) {
+            eprintln!("the rebase stopped on a conflict, resolve it in the workspace to continue");
+        }
+    })
+}
+
+/// Find a branch by its id, or failing that, by its name.
+fn resolve_branch(app: &App, branch: &str) -> Result<BranchId> {
+    if let Ok(id) = branch.parse::<BranchId>() {
+        return Ok(id);
+    }
+    app.project
+        .virtual_branches()
+        .list_branches()?
+        .into_iter()
+        .find(|b| b.name == branch)
+        .map(|b| b.id)
+        .ok_or_else(|| anyhow!("no virtual branch named '{branch}'"))
+}
+
+fn parse_commit(commit: &str) -> Result<git::Oid> {
+    commit
+        .parse()
+        .with_context(|| format!("invalid commit id: {commit}"))
+}
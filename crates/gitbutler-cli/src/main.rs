@@ -1,33 +1,128 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
-use gitbutler_core::{projects::Project, snapshots::snapshot::Oplog};
 
-use clap::{arg, Command};
+use clap::{arg, Arg, ArgAction, ArgMatches, Command};
 #[cfg(not(windows))]
 use pager::Pager;
 
+mod command;
+
+use command::{snapshot, vbranch, Output};
+
 fn cli() -> Command {
     Command::new("gitbutler-cli")
         .about("A CLI tool for GitButler")
         .arg(arg!(-C <path> "Run as if gitbutler-cli was started in <path> instead of the current working directory."))
+        .arg(arg!(--"data-dir" <DIR> "The directory to keep project metadata in. Defaults to `.git/gitbutler/cli` of the repository.").global(true))
+        .arg(arg!(--json "Print the result as JSON.").global(true))
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(true)
         .subcommand(
             Command::new("snapshot")
-                .about("List and restore snapshots.")
+                .about("List, compare and restore snapshots.")
                 .subcommand(Command::new("restore")
                 .about("Restores the state of the working direcory as well as virtual branches to a given snapshot.")
-                .arg(arg!(<SNAPSHOT_ID> "The snapshot to restore"))),
+                .arg(arg!(<SNAPSHOT_ID> "The snapshot to restore"))
+                .arg(arg!(-p --path <PATH> "Only restore this file or directory, leaving virtual branches untouched. Can be given multiple times").action(ArgAction::Append)))
+                .subcommand(Command::new("diff")
+                .about("Shows the changes between two snapshots.")
+                .arg(arg!(<FROM> "The snapshot to compare from"))
+                .arg(arg!(<TO> "The snapshot to compare to")))
+                .subcommand(Command::new("compact")
+                .about("Drops the snapshots that the project's retention policy doesn't keep.")),
+        )
+        .subcommand(Command::new("undo").about("Undoes the last operation."))
+        .subcommand(Command::new("redo").about("Redoes the last undone operation."))
+        .subcommand(
+            Command::new("branch")
+                .about("List and manage virtual branches.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("Lists all virtual branches.")
+                        .arg(arg!(-p --"pull-requests" "Look up the pull requests of pushed branches on their forge")),
+                )
+                .subcommand(
+                    Command::new("create")
+                        .about("Creates a new virtual branch.")
+                        .arg(arg!([NAME] "The name of the new branch"))
+                        .arg(arg!(-p --parent <BRANCH> "The id or name of the branch to stack the new branch on")),
+                )
+                .subcommand(
+                    Command::new("apply")
+                        .about("Applies a virtual branch to the workspace.")
+                        .arg(arg!(<BRANCH> "The id or name of the branch")),
+                )
+                .subcommand(
+                    Command::new("unapply")
+                        .about("Removes a virtual branch from the workspace.")
+                        .arg(arg!(<BRANCH> "The id or name of the branch")),
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .about("Deletes a virtual branch.")
+                        .arg(arg!(<BRANCH> "The id or name of the branch")),
+                ),
+        )
+        .subcommand(
+            Command::new("commit")
+                .about("Commits the changes owned by a virtual branch.")
+                .arg(arg!(-b --branch <BRANCH> "The id or name of the branch to commit to").required(true))
+                .arg(arg!(-m --message <MESSAGE> "The commit message").required(true))
+                .arg(arg!(--"no-verify" "Do not run the pre-commit and commit-msg hooks")),
+        )
+        .subcommand(
+            Command::new("push")
+                .about("Pushes a virtual branch to its upstream.")
+                .arg(arg!(<BRANCH> "The id or name of the branch"))
+                .arg(arg!(-f --force "Allow the push to overwrite the upstream branch"))
+                .arg(arg!(--"dry-run" "Run the pre-push hook and show what would be pushed, without pushing")),
+        )
+        .subcommand(Command::new("fetch").about("Fetches the remotes of the project."))
+        .subcommand(
+            Command::new("update-base")
+                .about("Integrates the latest changes of the base branch into all applied virtual branches."),
+        )
+        .subcommand(
+            Command::new("move-hunk")
+                .about("Moves hunks to another virtual branch.")
+                .arg(arg!(<OWNERSHIP> "The hunks to move, as in `path/to/file:1-5,10-12`"))
+                .arg(arg!(-t --to <BRANCH> "The id or name of the branch to move the hunks to").required(true)),
+        )
+        .subcommand(
+            Command::new("squash")
+                .about("Squashes a commit into its parent.")
+                .arg(arg!(-b --branch <BRANCH> "The id or name of the branch owning the commit").required(true))
+                .arg(arg!(<COMMIT> "The commit to squash")),
+        )
+        .subcommand(
+            Command::new("reorder")
+                .about("Moves a commit up or down within its virtual branch.")
+                .arg(arg!(-b --branch <BRANCH> "The id or name of the branch owning the commit").required(true))
+                .arg(arg!(<COMMIT> "The commit to move"))
+                .arg(
+                    Arg::new("OFFSET")
+                        .help("How many positions to move the commit by, negative values move it towards the branch head")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .action(ArgAction::Set),
+                ),
         )
 }
 
-fn main() -> Result<()> {
+#[tokio::main]
+async fn main() -> Result<()> {
     #[cfg(not(windows))]
     Pager::new().setup();
     let matches = cli().get_matches();
 
     let cwd = std::env::current_dir()?.to_string_lossy().to_string();
     let repo_dir = matches.get_one::<String>("path").unwrap_or(&cwd);
+    let data_dir = matches.get_one::<String>("data-dir");
+    let output = Output::from_flag(matches.get_flag("json"));
 
     match matches.subcommand() {
         Some(("snapshot", sub_matches)) => match sub_matches.subcommand() {
@@ -35,41 +130,130 @@ fn main() -> Result<()> {
                 let snapshot_id = sub_matches
                     .get_one::<String>("SNAPSHOT_ID")
                     .expect("required");
-                restore_snapshot(repo_dir, snapshot_id)?;
+                let paths = sub_matches
+                    .get_many::<String>("path")
+                    .map(|paths| paths.map(PathBuf::from).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                snapshot::restore(repo_dir, snapshot_id, &paths, output)?;
+            }
+            Some(("compact", _)) => {
+                snapshot::compact(repo_dir, output)?;
+            }
+            Some(("diff", sub_matches)) => {
+                snapshot::diff(
+                    repo_dir,
+                    required(sub_matches, "FROM"),
+                    required(sub_matches, "TO"),
+                    output,
+                )?;
             }
             _ => {
-                list_snapshots(repo_dir)?;
+                snapshot::list(repo_dir, output)?;
             }
         },
-        _ => unreachable!(),
+        Some(("undo", _)) => snapshot::undo(repo_dir, output)?,
+        Some(("redo", _)) => snapshot::redo(repo_dir, output)?,
+        Some((
+            name @ ("branch" | "commit" | "push" | "fetch" | "update-base" | "move-hunk" | "squash"
+            | "reorder"),
+            sub_matches,
+        )) => {
+            let app = command::App::open(repo_dir, data_dir)?;
+            run_vbranch_command(&app, name, sub_matches, output).await?;
+        }
+        Some((name, _)) => anyhow::bail!("unknown command: {name}"),
+        None => unreachable!(),
     }
 
     Ok(())
 }
 
-fn list_snapshots(repo_dir: &str) -> Result<()> {
-    let project = project_from_path(repo_dir);
-    let snapshots = project.list_snapshots(100, None)?;
-    for snapshot in snapshots {
-        let ts = chrono::DateTime::from_timestamp(snapshot.created_at / 1000, 0);
-        let details = snapshot.details;
-        if let (Some(ts), Some(details)) = (ts, details) {
-            println!("{} {} {}", ts, snapshot.id, details.operation);
+async fn run_vbranch_command(
+    app: &command::App,
+    name: &str,
+    matches: &ArgMatches,
+    output: Output,
+) -> Result<()> {
+    match name {
+        "branch" => match matches.subcommand() {
+            Some(("list", sub_matches)) => {
+                vbranch::list(app, sub_matches.get_flag("pull-requests"), output).await
+            }
+            Some(("create", sub_matches)) => {
+                vbranch::create(
+                    app,
+                    sub_matches.get_one::<String>("NAME"),
+                    sub_matches.get_one::<String>("parent"),
+                    output,
+                )
+                .await
+            }
+            Some(("apply", sub_matches)) => {
+                vbranch::apply(app, required(sub_matches, "BRANCH"), output).await
+            }
+            Some(("unapply", sub_matches)) => {
+                vbranch::unapply(app, required(sub_matches, "BRANCH"), output).await
+            }
+            Some(("delete", sub_matches)) => {
+                vbranch::delete(app, required(sub_matches, "BRANCH"), output).await
+            }
+            _ => unreachable!(),
+        },
+        "commit" => {
+            vbranch::commit(
+                app,
+                required(matches, "branch"),
+                required(matches, "message"),
+                !matches.get_flag("no-verify"),
+                output,
+            )
+            .await
+        }
+        "push" => {
+            vbranch::push(
+                app,
+                required(matches, "BRANCH"),
+                matches.get_flag("force"),
+                matches.get_flag("dry-run"),
+                output,
+            )
+            .await
         }
+        "fetch" => vbranch::fetch(app, output).await,
+        "update-base" => vbranch::update_base(app, output).await,
+        "move-hunk" => {
+            vbranch::move_hunk(
+                app,
+                required(matches, "OWNERSHIP"),
+                required(matches, "to"),
+                output,
+            )
+            .await
+        }
+        "squash" => {
+            vbranch::squash(
+                app,
+                required(matches, "branch"),
+                required(matches, "COMMIT"),
+                output,
+            )
+            .await
+        }
+        "reorder" => {
+            let offset = *matches.get_one::<i32>("OFFSET").expect("required");
+            vbranch::reorder(
+                app,
+                required(matches, "branch"),
+                required(matches, "COMMIT"),
+                offset,
+                output,
+            )
+            .await
+        }
+        _ => unreachable!("not a virtual branch command: {name}"),
     }
-    Ok(())
 }
 
-fn restore_snapshot(repo_dir: &str, snapshot_id: &str) -> Result<()> {
-    let project = project_from_path(repo_dir);
-    project.restore_snapshot(snapshot_id.to_owned())?;
-    Ok(())
-}
-
-fn project_from_path(repo_dir: &str) -> Project {
-    Project {
-        path: std::path::PathBuf::from(repo_dir),
-        enable_snapshots: Some(true),
-        ..Default::default()
-    }
+fn required<'a>(matches: &'a ArgMatches, id: &str) -> &'a str {
+    matches.get_one::<String>(id).expect("required")
 }
@@ -0,0 +1,143 @@
+use std::{fs, path::Path, process::Command};
+
+use gitbutler_core::{git, keys, projects, users, virtual_branches};
+use gitbutler_testsupport::{paths, TestProject};
+use serde_json::Value;
+
+/// Run the CLI on `repository` with `args`, keeping its metadata in `data_dir`, and return what it
+/// printed as JSON.
+fn cli(repository: &TestProject, data_dir: &Path, args: &[&str]) -> anyhow::Result<Value> {
+    let output = Command::new(env!("CARGO_BIN_EXE_gitbutler-cli"))
+        .arg("-C")
+        .arg(repository.path())
+        .arg("--data-dir")
+        .arg(data_dir)
+        .arg("--json")
+        .args(args)
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Register `repository` in `data_dir` the way the CLI does, with `origin/master` as base branch.
+async fn set_base_branch(repository: &TestProject, data_dir: &Path) {
+    let projects = projects::Controller::from_path(data_dir);
+    let project = projects
+        .add(repository.path().canonicalize().unwrap())
+        .unwrap();
+    virtual_branches::Controller::new(
+        projects,
+        users::Controller::from_path(data_dir),
+        keys::Controller::from_path(data_dir),
+        git::credentials::Helper::from_path(data_dir),
+    )
+    .set_base_branch(&project.id, &"refs/remotes/origin/master".parse().unwrap())
+    .await
+    .unwrap();
+}
+
+fn branch<'a>(branches: &'a Value, id: &Value) -> &'a Value {
+    branches["branches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|branch| &branch["id"] == id)
+        .unwrap()
+}
+
+fn file_paths(branch: &Value) -> Vec<&str> {
+    let mut paths = branch["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+#[tokio::test]
+async fn unknown_commands_do_not_register_the_project() {
+    let repository = TestProject::default();
+    let data_dir = paths::data_dir();
+
+    assert!(cli(&repository, data_dir.path(), &["frobnicate"]).is_err());
+
+    let projects = projects::Controller::from_path(data_dir.path());
+    assert!(projects.list().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn commit_to_branch_created_by_name() {
+    let repository = TestProject::default();
+    let data_dir = paths::data_dir();
+    set_base_branch(&repository, data_dir.path()).await;
+
+    let created = cli(
+        &repository,
+        data_dir.path(),
+        &["branch", "create", "feature"],
+    )
+    .unwrap();
+    fs::write(repository.path().join("file.txt"), "content\n").unwrap();
+
+    let committed = cli(
+        &repository,
+        data_dir.path(),
+        &["commit", "-b", "feature", "-m", "add file"],
+    )
+    .unwrap();
+    assert_eq!(committed["branchId"], created["branchId"]);
+
+    let branches = cli(&repository, data_dir.path(), &["branch", "list"]).unwrap();
+    let feature = branch(&branches, &created["branchId"]);
+    assert_eq!(feature["name"], "feature");
+    assert!(feature["files"].as_array().unwrap().is_empty());
+    assert_eq!(feature["commits"][0]["id"], committed["commitId"]);
+}
+
+#[tokio::test]
+async fn move_hunk_keeps_what_the_target_branch_owns() {
+    let repository = TestProject::default();
+    let data_dir = paths::data_dir();
+    set_base_branch(&repository, data_dir.path()).await;
+
+    let source = cli(
+        &repository,
+        data_dir.path(),
+        &["branch", "create", "source"],
+    )
+    .unwrap();
+    let target = cli(
+        &repository,
+        data_dir.path(),
+        &["branch", "create", "target"],
+    )
+    .unwrap();
+    fs::write(repository.path().join("one.txt"), "one\n").unwrap();
+    fs::write(repository.path().join("two.txt"), "two\n").unwrap();
+
+    cli(
+        &repository,
+        data_dir.path(),
+        &["move-hunk", "one.txt:1-2", "--to", "target"],
+    )
+    .unwrap();
+    cli(
+        &repository,
+        data_dir.path(),
+        &["move-hunk", "two.txt:1-2", "--to", "target"],
+    )
+    .unwrap();
+
+    let branches = cli(&repository, data_dir.path(), &["branch", "list"]).unwrap();
+    assert!(file_paths(branch(&branches, &source["branchId"])).is_empty());
+    assert_eq!(
+        file_paths(branch(&branches, &target["branchId"])),
+        ["one.txt", "two.txt"]
+    );
+}
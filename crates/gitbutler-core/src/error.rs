@@ -139,6 +139,7 @@ pub enum Code {
     Menu,
     PreCommitHook,
     CommitMsgHook,
+    PrePushHook,
 }
 
 impl std::fmt::Display for Code {
@@ -157,6 +158,7 @@ impl std::fmt::Display for Code {
             //TODO: rename js side to be more precise what kind of hook error this is
             Code::PreCommitHook => "errors.hook",
             Code::CommitMsgHook => "errors.hooks.commit.msg",
+            Code::PrePushHook => "errors.hooks.push.pre",
         };
         f.write_str(code)
     }
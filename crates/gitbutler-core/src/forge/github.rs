@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use reqwest::{header, Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    ChecksStatus, Error, Forge, Head, NewPullRequest, PullRequest, PullRequestState,
+    PullRequestUpdate, Repo, ReviewState,
+};
+
+const API_URL: &str = "https://api.github.com";
+
+/// Pull requests of a repository on GitHub, through its REST API.
+#[derive(Debug, Clone)]
+pub struct GitHub {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    repo: Repo,
+}
+
+impl GitHub {
+    pub fn new(repo: Repo, token: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url: API_URL.to_owned(),
+            token: token.into(),
+            repo,
+        }
+    }
+
+    /// Talk to the API at `api_url` instead of the one of github.com, like the one of a
+    /// GitHub Enterprise server.
+    #[must_use]
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        api_url
+            .into()
+            .trim_end_matches('/')
+            .clone_into(&mut self.api_url);
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(
+                method,
+                format!(
+                    "{}/repos/{}/{}{path}",
+                    self.api_url, self.repo.owner, self.repo.name
+                ),
+            )
+            .bearer_auth(&self.token)
+            .header(header::ACCEPT, "application/vnd.github+json")
+            .header(header::USER_AGENT, "GitButler")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+    }
+
+    async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            message: String,
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = serde_json::from_str::<ErrorBody>(&body)
+                .map(|body| body.message)
+                .unwrap_or(body);
+            return Err(Error::Api {
+                status: status.as_u16(),
+                message,
+            });
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Complete `pull_request` with the state of its reviews and of the checks on its head.
+    async fn with_status(&self, pull_request: api::PullRequest) -> Result<PullRequest, Error> {
+        let reviews: Vec<api::Review> = Self::send(self.request(
+            Method::GET,
+            &format!("/pulls/{}/reviews?per_page=100", pull_request.number),
+        ))
+        .await?;
+        let check_runs: api::CheckRuns = Self::send(self.request(
+            Method::GET,
+            &format!("/commits/{}/check-runs?per_page=100", pull_request.head.sha),
+        ))
+        .await?;
+
+        Ok(PullRequest {
+            number: pull_request.number,
+            title: pull_request.title,
+            url: pull_request.html_url,
+            state: match (pull_request.state.as_str(), pull_request.merged_at) {
+                (_, Some(_)) => PullRequestState::Merged,
+                ("closed", None) => PullRequestState::Closed,
+                _ => PullRequestState::Open,
+            },
+            draft: pull_request.draft,
+            review: review_state(&reviews),
+            checks: checks_status(&check_runs.check_runs),
+        })
+    }
+}
+
+#[async_trait]
+impl Forge for GitHub {
+    async fn create_pull_request(
+        &self,
+        head: &Head,
+        base: &str,
+        pull_request: &NewPullRequest,
+    ) -> Result<PullRequest, Error> {
+        #[derive(Serialize)]
+        struct Body<'a> {
+            title: &'a str,
+            body: Option<&'a str>,
+            head: String,
+            base: &'a str,
+            draft: bool,
+        }
+
+        let created = Self::send(self.request(Method::POST, "/pulls").json(&Body {
+            title: &pull_request.title,
+            body: pull_request.body.as_deref(),
+            head: head_label(head),
+            base,
+            draft: pull_request.draft,
+        }))
+        .await?;
+        self.with_status(created).await
+    }
+
+    async fn update_pull_request(
+        &self,
+        number: u64,
+        update: &PullRequestUpdate,
+    ) -> Result<PullRequest, Error> {
+        #[derive(Serialize)]
+        struct Body<'a> {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            title: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            body: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            base: Option<&'a str>,
+        }
+
+        let updated = Self::send(
+            self.request(Method::PATCH, &format!("/pulls/{number}"))
+                .json(&Body {
+                    title: update.title.as_deref(),
+                    body: update.body.as_deref(),
+                    base: update.base.as_deref(),
+                }),
+        )
+        .await?;
+        self.with_status(updated).await
+    }
+
+    async fn pull_request(&self, head: &Head) -> Result<Option<PullRequest>, Error> {
+        let pull_requests: Vec<api::PullRequest> = Self::send(
+            self.request(Method::GET, "/pulls")
+                .query(&[("head", head_label(head).as_str()), ("state", "all")]),
+        )
+        .await?;
+        // newest first, as that's how they are sorted by default
+        match pull_requests.into_iter().next() {
+            Some(pull_request) => self.with_status(pull_request).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// How GitHub refers to a branch that may live in a fork.
+fn head_label(head: &Head) -> String {
+    format!("{}:{}", head.owner, head.branch)
+}
+
+/// Only the latest review that approved or requested changes counts for each reviewer.
+fn review_state(reviews: &[api::Review]) -> ReviewState {
+    let mut by_reviewer = HashMap::new();
+    for review in reviews {
+        let Some(user) = &review.user else {
+            continue;
+        };
+        match review.state.as_str() {
+            "APPROVED" | "CHANGES_REQUESTED" | "DISMISSED" => {
+                by_reviewer.insert(&user.login, review.state.as_str());
+            }
+            _ => {}
+        }
+    }
+    if by_reviewer
+        .values()
+        .any(|state| *state == "CHANGES_REQUESTED")
+    {
+        ReviewState::ChangesRequested
+    } else if by_reviewer.values().any(|state| *state == "APPROVED") {
+        ReviewState::Approved
+    } else {
+        ReviewState::Pending
+    }
+}
+
+fn checks_status(check_runs: &[api::CheckRun]) -> ChecksStatus {
+    if check_runs.is_empty() {
+        return ChecksStatus::None;
+    }
+    let failed = check_runs.iter().any(|run| {
+        matches!(
+            run.conclusion.as_deref(),
+            Some("failure" | "timed_out" | "cancelled" | "action_required")
+        )
+    });
+    if failed {
+        ChecksStatus::Failure
+    } else if check_runs.iter().any(|run| run.status != "completed") {
+        ChecksStatus::Pending
+    } else {
+        ChecksStatus::Success
+    }
+}
+
+/// The parts of the GitHub API responses we use.
+mod api {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct PullRequest {
+        pub number: u64,
+        pub title: String,
+        pub html_url: String,
+        pub state: String,
+        #[serde(default)]
+        pub draft: bool,
+        pub merged_at: Option<String>,
+        pub head: Commit,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Commit {
+        pub sha: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Review {
+        pub state: String,
+        pub user: Option<User>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct User {
+        pub login: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct CheckRuns {
+        pub check_runs: Vec<CheckRun>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct CheckRun {
+        pub status: String,
+        pub conclusion: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reviews(json: &str) -> Vec<api::Review> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn check_runs(json: &str) -> Vec<api::CheckRun> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn latest_review_of_each_reviewer_counts() {
+        let state = review_state(&reviews(
+            r#"[
+                {"state":"CHANGES_REQUESTED","user":{"login":"a"}},
+                {"state":"COMMENTED","user":{"login":"b"}},
+                {"state":"APPROVED","user":{"login":"a"}}
+            ]"#,
+        ));
+        assert_eq!(state, ReviewState::Approved);
+    }
+
+    #[test]
+    fn requested_changes_win_over_approvals() {
+        let state = review_state(&reviews(
+            r#"[
+                {"state":"APPROVED","user":{"login":"a"}},
+                {"state":"CHANGES_REQUESTED","user":{"login":"b"}}
+            ]"#,
+        ));
+        assert_eq!(state, ReviewState::ChangesRequested);
+        assert_eq!(review_state(&[]), ReviewState::Pending);
+    }
+
+    #[test]
+    fn failed_checks_win_over_pending_ones() {
+        let status = checks_status(&check_runs(
+            r#"[
+                {"status":"completed","conclusion":"failure"},
+                {"status":"in_progress","conclusion":null}
+            ]"#,
+        ));
+        assert_eq!(status, ChecksStatus::Failure);
+    }
+
+    #[test]
+    fn checks_are_pending_until_all_completed() {
+        let status = checks_status(&check_runs(
+            r#"[
+                {"status":"completed","conclusion":"success"},
+                {"status":"in_progress","conclusion":null}
+            ]"#,
+        ));
+        assert_eq!(status, ChecksStatus::Pending);
+        assert_eq!(checks_status(&[]), ChecksStatus::None);
+    }
+}
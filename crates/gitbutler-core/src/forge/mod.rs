@@ -0,0 +1,215 @@
+//! Pull requests on the forges that host the remotes of a project.
+
+mod github;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bstr::ByteSlice;
+use serde::{Deserialize, Serialize};
+
+pub use github::GitHub;
+
+use crate::{git, users};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to reach the forge")]
+    Network(#[from] reqwest::Error),
+    #[error("the forge responded with {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("pull requests on {0:?} aren't supported yet")]
+    Unsupported(git::ForgeKind),
+    #[error("not authenticated with the forge")]
+    NotAuthenticated(git::ForgeKind),
+}
+
+/// A repository on a forge, like `gitbutlerapp/gitbutler` on GitHub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    pub owner: String,
+    pub name: String,
+}
+
+impl Repo {
+    /// The repository `url` points to, taken from the last two components of its path.
+    pub fn from_url(url: &git::Url) -> Option<Self> {
+        let path = url.path.to_str().ok()?.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let (owner, name) = path.rsplit_once('/')?;
+        let owner = owner.rsplit('/').next()?;
+        if owner.is_empty() || name.is_empty() {
+            return None;
+        }
+        Some(Self {
+            owner: owner.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+}
+
+/// The branch a pull request asks to merge, which may live in a fork owned by `owner`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    pub owner: String,
+    pub branch: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPullRequest {
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+/// Changes to an existing pull request, where `None` leaves a field as it is.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestUpdate {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub base: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PullRequestState {
+    Open,
+    Closed,
+    Merged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReviewState {
+    /// Nobody approved or requested changes yet.
+    Pending,
+    Approved,
+    ChangesRequested,
+}
+
+/// The combined outcome of the checks that ran on the head of a pull request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChecksStatus {
+    /// No checks ran on the head commit.
+    None,
+    Pending,
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    /// Where people can look at the pull request.
+    pub url: String,
+    pub state: PullRequestState,
+    pub draft: bool,
+    pub review: ReviewState,
+    pub checks: ChecksStatus,
+}
+
+/// A service hosting a repository that pull requests can be opened against.
+#[async_trait]
+pub trait Forge: Send + Sync {
+    /// Ask to merge `head` into the `base` branch.
+    async fn create_pull_request(
+        &self,
+        head: &Head,
+        base: &str,
+        pull_request: &NewPullRequest,
+    ) -> Result<PullRequest, Error>;
+
+    async fn update_pull_request(
+        &self,
+        number: u64,
+        update: &PullRequestUpdate,
+    ) -> Result<PullRequest, Error>;
+
+    /// The most recent pull request of `head`, with its reviews and checks, if there is one.
+    async fn pull_request(&self, head: &Head) -> Result<Option<PullRequest>, Error>;
+}
+
+/// Finds the forge hosting the repository at a remote url, like [`for_remote()`] does.
+pub type ForgeProvider = Arc<
+    dyn Fn(
+            &git::Url,
+            Option<git::ForgeKind>,
+            Option<&users::User>,
+        ) -> Result<Option<Box<dyn Forge>>, Error>
+        + Send
+        + Sync,
+>;
+
+/// The forge of `kind` hosting the repository at `url`, if there is one, or
+/// [`Error::NotAuthenticated`] if `user` can't authenticate with it.
+///
+/// Only GitHub is implemented. Other kinds get an [`Unsupported`] forge, which refuses to
+/// create or update pull requests and never finds any.
+pub fn for_remote(
+    url: &git::Url,
+    kind: Option<git::ForgeKind>,
+    user: Option<&users::User>,
+) -> Result<Option<Box<dyn Forge>>, Error> {
+    let Some(kind) = kind else {
+        return Ok(None);
+    };
+    match kind {
+        git::ForgeKind::GitHub => {
+            let (Some(repo), Some(host)) = (Repo::from_url(url), url.host.as_deref()) else {
+                return Ok(None);
+            };
+            let token = user
+                .and_then(|user| user.github_access_token.clone())
+                .ok_or(Error::NotAuthenticated(kind))?;
+            let github = GitHub::new(repo, token);
+            Ok(Some(Box::new(if host == "github.com" {
+                github
+            } else {
+                // GitHub Enterprise serves its API under the host of the instance
+                github.with_api_url(format!("https://{host}/api/v3"))
+            })))
+        }
+        git::ForgeKind::GitLab | git::ForgeKind::Gitea | git::ForgeKind::Bitbucket => {
+            Ok(Some(Box::new(Unsupported(kind))))
+        }
+    }
+}
+
+/// A forge we can't talk to yet.
+#[derive(Debug, Clone, Copy)]
+pub struct Unsupported(pub git::ForgeKind);
+
+#[async_trait]
+impl Forge for Unsupported {
+    async fn create_pull_request(
+        &self,
+        _head: &Head,
+        _base: &str,
+        _pull_request: &NewPullRequest,
+    ) -> Result<PullRequest, Error> {
+        Err(Error::Unsupported(self.0))
+    }
+
+    async fn update_pull_request(
+        &self,
+        _number: u64,
+        _update: &PullRequestUpdate,
+    ) -> Result<PullRequest, Error> {
+        Err(Error::Unsupported(self.0))
+    }
+
+    /// Nothing to show, rather than an error each time pull requests are refreshed.
+    async fn pull_request(&self, _head: &Head) -> Result<Option<PullRequest>, Error> {
+        Ok(None)
+    }
+}
@@ -98,7 +98,7 @@ impl Repository {
         &self.project.id
     }
 
-    fn remote(&self, user: Option<&users::User>) -> Result<Option<(git::Remote, String)>> {
+    fn remote(&self, user: Option<&users::User>) -> Result<Option<(git::Url, String)>> {
         // only push if logged in
         let access_token = match user {
             Some(user) => user.access_token.clone(),
@@ -110,63 +110,33 @@ impl Repository {
             Some(api) => api.git_url.clone(),
             None => return Ok(None),
         };
+        let remote_url = remote_url
+            .parse::<git::Url>()
+            .with_context(|| format!("failed to parse remote url {remote_url}"))?;
 
-        let remote = self
-            .git_repository
-            .remote_anonymous(&remote_url.parse().unwrap())
-            .with_context(|| {
-                format!(
-                    "failed to create anonymous remote for {}",
-                    remote_url.as_str()
-                )
-            })?;
-
-        Ok(Some((remote, access_token)))
+        Ok(Some((remote_url, access_token)))
     }
 
     pub fn fetch(&self, user: Option<&users::User>) -> Result<(), RemoteError> {
-        let (mut remote, access_token) = match self.remote(user)? {
-            Some((remote, access_token)) => (remote, access_token),
+        let (remote_url, access_token) = match self.remote(user)? {
+            Some((remote_url, access_token)) => (remote_url, access_token),
             None => return Result::Ok(()),
         };
 
-        let mut callbacks = git2::RemoteCallbacks::new();
-        if self.project.omit_certificate_check.unwrap_or(false) {
-            callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
-        }
-        callbacks.push_update_reference(move |refname, message| {
-            tracing::debug!(
-                project_id = %self.project.id,
-                refname,
-                message,
-                "pulling reference"
-            );
-            Result::Ok(())
-        });
-        callbacks.push_transfer_progress(move |one, two, three| {
-            tracing::debug!(
-                project_id = %self.project.id,
-                "transferred {}/{}/{} objects",
-                one,
-                two,
-                three
-            );
-        });
-
-        let mut fetch_opts = git2::FetchOptions::new();
-        fetch_opts.remote_callbacks(callbacks);
-        let auth_header = format!("Authorization: {}", access_token);
-        let headers = &[auth_header.as_str()];
-        fetch_opts.custom_headers(headers);
-
-        remote
-            .fetch(&["refs/heads/*:refs/remotes/*"], Some(&mut fetch_opts))
-            .map_err(|error| match error {
-                git::Error::Network(error) => {
-                    tracing::warn!(project_id = %self.project.id, error = %error, "failed to fetch gb repo");
-                    RemoteError::Network
-                }
-                error => RemoteError::Other(error.into()),
+        project_repository::remote_backend::for_gitbutler(&self.project)
+            .fetch(
+                &project_repository::remote_backend::Target::GitButler {
+                    repository: &self.git_repository,
+                    url: &remote_url,
+                    access_token: &access_token,
+                },
+                &["refs/heads/*:refs/remotes/*"],
+                project_repository::remote_backend::FetchOptions::default(),
+                &|_| {},
+            )
+            .map_err(|error| {
+                tracing::warn!(project_id = %self.project.id, %error, "failed to fetch gb repo");
+                error
             })?;
 
         tracing::info!(
@@ -178,52 +148,28 @@ impl Repository {
     }
 
     pub fn push(&self, user: Option<&users::User>) -> Result<(), RemoteError> {
-        let (mut remote, access_token) = match self.remote(user)? {
-            Some((remote, access_token)) => (remote, access_token),
+        let (remote_url, access_token) = match self.remote(user)? {
+            Some((remote_url, access_token)) => (remote_url, access_token),
             None => return Ok(()),
         };
 
-        // Set the remote's callbacks
-        let mut callbacks = git2::RemoteCallbacks::new();
-        if self.project.omit_certificate_check.unwrap_or(false) {
-            callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
-        }
-        callbacks.push_update_reference(move |refname, message| {
-            tracing::debug!(
-                project_id = %self.project.id,
-                refname,
-                message,
-                "pushing reference"
-            );
-            Result::Ok(())
-        });
-        callbacks.push_transfer_progress(move |current, total, bytes| {
-            tracing::debug!(
-                project_id = %self.project.id,
-                "transferred {}/{}/{} objects",
-                current,
-                total,
-                bytes
-            );
-        });
-
-        let mut push_options = git2::PushOptions::new();
-        push_options.remote_callbacks(callbacks);
-        let auth_header = format!("Authorization: {}", access_token);
-        let headers = &[auth_header.as_str()];
-        push_options.custom_headers(headers);
-
         let remote_refspec = format!("refs/heads/current:refs/heads/{}", self.project.id);
 
         // Push to the remote
-        remote
-            .push(&[&remote_refspec], Some(&mut push_options))
-            .map_err(|error| match error {
-                git::Error::Network(error) => {
-                    tracing::warn!(project_id = %self.project.id, error = %error, "failed to push gb repo");
-                    RemoteError::Network
-                }
-                error => RemoteError::Other(error.into()),
+        project_repository::remote_backend::for_gitbutler(&self.project)
+            .push(
+                &project_repository::remote_backend::Target::GitButler {
+                    repository: &self.git_repository,
+                    url: &remote_url,
+                    access_token: &access_token,
+                },
+                &[&remote_refspec],
+                None,
+                &|_| {},
+            )
+            .map_err(|error| {
+                tracing::warn!(project_id = %self.project.id, %error, "failed to push gb repo");
+                error
             })?;
 
         tracing::info!(project_id = %self.project.id,  "gb repository pushed");
@@ -648,6 +594,12 @@ fn build_wd_tree_from_repo(
         if added.contains(&file_path) {
             continue;
         }
+        // submodules are directories with the files of another repository
+        if std::fs::symlink_metadata(project_repository.root().join(&file_path))
+            .map_or(false, |metadata| metadata.is_dir())
+        {
+            continue;
+        }
 
         add_wd_path(
             &mut index,
@@ -914,3 +866,12 @@ pub enum RemoteError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+impl From<project_repository::RemoteError> for RemoteError {
+    fn from(error: project_repository::RemoteError) -> Self {
+        match error {
+            project_repository::RemoteError::Network => RemoteError::Network,
+            error => RemoteError::Other(error.into()),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use crate::error::{AnyhowContextExt, Code, Context, ErrorWithContext};
 use crate::{error, keys, project_repository, projects, users};
@@ -10,12 +10,14 @@ pub enum SshCredential {
         passphrase: Option<String>,
     },
     GitButlerKey(Box<keys::PrivateKey>),
+    Agent,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HttpsCredential {
     CredentialHelper { username: String, password: String },
     GitHubToken(String),
+    AccessToken(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -51,6 +53,12 @@ impl From<Credential> for git2::RemoteCallbacks<'_> {
                     git2::Cred::ssh_key_from_memory("git", None, &key.to_string(), None)
                 });
             }
+            Credential::Ssh(SshCredential::Agent) => {
+                remote_callbacks.credentials(move |url, username_from_url, _allowed_types| {
+                    tracing::info!("authenticating with {} using ssh-agent", url);
+                    git2::Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
+                });
+            }
             Credential::Https(HttpsCredential::CredentialHelper { username, password }) => {
                 remote_callbacks.credentials(move |url, _username_from_url, _allowed_types| {
                     tracing::info!("authenticating with {url} as '{username}' with password using credential helper");
@@ -63,16 +71,259 @@ impl From<Credential> for git2::RemoteCallbacks<'_> {
                     git2::Cred::userpass_plaintext("git", &token)
                 });
             }
+            Credential::Https(HttpsCredential::AccessToken(token)) => {
+                remote_callbacks.credentials(move |url, _username_from_url, _allowed_types| {
+                    tracing::info!("authenticating with {url} using access token");
+                    git2::Cred::userpass_plaintext("git", &token)
+                });
+            }
         };
         remote_callbacks
     }
 }
 
+/// The urls to try for a remote, each with the credentials to try it with, in order of preference.
+pub type Flow = Vec<(super::Url, Vec<Credential>)>;
+
+/// A source of credentials for authenticating with remotes.
+pub trait CredentialProvider: Send + Sync {
+    /// Return what to try to authenticate with the remote at `remote_url`, or nothing if this provider
+    /// has no credentials for it.
+    fn flow(
+        &self,
+        project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError>;
+}
+
+/// Authenticates over ssh with a private key on disk.
+pub struct Keyfile {
+    pub key_path: PathBuf,
+}
+
+impl CredentialProvider for Keyfile {
+    fn flow(
+        &self,
+        _project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError> {
+        Ok(vec![(
+            remote_url.as_ssh()?,
+            vec![Credential::Ssh(SshCredential::Keyfile {
+                key_path: self.key_path.clone(),
+                passphrase: None,
+            })],
+        )])
+    }
+}
+
+/// Authenticates over ssh with the keys held by the running ssh-agent.
+pub struct SshAgent;
+
+impl CredentialProvider for SshAgent {
+    fn flow(
+        &self,
+        _project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError> {
+        Ok(vec![(
+            remote_url.as_ssh()?,
+            vec![Credential::Ssh(SshCredential::Agent)],
+        )])
+    }
+}
+
+/// Authenticates over ssh with the key GitButler generated for the user.
+pub struct GeneratedKey {
+    keys: keys::Controller,
+}
+
+impl GeneratedKey {
+    pub fn new(keys: keys::Controller) -> Self {
+        Self { keys }
+    }
+}
+
+impl CredentialProvider for GeneratedKey {
+    fn flow(
+        &self,
+        _project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError> {
+        let key = self.keys.get_or_create()?;
+        Ok(vec![(
+            remote_url.as_ssh()?,
+            vec![Credential::Ssh(SshCredential::GitButlerKey(Box::new(key)))],
+        )])
+    }
+}
+
+/// Authenticates over https with whatever the configured git credential helper returns.
+pub struct GitCredentialHelper;
+
+impl GitCredentialHelper {
+    fn credentials(
+        project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Vec<Credential>, HelpError> {
+        let mut flow = vec![];
+
+        let mut helper = git2::CredentialHelper::new(&remote_url.to_string());
+        let config = project_repository.git_repository.config()?;
+        helper.config(&git2::Config::from(config));
+        if let Some((username, password)) = helper.execute() {
+            flow.push(Credential::Https(HttpsCredential::CredentialHelper {
+                username,
+                password,
+            }));
+        }
+
+        Ok(flow)
+    }
+}
+
+impl CredentialProvider for GitCredentialHelper {
+    fn flow(
+        &self,
+        project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError> {
+        let credentials = Self::credentials(project_repository, remote_url)?;
+        Ok(vec![(remote_url.as_https()?, credentials)])
+    }
+}
+
+/// Authenticates over https with the user's access token for the remote's host, which is their
+/// GitHub token for GitHub remotes unless they stored another one for it.
+pub struct AccessTokens {
+    users: users::Controller,
+}
+
+impl AccessTokens {
+    pub fn new(users: users::Controller) -> Self {
+        Self { users }
+    }
+
+    fn token(&self, remote_url: &super::Url) -> Result<Option<HttpsCredential>, HelpError> {
+        let Some(user) = self.users.get_user()? else {
+            return Ok(None);
+        };
+        if let Some(token) = remote_url
+            .host
+            .as_ref()
+            .and_then(|host| user.access_tokens.get(host))
+        {
+            return Ok(Some(HttpsCredential::AccessToken(token.clone())));
+        }
+        if remote_url.is_github() {
+            return Ok(user.github_access_token.map(HttpsCredential::GitHubToken));
+        }
+        Ok(None)
+    }
+}
+
+impl CredentialProvider for AccessTokens {
+    fn flow(
+        &self,
+        _project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError> {
+        Ok(match self.token(remote_url)? {
+            Some(token) => vec![(remote_url.as_https()?, vec![Credential::Https(token)])],
+            None => vec![],
+        })
+    }
+}
+
+/// Uses the user's access token for the remote if there is one. Otherwise tries the git credential
+/// helper over https and the usual ssh keys of the user, starting with the scheme of the remote.
+struct DefaultFlow {
+    tokens: AccessTokens,
+    keys: keys::Controller,
+    home_dir: Option<PathBuf>,
+}
+
+impl DefaultFlow {
+    fn ssh_credentials(&self) -> Result<Vec<Credential>, HelpError> {
+        let mut flow = vec![];
+        if let Some(home_path) = self.home_dir.as_ref() {
+            for key_name in ["id_rsa", "id_ed25519", "id_ecdsa"] {
+                let key_path = home_path.join(".ssh").join(key_name);
+                if key_path.exists() {
+                    flow.push(Credential::Ssh(SshCredential::Keyfile {
+                        key_path,
+                        passphrase: None,
+                    }));
+                }
+            }
+        }
+
+        let key = self.keys.get_or_create()?;
+        flow.push(Credential::Ssh(SshCredential::GitButlerKey(Box::new(key))));
+        Ok(flow)
+    }
+}
+
+impl CredentialProvider for DefaultFlow {
+    fn flow(
+        &self,
+        project_repository: &project_repository::Repository,
+        remote_url: &super::Url,
+    ) -> Result<Flow, HelpError> {
+        // if the user has a token for the host, only try that.
+        let token_flow = self.tokens.flow(project_repository, remote_url)?;
+        if !token_flow.is_empty() {
+            return Ok(token_flow);
+        }
+
+        let mut flow = vec![];
+        match remote_url.scheme {
+            super::Scheme::Https => {
+                let https_flow = GitCredentialHelper::credentials(project_repository, remote_url)?;
+                if !https_flow.is_empty() {
+                    flow.push((remote_url.clone(), https_flow));
+                }
+
+                if let Ok(ssh_url) = remote_url.as_ssh() {
+                    flow.push((ssh_url, self.ssh_credentials()?));
+                }
+            }
+            super::Scheme::Ssh => {
+                flow.push((remote_url.clone(), self.ssh_credentials()?));
+
+                if let Ok(https_url) = remote_url.as_https() {
+                    let https_flow =
+                        GitCredentialHelper::credentials(project_repository, &https_url)?;
+                    if !https_flow.is_empty() {
+                        flow.push((https_url, https_flow));
+                    }
+                }
+            }
+            _ => {
+                if let Ok(https_url) = remote_url.as_https() {
+                    let https_flow =
+                        GitCredentialHelper::credentials(project_repository, &https_url)?;
+                    if !https_flow.is_empty() {
+                        flow.push((https_url, https_flow));
+                    }
+                }
+
+                if let Ok(ssh_url) = remote_url.as_ssh() {
+                    flow.push((ssh_url, self.ssh_credentials()?));
+                }
+            }
+        }
+        Ok(flow)
+    }
+}
+
 #[derive(Clone)]
 pub struct Helper {
     keys: keys::Controller,
     users: users::Controller,
     home_dir: Option<PathBuf>,
+    /// If set, the providers to ask for credentials instead of those picked by the project's preferred key.
+    providers: Option<Vec<Arc<dyn CredentialProvider>>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -110,6 +361,7 @@ impl Helper {
             keys,
             users,
             home_dir,
+            providers: None,
         }
     }
 
@@ -121,264 +373,71 @@ impl Helper {
         Self::new(keys, users, home_dir)
     }
 
-    pub fn help<'a>(
-        &'a self,
-        project_repository: &'a project_repository::Repository,
-        remote_name: &str,
-    ) -> Result<Vec<(super::Remote, Vec<Credential>)>, HelpError> {
-        let remote = project_repository.git_repository.find_remote(remote_name)?;
-        let remote_url = remote.url()?.ok_or(HelpError::NoUrlSet)?;
-
-        // if file, no auth needed.
-        if remote_url.scheme == super::Scheme::File {
-            return Ok(vec![(remote, vec![Credential::Noop])]);
-        }
-
-        match &project_repository.project().preferred_key {
-            projects::AuthKey::Local { private_key_path } => {
-                let ssh_remote = if remote_url.scheme == super::Scheme::Ssh {
-                    Ok(remote)
-                } else {
-                    let ssh_url = remote_url.as_ssh()?;
-                    project_repository.git_repository.remote_anonymous(&ssh_url)
-                }?;
-
-                Ok(vec![(
-                    ssh_remote,
-                    vec![Credential::Ssh(SshCredential::Keyfile {
-                        key_path: private_key_path.clone(),
-                        passphrase: None,
-                    })],
-                )])
-            }
-            projects::AuthKey::GitCredentialsHelper => {
-                let https_remote = if remote_url.scheme == super::Scheme::Https {
-                    Ok(remote)
-                } else {
-                    let url = remote_url.as_https()?;
-                    project_repository.git_repository.remote_anonymous(&url)
-                }?;
-                let flow = Self::https_flow(project_repository, &remote_url)?
-                    .into_iter()
-                    .map(Credential::Https)
-                    .collect::<Vec<_>>();
-                Ok(vec![(https_remote, flow)])
-            }
-            projects::AuthKey::Generated => {
-                let generated_flow = self.generated_flow(remote, project_repository)?;
-
-                let remote = project_repository.git_repository.find_remote(remote_name)?;
-                let default_flow = self.default_flow(remote, project_repository)?;
+    /// Ask only `providers` for credentials, in order, whatever the project's preferred key is.
+    #[must_use]
+    pub fn with_providers(mut self, providers: Vec<Arc<dyn CredentialProvider>>) -> Self {
+        self.providers = Some(providers);
+        self
+    }
 
-                Ok(vec![generated_flow, default_flow]
-                    .into_iter()
-                    .flatten()
-                    .collect())
-            }
-            projects::AuthKey::Default => self.default_flow(remote, project_repository),
-            projects::AuthKey::SystemExecutable => {
-                tracing::error!("WARNING: FIXME: this codepath should NEVER be hit. Something is seriously wrong.");
-                self.default_flow(remote, project_repository)
+    fn providers(&self, preferred_key: &projects::AuthKey) -> Vec<Arc<dyn CredentialProvider>> {
+        let default_flow = || -> Arc<dyn CredentialProvider> {
+            Arc::new(DefaultFlow {
+                tokens: AccessTokens::new(self.users.clone()),
+                keys: self.keys.clone(),
+                home_dir: self.home_dir.clone(),
+            })
+        };
+        match preferred_key {
+            projects::AuthKey::Local { private_key_path } => vec![Arc::new(Keyfile {
+                key_path: private_key_path.clone(),
+            })],
+            projects::AuthKey::SshAgent => vec![Arc::new(SshAgent)],
+            projects::AuthKey::GitCredentialsHelper => vec![Arc::new(GitCredentialHelper)],
+            projects::AuthKey::Generated => vec![
+                Arc::new(GeneratedKey::new(self.keys.clone())),
+                default_flow(),
+            ],
+            // the system executable authenticates by itself, so this is only used for operations
+            // that don't go through it.
+            projects::AuthKey::Default | projects::AuthKey::SystemExecutable => {
+                vec![default_flow()]
             }
         }
     }
 
-    fn generated_flow<'a>(
-        &'a self,
-        remote: super::Remote<'a>,
-        project_repository: &'a project_repository::Repository,
-    ) -> Result<Vec<(super::Remote, Vec<Credential>)>, HelpError> {
-        let remote_url = remote.url()?.ok_or(HelpError::NoUrlSet)?;
-
-        let ssh_remote = if remote_url.scheme == super::Scheme::Ssh {
-            Ok(remote)
-        } else {
-            let ssh_url = remote_url.as_ssh()?;
-            project_repository.git_repository.remote_anonymous(&ssh_url)
-        }?;
-
-        let key = self.keys.get_or_create()?;
-        Ok(vec![(
-            ssh_remote,
-            vec![Credential::Ssh(SshCredential::GitButlerKey(Box::new(key)))],
-        )])
-    }
-
-    fn default_flow<'a>(
+    pub fn help<'a>(
         &'a self,
-        remote: super::Remote<'a>,
         project_repository: &'a project_repository::Repository,
+        remote_name: &str,
     ) -> Result<Vec<(super::Remote, Vec<Credential>)>, HelpError> {
-        let remote_url = remote.url()?.ok_or(HelpError::NoUrlSet)?;
-
-        // is github is authenticated, only try github.
-        if remote_url.is_github() {
-            if let Some(github_access_token) = self
-                .users
-                .get_user()?
-                .and_then(|user| user.github_access_token)
-            {
-                let https_remote = if remote_url.scheme == super::Scheme::Https {
-                    Ok(remote)
-                } else {
-                    let url = remote_url.as_https()?;
-                    project_repository.git_repository.remote_anonymous(&url)
-                }?;
-                return Ok(vec![(
-                    https_remote,
-                    vec![Credential::Https(HttpsCredential::GitHubToken(
-                        github_access_token,
-                    ))],
-                )]);
-            }
-        }
-
-        match remote_url.scheme {
-            super::Scheme::Https => {
-                let mut flow = vec![];
-
-                let https_flow = Self::https_flow(project_repository, &remote_url)?
-                    .into_iter()
-                    .map(Credential::Https)
-                    .collect::<Vec<_>>();
-
-                if !https_flow.is_empty() {
-                    flow.push((remote, https_flow));
-                }
-
-                if let Ok(ssh_url) = remote_url.as_ssh() {
-                    let ssh_flow = self
-                        .ssh_flow()?
-                        .into_iter()
-                        .map(Credential::Ssh)
-                        .collect::<Vec<_>>();
-                    if !ssh_flow.is_empty() {
-                        flow.push((
-                            project_repository
-                                .git_repository
-                                .remote_anonymous(&ssh_url)?,
-                            ssh_flow,
-                        ));
-                    }
-                }
-
-                Ok(flow)
-            }
-            super::Scheme::Ssh => {
-                let mut flow = vec![];
-
-                let ssh_flow = self
-                    .ssh_flow()?
-                    .into_iter()
-                    .map(Credential::Ssh)
-                    .collect::<Vec<_>>();
-                if !ssh_flow.is_empty() {
-                    flow.push((remote, ssh_flow));
-                }
-
-                if let Ok(https_url) = remote_url.as_https() {
-                    let https_flow = Self::https_flow(project_repository, &https_url)?
-                        .into_iter()
-                        .map(Credential::Https)
-                        .collect::<Vec<_>>();
-                    if !https_flow.is_empty() {
-                        flow.push((
-                            project_repository
-                                .git_repository
-                                .remote_anonymous(&https_url)?,
-                            https_flow,
-                        ));
-                    }
-                }
-
-                Ok(flow)
-            }
-            _ => {
-                let mut flow = vec![];
-
-                if let Ok(https_url) = remote_url.as_https() {
-                    let https_flow = Self::https_flow(project_repository, &https_url)?
-                        .into_iter()
-                        .map(Credential::Https)
-                        .collect::<Vec<_>>();
-
-                    if !https_flow.is_empty() {
-                        flow.push((
-                            project_repository
-                                .git_repository
-                                .remote_anonymous(&https_url)?,
-                            https_flow,
-                        ));
-                    }
-                }
-
-                if let Ok(ssh_url) = remote_url.as_ssh() {
-                    let ssh_flow = self
-                        .ssh_flow()?
-                        .into_iter()
-                        .map(Credential::Ssh)
-                        .collect::<Vec<_>>();
-                    if !ssh_flow.is_empty() {
-                        flow.push((
-                            project_repository
-                                .git_repository
-                                .remote_anonymous(&ssh_url)?,
-                            ssh_flow,
-                        ));
-                    }
-                }
-
-                Ok(flow)
+        let remote_url = project_repository
+            .git_repository
+            .find_remote(remote_name)?
+            .url()?
+            .ok_or(HelpError::NoUrlSet)?;
+
+        let providers = match &self.providers {
+            Some(providers) => providers.clone(),
+            // if file, no auth needed.
+            None if remote_url.scheme == super::Scheme::File => {
+                let remote = project_repository.git_repository.find_remote(remote_name)?;
+                return Ok(vec![(remote, vec![Credential::Noop])]);
             }
-        }
-    }
-
-    fn https_flow(
-        project_repository: &project_repository::Repository,
-        remote_url: &super::Url,
-    ) -> Result<Vec<HttpsCredential>, HelpError> {
-        let mut flow = vec![];
-
-        let mut helper = git2::CredentialHelper::new(&remote_url.to_string());
-        let config = project_repository.git_repository.config()?;
-        helper.config(&git2::Config::from(config));
-        if let Some((username, password)) = helper.execute() {
-            flow.push(HttpsCredential::CredentialHelper { username, password });
-        }
-
-        Ok(flow)
-    }
+            None => self.providers(&project_repository.project().preferred_key),
+        };
 
-    fn ssh_flow(&self) -> Result<Vec<SshCredential>, HelpError> {
         let mut flow = vec![];
-        if let Some(home_path) = self.home_dir.as_ref() {
-            let id_rsa_path = home_path.join(".ssh").join("id_rsa");
-            if id_rsa_path.exists() {
-                flow.push(SshCredential::Keyfile {
-                    key_path: id_rsa_path.clone(),
-                    passphrase: None,
-                });
-            }
-
-            let id_ed25519_path = home_path.join(".ssh").join("id_ed25519");
-            if id_ed25519_path.exists() {
-                flow.push(SshCredential::Keyfile {
-                    key_path: id_ed25519_path.clone(),
-                    passphrase: None,
-                });
-            }
-
-            let id_ecdsa_path = home_path.join(".ssh").join("id_ecdsa");
-            if id_ecdsa_path.exists() {
-                flow.push(SshCredential::Keyfile {
-                    key_path: id_ecdsa_path.clone(),
-                    passphrase: None,
-                });
+        for provider in providers {
+            for (url, credentials) in provider.flow(project_repository, &remote_url)? {
+                let remote = if url == remote_url {
+                    project_repository.git_repository.find_remote(remote_name)?
+                } else {
+                    project_repository.git_repository.remote_anonymous(&url)?
+                };
+                flow.push((remote, credentials));
             }
         }
-
-        let key = self.keys.get_or_create()?;
-        flow.push(SshCredential::GitButlerKey(Box::new(key)));
         Ok(flow)
     }
 }
@@ -1,8 +1,11 @@
 use std::borrow::Cow;
-use std::path::PathBuf;
-use std::{collections::HashMap, str};
+use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    str,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 use bstr::{BStr, BString, ByteSlice, ByteVec};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
@@ -10,6 +13,7 @@ use tracing::instrument;
 use super::Repository;
 use crate::git;
 use crate::id::Id;
+use crate::projects::{DiffAlgorithm, DiffSettings, Whitespace};
 use crate::virtual_branches::Branch;
 
 pub type DiffByPathMap = HashMap<PathBuf, FileDiff>;
@@ -24,6 +28,10 @@ pub enum ChangeType {
     Deleted,
     /// Entry content changed between old and new
     Modified,
+    /// Entry was moved from another path, and may have changed along the way
+    Renamed,
+    /// Entry was copied from another path, which still exists, and may have changed
+    Copied,
 }
 impl From<git2::Delta> for ChangeType {
     fn from(v: git2::Delta) -> Self {
@@ -31,12 +39,9 @@ impl From<git2::Delta> for ChangeType {
         use ChangeType as C;
         match v {
             D::Untracked | D::Added => C::Added,
-            D::Modified
-            | D::Unmodified
-            | D::Renamed
-            | D::Copied
-            | D::Typechange
-            | D::Conflicted => C::Modified,
+            D::Modified | D::Unmodified | D::Typechange | D::Conflicted => C::Modified,
+            D::Renamed => C::Renamed,
+            D::Copied => C::Copied,
             D::Ignored | D::Unreadable | D::Deleted => C::Deleted,
         }
     }
@@ -56,13 +61,32 @@ pub struct GitHunk {
     pub binary: bool,
     pub locked_to: Box<[HunkLock]>,
     pub change_type: ChangeType,
+    /// The hunk `git2` found and which of its changes are left in this one, if only some are,
+    /// so it doesn't describe what's in the worktree anymore.
+    #[serde(skip)]
+    pub selection: Option<Box<Selection>>,
+    /// The file the old lines are from if it was renamed or copied to the path of this hunk.
+    #[serde(skip)]
+    pub old_path: Option<PathBuf>,
+    /// The commits of the submodule this hunk moves to another commit, if it is about one.
+    #[serde(skip)]
+    pub submodule: Option<SubmoduleChange>,
+}
+
+/// The commit a submodule pointed to before and after a change, where `None` means
+/// it was added or removed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmoduleChange {
+    pub old_id: Option<git::Oid>,
+    pub new_id: Option<git::Oid>,
 }
 
 /// Lifecycle
 impl GitHunk {
     /// A special hunk that signals a binary file whose complete content is a blob under `hex_id` in Git.
     /// `changetype` is tells us what happened with the file.
-    fn binary_marker(hex_id: String, change_type: ChangeType) -> Self {
+    pub(crate) fn binary_marker(hex_id: String, change_type: ChangeType) -> Self {
         GitHunk {
             old_start: 0,
             old_lines: 0,
@@ -72,9 +96,19 @@ impl GitHunk {
             binary: true,
             change_type,
             locked_to: Box::new([]),
+            selection: None,
+            old_path: None,
+            submodule: None,
         }
     }
 
+    /// A special hunk that signals a file whose complete content is the file on disk, for files too
+    /// large to diff. Unlike with [`Self::binary_marker()`], it's only stored in Git once it's
+    /// written into a tree.
+    pub(crate) fn worktree_marker(change_type: ChangeType) -> Self {
+        Self::binary_marker(String::new(), change_type)
+    }
+
     /// Return a hunk that represents a new file by convention.
     fn generic_new_file() -> Self {
         Self {
@@ -86,6 +120,9 @@ impl GitHunk {
             binary: false,
             change_type: ChangeType::Modified,
             locked_to: Box::new([]),
+            selection: None,
+            old_path: None,
+            submodule: None,
         }
     }
 }
@@ -100,6 +137,172 @@ impl GitHunk {
         self.locked_to = locks.to_owned().into();
         self
     }
+
+    /// The hunk as `git2` found it, which is this one unless it only has some of its changes.
+    pub fn whole(&self) -> &GitHunk {
+        self.selection
+            .as_ref()
+            .map_or(self, |selection| &selection.whole)
+    }
+
+    /// `true` if this is a [`Self::worktree_marker()`].
+    pub fn is_worktree_marker(&self) -> bool {
+        self.binary && self.diff_lines.is_empty()
+    }
+
+    /// `true` if only some of the changes of the hunk `git2` found are in this one.
+    pub fn is_partial(&self) -> bool {
+        self.selection.is_some()
+    }
+
+    /// A copy of this hunk with only the changed lines `keep` returns `true` for. Removals that
+    /// aren't kept turn into context, and additions that aren't kept are dropped.
+    /// Lines are numbered like in [`Self::whole()`], even if this hunk has only some of them.
+    /// Returns `None` if no change is left.
+    pub fn filter_changes(&self, keep: impl Fn(ChangedLine) -> bool) -> Option<GitHunk> {
+        let whole = self.whole();
+        let is_left = |line: ChangedLine| {
+            self.selection
+                .as_ref()
+                .map_or(true, |selection| selection.lines.contains(&line))
+        };
+        if whole.binary {
+            // there are no lines to pick from
+            return Some(self.clone());
+        }
+        let mut lines = whole.diff_lines.lines_with_terminator();
+        let Some(header) = lines.next().filter(|line| line.starts_with(b"@@")) else {
+            return Some(self.clone());
+        };
+
+        let mut body = BString::default();
+        let (mut old_line, mut new_line) = (whole.old_start, whole.new_start);
+        let mut new_lines = 0;
+        let (mut kept, mut dropped) = (Vec::new(), 0);
+        // whether the line a `\ No newline at end of file` marker refers to made it
+        let mut previous_kept = false;
+        for line in lines {
+            previous_kept = match line.first() {
+                Some(b'-') => {
+                    old_line += 1;
+                    let changed_line = ChangedLine::Removed(old_line - 1);
+                    if is_left(changed_line) && keep(changed_line) {
+                        kept.push(changed_line);
+                        body.push_str(line);
+                    } else {
+                        dropped += 1;
+                        new_lines += 1;
+                        body.push(b' ');
+                        body.push_str(&line[1..]);
+                    }
+                    true
+                }
+                Some(b'+') => {
+                    new_line += 1;
+                    let changed_line = ChangedLine::Added(new_line - 1);
+                    if is_left(changed_line) && keep(changed_line) {
+                        kept.push(changed_line);
+                        new_lines += 1;
+                        body.push_str(line);
+                        true
+                    } else {
+                        dropped += 1;
+                        false
+                    }
+                }
+                Some(b'\\') => {
+                    if previous_kept {
+                        body.push_str(line);
+                    }
+                    previous_kept
+                }
+                _ => {
+                    old_line += 1;
+                    new_line += 1;
+                    new_lines += 1;
+                    body.push_str(line);
+                    true
+                }
+            };
+        }
+
+        if kept.is_empty() {
+            return None;
+        }
+        if dropped == 0 {
+            return Some(GitHunk {
+                locked_to: self.locked_to.clone(),
+                ..whole.clone()
+            });
+        }
+
+        // keep whatever follows the ranges, like the name of the enclosing function
+        let section = header
+            .get(2..)
+            .and_then(|rest| rest.find(b"@@").map(|pos| &rest[pos + 2..]))
+            .unwrap_or(&b"\n"[..]);
+        let mut diff_lines = BString::from(format!(
+            "@@ -{},{} +{},{} @@",
+            whole.old_start, whole.old_lines, whole.new_start, new_lines
+        ));
+        diff_lines.push_str(section);
+        diff_lines.push_str(body);
+        Some(GitHunk {
+            new_lines,
+            diff_lines,
+            locked_to: self.locked_to.clone(),
+            selection: Some(Box::new(Selection {
+                whole: whole.clone(),
+                lines: kept,
+            })),
+            ..whole.clone()
+        })
+    }
+}
+
+/// The changes of a hunk `git2` found that a [`GitHunk`] was cut down to.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Selection {
+    /// The hunk as `git2` found it.
+    pub whole: GitHunk,
+    /// The changed lines that are left, by their numbers in `whole`.
+    pub lines: Vec<ChangedLine>,
+}
+
+/// A line a hunk adds or removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangedLine {
+    /// An added line, by its number in the new version of the file.
+    Added(u32),
+    /// A removed line, by its number in the old version of the file.
+    Removed(u32),
+}
+
+impl std::fmt::Display for ChangedLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangedLine::Added(line) => write!(f, "+{line}"),
+            ChangedLine::Removed(line) => write!(f, "-{line}"),
+        }
+    }
+}
+
+impl str::FromStr for ChangedLine {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_line = |line: &str| {
+            line.parse::<u32>()
+                .with_context(|| format!("invalid line number in {s:?}"))
+        };
+        if let Some(line) = s.strip_prefix('+') {
+            parse_line(line).map(ChangedLine::Added)
+        } else if let Some(line) = s.strip_prefix('-') {
+            parse_line(line).map(ChangedLine::Removed)
+        } else {
+            anyhow::bail!("changed line must start with '+' or '-': {s:?}")
+        }
+    }
 }
 
 // A hunk is locked when it depends on changes in commits that are in your
@@ -126,10 +329,32 @@ pub struct FileDiff {
     pub binary: bool,
     pub old_size_bytes: u64,
     pub new_size_bytes: u64,
+    /// Set if the file at `new_path` was renamed or copied from the one at `old_path`.
+    pub similarity: Option<Similarity>,
 }
 
+/// How a renamed or copied file relates to the one it came from.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Similarity {
+    /// Either [`ChangeType::Renamed`] or [`ChangeType::Copied`].
+    pub change_type: ChangeType,
+    /// How much of the content both files share, in percent.
+    pub score: u16,
+}
+
+/// The changes in the worktree since `commit_oid`, compared as `settings` ask for.
+/// As these are the changes that get committed, `settings` can't leave out whitespace changes.
 #[instrument(skip(repository))]
-pub fn workdir(repository: &Repository, commit_oid: &git::Oid) -> Result<DiffByPathMap> {
+pub fn workdir(
+    repository: &Repository,
+    commit_oid: &git::Oid,
+    settings: &DiffSettings,
+) -> Result<DiffByPathMap> {
+    ensure!(
+        settings.whitespace == Whitespace::Keep,
+        "whitespace changes of the worktree can't be ignored"
+    );
     let commit = repository
         .find_commit(*commit_oid)
         .context("failed to find commit")?;
@@ -140,51 +365,210 @@ pub fn workdir(repository: &Repository, commit_oid: &git::Oid) -> Result<DiffByP
         .recurse_untracked_dirs(true)
         .include_untracked(true)
         .show_binary(true)
-        .show_untracked_content(true)
-        .ignore_submodules(true)
-        .context_lines(3);
+        .show_untracked_content(true);
+    apply_settings(&mut diff_opts, settings)?;
 
     let mut diff = repository.diff_tree_to_workdir(Some(&tree), Some(&mut diff_opts))?;
-    let (mut diff_opts, skipped_files) = without_large_files(50_000_000, &diff, diff_opts);
-    if !skipped_files.is_empty() {
-        diff = repository.diff_tree_to_workdir(Some(&tree), Some(&mut diff_opts))?;
+    let cleaned_files = cleaned_files(repository, &diff)?;
+    let (diff_opts, skipped_files) =
+        without_large_files(settings.large_file_limit_bytes, &diff, diff_opts, |path| {
+            cleaned_files.contains_key(path)
+        });
+    let mut diff_files = match diff_opts {
+        Some(mut diff_opts) => {
+            if !skipped_files.is_empty() || !cleaned_files.is_empty() {
+                diff = repository.diff_tree_to_workdir(Some(&tree), Some(&mut diff_opts))?;
+            }
+            find_renames(&mut diff)?;
+            hunks_by_filepath(Some(repository), &diff)?
+        }
+        None => DiffByPathMap::new(),
+    };
+    diff_files.extend(skipped_files);
+    diff_files.extend(
+        cleaned_files
+            .into_iter()
+            .filter_map(|(path, file)| Some((path, file?))),
+    );
+    for (path, file) in added_submodules(repository, &tree)? {
+        diff_files.entry(path).or_insert(file);
     }
-    let diff_files = hunks_by_filepath(Some(repository), &diff);
-    diff_files.map(|mut df| {
-        for (key, value) in skipped_files {
-            df.insert(key, value);
+    Ok(diff_files)
+}
+
+/// The submodules that `.gitmodules` has but `tree` doesn't, as files with a single hunk that adds
+/// the commit they have checked out. `git2` has no patch for them, and doesn't even see them once
+/// they aren't in the index either.
+fn added_submodules(repository: &Repository, tree: &git::Tree) -> Result<DiffByPathMap> {
+    let repo: &git2::Repository = repository.into();
+    let mut files = DiffByPathMap::new();
+    for submodule in repo.submodules()? {
+        let path = submodule.path();
+        if tree.get_path(path).is_ok() {
+            continue;
         }
-        df
-    })
+        let Some(new_id) = submodule_head(repository, path) else {
+            continue;
+        };
+        let hunk = GitHunk {
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: 1,
+            diff_lines: format!("@@ -0,0 +1 @@\n+Subproject commit {new_id}\n").into(),
+            binary: false,
+            change_type: ChangeType::Added,
+            locked_to: Box::new([]),
+            selection: None,
+            old_path: None,
+            submodule: Some(SubmoduleChange {
+                old_id: None,
+                new_id: Some(new_id),
+            }),
+        };
+        files.insert(
+            path.to_owned(),
+            FileDiff {
+                old_path: None,
+                new_path: Some(path.to_owned()),
+                hunks: vec![hunk],
+                skipped: false,
+                binary: false,
+                old_size_bytes: 0,
+                new_size_bytes: 0,
+                similarity: None,
+            },
+        );
+    }
+    Ok(files)
+}
+
+/// The changes of the worktree files in `diff` that a clean filter like `git lfs` applies to,
+/// which are compared by what the filter turns them into. As that is what gets committed,
+/// each has a single hunk with the cleaned blob, just like binary files.
+/// Files whose cleaned content is unchanged map to `None`.
+fn cleaned_files(
+    repository: &Repository,
+    diff: &git2::Diff,
+) -> Result<HashMap<PathBuf, Option<FileDiff>>> {
+    let mut files = HashMap::new();
+    for delta in diff.deltas() {
+        if delta.status() == git2::Delta::Deleted || submodule_change(None, &delta).is_some() {
+            continue;
+        }
+        let Some(path) = delta.new_file().path() else {
+            continue;
+        };
+        let Some(blob_id) = repository
+            .blob_path_cleaned(path)
+            .with_context(|| format!("failed to clean {}", path.display()))?
+        else {
+            continue;
+        };
+        let file = (git2::Oid::from(blob_id) != delta.old_file().id()).then(|| FileDiff {
+            old_path: delta.old_file().path().map(ToOwned::to_owned),
+            new_path: Some(path.to_owned()),
+            hunks: vec![GitHunk::binary_marker(
+                blob_id.to_string(),
+                delta.status().into(),
+            )],
+            skipped: false,
+            binary: true,
+            old_size_bytes: delta.old_file().size(),
+            new_size_bytes: delta.new_file().size(),
+            similarity: None,
+        });
+        files.insert(path.to_owned(), file);
+    }
+    Ok(files)
 }
 
 pub fn trees(
     repository: &Repository,
     old_tree: &git::Tree,
     new_tree: &git::Tree,
+    settings: &DiffSettings,
 ) -> Result<DiffByPathMap> {
     let mut diff_opts = git2::DiffOptions::new();
     diff_opts
         .recurse_untracked_dirs(true)
         .include_untracked(true)
         .show_binary(true)
-        .ignore_submodules(true)
-        .context_lines(3)
         .show_untracked_content(true);
+    apply_settings(&mut diff_opts, settings)?;
 
-    let diff =
+    let mut diff =
         repository.diff_tree_to_tree(Some(old_tree), Some(new_tree), Some(&mut diff_opts))?;
+    find_renames(&mut diff)?;
 
     hunks_by_filepath(None, &diff)
 }
 
+fn apply_settings(diff_opts: &mut git2::DiffOptions, settings: &DiffSettings) -> Result<()> {
+    // hunks without any context can't be placed when applied onto a different base
+    ensure!(
+        settings.context_lines > 0,
+        "diffs need at least one line of context"
+    );
+    diff_opts
+        .context_lines(settings.context_lines)
+        .minimal(settings.algorithm == DiffAlgorithm::Minimal)
+        .patience(settings.algorithm == DiffAlgorithm::Patience)
+        .ignore_whitespace_eol(settings.whitespace == Whitespace::IgnoreAtEol)
+        .ignore_whitespace(settings.whitespace == Whitespace::IgnoreAll);
+    Ok(())
+}
+
+/// Pair up deleted and added files with similar content into renames, and mark added files
+/// similar to modified ones as copies.
+fn find_renames(diff: &mut git2::Diff) -> Result<()> {
+    let mut find_opts = git2::DiffFindOptions::new();
+    find_opts.renames(true).copies(true).for_untracked(true);
+    diff.find_similar(Some(&mut find_opts))
+        .context("failed to find renames")
+}
+
+/// The similarity score of each renamed or copied file in `diff`, by its new path.
+fn similarity_by_path(diff: &git2::Diff) -> Result<HashMap<PathBuf, u16>> {
+    let mut scores = HashMap::new();
+    // `git2` doesn't expose the score of a delta, but the raw format prints it after the
+    // status, like in `:100644 100644 1234567... 89abcde... R086\told.txt new.txt`
+    diff.print(git2::DiffFormat::Raw, |delta, _, line| {
+        if !matches!(delta.status(), git2::Delta::Renamed | git2::Delta::Copied) {
+            return true;
+        }
+        let score = line
+            .content()
+            .split_str("\t")
+            .next()
+            .and_then(|fields| fields.fields().last())
+            .and_then(|status| status.get(1..))
+            .and_then(|score| score.to_str().ok())
+            .and_then(|score| score.parse().ok());
+        if let Some((path, score)) = delta.new_file().path().zip(score) {
+            scores.insert(path.to_owned(), score);
+        }
+        true
+    })
+    .context("failed to print diff")?;
+    Ok(scores)
+}
+
+/// Set aside the files of `diff` that are bigger than `size_limit_bytes`, and return them along
+/// with `diff_opts` limited to the remaining files that aren't `excluded` either.
+/// The options are `None` if no file remains to be diffed.
 pub fn without_large_files(
     size_limit_bytes: u64,
     diff: &git2::Diff,
     mut diff_opts: git2::DiffOptions,
-) -> (git2::DiffOptions, DiffByPathMap) {
+    excluded: impl Fn(&Path) -> bool,
+) -> (Option<git2::DiffOptions>, DiffByPathMap) {
     let mut skipped_files = HashMap::new();
+    let mut remaining = 0;
     for delta in diff.deltas() {
+        if delta.new_file().path().map_or(false, &excluded) {
+            continue;
+        }
         if delta.new_file().size() > size_limit_bytes {
             if let Some(path) = delta.new_file().path() {
                 skipped_files.insert(
@@ -197,6 +581,7 @@ pub fn without_large_files(
                         binary: true,
                         old_size_bytes: delta.old_file().size(),
                         new_size_bytes: delta.new_file().size(),
+                        similarity: None,
                     },
                 );
             }
@@ -205,10 +590,11 @@ pub fn without_large_files(
                 // TODO(ST): use negative pathspecs instead, but with `gitoxide` this might not even be necessary.
                 //           Currently, performance could be bad if there are thousands of pathspecs.
                 diff_opts.pathspec(path);
+                remaining += 1;
             }
         }
     }
-    (diff_opts, skipped_files)
+    ((remaining > 0).then_some(diff_opts), skipped_files)
 }
 
 /// Transform `diff` into a mapping of `worktree-relative path -> FileDiff`, where `FileDiff` is
@@ -223,8 +609,23 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
         Line(Cow<'a, BStr>),
         HexHashOfBinaryBlob(String),
     }
+    let similarities = similarity_by_path(diff)?;
     // find all the hunks
     let mut diff_files = HashMap::new();
+    // modified files whose mode didn't change, which only end up without hunks if they differ
+    // in ignored whitespace
+    let mut content_changes = HashSet::new();
+    // looked up once per file as it may have to open the submodule
+    let submodules: HashMap<PathBuf, SubmoduleChange> = diff
+        .deltas()
+        .filter_map(|delta| {
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())?;
+            Some((path.to_owned(), submodule_change(repo, &delta)?))
+        })
+        .collect();
 
     diff.print(
         git2::DiffFormat::Patch,
@@ -236,6 +637,11 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
                     .path()
                     .expect("failed to get file name from diff")
             });
+            let submodule = submodules.get(file_path).copied();
+            if submodule.map_or(false, |submodule| submodule.old_id == submodule.new_id) {
+                // only the worktree of the submodule changed, which isn't ours to commit
+                return true;
+            }
 
             let new_start = hunk.as_ref().map_or(0, git2::DiffHunk::new_start);
             let new_lines = hunk.as_ref().map_or(0, git2::DiffHunk::new_lines);
@@ -275,6 +681,11 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
 
             match line {
                 None => {
+                    if change_type == ChangeType::Modified
+                        && delta.old_file().mode() == delta.new_file().mode()
+                    {
+                        content_changes.insert(file_path.to_path_buf());
+                    }
                     let existing = diff_files
                         .insert(file_path.to_path_buf(),
                             FileDiff {
@@ -285,6 +696,10 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
                                 binary: delta.new_file().is_binary(),
                                 old_size_bytes: delta.old_file().size(),
                                 new_size_bytes: delta.new_file().size(),
+                                similarity: similarities.get(file_path).map(|score| Similarity {
+                                    change_type,
+                                    score: *score,
+                                }),
                         });
                     assert_eq!(existing, None, "BUG: this only happens for file-headers, they are provided once");
                 }
@@ -318,6 +733,9 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
                                         binary: false,
                                         change_type,
                                         locked_to: Box::new([]),
+                                        selection: None,
+                                        old_path: None,
+                                        submodule,
                                     }
                                 }
                                 LineOrHexHash::HexHashOfBinaryBlob(id) => {
@@ -334,6 +752,9 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
     )
     .context("failed to print diff")?;
 
+    diff_files.retain(|path, file: &mut FileDiff| {
+        !(file.hunks.is_empty() && content_changes.contains(path))
+    });
     for file in diff_files.values_mut() {
         if let Some(binary_hunk) = file
             .hunks
@@ -348,11 +769,53 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
         } else if file.hunks.is_empty() {
             file.hunks = vec![GitHunk::generic_new_file()];
         }
+
+        if let Some(similarity) = file.similarity {
+            // even a file that was moved without changing has a hunk, which carries the move
+            for hunk in &mut file.hunks {
+                hunk.change_type = similarity.change_type;
+                hunk.old_path.clone_from(&file.old_path);
+            }
+        }
     }
 
     Ok(diff_files)
 }
 
+/// How `delta` moves a submodule to another commit, if it is about a submodule.
+/// With `repo`, the commit of a submodule in the worktree is looked up if `delta` doesn't know it.
+fn submodule_change(
+    repo: Option<&Repository>,
+    delta: &git2::DiffDelta<'_>,
+) -> Option<SubmoduleChange> {
+    let is_submodule = |file: &git2::DiffFile<'_>| file.mode() == git2::FileMode::Commit;
+    let (old, new) = (delta.old_file(), delta.new_file());
+    if !is_submodule(&old) && !is_submodule(&new) {
+        return None;
+    }
+    let commit_id = |file: &git2::DiffFile<'_>| -> Option<git::Oid> {
+        (is_submodule(file) && !file.id().is_zero()).then(|| file.id().into())
+    };
+    let new_id = commit_id(&new).or_else(|| {
+        if !is_submodule(&new) {
+            return None;
+        }
+        // submodules that aren't tracked yet are only known by their path
+        submodule_head(repo?, new.path()?)
+    });
+    Some(SubmoduleChange {
+        old_id: commit_id(&old),
+        new_id,
+    })
+}
+
+/// The commit checked out in the submodule at `rel_path` of the worktree of `repo`, if any.
+fn submodule_head(repo: &Repository, rel_path: &Path) -> Option<git::Oid> {
+    let path = repo.workdir()?.join(rel_path);
+    let head = git2::Repository::open(path).ok()?.head().ok()?.target()?;
+    Some(head.into())
+}
+
 // returns None if it cannot reverse the patch header
 fn reverse_patch_header(header: &BStr) -> Option<BString> {
     let mut parts = header.split(|b| b.is_ascii_whitespace());
@@ -425,6 +888,14 @@ pub fn reverse_hunk(hunk: &GitHunk) -> Option<GitHunk> {
             binary: hunk.binary,
             change_type: hunk.change_type,
             locked_to: Box::new([]),
+            // only tells that this isn't all of the change anymore
+            selection: hunk.selection.clone(),
+            // the reverse of a hunk is about the file it's in
+            old_path: None,
+            submodule: hunk.submodule.map(|submodule| SubmoduleChange {
+                old_id: submodule.new_id,
+                new_id: submodule.old_id,
+            }),
         })
     }
 }
@@ -28,6 +28,8 @@ pub enum Error {
     Other(git2::Error),
     #[error(transparent)]
     Utf8(#[from] Utf8Error),
+    #[error("filter `{command}` failed: {stderr}")]
+    Filter { command: String, stderr: String },
 }
 
 impl From<git2::Error> for Error {
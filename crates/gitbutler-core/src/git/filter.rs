@@ -0,0 +1,180 @@
+//! Content filters that git runs through external commands, like `git lfs` does to keep large files
+//! out of the repository. A path is filtered if its `filter` attribute names a driver whose commands
+//! are configured as `filter.<driver>.clean` and `filter.<driver>.smudge`.
+//! `git2` doesn't run these, so we do it ourselves where it matters.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    rc::Rc,
+    sync::{Mutex, MutexGuard, OnceLock},
+    thread,
+    time::SystemTime,
+};
+
+use super::{Error, Result};
+
+/// The blobs that worktree files were cleaned into, by their absolute path.
+/// A file is only cleaned again once its size or modification time changes, as the status of
+/// the worktree is computed all the time and filters like `git lfs` are slow on large files.
+fn cleaned() -> MutexGuard<'static, HashMap<PathBuf, Cleaned>> {
+    static CLEANED: OnceLock<Mutex<HashMap<PathBuf, Cleaned>>> = OnceLock::new();
+    CLEANED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+struct Cleaned {
+    command: String,
+    len: u64,
+    modified: SystemTime,
+    blob_id: git2::Oid,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Direction {
+    /// Turn worktree content into what is stored in git.
+    Clean,
+    /// Turn what is stored in git into worktree content.
+    Smudge,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Clean => "clean",
+            Direction::Smudge => "smudge",
+        }
+    }
+}
+
+/// The command that filters `rel_path` in `direction`, if there is one.
+pub(super) fn command(
+    repo: &git2::Repository,
+    rel_path: &Path,
+    direction: Direction,
+) -> Result<Option<String>> {
+    let attr = repo.get_attr(rel_path, "filter", git2::AttrCheckFlags::FILE_THEN_INDEX)?;
+    let driver = match git2::AttrValue::from_string(attr) {
+        git2::AttrValue::String(driver) => driver.to_owned(),
+        _ => return Ok(None),
+    };
+    let key = format!("filter.{driver}.{}", direction.as_str());
+    match repo.config()?.get_string(&key) {
+        Ok(command) => Ok(Some(command)),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Run the filter `command` for `rel_path` with `input` on its standard input, like git does.
+pub(super) fn run(
+    repo: &git2::Repository,
+    command: &str,
+    rel_path: &Path,
+    input: &[u8],
+) -> Result<Vec<u8>> {
+    let command = command.replace("%f", &shell_quote(rel_path));
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .current_dir(repo.workdir().unwrap_or(repo.path()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("piped");
+    // large inputs would fill up the pipes if we didn't read while writing
+    let output = thread::scope(|scope| {
+        scope.spawn(move || {
+            // the filter is free to exit without reading its input
+            let _ = stdin.write_all(input);
+        });
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(Error::Filter {
+            command,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(output.stdout)
+}
+
+/// Pass the worktree file at `rel_path` through the clean filter `command` and store the result
+/// as a blob, unless the file didn't change since it was last cleaned.
+pub(super) fn clean(repo: &git2::Repository, command: &str, rel_path: &Path) -> Result<git2::Oid> {
+    let path = repo.workdir().unwrap_or(repo.path()).join(rel_path);
+    let metadata = std::fs::metadata(&path)?;
+    let (len, modified) = (metadata.len(), metadata.modified()?);
+
+    let known_blob_id = cleaned()
+        .get(&path)
+        .filter(|known| known.command == command && known.len == len && known.modified == modified)
+        .map(|known| known.blob_id);
+    if let Some(blob_id) = known_blob_id {
+        // the blob may have been pruned since
+        if repo.odb()?.exists(blob_id) {
+            return Ok(blob_id);
+        }
+    }
+
+    let content = run(repo, command, rel_path, &std::fs::read(&path)?)?;
+    let blob_id = repo.blob(&content)?;
+    cleaned().insert(
+        path,
+        Cleaned {
+            command: command.to_owned(),
+            len,
+            modified,
+            blob_id,
+        },
+    );
+    Ok(blob_id)
+}
+
+/// Collect the paths `checkout_builder` writes to the worktree, to [`smudge()`] them afterwards.
+pub(super) fn track_updates(
+    checkout_builder: &mut git2::build::CheckoutBuilder<'_>,
+) -> Rc<RefCell<Vec<PathBuf>>> {
+    let updated = Rc::new(RefCell::new(Vec::new()));
+    let paths = Rc::clone(&updated);
+    checkout_builder
+        .notify_on(git2::CheckoutNotificationType::UPDATED)
+        .notify(move |_, path, _, _, _| {
+            if let Some(path) = path {
+                paths.borrow_mut().push(path.to_owned());
+            }
+            true
+        });
+    updated
+}
+
+/// Replace what a checkout wrote to `rel_paths` with the output of their smudge filter,
+/// if they have one.
+pub(super) fn smudge(repo: &git2::Repository, rel_paths: &[PathBuf]) -> Result<()> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(());
+    };
+    for rel_path in rel_paths {
+        let Some(command) = command(repo, rel_path, Direction::Smudge)? else {
+            continue;
+        };
+        let path = workdir.join(rel_path);
+        if !path.is_file() {
+            continue;
+        }
+        let content = run(repo, &command, rel_path, &std::fs::read(&path)?)?;
+        std::fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
@@ -1,6 +1,8 @@
 pub mod credentials;
 pub mod diff;
 
+mod filter;
+
 mod blob;
 pub use blob::*;
 
@@ -34,6 +36,9 @@ pub use oid::*;
 mod signature;
 pub use signature::*;
 
+mod signing;
+pub use signing::*;
+
 mod config;
 pub use config::*;
 
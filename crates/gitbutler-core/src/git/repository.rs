@@ -1,13 +1,18 @@
-use std::{io::Write, path::Path, str};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str,
+};
 
 use git2::{BlameOptions, Submodule};
 use git2_hooks::HookResult;
 
 use super::{
-    Blob, Branch, Commit, Config, Index, Oid, Reference, Refname, Remote, Result, Signature, Tree,
-    TreeBuilder, Url,
+    filter, Blob, Branch, Commit, Config, Index, Oid, Reference, Refname, Remote, Result,
+    Signature, Signer, Tree, TreeBuilder, Url,
 };
-use crate::{keys, path::Normalize};
+use crate::path::Normalize;
 
 // wrapper around git2::Repository to get control over how it's used.
 pub struct Repository(git2::Repository);
@@ -231,6 +236,17 @@ impl Repository {
             .map_err(Into::into)
     }
 
+    /// Like [`Self::blob_path()`], but passes the worktree file at `rel_path` through the clean filter
+    /// that its `filter` attribute asks for, which is how `git lfs` turns it into a pointer.
+    /// Returns `None` if no clean filter applies to it.
+    /// The filter only runs again once the size or modification time of the file changed.
+    pub fn blob_path_cleaned(&self, rel_path: &Path) -> Result<Option<Oid>> {
+        let Some(command) = filter::command(&self.0, rel_path, filter::Direction::Clean)? else {
+            return Ok(None);
+        };
+        filter::clean(&self.0, &command, rel_path).map(|blob_id| Some(blob_id.into()))
+    }
+
     pub fn cherry_pick(&self, base: &Commit, target: &Commit) -> Result<Index> {
         self.0
             .cherrypick_commit(target.into(), base.into(), 0, None)
@@ -271,10 +287,11 @@ impl Repository {
     pub fn commit_signed(
         &self,
         author: &Signature<'_>,
+        committer: &Signature<'_>,
         message: &str,
         tree: &Tree<'_>,
         parents: &[&Commit<'_>],
-        key: &keys::PrivateKey,
+        signer: &dyn Signer,
     ) -> Result<Oid> {
         let parents: Vec<&git2::Commit> = parents
             .iter()
@@ -282,15 +299,13 @@ impl Repository {
             .collect::<Vec<_>>();
         let commit_buffer = self.0.commit_create_buffer(
             author.into(),
-            // author and committer must be the same
-            // for signed commits
-            author.into(),
+            committer.into(),
             message,
             tree.into(),
             &parents,
         )?;
         let commit_buffer = str::from_utf8(&commit_buffer).unwrap();
-        let signature = key.sign(commit_buffer.as_bytes())?;
+        let signature = signer.sign(commit_buffer.as_bytes())?;
         self.0
             .commit_signed(commit_buffer, &signature, None)
             .map(Into::into)
@@ -352,6 +367,17 @@ impl Repository {
         self.0.find_remote(name).map(Into::into).map_err(Into::into)
     }
 
+    /// Returns the names of all configured remotes.
+    pub fn remotes(&self) -> Result<Vec<String>> {
+        Ok(self
+            .0
+            .remotes()?
+            .iter()
+            .flatten()
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
     pub fn find_branch(&self, name: &Refname) -> Result<Branch> {
         self.0
             .find_branch(
@@ -390,12 +416,19 @@ impl Repository {
         let mut builder = git2::build::CheckoutBuilder::new();
         builder.path(path.as_ref());
         builder.force();
+        let updated = filter::track_updates(&mut builder);
 
         let mut index = self.0.index()?;
         self.0
             .checkout_index(Some(&mut index), Some(&mut builder))?;
 
-        Ok(())
+        filter::smudge(&self.0, &updated.borrow())?;
+        match index.get_path(path.as_ref(), 0) {
+            Some(entry) if entry.mode == u32::from(git2::FileMode::Commit) => {
+                checkout_submodule(&self.0, path.as_ref(), entry.id)
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn checkout_tree<'a>(&'a self, tree: &'a Tree<'a>) -> CheckoutTreeBuidler {
@@ -479,6 +512,70 @@ impl Repository {
         Ok(())
     }
 
+    /// Runs the `pre-push` hook with `remote_name` and `remote_url` as arguments and one line per entry of `updates`
+    /// on its standard input, just like `git push` does. Returns `None` if there is no such hook.
+    pub fn run_hook_pre_push(
+        &self,
+        remote_name: &str,
+        remote_url: &str,
+        updates: &[RefUpdate],
+    ) -> Result<Option<HookOutput>> {
+        let Some(hook) = self.find_hook("pre-push") else {
+            return Ok(None);
+        };
+
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("sh");
+            command.arg(&hook);
+            command
+        } else {
+            Command::new(&hook)
+        };
+        let mut child = command
+            .args([remote_name, remote_url])
+            .current_dir(self.0.workdir().unwrap_or(self.0.path()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let input = updates.iter().fold(String::new(), |mut input, update| {
+            input.push_str(&format!(
+                "{} {} {} {}\n",
+                update.local_ref, update.local_oid, update.remote_ref, update.remote_oid
+            ));
+            input
+        });
+        if let Some(mut stdin) = child.stdin.take() {
+            // the hook is free to exit without reading its input
+            let _ = stdin.write_all(input.as_bytes());
+        }
+
+        let output = child.wait_with_output()?;
+        Ok(Some(HookOutput {
+            hook,
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }))
+    }
+
+    /// Returns the path of the executable hook `name` in `core.hooksPath`, or in `.git/hooks` if that isn't set.
+    fn find_hook(&self, name: &str) -> Option<PathBuf> {
+        let hooks_dir = self
+            .0
+            .config()
+            .ok()
+            .and_then(|config| config.get_path("core.hooksPath").ok())
+            .map(|path| match self.0.workdir() {
+                Some(workdir) if path.is_relative() => workdir.join(path),
+                _ => path,
+            })
+            .unwrap_or_else(|| self.0.path().join("hooks"));
+
+        Some(hooks_dir.join(name)).filter(|path| is_executable(path))
+    }
+
     pub fn blame(
         &self,
         path: &Path,
@@ -516,12 +613,48 @@ impl CheckoutTreeBuidler<'_> {
     }
 
     pub fn checkout(&mut self) -> Result<()> {
+        let updated = filter::track_updates(&mut self.checkout_builder);
         self.repo
-            .checkout_tree(self.tree.as_object(), Some(&mut self.checkout_builder))
-            .map_err(Into::into)
+            .checkout_tree(self.tree.as_object(), Some(&mut self.checkout_builder))?;
+        filter::smudge(self.repo, &updated.borrow())?;
+        for submodule in self.repo.submodules()? {
+            match self.tree.get_path(submodule.path()) {
+                Ok(entry) if entry.filemode() == i32::from(git2::FileMode::Commit) => {
+                    checkout_submodule(self.repo, submodule.path(), entry.id())?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
     }
 }
 
+/// Move the checkout of the submodule at `rel_path` to `commit_id`, as checking out the repository
+/// that has it only changes its gitlink. Submodules that aren't checked out are left alone, and
+/// changes in their worktree that are in the way are an error.
+fn checkout_submodule(
+    repo: &git2::Repository,
+    rel_path: &Path,
+    commit_id: git2::Oid,
+) -> Result<()> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(());
+    };
+    let Ok(submodule) = git2::Repository::open(workdir.join(rel_path)) else {
+        return Ok(());
+    };
+    if submodule.head().ok().and_then(|head| head.target()) == Some(commit_id) {
+        return Ok(());
+    }
+    let commit = submodule.find_commit(commit_id)?;
+    submodule.checkout_tree(
+        commit.as_object(),
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    )?;
+    submodule.set_head_detached(commit_id)?;
+    Ok(())
+}
+
 pub struct CheckoutIndexBuilder<'a> {
     repo: &'a git2::Repository,
     index: &'a mut git2::Index,
@@ -545,8 +678,50 @@ impl CheckoutIndexBuilder<'_> {
     }
 
     pub fn checkout(&mut self) -> Result<()> {
+        let updated = filter::track_updates(&mut self.checkout_builder);
         self.repo
-            .checkout_index(Some(&mut self.index), Some(&mut self.checkout_builder))
-            .map_err(Into::into)
+            .checkout_index(Some(&mut self.index), Some(&mut self.checkout_builder))?;
+        filter::smudge(self.repo, &updated.borrow())?;
+        for submodule in self.repo.submodules()? {
+            match self.index.get_path(submodule.path(), 0) {
+                Some(entry) if entry.mode == u32::from(git2::FileMode::Commit) => {
+                    checkout_submodule(self.repo, submodule.path(), entry.id)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
     }
 }
+
+/// A ref update that is about to be pushed, as the `pre-push` hook sees it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefUpdate {
+    pub local_ref: String,
+    pub local_oid: Oid,
+    pub remote_ref: String,
+    /// The current value of the remote ref, or the zero id if the push creates it.
+    pub remote_oid: Oid,
+}
+
+/// The result of a hook run by us rather than by `git2_hooks`, which keeps the output of successful runs too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookOutput {
+    pub hook: PathBuf,
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,147 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use crate::keys;
+
+/// Produces the signature stored in a commit's `gpgsig` header.
+pub trait Signer {
+    fn sign(&self, buffer: &[u8]) -> Result<String, keys::SignError>;
+}
+
+impl Signer for keys::PrivateKey {
+    fn sign(&self, buffer: &[u8]) -> Result<String, keys::SignError> {
+        keys::PrivateKey::sign(self, buffer)
+    }
+}
+
+/// The value of git's `gpg.format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureFormat {
+    #[default]
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl FromStr for SignatureFormat {
+    type Err = keys::SignError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "openpgp" => Ok(Self::OpenPgp),
+            "x509" => Ok(Self::X509),
+            "ssh" => Ok(Self::Ssh),
+            _ => Err(keys::SignError::UnsupportedFormat(s.to_string())),
+        }
+    }
+}
+
+/// Signs by shelling out to the program git would use, like `gpg`, `gpgsm` or `ssh-keygen`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramSigner {
+    pub format: SignatureFormat,
+    pub program: String,
+    /// The value of `user.signingkey`.
+    pub signing_key: String,
+}
+
+impl Signer for ProgramSigner {
+    fn sign(&self, buffer: &[u8]) -> Result<String, keys::SignError> {
+        match self.format {
+            SignatureFormat::OpenPgp | SignatureFormat::X509 => self.sign_gpg(buffer),
+            SignatureFormat::Ssh => self.sign_ssh(buffer),
+        }
+    }
+}
+
+impl ProgramSigner {
+    fn sign_gpg(&self, buffer: &[u8]) -> Result<String, keys::SignError> {
+        let mut child = Command::new(&self.program)
+            .args(["--status-fd=2", "-bsau", &self.signing_key])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(buffer)?;
+        let output = child.wait_with_output()?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // like git, only trust the signature if gpg reports having created it
+        if !output.status.success() || !stderr.contains("[GNUPG:] SIG_CREATED ") {
+            return Err(self.failed(&stderr));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn sign_ssh(&self, buffer: &[u8]) -> Result<String, keys::SignError> {
+        let files = TempFiles::default();
+
+        let buffer_path = files.path("buffer");
+        std::fs::write(&buffer_path, buffer)?;
+
+        let mut command = Command::new(&self.program);
+        command.args(["-Y", "sign", "-n", "git", "-f"]);
+        if let Some(literal_key) = self.literal_ssh_key() {
+            // a public key given inline is looked up in the ssh agent
+            let key_path = files.path("key.pub");
+            std::fs::write(&key_path, literal_key)?;
+            command.arg(&key_path).arg("-U");
+        } else {
+            command.arg(&self.signing_key);
+        }
+        let output = command.arg(&buffer_path).stdin(Stdio::null()).output()?;
+
+        if !output.status.success() {
+            return Err(self.failed(&String::from_utf8_lossy(&output.stderr)));
+        }
+        let mut signature_path = buffer_path.into_os_string();
+        signature_path.push(".sig");
+        Ok(std::fs::read_to_string(signature_path)?)
+    }
+
+    fn literal_ssh_key(&self) -> Option<&str> {
+        self.signing_key.strip_prefix("key::").or_else(|| {
+            self.signing_key
+                .starts_with("ssh-")
+                .then_some(&*self.signing_key)
+        })
+    }
+
+    fn failed(&self, stderr: &str) -> keys::SignError {
+        keys::SignError::Program {
+            program: self.program.clone(),
+            stderr: stderr.trim().to_string(),
+        }
+    }
+}
+
+/// A scratch directory for the files `ssh-keygen` reads and writes, removed on drop.
+struct TempFiles(PathBuf);
+
+impl Default for TempFiles {
+    fn default() -> Self {
+        Self(std::env::temp_dir().join(format!("gitbutler-sign-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl TempFiles {
+    fn path(&self, name: &str) -> PathBuf {
+        // failing to create the directory surfaces as an error when writing to it
+        let _ = std::fs::create_dir_all(&self.0);
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
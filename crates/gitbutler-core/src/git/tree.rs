@@ -95,6 +95,8 @@ pub enum FileMode {
     BlobExecutable,
     Link,
     Tree,
+    /// A submodule, which points to a commit of another repository.
+    Commit,
 }
 
 impl From<FileMode> for git2::FileMode {
@@ -104,6 +106,7 @@ impl From<FileMode> for git2::FileMode {
             FileMode::BlobExecutable => git2::FileMode::BlobExecutable,
             FileMode::Link => git2::FileMode::Link,
             FileMode::Tree => git2::FileMode::Tree,
+            FileMode::Commit => git2::FileMode::Commit,
         }
     }
 }
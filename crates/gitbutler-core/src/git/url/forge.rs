@@ -0,0 +1,135 @@
+use std::str::FromStr;
+
+use bstr::ByteSlice;
+use serde::{Deserialize, Serialize};
+
+use super::{Scheme, Url};
+
+/// The kind of service hosting a remote, which decides how its web pages and API look.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+    /// Gitea and its fork Forgejo, which share their URL layout and API.
+    Gitea,
+    Bitbucket,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("unknown forge '{0}', expected one of github, gitlab, gitea, forgejo or bitbucket")]
+pub struct UnknownForgeError(String);
+
+impl FromStr for ForgeKind {
+    type Err = UnknownForgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "github" => Ok(ForgeKind::GitHub),
+            "gitlab" => Ok(ForgeKind::GitLab),
+            "gitea" | "forgejo" => Ok(ForgeKind::Gitea),
+            "bitbucket" => Ok(ForgeKind::Bitbucket),
+            _ => Err(UnknownForgeError(s.to_string())),
+        }
+    }
+}
+
+impl ForgeKind {
+    /// The forge behind `host`, if it's one of the well-known public instances or named like
+    /// a self-hosted one usually is, e.g. `gitlab.example.com`.
+    pub fn from_host(host: &str) -> Option<Self> {
+        let host = host.to_ascii_lowercase();
+        let first_label = host.split('.').next().unwrap_or_default();
+        if host.contains("github.com") {
+            Some(ForgeKind::GitHub)
+        } else if host == "gitlab.com" || first_label == "gitlab" {
+            Some(ForgeKind::GitLab)
+        } else if matches!(host.as_str(), "codeberg.org" | "gitea.com")
+            || matches!(first_label, "gitea" | "forgejo")
+        {
+            Some(ForgeKind::Gitea)
+        } else if host.contains("bitbucket.org") {
+            Some(ForgeKind::Bitbucket)
+        } else {
+            None
+        }
+    }
+}
+
+impl Url {
+    /// The forge hosting this remote, as far as its host tells.
+    pub fn forge_kind(&self) -> Option<ForgeKind> {
+        self.host.as_deref().and_then(ForgeKind::from_host)
+    }
+
+    /// The address of the web page of the repository this remote points to, if it's
+    /// reachable over the network.
+    pub fn web_url(&self) -> Option<String> {
+        let host = self.host.as_deref()?;
+        let port = match (&self.scheme, self.port) {
+            // ports of ssh remotes have nothing to do with the web server
+            (Scheme::Http | Scheme::Https, Some(port)) => format!(":{port}"),
+            (Scheme::Http | Scheme::Https | Scheme::Ssh | Scheme::Git, _) => String::new(),
+            _ => return None,
+        };
+        let scheme = if self.scheme == Scheme::Http {
+            "http"
+        } else {
+            "https"
+        };
+        let path = self.path.to_str().ok()?.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        Some(format!("{scheme}://{host}{port}/{path}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_host_test() {
+        for (host, expected) in [
+            ("github.com", Some(ForgeKind::GitHub)),
+            ("gitlab.com", Some(ForgeKind::GitLab)),
+            ("gitlab.example.com", Some(ForgeKind::GitLab)),
+            ("codeberg.org", Some(ForgeKind::Gitea)),
+            ("gitea.example.com", Some(ForgeKind::Gitea)),
+            ("forgejo.example.com", Some(ForgeKind::Gitea)),
+            ("bitbucket.org", Some(ForgeKind::Bitbucket)),
+            ("git.example.com", None),
+            ("notgitlab.com", None),
+        ] {
+            assert_eq!(ForgeKind::from_host(host), expected, "test case {host}");
+        }
+    }
+
+    #[test]
+    fn web_url_test() {
+        for (input, expected) in [
+            (
+                "git@github.com:gitbutlerapp/gitbutler.git",
+                Some("https://github.com/gitbutlerapp/gitbutler"),
+            ),
+            (
+                "ssh://git@gitlab.example.com:2222/group/subgroup/repo.git",
+                Some("https://gitlab.example.com/group/subgroup/repo"),
+            ),
+            (
+                "http://gitea.local:3000/owner/repo",
+                Some("http://gitea.local:3000/owner/repo"),
+            ),
+            ("file:///srv/repo.git", None),
+        ] {
+            let url: Url = input.parse().unwrap();
+            assert_eq!(url.web_url().as_deref(), expected, "test case {input}");
+        }
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!("GitLab".parse(), Ok(ForgeKind::GitLab));
+        assert_eq!("forgejo".parse(), Ok(ForgeKind::Gitea));
+        assert!("sourcehut".parse::<ForgeKind>().is_err());
+    }
+}
@@ -11,6 +11,12 @@ pub struct PrivateKey(ssh_key::PrivateKey);
 pub enum SignError {
     #[error(transparent)]
     Ssh(#[from] ssh_key::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{program} failed to sign the data: {stderr}")]
+    Program { program: String, stderr: String },
+    #[error("unsupported gpg.format: {0}")]
+    UnsupportedFormat(String),
 }
 
 impl PrivateKey {
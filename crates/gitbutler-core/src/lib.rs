@@ -19,6 +19,7 @@ pub mod database;
 pub mod dedup;
 pub mod deltas;
 pub mod error;
+pub mod forge;
 pub mod fs;
 pub mod gb_repository;
 pub mod git;
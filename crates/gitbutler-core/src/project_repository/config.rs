@@ -23,6 +23,56 @@ impl Config<'_> {
         Ok(sign_commits)
     }
 
+    /// The program git itself would sign commits with, going by `gpg.format` and
+    /// `user.signingkey`, or `None` if no signing key is configured.
+    pub fn program_signer(&self) -> Result<Option<git::ProgramSigner>, git::Error> {
+        let config = self.git_repository.config()?;
+        let Some(signing_key) = config.get_string("user.signingkey")? else {
+            return Ok(None);
+        };
+        let format = match config.get_string("gpg.format")? {
+            Some(format) => format.parse()?,
+            None => git::SignatureFormat::default(),
+        };
+        let program = match format {
+            git::SignatureFormat::OpenPgp => config
+                .get_string("gpg.openpgp.program")?
+                .or(config.get_string("gpg.program")?)
+                .unwrap_or_else(|| "gpg".to_string()),
+            git::SignatureFormat::X509 => config
+                .get_string("gpg.x509.program")?
+                .unwrap_or_else(|| "gpgsm".to_string()),
+            git::SignatureFormat::Ssh => config
+                .get_string("gpg.ssh.program")?
+                .unwrap_or_else(|| "ssh-keygen".to_string()),
+        };
+        Ok(Some(git::ProgramSigner {
+            format,
+            program,
+            signing_key,
+        }))
+    }
+
+    /// The forge hosting `url`, as configured for its host with `gitbutler.<host>.forge`, which is
+    /// how self-hosted instances are recognized, or as detected from the host otherwise.
+    pub fn forge_kind(&self, url: &git::Url) -> Result<Option<git::ForgeKind>, git::Error> {
+        let Some(host) = url.host.as_deref() else {
+            return Ok(None);
+        };
+        let configured = self
+            .git_repository
+            .config()?
+            .get_string(&format!("gitbutler.{host}.forge"))?;
+        match configured.map(|kind| kind.parse::<git::ForgeKind>()) {
+            Some(Ok(kind)) => Ok(Some(kind)),
+            Some(Err(error)) => {
+                tracing::warn!(%host, %error, "ignoring configured forge");
+                Ok(url.forge_kind())
+            }
+            None => Ok(url.forge_kind()),
+        }
+    }
+
     pub fn user_real_comitter(&self) -> Result<bool, git::Error> {
         let gb_comitter = self
             .git_repository
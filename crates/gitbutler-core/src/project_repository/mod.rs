@@ -1,8 +1,10 @@
 mod config;
 pub mod conflicts;
+pub mod remote_backend;
 mod repository;
 
 pub use config::Config;
+pub use remote_backend::RemoteBackend;
 pub use repository::{LogUntil, OpenError, RemoteError, Repository};
 
 pub mod signatures;
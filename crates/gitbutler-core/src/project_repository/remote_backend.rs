@@ -0,0 +1,710 @@
+use std::{future::Future, sync::Arc};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use super::{RemoteError, Repository};
+pub use gitbutler_git::{Progress, Stage};
+
+use crate::{
+    askpass::{self, AskpassBroker},
+    error::Code,
+    git::{self, credentials::Credential, Url},
+    projects::{self, ProjectId, RemoteBackendKind},
+    ssh,
+};
+
+/// The other end of a fetch or push.
+pub enum Target<'a> {
+    /// A remote configured in the project's repository, authenticated the way the project is set up to.
+    Named {
+        project_repository: &'a Repository,
+        name: &'a str,
+        credentials: &'a git::credentials::Helper,
+        /// Where prompts of the git executable go, if anywhere.
+        askpass: Option<(AskpassBroker, askpass::Context)>,
+    },
+    /// A GitButler server, authenticated with the user's access token.
+    GitButler {
+        /// The repository to fetch into or push from.
+        repository: &'a git::Repository,
+        url: &'a Url,
+        access_token: &'a str,
+    },
+}
+
+impl Target<'_> {
+    fn auth_header(&self) -> Option<String> {
+        match self {
+            Target::Named { .. } => None,
+            Target::GitButler { access_token, .. } => {
+                Some(format!("Authorization: {access_token}"))
+            }
+        }
+    }
+}
+
+/// A branch that a push may only overwrite if the remote still has it where it's expected to be.
+pub struct Lease<'a> {
+    pub branch: &'a git::RemoteRefname,
+    /// Where the branch is expected to be, or `None` if it's expected not to exist.
+    pub expected: Option<git::Oid>,
+}
+
+impl Lease<'_> {
+    fn refname(&self) -> String {
+        format!("refs/heads/{}", self.branch.branch())
+    }
+}
+
+/// Whether progress was made fetching or pushing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Fetch,
+    Push,
+}
+
+/// How far a fetch or push of a project got, for showing to the user.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEvent {
+    pub project_id: ProjectId,
+    pub remote: String,
+    pub direction: Direction,
+    pub progress: Progress,
+}
+
+/// Receives the progress of fetches and pushes of projects.
+pub type OnProgress = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FetchOptions {
+    /// Whether remote-tracking refs that are gone from the remote are removed.
+    pub prune: bool,
+    /// Whether all tags are fetched, rather than only those pointing into fetched history.
+    pub tags: bool,
+}
+
+/// An implementation of the operations that talk to remotes. Both report how far they got to `on_progress`
+/// while they run.
+pub trait RemoteBackend {
+    /// Fetch `refspecs` from `target`.
+    fn fetch(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        options: FetchOptions,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError>;
+
+    /// Push `refspecs` to `target`. With a `lease`, the push is rejected with [`RemoteError::LeaseExpired`]
+    /// if the leased branch moved, even if the refspecs are forced.
+    fn push(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        lease: Option<&Lease<'_>>,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError>;
+}
+
+/// Returns the backend that `project` is set up to fetch and push with.
+pub fn for_project(project: &projects::Project) -> Box<dyn RemoteBackend> {
+    let git_cli = GitCli {
+        omit_certificate_check: project.omit_certificate_check.unwrap_or(false),
+    };
+    match project.remote_backend_kind() {
+        RemoteBackendKind::Git2 => Box::new(Git2 {
+            project_id: project.id,
+            omit_certificate_check: git_cli.omit_certificate_check,
+        }),
+        RemoteBackendKind::GitCli => Box::new(git_cli),
+        RemoteBackendKind::Gix => Box::new(Gix { git_cli }),
+    }
+}
+
+/// Returns the backend that talks to GitButler servers, which is libgit2 whatever the project is set up with,
+/// as the access token is sent as a header only it is known to send along.
+pub fn for_gitbutler(project: &projects::Project) -> Box<dyn RemoteBackend> {
+    Box::new(Git2 {
+        project_id: project.id,
+        omit_certificate_check: project.omit_certificate_check.unwrap_or(false),
+    })
+}
+
+/// Talks to remotes with libgit2.
+pub struct Git2 {
+    project_id: ProjectId,
+    omit_certificate_check: bool,
+}
+
+impl Git2 {
+    /// The remotes to try, each with the credentials to try it with, in order.
+    fn attempts<'a>(
+        &self,
+        target: &Target<'a>,
+    ) -> Result<Vec<(git::Remote<'a>, Vec<Credential>)>, RemoteError> {
+        match target {
+            Target::Named {
+                project_repository,
+                name,
+                credentials,
+                ..
+            } => {
+                let attempts = (*credentials).help(project_repository, name)?;
+                if !self.omit_certificate_check {
+                    for (remote, _) in &attempts {
+                        if let Some(url) = remote.url().context("failed to get remote url")? {
+                            ssh::check_known_host(&url).context("failed to check known host")?;
+                        }
+                    }
+                }
+                Ok(attempts)
+            }
+            Target::GitButler {
+                repository, url, ..
+            } => {
+                let remote = repository
+                    .remote_anonymous(url)
+                    .with_context(|| format!("failed to create anonymous remote for {url}"))?;
+                Ok(vec![(remote, vec![Credential::Noop])])
+            }
+        }
+    }
+
+    fn callbacks<'cb>(&self, credential: Credential) -> git2::RemoteCallbacks<'cb> {
+        let mut callbacks: git2::RemoteCallbacks = credential.into();
+        if self.omit_certificate_check {
+            callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+        }
+        callbacks
+    }
+}
+
+impl RemoteBackend for Git2 {
+    fn fetch(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        options: FetchOptions,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError> {
+        let auth_header = target.auth_header();
+        let headers = auth_header.iter().map(String::as_str).collect::<Vec<_>>();
+        for (mut remote, credentials) in self.attempts(target)? {
+            for credential in credentials {
+                let mut cbs = self.callbacks(credential);
+                cbs.transfer_progress(|stats| {
+                    on_progress(Progress::Objects {
+                        stage: Stage::Receiving,
+                        current: stats.received_objects(),
+                        total: Some(stats.total_objects()),
+                    });
+                    if stats.total_deltas() > 0 {
+                        on_progress(Progress::Objects {
+                            stage: Stage::Resolving,
+                            current: stats.indexed_deltas(),
+                            total: Some(stats.total_deltas()),
+                        });
+                    }
+                    on_progress(Progress::Bytes {
+                        transferred: stats.received_bytes(),
+                    });
+                    true
+                });
+                cbs.update_tips(|refname, _old, _new| {
+                    on_progress(Progress::RefUpdate {
+                        refname: refname.to_owned(),
+                        error: None,
+                    });
+                    true
+                });
+                let mut fetch_opts = git2::FetchOptions::new();
+                fetch_opts.remote_callbacks(cbs);
+                fetch_opts.custom_headers(&headers);
+                fetch_opts.prune(if options.prune {
+                    git2::FetchPrune::On
+                } else {
+                    git2::FetchPrune::Off
+                });
+                if options.tags {
+                    fetch_opts.download_tags(git2::AutotagOption::All);
+                }
+
+                match remote.fetch(refspecs, Some(&mut fetch_opts)) {
+                    Ok(()) => return Ok(()),
+                    Err(git::Error::Auth(error) | git::Error::Http(error)) => {
+                        tracing::warn!(project_id = %self.project_id, ?error, "fetch failed");
+                        continue;
+                    }
+                    Err(git::Error::Network(error)) => {
+                        tracing::warn!(project_id = %self.project_id, ?error, "fetch failed");
+                        return Err(RemoteError::Network);
+                    }
+                    Err(error) => return Err(RemoteError::Other(error.into())),
+                }
+            }
+        }
+
+        Err(RemoteError::Auth)
+    }
+
+    fn push(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        lease: Option<&Lease<'_>>,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError> {
+        let auth_header = target.auth_header();
+        let headers = auth_header.iter().map(String::as_str).collect::<Vec<_>>();
+        let leased_refname = lease.map(Lease::refname);
+        for (mut remote, credentials) in self.attempts(target)? {
+            let mut update_refs_error: Option<git2::Error> = None;
+            let mut lease_expired = false;
+            for credential in credentials {
+                let mut cbs = self.callbacks(credential);
+                if let (Some(lease), Some(leased_refname)) = (lease, &leased_refname) {
+                    // libgit2 has no leases, so compare what the remote has right before sending anything
+                    cbs.push_negotiation(|updates| {
+                        let expected = lease
+                            .expected
+                            .map(git2::Oid::from)
+                            .unwrap_or(git2::Oid::zero());
+                        if updates.iter().any(|update| {
+                            update.dst_refname() == Some(leased_refname.as_str())
+                                && update.src() != expected
+                        }) {
+                            lease_expired = true;
+                            return Err(git2::Error::from_str("stale info"));
+                        }
+                        Ok(())
+                    });
+                }
+                cbs.push_update_reference(|reference: &str, status: Option<&str>| {
+                    on_progress(Progress::RefUpdate {
+                        refname: reference.to_owned(),
+                        error: status.map(ToOwned::to_owned),
+                    });
+                    if let Some(status) = status {
+                        update_refs_error = Some(git2::Error::from_str(status));
+                        return Err(git2::Error::from_str(status));
+                    };
+                    Ok(())
+                });
+                cbs.pack_progress(|stage, current, total| {
+                    let stage = match stage {
+                        git2::PackBuilderStage::AddingObjects => Stage::Counting,
+                        git2::PackBuilderStage::Deltafication => Stage::Compressing,
+                    };
+                    on_progress(Progress::Objects {
+                        stage,
+                        current,
+                        total: Some(total),
+                    });
+                });
+                cbs.push_transfer_progress(|current, total, bytes| {
+                    on_progress(Progress::Objects {
+                        stage: Stage::Writing,
+                        current,
+                        total: Some(total),
+                    });
+                    on_progress(Progress::Bytes { transferred: bytes });
+                });
+
+                let push_result = remote.push(
+                    refspecs,
+                    Some(
+                        git2::PushOptions::new()
+                            .remote_callbacks(cbs)
+                            .custom_headers(&headers),
+                    ),
+                );
+                match push_result {
+                    Ok(()) => return Ok(()),
+                    Err(git::Error::Auth(error) | git::Error::Http(error)) => {
+                        tracing::warn!(project_id = %self.project_id, ?error, "git push failed");
+                        continue;
+                    }
+                    Err(git::Error::Network(error)) => {
+                        tracing::warn!(project_id = %self.project_id, ?error, "git push failed");
+                        return Err(RemoteError::Network);
+                    }
+                    Err(_) if lease_expired => {
+                        let lease = lease.expect("only leased pushes expire");
+                        return Err(RemoteError::LeaseExpired(lease.branch.clone()));
+                    }
+                    Err(err) => {
+                        if let Some(err) = update_refs_error.as_ref() {
+                            return Err(RemoteError::Other(
+                                anyhow::anyhow!(err.to_string()).context(Code::ProjectGitPush),
+                            ));
+                        }
+                        return Err(RemoteError::Other(err.into()));
+                    }
+                }
+            }
+        }
+
+        Err(RemoteError::Auth)
+    }
+}
+
+/// Talks to remotes with the git executable, which authenticates by itself and asks the user
+/// through askpass when it can't.
+pub struct GitCli {
+    omit_certificate_check: bool,
+}
+
+/// How the git executable is run for an operation.
+struct Invocation {
+    /// Where git runs.
+    path: std::path::PathBuf,
+    /// The name or url of the remote git talks to.
+    remote: String,
+    /// The configuration applied on top of git's own.
+    config: Vec<(&'static str, String)>,
+    /// Where prompts of git go, if anywhere.
+    askpass: Option<(AskpassBroker, askpass::Context)>,
+}
+
+impl Invocation {
+    fn config(&self) -> Vec<(&str, &str)> {
+        self.config
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .collect()
+    }
+}
+
+impl GitCli {
+    fn invocation(&self, target: &Target<'_>) -> Invocation {
+        let mut config = vec![];
+        if self.omit_certificate_check {
+            config.push(("http.sslVerify", "false".to_owned()));
+        }
+        if let Some(auth_header) = target.auth_header() {
+            config.push(("http.extraHeader", auth_header));
+        }
+        match target {
+            Target::Named {
+                project_repository,
+                name,
+                askpass,
+                ..
+            } => Invocation {
+                path: project_repository.path().to_path_buf(),
+                remote: (*name).to_owned(),
+                config,
+                askpass: askpass.clone(),
+            },
+            Target::GitButler {
+                repository, url, ..
+            } => Invocation {
+                path: repository.path().to_path_buf(),
+                remote: url.to_string(),
+                config,
+                askpass: None,
+            },
+        }
+    }
+}
+
+impl RemoteBackend for GitCli {
+    fn fetch(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        options: FetchOptions,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError> {
+        let refspecs = parse_refspecs(refspecs)?;
+        let invocation = self.invocation(target);
+        block_on(move || async move {
+            gitbutler_git::fetch(
+                &invocation.path,
+                gitbutler_git::tokio::TokioExecutor,
+                &invocation.remote,
+                &refspecs,
+                options.prune,
+                options.tags,
+                &invocation.config(),
+                handle_git_prompt,
+                invocation.askpass.clone(),
+                on_progress,
+            )
+            .await
+        })
+        .map_err(|error| from_git_cli_error(error, None))
+    }
+
+    fn push(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        lease: Option<&Lease<'_>>,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError> {
+        let mut refspecs = parse_refspecs(refspecs)?;
+        let force = match lease {
+            Some(lease) => {
+                // the lease is what allows the update to not be a fast-forward, a forced refspec would bypass it
+                refspecs = refspecs
+                    .into_iter()
+                    .map(|refspec| refspec.with_update_non_fastforward(false))
+                    .collect();
+                gitbutler_git::Force::WithLease {
+                    refname: lease.refname(),
+                    expected: lease.expected.map(|oid| oid.to_string()),
+                }
+            }
+            None => gitbutler_git::Force::No,
+        };
+        let invocation = self.invocation(target);
+        block_on(move || async move {
+            gitbutler_git::push(
+                &invocation.path,
+                gitbutler_git::tokio::TokioExecutor,
+                &invocation.remote,
+                &refspecs,
+                force,
+                &invocation.config(),
+                handle_git_prompt,
+                invocation.askpass.clone(),
+                on_progress,
+            )
+            .await
+        })
+        .map_err(|error| from_git_cli_error(error, lease))
+    }
+}
+
+/// Talks to remotes with gitoxide. It can't push yet, so pushes go through the git executable.
+/// Only remotes of the project can be fetched from, not GitButler servers, and the progress of
+/// fetches is reported per updated ref.
+pub struct Gix {
+    git_cli: GitCli,
+}
+
+impl RemoteBackend for Gix {
+    fn fetch(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        options: FetchOptions,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError> {
+        let Target::Named {
+            project_repository,
+            name,
+            ..
+        } = target
+        else {
+            return Err(RemoteError::Other(anyhow::anyhow!(
+                "gitoxide can't fetch from GitButler servers"
+            )));
+        };
+
+        let mut config_overrides = vec![];
+        if self.git_cli.omit_certificate_check {
+            config_overrides.push("http.sslVerify=false".to_owned());
+        }
+        let repo = gix::open_opts(
+            project_repository.path(),
+            gix::open::Options::default().config_overrides(config_overrides),
+        )
+        .context("failed to open repository")?;
+
+        // an anonymous remote, so that only the given refspecs are fetched
+        let url = repo
+            .find_remote(*name)
+            .with_context(|| format!("failed to find remote {name}"))?
+            .url(gix::remote::Direction::Fetch)
+            .cloned()
+            .ok_or(git::credentials::HelpError::NoUrlSet)?;
+        let remote = repo
+            .remote_at(url)
+            .context("failed to create anonymous remote")?
+            .with_refspecs(refspecs.iter().copied(), gix::remote::Direction::Fetch)
+            .context("failed to parse refspecs")?
+            .with_fetch_tags(if options.tags {
+                gix::remote::fetch::Tags::All
+            } else {
+                gix::remote::fetch::Tags::Included
+            });
+
+        let outcome = remote
+            .connect(gix::remote::Direction::Fetch)
+            .map_err(from_gix_error)?
+            .prepare_fetch(gix::progress::Discard, Default::default())
+            .map_err(from_gix_error)?
+            .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+            .map_err(from_gix_error)?;
+
+        let fetched = outcome
+            .ref_map
+            .mappings
+            .iter()
+            .filter_map(|mapping| mapping.local.as_ref())
+            .map(|local| local.to_string())
+            .collect::<Vec<_>>();
+        for refname in &fetched {
+            on_progress(Progress::RefUpdate {
+                refname: refname.clone(),
+                error: None,
+            });
+        }
+
+        if options.prune {
+            prune(&project_repository.git_repository, refspecs, &fetched)?;
+        }
+        Ok(())
+    }
+
+    fn push(
+        &self,
+        target: &Target<'_>,
+        refspecs: &[&str],
+        lease: Option<&Lease<'_>>,
+        on_progress: &(dyn Fn(Progress) + Sync),
+    ) -> Result<(), RemoteError> {
+        self.git_cli.push(target, refspecs, lease, on_progress)
+    }
+}
+
+/// Deletes the refs that the wildcard `refspecs` map to locally but that weren't among the `fetched` refs,
+/// as their branch is gone from the remote.
+fn prune(
+    repository: &git::Repository,
+    refspecs: &[&str],
+    fetched: &[String],
+) -> Result<(), RemoteError> {
+    for refspec in refspecs {
+        let Some((_, destination)) = refspec.split_once(':') else {
+            continue;
+        };
+        if !destination.ends_with('*') {
+            continue;
+        }
+        for reference in repository
+            .references_glob(destination)
+            .context("failed to list references")?
+        {
+            let mut reference = reference.context("failed to read reference")?;
+            let refname = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+            // symbolic refs like `refs/remotes/origin/HEAD` aren't fetched, but point at what was
+            if reference.target().is_some() && !fetched.contains(&refname) {
+                reference
+                    .delete()
+                    .with_context(|| format!("failed to prune {refname}"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_refspecs(refspecs: &[&str]) -> Result<Vec<gitbutler_git::RefSpec>, RemoteError> {
+    refspecs
+        .iter()
+        .map(|refspec| {
+            gitbutler_git::RefSpec::parse(refspec)
+                .with_context(|| format!("failed to parse refspec {refspec}"))
+                .map_err(Into::into)
+        })
+        .collect()
+}
+
+/// Runs the future made by `operation` to completion on a runtime of its own, as the remote
+/// operations aren't async.
+fn block_on<F, Fut>(operation: F) -> Fut::Output
+where
+    F: FnOnce() -> Fut + Send,
+    Fut: Future,
+    Fut::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(move || {
+                tokio::runtime::Runtime::new()
+                    .unwrap()
+                    .block_on(operation())
+            })
+            .join()
+            .unwrap()
+    })
+}
+
+fn from_git_cli_error<E>(error: gitbutler_git::Error<E>, lease: Option<&Lease<'_>>) -> RemoteError
+where
+    E: std::error::Error + core::fmt::Debug + Send + Sync + 'static,
+{
+    match (error, lease) {
+        (gitbutler_git::Error::StaleRef(_), Some(lease)) => {
+            RemoteError::LeaseExpired(lease.branch.clone())
+        }
+        (gitbutler_git::Error::AuthorizationFailed(error), _) => {
+            tracing::warn!(?error, "git executable failed to authenticate");
+            RemoteError::Auth
+        }
+        (gitbutler_git::Error::Unreachable(error), _) => {
+            tracing::warn!(?error, "git executable failed to reach the remote");
+            RemoteError::Network
+        }
+        (error, _) => RemoteError::Other(error.into()),
+    }
+}
+
+fn from_gix_error(error: impl std::error::Error + Send + Sync + 'static) -> RemoteError {
+    use gix::{
+        protocol::{handshake, transport::client},
+        remote::{fetch::prepare, ref_map},
+    };
+
+    // preparing a fetch wraps the errors of the handshake transparently, which leaves them out
+    // of the sources
+    let ref_map_error = match (&error as &(dyn std::error::Error + 'static)).downcast_ref() {
+        Some(prepare::Error::RefMap(ref_map_error)) => Some(ref_map_error),
+        _ => None,
+    };
+    let handshake_error = match ref_map_error {
+        Some(ref_map::Error::Handshake(handshake_error)) => Some(handshake_error),
+        _ => std::iter::successors(
+            Some(&error as &(dyn std::error::Error + 'static)),
+            |cause| cause.source(),
+        )
+        .find_map(|cause| cause.downcast_ref::<handshake::Error>()),
+    };
+    match (handshake_error, ref_map_error) {
+        (
+            Some(
+                handshake::Error::Credentials(_)
+                | handshake::Error::EmptyCredentials
+                | handshake::Error::InvalidCredentials { .. },
+            ),
+            _,
+        ) => {
+            tracing::warn!(?error, "gix failed to authenticate");
+            RemoteError::Auth
+        }
+        (Some(handshake::Error::Transport(client::Error::Io(_))), _)
+        | (_, Some(ref_map::Error::Transport(client::Error::Io(_)))) => {
+            tracing::warn!(?error, "gix failed to reach the remote");
+            RemoteError::Network
+        }
+        _ => RemoteError::Other(error.into()),
+    }
+}
+
+async fn handle_git_prompt(
+    prompt: String,
+    askpass: Option<(AskpassBroker, askpass::Context)>,
+) -> Option<String> {
+    if let Some((askpass_broker, context)) = askpass {
+        tracing::info!("received prompt for {context:?}: {prompt:?}");
+        askpass_broker.submit_prompt(prompt, context).await
+    } else {
+        tracing::warn!("received askpass prompt but no broker was supplied; returning None");
+        None
+    }
+}
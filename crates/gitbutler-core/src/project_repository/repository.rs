@@ -1,21 +1,15 @@
-use std::{
-    path,
-    str::FromStr,
-    sync::{atomic::AtomicUsize, Arc},
-};
+use std::{collections::BTreeMap, path, str::FromStr};
 
 use anyhow::{Context, Result};
 
-use super::conflicts;
+use super::{conflicts, remote_backend, remote_backend::OnProgress, RemoteBackend};
 use crate::error::{AnyhowContextExt, Code, ErrorWithContext};
 use crate::{
     askpass,
     askpass::AskpassBroker,
     error,
     git::{self, credentials::HelpError, Url},
-    keys,
-    projects::{self, AuthKey},
-    ssh, users,
+    keys, projects, users,
     virtual_branches::{Branch, BranchId},
 };
 
@@ -188,9 +182,11 @@ impl Repository {
             &commit_id,
             &refname,
             false,
+            None,
             credentials,
             None,
             askpass.clone(),
+            None,
         ) {
             Ok(()) => Ok(()),
             Err(e) => Err(anyhow::anyhow!(e.to_string())),
@@ -201,9 +197,11 @@ impl Repository {
             &commit_id,
             &refname,
             false,
+            None,
             credentials,
             empty_refspec,
             askpass,
+            None,
         ) {
             Ok(()) => Ok(()),
             Err(e) => Err(anyhow::anyhow!(e.to_string())),
@@ -347,9 +345,33 @@ impl Repository {
         signing_key: Option<&keys::PrivateKey>,
     ) -> Result<git::Oid> {
         let (author, committer) = self.git_signatures(user)?;
-        if let Some(key) = signing_key {
+        if let Some(signer) = self.signer(signing_key)? {
+            self.git_repository
+                .commit_signed(&author, &committer, message, tree, parents, &*signer)
+                .context("failed to commit signed")
+        } else {
+            self.git_repository
+                .commit(None, &author, &committer, message, tree, parents)
+                .context("failed to commit")
+        }
+    }
+
+    /// Writes a new version of `original` with the given message, tree and parents, keeping
+    /// its author. The committer is the configured one, like for any other commit we make,
+    /// and if `signing_key` is set the new commit is signed with it.
+    pub fn recommit(
+        &self,
+        original: &git::Commit,
+        message: &str,
+        tree: &git::Tree,
+        parents: &[&git::Commit],
+        signing_key: Option<&keys::PrivateKey>,
+    ) -> Result<git::Oid> {
+        let (_, committer) = self.git_signatures(None)?;
+        let author = original.author();
+        if let Some(signer) = self.signer(signing_key)? {
             self.git_repository
-                .commit_signed(&author, message, tree, parents, key)
+                .commit_signed(&author, &committer, message, tree, parents, &*signer)
                 .context("failed to commit signed")
         } else {
             self.git_repository
@@ -358,11 +380,55 @@ impl Repository {
         }
     }
 
+    /// Commits are signed the way git is configured to sign them, falling back to
+    /// GitButler's own key when no `user.signingkey` is set.
+    fn signer(
+        &self,
+        signing_key: Option<&keys::PrivateKey>,
+    ) -> Result<Option<Box<dyn git::Signer>>> {
+        let Some(key) = signing_key else {
+            return Ok(None);
+        };
+        let program_signer = self
+            .config()
+            .program_signer()
+            .context("failed to read signing config")?;
+        Ok(Some(match program_signer {
+            Some(program_signer) => Box::new(program_signer),
+            None => Box::new(key.clone()),
+        }))
+    }
+
+    /// Returns the backend that fetches and pushes of the project go through.
+    pub fn remote_backend(&self) -> Box<dyn RemoteBackend> {
+        remote_backend::for_project(&self.project)
+    }
+
+    /// Returns a receiver of the progress of a fetch from or push to `remote`, which passes it on to `on_progress`.
+    fn progress_of<'a>(
+        &self,
+        remote: &'a str,
+        direction: remote_backend::Direction,
+        on_progress: Option<&'a OnProgress>,
+    ) -> impl Fn(remote_backend::Progress) + Sync + 'a {
+        let project_id = self.project.id;
+        move |progress| {
+            if let Some(on_progress) = on_progress {
+                on_progress(remote_backend::ProgressEvent {
+                    project_id,
+                    remote: remote.to_owned(),
+                    direction,
+                    progress,
+                });
+            }
+        }
+    }
+
     pub fn push_to_gitbutler_server(
         &self,
         user: Option<&users::User>,
         ref_specs: &[&str],
-    ) -> Result<bool, RemoteError> {
+    ) -> Result<(), RemoteError> {
         let url = self
             .project
             .api
@@ -385,160 +451,78 @@ impl Repository {
             .map(|user| user.access_token.clone())
             .ok_or(RemoteError::Auth)?;
 
-        let mut callbacks = git2::RemoteCallbacks::new();
-        if self.project.omit_certificate_check.unwrap_or(false) {
-            callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
-        }
-        let bytes_pushed = Arc::new(AtomicUsize::new(0));
-        let total_objects = Arc::new(AtomicUsize::new(0));
-        {
-            let byte_counter = Arc::<AtomicUsize>::clone(&bytes_pushed);
-            let total_counter = Arc::<AtomicUsize>::clone(&total_objects);
-            callbacks.push_transfer_progress(move |_current, total, bytes| {
-                byte_counter.store(bytes, std::sync::atomic::Ordering::Relaxed);
-                total_counter.store(total, std::sync::atomic::Ordering::Relaxed);
-            });
-        }
-
-        let mut push_options = git2::PushOptions::new();
-        push_options.remote_callbacks(callbacks);
-        let auth_header = format!("Authorization: {}", access_token);
-        let headers = &[auth_header.as_str()];
-        push_options.custom_headers(headers);
-
-        let mut remote = self
-            .git_repository
-            .remote_anonymous(&url)
-            .map_err(|e| RemoteError::Other(e.into()))?;
-
-        remote
-            .push(ref_specs, Some(&mut push_options))
-            .map_err(|error| match error {
-                git::Error::Network(error) => {
-                    tracing::warn!(project_id = %self.project.id, ?error, "git push failed",);
-                    RemoteError::Network
-                }
-                git::Error::Auth(error) => {
-                    tracing::warn!(project_id = %self.project.id, ?error, "git push failed",);
-                    RemoteError::Auth
-                }
-                error => RemoteError::Other(error.into()),
-            })?;
-
-        let bytes_pushed = bytes_pushed.load(std::sync::atomic::Ordering::Relaxed);
-        let total_objects_pushed = total_objects.load(std::sync::atomic::Ordering::Relaxed);
+        remote_backend::for_gitbutler(&self.project).push(
+            &remote_backend::Target::GitButler {
+                repository: &self.git_repository,
+                url: &url,
+                access_token: &access_token,
+            },
+            ref_specs,
+            None,
+            &|_| {},
+        )?;
 
         tracing::debug!(
             project_id = %self.project.id,
             ref_spec = ref_specs.join(" "),
-            bytes = bytes_pushed,
-            objects = total_objects_pushed,
             "pushed to gb repo tmp ref",
         );
 
-        Ok(total_objects_pushed > 0)
+        Ok(())
     }
 
+    /// Pushes `head` to `branch`. A forced push only goes through if the remote branch still points at
+    /// `expected_head`, or doesn't exist yet if that is `None`, so that commits pushed by others aren't lost.
+    #[allow(clippy::too_many_arguments)]
     pub fn push(
         &self,
         head: &git::Oid,
         branch: &git::RemoteRefname,
         with_force: bool,
+        expected_head: Option<git::Oid>,
         credentials: &git::credentials::Helper,
         refspec: Option<String>,
         askpass_broker: Option<(AskpassBroker, Option<BranchId>)>,
+        on_progress: Option<&OnProgress>,
     ) -> Result<(), RemoteError> {
         let refspec = refspec.unwrap_or_else(|| {
+            let remote_refname = format!("refs/heads/{}", branch.branch());
             if with_force {
-                format!("+{}:refs/heads/{}", head, branch.branch())
+                format!("+{}:{}", head, remote_refname)
             } else {
-                format!("{}:refs/heads/{}", head, branch.branch())
+                format!("{}:{}", head, remote_refname)
             }
         });
+        let lease = with_force.then_some(remote_backend::Lease {
+            branch,
+            expected: expected_head,
+        });
 
-        // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
-        // NOTE(qix-): in a way that allows us to really incorporate new backends
-        // NOTE(qix-): without a lot of work. This is a temporary measure to
-        // NOTE(qix-): work around a time-sensitive change that was necessary
-        // NOTE(qix-): without having to refactor a large portion of the codebase.
-        if self.project.preferred_key == AuthKey::SystemExecutable {
-            let path = self.path().to_path_buf();
-            let remote = branch.remote().to_string();
-            return std::thread::spawn(move || {
-                tokio::runtime::Runtime::new()
-                    .unwrap()
-                    .block_on(gitbutler_git::push(
-                        path,
-                        gitbutler_git::tokio::TokioExecutor,
-                        &remote,
-                        gitbutler_git::RefSpec::parse(refspec).unwrap(),
-                        with_force,
-                        handle_git_prompt_push,
-                        askpass_broker,
-                    ))
-            })
-            .join()
-            .unwrap()
-            .map_err(|e| RemoteError::Other(e.into()));
-        }
-
-        let auth_flows = credentials.help(self, branch.remote())?;
-        for (mut remote, callbacks) in auth_flows {
-            if let Some(url) = remote.url().context("failed to get remote url")? {
-                if !self.project.omit_certificate_check.unwrap_or(false) {
-                    ssh::check_known_host(&url).context("failed to check known host")?;
-                }
-            }
-            let mut update_refs_error: Option<git2::Error> = None;
-            for callback in callbacks {
-                let mut cbs: git2::RemoteCallbacks = callback.into();
-                if self.project.omit_certificate_check.unwrap_or(false) {
-                    cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
-                }
-                cbs.push_update_reference(|_reference: &str, status: Option<&str>| {
-                    if let Some(status) = status {
-                        update_refs_error = Some(git2::Error::from_str(status));
-                        return Err(git2::Error::from_str(status));
-                    };
-                    Ok(())
-                });
-
-                let push_result = remote.push(
-                    &[refspec.as_str()],
-                    Some(&mut git2::PushOptions::new().remote_callbacks(cbs)),
-                );
-                match push_result {
-                    Ok(()) => {
-                        tracing::info!(
-                            project_id = %self.project.id,
-                            remote = %branch.remote(),
-                            %head,
-                            branch = branch.branch(),
-                            "pushed git branch"
-                        );
-                        return Ok(());
-                    }
-                    Err(git::Error::Auth(error) | git::Error::Http(error)) => {
-                        tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
-                        continue;
-                    }
-                    Err(git::Error::Network(error)) => {
-                        tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
-                        return Err(RemoteError::Network);
-                    }
-                    Err(err) => {
-                        if let Some(err) = update_refs_error.as_ref() {
-                            return Err(RemoteError::Other(
-                                anyhow::anyhow!(err.to_string()).context(Code::ProjectGitPush),
-                            ));
-                        }
-                        return Err(RemoteError::Other(err.into()));
-                    }
-                }
-            }
-        }
+        self.remote_backend().push(
+            &remote_backend::Target::Named {
+                project_repository: self,
+                name: branch.remote(),
+                credentials,
+                askpass: askpass_broker
+                    .map(|(broker, branch_id)| (broker, askpass::Context::Push { branch_id })),
+            },
+            &[&refspec],
+            lease.as_ref(),
+            &self.progress_of(
+                branch.remote(),
+                remote_backend::Direction::Push,
+                on_progress,
+            ),
+        )?;
 
-        Err(RemoteError::Auth)
+        tracing::info!(
+            project_id = %self.project.id,
+            remote = %branch.remote(),
+            %head,
+            branch = branch.branch(),
+            "pushed git branch"
+        );
+        Ok(())
     }
 
     pub fn fetch(
@@ -546,69 +530,63 @@ impl Repository {
         remote_name: &str,
         credentials: &git::credentials::Helper,
         askpass: Option<(AskpassBroker, String)>,
+        on_progress: Option<&OnProgress>,
     ) -> Result<(), RemoteError> {
         let refspec = format!("+refs/heads/*:refs/remotes/{}/*", remote_name);
+        let settings = &self.project.fetch_settings;
+
+        self.remote_backend().fetch(
+            &remote_backend::Target::Named {
+                project_repository: self,
+                name: remote_name,
+                credentials,
+                askpass: askpass
+                    .map(|(broker, action)| (broker, askpass::Context::Fetch { action })),
+            },
+            &[&refspec],
+            remote_backend::FetchOptions {
+                prune: settings.prune,
+                tags: settings.tags,
+            },
+            &self.progress_of(remote_name, remote_backend::Direction::Fetch, on_progress),
+        )?;
+
+        tracing::info!(project_id = %self.project.id, %refspec, "git fetched");
+        Ok(())
+    }
 
-        // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
-        // NOTE(qix-): in a way that allows us to really incorporate new backends
-        // NOTE(qix-): without a lot of work. This is a temporary measure to
-        // NOTE(qix-): work around a time-sensitive change that was necessary
-        // NOTE(qix-): without having to refactor a large portion of the codebase.
-        if self.project.preferred_key == AuthKey::SystemExecutable {
-            let path = self.path().to_path_buf();
-            let remote = remote_name.to_string();
-            return std::thread::spawn(move || {
-                tokio::runtime::Runtime::new()
-                    .unwrap()
-                    .block_on(gitbutler_git::fetch(
-                        path,
-                        gitbutler_git::tokio::TokioExecutor,
-                        &remote,
-                        gitbutler_git::RefSpec::parse(refspec).unwrap(),
-                        handle_git_prompt_fetch,
-                        askpass,
-                    ))
-            })
-            .join()
-            .unwrap()
-            .map_err(|e| RemoteError::Other(e.into()));
-        }
-
-        let auth_flows = credentials.help(self, remote_name)?;
-        for (mut remote, callbacks) in auth_flows {
-            if let Some(url) = remote.url().context("failed to get remote url")? {
-                if !self.project.omit_certificate_check.unwrap_or(false) {
-                    ssh::check_known_host(&url).context("failed to check known host")?;
-                }
-            }
-            for callback in callbacks {
-                let mut fetch_opts = git2::FetchOptions::new();
-                let mut cbs: git2::RemoteCallbacks = callback.into();
-                if self.project.omit_certificate_check.unwrap_or(false) {
-                    cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
-                }
-                fetch_opts.remote_callbacks(cbs);
-                fetch_opts.prune(git2::FetchPrune::On);
-
-                match remote.fetch(&[&refspec], Some(&mut fetch_opts)) {
-                    Ok(()) => {
-                        tracing::info!(project_id = %self.project.id, %refspec, "git fetched");
-                        return Ok(());
-                    }
-                    Err(git::Error::Auth(error) | git::Error::Http(error)) => {
-                        tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
-                        continue;
-                    }
-                    Err(git::Error::Network(error)) => {
-                        tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
-                        return Err(RemoteError::Network);
-                    }
-                    Err(error) => return Err(RemoteError::Other(error.into())),
-                }
+    /// Fetches the remotes selected in the project's fetch settings, and those in `also` that aren't among them.
+    /// A remote that fails to fetch doesn't keep the others from being fetched, each has its own result.
+    pub fn fetch_remotes(
+        &self,
+        also: &[&str],
+        credentials: &git::credentials::Helper,
+        askpass: Option<(AskpassBroker, String)>,
+        on_progress: Option<&OnProgress>,
+    ) -> Result<BTreeMap<String, Result<(), RemoteError>>> {
+        let mut remotes = match &self.project.fetch_settings.remotes {
+            Some(remotes) => remotes.clone(),
+            None => self
+                .git_repository
+                .remotes()
+                .context("failed to list remotes")?,
+        };
+        for also in also {
+            if !remotes.iter().any(|remote| remote == also) {
+                remotes.push((*also).to_owned());
             }
         }
 
-        Err(RemoteError::Auth)
+        Ok(remotes
+            .into_iter()
+            .map(|remote| {
+                let result = self.fetch(&remote, credentials, askpass.clone(), on_progress);
+                if let Err(error) = &result {
+                    tracing::warn!(project_id = %self.project.id, %remote, ?error, "failed to fetch remote");
+                }
+                (remote, result)
+            })
+            .collect())
     }
 }
 
@@ -622,6 +600,8 @@ pub enum RemoteError {
     Auth,
     #[error("Git failed")]
     Git(#[from] git::Error),
+    #[error("remote branch {0} changed since it was last pushed")]
+    LeaseExpired(git::RemoteRefname),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -640,6 +620,12 @@ impl ErrorWithContext for RemoteError {
             RemoteError::Git(_) => {
                 error::Context::new_static(Code::ProjectGitRemote, "Git command failed")
             }
+            RemoteError::LeaseExpired(branch) => error::Context::new(
+                Code::ProjectGitPush,
+                format!(
+                    "{branch} has changed since it was last pushed, fetch it before pushing again"
+                ),
+            ),
             RemoteError::Other(error) => {
                 return error.custom_context_or_root_cause().into();
             }
@@ -655,33 +641,3 @@ pub enum LogUntil {
     When(Box<OidFilter>),
     End,
 }
-
-async fn handle_git_prompt_push(
-    prompt: String,
-    askpass: Option<(AskpassBroker, Option<BranchId>)>,
-) -> Option<String> {
-    if let Some((askpass_broker, branch_id)) = askpass {
-        tracing::info!("received prompt for branch push {branch_id:?}: {prompt:?}");
-        askpass_broker
-            .submit_prompt(prompt, askpass::Context::Push { branch_id })
-            .await
-    } else {
-        tracing::warn!("received askpass push prompt but no broker was supplied; returning None");
-        None
-    }
-}
-
-async fn handle_git_prompt_fetch(
-    prompt: String,
-    askpass: Option<(AskpassBroker, String)>,
-) -> Option<String> {
-    if let Some((askpass_broker, action)) = askpass {
-        tracing::info!("received prompt for fetch with action {action:?}: {prompt:?}");
-        askpass_broker
-            .submit_prompt(prompt, askpass::Context::Fetch { action })
-            .await
-    } else {
-        tracing::warn!("received askpass fetch prompt but no broker was supplied; returning None");
-        None
-    }
-}
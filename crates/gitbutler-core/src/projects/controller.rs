@@ -80,9 +80,6 @@ impl Controller {
                     return Err(AddError::WorktreeNotSupported);
                 };
             }
-            Ok(repo) if repo.submodules().map_or(false, |sm| sm.is_some()) => {
-                return Err(AddError::SubmodulesNotSupported);
-            }
             Ok(_repo) => {}
             Err(err) => {
                 return Err(AddError::NotAGitRepository(Box::new(err)));
@@ -149,6 +146,15 @@ impl Controller {
             }
         }
 
+        if project
+            .diff_settings
+            .is_some_and(|settings| settings.context_lines == 0)
+        {
+            return Err(UpdateError::Validation(
+                UpdateValidationError::NoDiffContext,
+            ));
+        }
+
         // FIXME(qix-): On windows, we have to force to system executable.
         // FIXME(qix-): This is a hack for now, and will be smoothed over in the future.
         #[cfg(windows)]
@@ -357,6 +363,12 @@ impl ErrorWithContext for UpdateError {
                     format!("'{}' is not a file", path.display()),
                 )
             }
+            UpdateError::Validation(UpdateValidationError::NoDiffContext) => {
+                error::Context::new_static(
+                    Code::Projects,
+                    "Diffs need at least one line of context",
+                )
+            }
             UpdateError::NotFound => {
                 error::Context::new_static(Code::Projects, "Project not found")
             }
@@ -371,6 +383,8 @@ pub enum UpdateValidationError {
     KeyNotFound(PathBuf),
     #[error("{0} is not a file")]
     KeyNotFile(PathBuf),
+    #[error("diffs need at least one line of context")]
+    NoDiffContext,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -387,8 +401,6 @@ pub enum AddError {
     PathNotFound,
     #[error("project already exists")]
     AlreadyExists,
-    #[error("submodules not supported")]
-    SubmodulesNotSupported,
     #[error(transparent)]
     OpenProjectRepository(#[from] project_repository::OpenError),
     #[error(transparent)]
@@ -413,10 +425,6 @@ impl ErrorWithContext for AddError {
                 error::Context::new(Code::Projects, "Can only work in main worktrees")
             }
             AddError::PathNotFound => error::Context::new(Code::Projects, "Path not found"),
-            AddError::SubmodulesNotSupported => error::Context::new_static(
-                Code::Projects,
-                "Repositories with git submodules are not supported",
-            ),
             AddError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
     }
@@ -3,5 +3,8 @@ mod project;
 pub mod storage;
 
 pub use controller::*;
-pub use project::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+pub use project::{
+    ApiProject, AuthKey, CodePushState, DiffAlgorithm, DiffSettings, FetchResult, FetchSettings,
+    Project, ProjectId, RemoteBackendKind, Whitespace,
+};
 pub use storage::UpdateRequest;
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     path::{self, PathBuf},
     time,
 };
@@ -6,7 +7,8 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    git, id::Id, types::default_true::DefaultTrue, virtual_branches::VirtualBranchesHandle,
+    git, id::Id, snapshots::retention::SnapshotRetention, types::default_true::DefaultTrue,
+    virtual_branches::VirtualBranchesHandle,
 };
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
@@ -17,11 +19,24 @@ pub enum AuthKey {
     #[default]
     SystemExecutable,
     GitCredentialsHelper,
+    SshAgent,
     Local {
         private_key_path: path::PathBuf,
     },
 }
 
+/// The implementation that fetches and pushes go through.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RemoteBackendKind {
+    /// libgit2, authenticating with the credentials the preferred key selects.
+    Git2,
+    /// The git executable, authenticating the way the user's git is set up to.
+    GitCli,
+    /// gitoxide, which fetches by itself but pushes with the git executable.
+    Gix,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiProject {
     pub name: String,
@@ -54,6 +69,96 @@ impl FetchResult {
     }
 }
 
+/// Which remotes are fetched, and how.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FetchSettings {
+    /// The remotes to fetch, or all configured remotes if `None`.
+    /// The remote of the default target is always fetched, and by default it's the only one.
+    pub remotes: Option<Vec<String>>,
+    /// Whether all tags are fetched, rather than only those pointing into fetched history.
+    pub tags: bool,
+    /// Whether remote-tracking branches that are gone from the remote are removed.
+    pub prune: bool,
+    /// How often the remotes are fetched in the background, in seconds, or never if `None`.
+    pub auto_fetch_interval_secs: Option<u64>,
+}
+
+impl Default for FetchSettings {
+    fn default() -> Self {
+        Self {
+            remotes: Some(Vec::new()),
+            tags: false,
+            prune: true,
+            auto_fetch_interval_secs: Some(15 * 60),
+        }
+    }
+}
+
+/// How files are compared, which decides how their changes are split into hunks.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffSettings {
+    pub algorithm: DiffAlgorithm,
+    /// How many unchanged lines surround the changes of a hunk, which also decides how close
+    /// changes have to be to end up in the same hunk. At least one, or hunks couldn't be placed
+    /// when applied onto another base.
+    pub context_lines: u32,
+    /// Which whitespace changes are left out. This only applies to diffs nothing is written from,
+    /// like those of commits on remote branches or of snapshots, as leaving out changes from hunks
+    /// that are committed or moved would lose them.
+    pub whitespace: Whitespace,
+    /// Changed worktree files bigger than this aren't diffed, and only go into a branch that
+    /// claims them explicitly. Files that a filter like `git lfs` turns into pointers are exempt.
+    pub large_file_limit_bytes: u64,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: DiffAlgorithm::default(),
+            context_lines: 3,
+            whitespace: Whitespace::default(),
+            large_file_limit_bytes: 50_000_000,
+        }
+    }
+}
+
+impl DiffSettings {
+    /// These settings, but comparing all whitespace, for diffs that trees are written from.
+    #[must_use]
+    pub fn keeping_whitespace(self) -> Self {
+        Self {
+            whitespace: Whitespace::Keep,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    /// Myers, but spending extra time to find the smallest diff.
+    Minimal,
+    /// Anchors hunks on lines that are unique on both sides, which keeps moved blocks and
+    /// functions apart.
+    Patience,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Whitespace {
+    /// Every whitespace change is a change.
+    #[default]
+    Keep,
+    /// Whitespace at the end of lines doesn't count.
+    IgnoreAtEol,
+    /// No whitespace counts, like with `git diff --ignore-all-space`.
+    IgnoreAll,
+}
+
 #[derive(Debug, Deserialize, Serialize, Copy, Clone)]
 pub struct CodePushState {
     pub id: git::Oid,
@@ -71,6 +176,9 @@ pub struct Project {
     pub path: path::PathBuf,
     #[serde(default)]
     pub preferred_key: AuthKey,
+    /// The implementation fetches and pushes go through, or the one that suits the preferred key if `None`.
+    #[serde(default)]
+    pub remote_backend: Option<RemoteBackendKind>,
     /// if ok_with_force_push is true, we'll not try to avoid force pushing
     /// for example, when updating base branch
     #[serde(default)]
@@ -82,6 +190,13 @@ pub struct Project {
     pub gitbutler_code_push_state: Option<CodePushState>,
     #[serde(default)]
     pub project_data_last_fetch: Option<FetchResult>,
+    /// The outcome of the last fetch of each remote, by remote name.
+    #[serde(default)]
+    pub remotes_last_fetch: BTreeMap<String, FetchResult>,
+    #[serde(default)]
+    pub fetch_settings: FetchSettings,
+    #[serde(default)]
+    pub diff_settings: DiffSettings,
     #[serde(default)]
     pub omit_certificate_check: Option<bool>,
     #[serde(default)]
@@ -89,6 +204,9 @@ pub struct Project {
     // The number of changed lines that will trigger a snapshot
     #[serde(default = "default_snapshot_lines_threshold")]
     pub snapshot_lines_threshold: usize,
+    /// Which snapshots are kept when the oplog is compacted
+    #[serde(default)]
+    pub snapshot_retention: SnapshotRetention,
 }
 
 fn default_snapshot_lines_threshold() -> usize {
@@ -107,6 +225,15 @@ impl Project {
             .unwrap_or_default()
     }
 
+    /// Returns the implementation that fetches and pushes of this project go through.
+    pub fn remote_backend_kind(&self) -> RemoteBackendKind {
+        match (self.remote_backend, &self.preferred_key) {
+            (Some(kind), _) => kind,
+            (None, AuthKey::SystemExecutable) => RemoteBackendKind::GitCli,
+            (None, _) => RemoteBackendKind::Git2,
+        }
+    }
+
     /// Returns the path to the directory containing the `GitButler` state for this project.
     ///
     /// Normally this is `.git/gitbutler` in the project's repository.
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use crate::{
     projects::{project, ProjectId},
+    snapshots::retention::SnapshotRetention,
     storage,
 };
 
@@ -21,11 +22,18 @@ pub struct UpdateRequest {
     pub api: Option<project::ApiProject>,
     pub gitbutler_data_last_fetched: Option<project::FetchResult>,
     pub preferred_key: Option<project::AuthKey>,
+    pub remote_backend: Option<project::RemoteBackendKind>,
     pub ok_with_force_push: Option<bool>,
     pub gitbutler_code_push_state: Option<project::CodePushState>,
     pub project_data_last_fetched: Option<project::FetchResult>,
+    /// The fetch results of all remotes, replacing the recorded ones.
+    pub remotes_last_fetched: Option<BTreeMap<String, project::FetchResult>>,
+    pub fetch_settings: Option<project::FetchSettings>,
+    pub diff_settings: Option<project::DiffSettings>,
     pub omit_certificate_check: Option<bool>,
     pub use_diff_context: Option<bool>,
+    pub enable_snapshots: Option<bool>,
+    pub snapshot_retention: Option<SnapshotRetention>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -103,6 +111,10 @@ impl Storage {
             project.preferred_key = preferred_key.clone();
         }
 
+        if let Some(remote_backend) = update_request.remote_backend {
+            project.remote_backend = Some(remote_backend);
+        }
+
         if let Some(gitbutler_data_last_fetched) =
             update_request.gitbutler_data_last_fetched.as_ref()
         {
@@ -113,6 +125,18 @@ impl Storage {
             project.project_data_last_fetch = Some(project_data_last_fetched.clone());
         }
 
+        if let Some(remotes_last_fetched) = &update_request.remotes_last_fetched {
+            project.remotes_last_fetch = remotes_last_fetched.clone();
+        }
+
+        if let Some(fetch_settings) = &update_request.fetch_settings {
+            project.fetch_settings = fetch_settings.clone();
+        }
+
+        if let Some(diff_settings) = update_request.diff_settings {
+            project.diff_settings = diff_settings;
+        }
+
         if let Some(state) = update_request.gitbutler_code_push_state {
             project.gitbutler_code_push_state = Some(state);
         }
@@ -125,6 +149,14 @@ impl Storage {
             project.omit_certificate_check = Some(omit_certificate_check);
         }
 
+        if let Some(enable_snapshots) = update_request.enable_snapshots {
+            project.enable_snapshots = Some(enable_snapshots);
+        }
+
+        if let Some(snapshot_retention) = &update_request.snapshot_retention {
+            project.snapshot_retention = snapshot_retention.clone();
+        }
+
         self.inner
             .write(PROJECTS_FILE, &serde_json::to_string_pretty(&projects)?)?;
 
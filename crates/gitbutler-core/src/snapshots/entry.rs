@@ -11,6 +11,8 @@ use strum::EnumString;
 
 use serde::Serialize;
 
+use crate::git::diff::{DiffByPathMap, FileDiff};
+
 /// A snapshot of the repository and virtual branches state that GitButler can restore to.
 /// It captures the state of the working directory, virtual branches and commits.
 #[derive(Debug, PartialEq, Clone, Serialize)]
@@ -30,6 +32,16 @@ pub struct Snapshot {
     pub details: Option<SnapshotDetails>,
 }
 
+/// The changes between two snapshots.
+#[derive(Debug, PartialEq, Clone, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    /// The working directory changes, keyed by their path relative to the project root
+    pub files: DiffByPathMap,
+    /// The changes to `virtual_branches.toml`, if the virtual branches state differs
+    pub virtual_branches: Option<FileDiff>,
+}
+
 /// The payload of a snapshot commit
 ///
 /// This is persisted as a commit message in the title, body and trailers format (https://git-scm.com/docs/git-interpret-trailers)
@@ -38,7 +50,7 @@ pub struct Snapshot {
 pub struct SnapshotDetails {
     /// The version of the snapshot format
     pub version: Version,
-    /// The type of operation that was performed just before the snapshot was created
+    /// The type of operation that the snapshot was taken before, or after for snapshots of version 1
     pub operation: OperationType,
     /// The title / lablel of the snapshot
     pub title: String,
@@ -59,6 +71,12 @@ impl SnapshotDetails {
             trailers: vec![],
         }
     }
+
+    /// Adds trailers describing what the operation affects.
+    pub fn with_trailers(mut self, trailers: impl IntoIterator<Item = Trailer>) -> Self {
+        self.trailers.extend(trailers);
+        self
+    }
 }
 
 impl FromStr for SnapshotDetails {
@@ -156,9 +174,15 @@ pub enum OperationType {
     UpdateCommitMessage,
     MoveCommit,
     RestoreFromSnapshot,
+    Undo,
+    Redo,
+    ResetBranch,
+    SetPushRemote,
     ReorderCommit,
     InsertBlankCommit,
     MoveCommitFile,
+    ContinueRebase,
+    AbortRebase,
     FileChanges,
     #[default]
     Unknown,
@@ -170,11 +194,21 @@ impl fmt::Display for OperationType {
     }
 }
 
+/// The version of the snapshot format.
+///
+/// Snapshots of version 1 hold the state from after their operation, later ones the state from before it.
 #[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct Version(u32);
 impl Default for Version {
     fn default() -> Self {
-        Version(1)
+        Version(2)
+    }
+}
+
+impl Version {
+    /// Whether the snapshot holds the state its operation started from, rather than the state it ended with.
+    pub fn is_before_operation(&self) -> bool {
+        self.0 >= 2
     }
 }
 
@@ -203,6 +237,16 @@ pub struct Trailer {
     pub value: String,
 }
 
+impl Trailer {
+    /// Trailers are single lines, so line breaks in `value` are replaced with spaces.
+    pub fn new(key: &str, value: impl fmt::Display) -> Self {
+        Self {
+            key: key.to_string(),
+            value: value.to_string().replace(['\r', '\n'], " "),
+        }
+    }
+}
+
 impl Display for Trailer {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "{}: {}", self.key, self.value)
@@ -1,4 +1,5 @@
 pub mod entry;
 mod reflog;
+pub mod retention;
 pub mod snapshot;
 mod state;
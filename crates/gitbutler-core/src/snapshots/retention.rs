@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::entry::OperationType;
+
+const SECONDS_PER_HOUR: i64 = 60 * 60;
+const SECONDS_PER_DAY: i64 = 24 * SECONDS_PER_HOUR;
+
+/// Decides which snapshots survive when the oplog is compacted.
+///
+/// Snapshots of operations other than [`OperationType::FileChanges`] are always kept.
+/// Of the `FileChanges` snapshots, a snapshot is kept if any of the rules below select it.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRetention {
+    /// The number of most recent snapshots to keep.
+    pub keep_last: usize,
+    /// Keep the newest snapshot of each of this many most recent hours that have snapshots.
+    pub keep_hourly: usize,
+    /// Keep the newest snapshot of each of this many most recent days that have snapshots.
+    pub keep_daily: usize,
+}
+
+impl Default for SnapshotRetention {
+    fn default() -> Self {
+        Self {
+            keep_last: 100,
+            keep_hourly: 24,
+            keep_daily: 30,
+        }
+    }
+}
+
+impl SnapshotRetention {
+    /// Returns whether each of `snapshots` should be kept. `snapshots` are given newest first,
+    /// as the operation they were taken for and their creation time in seconds since the epoch.
+    pub fn retain(&self, snapshots: &[(OperationType, i64)]) -> Vec<bool> {
+        let mut hours = HashSet::new();
+        let mut days = HashSet::new();
+        snapshots
+            .iter()
+            .enumerate()
+            .map(|(index, (operation, created_at))| {
+                // every rule is evaluated so that the buckets fill up with the newest snapshots
+                let is_recent = index < self.keep_last;
+                let is_newest_of_hour = hours.len() < self.keep_hourly
+                    && hours.insert(created_at.div_euclid(SECONDS_PER_HOUR));
+                let is_newest_of_day = days.len() < self.keep_daily
+                    && days.insert(created_at.div_euclid(SECONDS_PER_DAY));
+                *operation != OperationType::FileChanges
+                    || is_recent
+                    || is_newest_of_hour
+                    || is_newest_of_day
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_changes(created_at: i64) -> (OperationType, i64) {
+        (OperationType::FileChanges, created_at)
+    }
+
+    #[test]
+    fn keeps_last() {
+        let policy = SnapshotRetention {
+            keep_last: 2,
+            keep_hourly: 0,
+            keep_daily: 0,
+        };
+        let snapshots = [file_changes(3), file_changes(2), file_changes(1)];
+        assert_eq!(policy.retain(&snapshots), vec![true, true, false]);
+    }
+
+    #[test]
+    fn always_keeps_other_operations() {
+        let policy = SnapshotRetention {
+            keep_last: 0,
+            keep_hourly: 0,
+            keep_daily: 0,
+        };
+        let snapshots = [
+            file_changes(3),
+            (OperationType::CreateCommit, 2),
+            file_changes(1),
+        ];
+        assert_eq!(policy.retain(&snapshots), vec![false, true, false]);
+    }
+
+    #[test]
+    fn keeps_newest_per_bucket() {
+        let policy = SnapshotRetention {
+            keep_last: 0,
+            keep_hourly: 2,
+            keep_daily: 1,
+        };
+        let day = SECONDS_PER_DAY;
+        let hour = SECONDS_PER_HOUR;
+        let snapshots = [
+            // today, two snapshots in the latest hour and one in the hour before
+            file_changes(day + 2 * hour + 20),
+            file_changes(day + 2 * hour + 10),
+            file_changes(day + hour),
+            // yesterday, beyond the hourly and daily buckets
+            file_changes(hour),
+        ];
+        assert_eq!(policy.retain(&snapshots), vec![true, false, true, false]);
+    }
+}
@@ -1,25 +1,34 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use git2::FileMode;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 
-use crate::projects::Project;
+use crate::{git, projects::Project};
 
 use super::{
-    entry::{OperationType, Snapshot, SnapshotDetails, Trailer},
+    entry::{OperationType, Snapshot, SnapshotDetails, SnapshotDiff, Trailer},
     reflog::set_reference_to_oplog,
     state::OplogHandle,
 };
 
 const SNAPSHOT_FILE_LIMIT_BYTES: u64 = 32 * 1024 * 1024;
+/// The trailer of a restoring snapshot that holds the sha of the snapshot it restored.
+const RESTORED_FROM_TRAILER: &str = "restored_from";
+/// How often the oplog is compacted while snapshots of file changes are taken.
+pub const COMPACTION_INTERVAL_SECONDS: i64 = 60 * 60;
 
 /// The Oplog trait allows for crating snapshots of the current state of the project as well as restoring to a previous snapshot.
 /// Snapshots include the state of the working directory as well as all additional GitButler state (e.g virtual branches, conflict state).
 pub trait Oplog {
     /// Creates a snapshot of the current state of the repository and virtual branches using the given label.
+    /// Operations take their snapshot before they change anything, so a snapshot holds the state that its operation started from.
     ///
     /// If this is the first shapshot created, supporting structures are initialized:
     ///  - The current oplog head is persisted in `.git/gitbutler/oplog.toml`.
@@ -29,6 +38,10 @@ pub trait Oplog {
     ///  - The current state of the working directory under a subtree `workdir`.
     ///  - The state of virtual branches from `.git/gitbutler/virtual_branches.toml` as a blob `virtual_branches.toml`.
     ///  - The state of conflicts from `.git/base_merge_parent` and `.git/conflicts` if present as blobs under a subtree `conflicts`
+    ///  - The heads of `refs/gitbutler/*`, `gitbutler/integration` and the default target as a blob `refs.toml`.
+    ///
+    /// Besides the previous snapshot, the snapshot commit has the recorded heads, the default target and the heads of all virtual branches as parents.
+    /// This keeps the commits a snapshot refers to from being garbage collected.
     ///
     /// If there are files that are untracked and larger than SNAPSHOT_FILE_LIMIT_BYTES, they are excluded from snapshot creation and restoring.
     /// Returns the sha of the created snapshot commit or None if snapshots are disabled.
@@ -40,16 +53,51 @@ pub trait Oplog {
     fn list_snapshots(&self, limit: usize, sha: Option<String>) -> Result<Vec<Snapshot>>;
     /// Reverts to a previous state of the working directory, virtual branches and commits.
     /// The provided sha must refer to a valid snapshot commit.
-    /// Upon success, a new snapshot is created.
+    /// Before restoring, a snapshot of the current state is created.
     ///
     /// This will restore the following:
     ///  - The state of the working directory is checked out from the subtree `workdir` in the snapshot.
     ///  - The state of virtual branches is restored from the blob `virtual_branches.toml` in the snapshot.
     ///  - The state of conflicts (.git/base_merge_parent and .git/conflicts) is restored from the subtree `conflicts` in the snapshot (if not present, existing files are deleted).
+    ///  - The `refs/gitbutler/*` and `gitbutler/integration` refs are restored from the blob `refs.toml` in the snapshot (if present). Other `refs/gitbutler/*` refs are deleted.
     ///
     /// If there are files that are untracked and larger than SNAPSHOT_FILE_LIMIT_BYTES, they are excluded from snapshot creation and restoring.
-    /// Returns the sha of the created revert snapshot commit or None if snapshots are disabled.
+    /// Returns the sha of the snapshot commit created before restoring or None if snapshots are disabled.
     fn restore_snapshot(&self, sha: String) -> Result<Option<String>>;
+    /// Restores only the given paths of the working directory from the subtree `workdir` of the snapshot with the provided sha.
+    /// Paths that did not exist when the snapshot was taken are deleted, but only as far as they aren't part of `HEAD`.
+    /// All paths must be relative and point into the working directory, otherwise nothing is restored.
+    /// Virtual branches, commits and conflict state are left untouched.
+    /// Before restoring, a snapshot of the current state is created.
+    ///
+    /// Returns the sha of the snapshot commit created before restoring or None if snapshots are disabled.
+    fn restore_snapshot_paths(&self, sha: String, paths: &[PathBuf]) -> Result<Option<String>>;
+    /// Computes the changes from the snapshot `from_sha` to the snapshot `to_sha`.
+    /// This includes the per-file hunks of the `workdir` subtree as well as the changes to `virtual_branches.toml`.
+    fn diff_snapshots(&self, from_sha: String, to_sha: String) -> Result<SnapshotDiff>;
+    /// Drops the snapshots that the project's `snapshot_retention` policy doesn't keep.
+    ///
+    /// The oplog chain is rewritten from the oldest dropped snapshot onwards, so the kept snapshots get new shas.
+    /// The oplog head and the `gitbutler/target` reflog are updated to the rewritten chain, which leaves
+    /// the dropped snapshots unreachable such that `git gc` can reclaim their trees.
+    ///
+    /// Returns the number of dropped snapshots.
+    fn compact_snapshots(&self) -> Result<usize>;
+    /// Returns whether the oplog is due to be compacted, which it is once per [`COMPACTION_INTERVAL_SECONDS`].
+    fn should_compact_snapshots(&self) -> Result<bool>;
+    /// Undoes the most recent operation that hasn't been undone yet by restoring the snapshot taken before it.
+    /// `FileChanges` snapshots are not considered operations, and undo and redo entries continue from before the snapshot they restored.
+    /// Snapshots of version 1 were taken after their operation, so for them the snapshot before is restored instead.
+    ///
+    /// The undo snapshot holds the state before undoing and is pushed onto the redo stack in `.git/gitbutler/oplog.toml`.
+    /// Recording any other operation clears the redo stack.
+    ///
+    /// Returns the sha of the created undo snapshot commit or None if there is nothing to undo.
+    fn undo(&self) -> Result<Option<String>>;
+    /// Restores the state from before the most recent undo that hasn't been redone yet.
+    ///
+    /// Returns the sha of the created redo snapshot commit or None if there is nothing to redo.
+    fn redo(&self) -> Result<Option<String>>;
     /// Returns the number of lines of code (added plus removed) since the last snapshot. Includes untracked files.
     ///
     /// If there are no snapshots, 0 is returned.
@@ -89,6 +137,10 @@ impl Oplog for Project {
         // Create a tree out of the conflicts state if present
         let conflicts_tree = write_conflicts_tree(repo_path, &repo)?;
 
+        // Record the GitButler refs, and collect the commits the snapshot must keep reachable
+        let (refs_blob, referenced_commits) =
+            write_refs_blob(&repo, &vb_state, default_target_sha.into())?;
+
         // Exclude files that are larger than the limit (eg. database.sql which may never be intended to be committed)
         let files_to_exclude = get_exclude_list(&repo)?;
         // In-memory, libgit2 internal ignore rule
@@ -106,6 +158,7 @@ impl Oplog for Project {
         tree_builder.insert("workdir", tree_id, FileMode::Tree.into())?;
         tree_builder.insert("virtual_branches.toml", vb_blob, FileMode::Blob.into())?;
         tree_builder.insert("conflicts", conflicts_tree, FileMode::Tree.into())?;
+        tree_builder.insert("refs.toml", refs_blob, FileMode::Blob.into())?;
 
         let tree_id = tree_builder.write()?;
         let tree = repo.find_tree(tree_id)?;
@@ -114,13 +167,21 @@ impl Oplog for Project {
         let name = "GitButler";
         let email = "gitbutler@gitbutler.com";
         let signature = git2::Signature::now(name, email).unwrap();
+        // The previous snapshot is always the first parent
+        let parents = std::iter::once(&oplog_head_commit)
+            .chain(
+                referenced_commits
+                    .iter()
+                    .filter(|commit| commit.id() != oplog_head_commit.id()),
+            )
+            .collect_vec();
         let new_commit_oid = repo.commit(
             None,
             &signature,
             &signature,
             &details.to_string(),
             &tree,
-            &[&oplog_head_commit],
+            &parents,
         )?;
 
         // Reset the workdir to how it was
@@ -136,6 +197,13 @@ impl Oplog for Project {
         )?;
 
         oplog_state.set_oplog_head(new_commit_oid.to_string())?;
+        // A new operation can't be followed by a redo of what was undone before it
+        if !matches!(
+            details.operation,
+            OperationType::FileChanges | OperationType::Undo | OperationType::Redo
+        ) {
+            oplog_state.set_redo_stack(vec![])?;
+        }
 
         set_reference_to_oplog(
             self,
@@ -166,6 +234,8 @@ impl Oplog for Project {
         let oplog_head_commit = repo.find_commit(git2::Oid::from_str(&head_sha)?)?;
 
         let mut revwalk = repo.revwalk()?;
+        // Other parents are the commits a snapshot keeps reachable
+        revwalk.simplify_first_parent()?;
         revwalk.push(oplog_head_commit.id())?;
 
         let mut snapshots = Vec::new();
@@ -174,17 +244,13 @@ impl Oplog for Project {
             let commit_id = commit_id?;
             let commit = repo.find_commit(commit_id)?;
 
-            if commit.parent_count() > 1 {
-                break;
-            }
-
             let tree = commit.tree()?;
             let wd_tree_entry = tree.get_name("workdir");
             let tree = if let Some(wd_tree_entry) = wd_tree_entry {
                 repo.find_tree(wd_tree_entry.id())?
             } else {
-                // We reached a tree that is not a snapshot
-                continue;
+                // We reached the commit the oplog is rooted at, which is not a snapshot
+                break;
             };
 
             let parent_tree = commit.parent(0)?.tree()?;
@@ -229,24 +295,43 @@ impl Oplog for Project {
     }
 
     fn restore_snapshot(&self, sha: String) -> Result<Option<String>> {
+        restore(
+            self,
+            sha,
+            OperationType::RestoreFromSnapshot,
+            "Restored from snapshot",
+        )
+    }
+
+    fn restore_snapshot_paths(&self, sha: String, paths: &[PathBuf]) -> Result<Option<String>> {
         let repo_path = self.path.as_path();
         let repo = git2::Repository::init(repo_path)?;
 
+        for path in paths {
+            ensure_in_workdir(repo_path, path)?;
+        }
+
+        // Snapshot the current state before overwriting any of it
+        let mut trailers = vec![Trailer {
+            key: RESTORED_FROM_TRAILER.to_string(),
+            value: sha.clone(),
+        }];
+        trailers.extend(paths.iter().map(|path| Trailer {
+            key: "restored_path".to_string(),
+            value: path.display().to_string(),
+        }));
+        let details = SnapshotDetails {
+            version: Default::default(),
+            operation: OperationType::RestoreFromSnapshot,
+            title: "Restored files from snapshot".to_string(),
+            body: None,
+            trailers,
+        };
+        let snapshot_sha = self.create_snapshot(details)?;
+
         let commit = repo.find_commit(git2::Oid::from_str(&sha)?)?;
-        // Top tree
-        let tree = commit.tree()?;
-        let vb_tree_entry = tree
-            .get_name("virtual_branches.toml")
-            .ok_or(anyhow!("failed to get virtual_branches tree entry"))?;
-        // virtual_branches.toml blob
-        let vb_blob = vb_tree_entry
-            .to_object(&repo)?
-            .into_blob()
-            .map_err(|_| anyhow!("failed to convert virtual_branches tree entry to blob"))?;
-        // Restore the state of .git/base_merge_parent and .git/conflicts from the snapshot
-        // Will remove those files if they are not present in the snapshot
-        _ = restore_conflicts_tree(&tree, &repo, repo_path);
-        let wd_tree_entry = tree
+        let commit_tree = commit.tree()?;
+        let wd_tree_entry = commit_tree
             .get_name("workdir")
             .ok_or(anyhow!("failed to get workdir tree entry"))?;
         // workdir tree
@@ -257,34 +342,220 @@ impl Oplog for Project {
         // In-memory, libgit2 internal ignore rule
         repo.add_ignore_rule(&files_to_exclude)?;
 
-        // Define the checkout builder
+        // Only the given paths are checked out, and the index is left alone
         let mut checkout_builder = git2::build::CheckoutBuilder::new();
         checkout_builder.remove_untracked(true);
         checkout_builder.force();
-        // Checkout the tree
-        repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))?;
-
-        // Update virtual_branches.toml with the state from the snapshot
-        fs::write(
-            repo_path
-                .join(".git")
-                .join("gitbutler")
-                .join("virtual_branches.toml"),
-            vb_blob.content(),
+        checkout_builder.update_index(false);
+        let mut paths_in_snapshot = 0;
+        for path in paths {
+            if tree.get_path(path).is_ok() {
+                checkout_builder.path(path);
+                paths_in_snapshot += 1;
+            } else {
+                // The path didn't exist when the snapshot was taken
+                remove_untracked(&repo, repo_path, path)?;
+            }
+        }
+        if paths_in_snapshot > 0 {
+            repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))?;
+        }
+
+        Ok(snapshot_sha)
+    }
+
+    fn diff_snapshots(&self, from_sha: String, to_sha: String) -> Result<SnapshotDiff> {
+        let repo = git::Repository::open(&self.path)?;
+
+        let from_tree = repo.find_commit(git::Oid::from_str(&from_sha)?)?.tree()?;
+        let to_tree = repo.find_commit(git::Oid::from_str(&to_sha)?)?.tree()?;
+        // Diffing the top trees covers both the `workdir` subtree and `virtual_branches.toml`
+        let diff = git::diff::trees(&repo, &from_tree, &to_tree, &self.diff_settings)?;
+
+        let mut snapshot_diff = SnapshotDiff::default();
+        for (path, mut file_diff) in diff {
+            if path == Path::new("virtual_branches.toml") {
+                snapshot_diff.virtual_branches = Some(file_diff);
+            } else if let Ok(path) = path.strip_prefix("workdir") {
+                file_diff.old_path = file_diff.old_path.map(strip_workdir_prefix);
+                file_diff.new_path = file_diff.new_path.map(strip_workdir_prefix);
+                snapshot_diff.files.insert(path.to_path_buf(), file_diff);
+            }
+        }
+        Ok(snapshot_diff)
+    }
+
+    fn compact_snapshots(&self) -> Result<usize> {
+        let repo_path = self.path.as_path();
+        let repo = git2::Repository::init(repo_path)?;
+
+        let oplog_state = OplogHandle::new(&self.gb_dir());
+        let Some(head_sha) = oplog_state.get_oplog_head()? else {
+            return Ok(0);
+        };
+
+        oplog_state.set_compacted_at(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)?
+                .as_secs()
+                .try_into()?,
         )?;
 
-        // create new snapshot
-        let details = SnapshotDetails {
-            version: Default::default(),
-            operation: OperationType::RestoreFromSnapshot,
-            title: "Restored from snapshot".to_string(),
-            body: None,
-            trailers: vec![Trailer {
-                key: "restored_from".to_string(),
-                value: sha,
-            }],
+        // Walk the chain down to the commit it is rooted at, which is not a snapshot
+        let mut snapshots = Vec::new();
+        let mut operations = Vec::new();
+        let mut commit = repo.find_commit(git2::Oid::from_str(&head_sha)?)?;
+        let root = loop {
+            let details = match snapshot_details(&commit) {
+                Some(details) if commit.parent_count() > 0 => details,
+                _ => break commit,
+            };
+            let parent = commit.parent(0)?;
+            operations.push((details.operation, commit.time().seconds()));
+            snapshots.push(commit);
+            commit = parent;
+        };
+
+        let mut retained = self.snapshot_retention.retain(&operations);
+        // Keep the snapshots that undo and redo may still restore
+        let redo_stack = oplog_state.get_redo_stack()?;
+        let mut restorable: HashSet<String> = redo_stack.iter().cloned().collect();
+        for (commit, keep) in snapshots.iter().zip(retained.iter_mut()) {
+            *keep |= restorable.contains(&commit.id().to_string());
+            if *keep {
+                restorable.extend(restored_from(commit));
+            }
+        }
+        let Some(oldest_dropped) = retained.iter().rposition(|keep| !keep) else {
+            return Ok(0);
+        };
+
+        // Snapshots older than the oldest dropped one keep their shas
+        let mut rewritten: HashMap<String, String> = HashMap::new();
+        let mut parent = match snapshots.get(oldest_dropped + 1) {
+            Some(commit) => commit.clone(),
+            None => root,
+        };
+        for (commit, _) in snapshots
+            .iter()
+            .zip(&retained)
+            .take(oldest_dropped)
+            .rev()
+            .filter(|(_, keep)| **keep)
+        {
+            // Keep the commits the snapshot refers to reachable
+            let referenced_commits = commit.parents().skip(1).collect_vec();
+            let parents = std::iter::once(&parent)
+                .chain(&referenced_commits)
+                .collect_vec();
+            // Point restoring snapshots to the rewritten snapshot they restored
+            let mut message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+            if let Some((old_sha, new_sha)) = restored_from(commit)
+                .and_then(|old_sha| rewritten.get(&old_sha).map(|new_sha| (old_sha, new_sha)))
+            {
+                message = message.replace(&old_sha, new_sha.as_str());
+            }
+            let new_commit_oid = repo.commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                &message,
+                &commit.tree()?,
+                &parents,
+            )?;
+            rewritten.insert(commit.id().to_string(), new_commit_oid.to_string());
+            parent = repo.find_commit(new_commit_oid)?;
+        }
+        oplog_state.set_redo_stack(
+            redo_stack
+                .into_iter()
+                .map(|sha| rewritten.get(&sha).cloned().unwrap_or(sha))
+                .collect(),
+        )?;
+
+        let new_head_sha = parent.id().to_string();
+        oplog_state.set_oplog_head(new_head_sha.clone())?;
+        let default_target_sha = self.virtual_branches().get_default_target()?.sha;
+        set_reference_to_oplog(self, &default_target_sha.to_string(), &new_head_sha)?;
+
+        Ok(retained.iter().filter(|keep| !**keep).count())
+    }
+
+    fn should_compact_snapshots(&self) -> Result<bool> {
+        let oplog_state = OplogHandle::new(&self.gb_dir());
+        let Some(compacted_at) = oplog_state.get_compacted_at()? else {
+            return Ok(true);
         };
-        self.create_snapshot(details)
+        let now: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .as_secs()
+            .try_into()?;
+        Ok(now - compacted_at >= COMPACTION_INTERVAL_SECONDS)
+    }
+
+    fn undo(&self) -> Result<Option<String>> {
+        let repo = git2::Repository::init(self.path.as_path())?;
+        let oplog_state = OplogHandle::new(&self.gb_dir());
+
+        let Some(head_sha) = oplog_state.get_oplog_head()? else {
+            return Ok(None);
+        };
+
+        let mut commit = repo.find_commit(git2::Oid::from_str(&head_sha)?)?;
+        let before_operation = loop {
+            let Some(details) = snapshot_details(&commit) else {
+                // We reached the commit the oplog is rooted at without finding an operation
+                return Ok(None);
+            };
+            commit = match details.operation {
+                OperationType::FileChanges => commit.parent(0)?,
+                OperationType::Undo | OperationType::Redo => {
+                    let sha = restored_from(&commit).ok_or(anyhow!(
+                        "failed to get the snapshot {} restored",
+                        commit.id()
+                    ))?;
+                    let restored = repo.find_commit(git2::Oid::from_str(&sha)?)?;
+                    if details.version.is_before_operation() {
+                        // The restored snapshot was undone already, continue with what came before it
+                        restored.parent(0)?
+                    } else {
+                        // Version 1 undo snapshots restored the state before the operation, continue from there
+                        restored
+                    }
+                }
+                _ if details.version.is_before_operation() => break commit,
+                // Version 1 snapshots hold the state after their operation, which the snapshot before has
+                _ => {
+                    let before = commit.parent(0)?;
+                    if snapshot_details(&before).is_none() {
+                        return Ok(None);
+                    }
+                    break before;
+                }
+            };
+        };
+
+        let undo_sha = restore(
+            self,
+            before_operation.id().to_string(),
+            OperationType::Undo,
+            "Undo",
+        )?;
+        if let Some(undo_sha) = &undo_sha {
+            oplog_state.push_redo(undo_sha.clone())?;
+        }
+        Ok(undo_sha)
+    }
+
+    fn redo(&self) -> Result<Option<String>> {
+        let oplog_state = OplogHandle::new(&self.gb_dir());
+        let Some(sha) = oplog_state.get_redo_stack()?.pop() else {
+            return Ok(None);
+        };
+
+        let redo_sha = restore(self, sha, OperationType::Redo, "Redo")?;
+        oplog_state.pop_redo()?;
+        Ok(redo_sha)
     }
 
     fn lines_since_snapshot(&self) -> Result<usize> {
@@ -319,6 +590,282 @@ impl Oplog for Project {
     }
 }
 
+/// Records the current state as a snapshot of the given operation and title, then restores the snapshot `sha`.
+fn restore(
+    project: &Project,
+    sha: String,
+    operation: OperationType,
+    title: &str,
+) -> Result<Option<String>> {
+    let repo_path = project.path.as_path();
+    let repo = git2::Repository::init(repo_path)?;
+
+    let commit = repo.find_commit(git2::Oid::from_str(&sha)?)?;
+
+    // Snapshot the current state before overwriting any of it
+    let details = SnapshotDetails {
+        version: Default::default(),
+        operation,
+        title: title.to_string(),
+        body: None,
+        trailers: vec![Trailer {
+            key: RESTORED_FROM_TRAILER.to_string(),
+            value: sha,
+        }],
+    };
+    let snapshot_sha = project.create_snapshot(details)?;
+
+    // Top tree
+    let tree = commit.tree()?;
+    let vb_tree_entry = tree
+        .get_name("virtual_branches.toml")
+        .ok_or(anyhow!("failed to get virtual_branches tree entry"))?;
+    // virtual_branches.toml blob
+    let vb_blob = vb_tree_entry
+        .to_object(&repo)?
+        .into_blob()
+        .map_err(|_| anyhow!("failed to convert virtual_branches tree entry to blob"))?;
+    // Restore the state of .git/base_merge_parent and .git/conflicts from the snapshot
+    // Will remove those files if they are not present in the snapshot
+    _ = restore_conflicts_tree(&tree, &repo, repo_path);
+    let snapshot_refs = read_refs_blob(&tree, &repo)?;
+    let wd_tree_entry = tree
+        .get_name("workdir")
+        .ok_or(anyhow!("failed to get workdir tree entry"))?;
+    // workdir tree
+    let tree = repo.find_tree(wd_tree_entry.id())?;
+
+    // Exclude files that are larger than the limit (eg. database.sql which may never be intended to be committed)
+    let files_to_exclude = get_exclude_list(&repo)?;
+    // In-memory, libgit2 internal ignore rule
+    repo.add_ignore_rule(&files_to_exclude)?;
+
+    // Define the checkout builder
+    let mut checkout_builder = git2::build::CheckoutBuilder::new();
+    checkout_builder.remove_untracked(true);
+    checkout_builder.force();
+    // Checkout the tree
+    repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))?;
+
+    // Update virtual_branches.toml with the state from the snapshot
+    fs::write(
+        repo_path
+            .join(".git")
+            .join("gitbutler")
+            .join("virtual_branches.toml"),
+        vb_blob.content(),
+    )?;
+
+    // Restore the refs, including the integration branch
+    if let Some(snapshot_refs) = snapshot_refs {
+        restore_refs(&snapshot_refs, &repo)?;
+    }
+
+    Ok(snapshot_sha)
+}
+
+/// The refs recorded in a snapshot as `refs.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SnapshotRefs {
+    /// The sha of the default target
+    default_target: String,
+    /// The head of `gitbutler/integration`, if it exists
+    integration: Option<String>,
+    /// The heads of `refs/gitbutler/*` by ref name
+    #[serde(default)]
+    gitbutler: BTreeMap<String, String>,
+}
+
+const INTEGRATION_REF: &str = "refs/heads/gitbutler/integration";
+
+fn write_refs_blob<'repo>(
+    repo: &'repo git2::Repository,
+    vb_state: &crate::virtual_branches::VirtualBranchesHandle,
+    default_target_sha: git2::Oid,
+) -> Result<(git2::Oid, Vec<git2::Commit<'repo>>)> {
+    let mut snapshot_refs = SnapshotRefs {
+        default_target: default_target_sha.to_string(),
+        ..Default::default()
+    };
+    let mut referenced_oids = vec![default_target_sha];
+
+    if let Ok(integration) = repo.refname_to_id(INTEGRATION_REF) {
+        snapshot_refs.integration = Some(integration.to_string());
+        referenced_oids.push(integration);
+    }
+    for reference in repo.references_glob("refs/gitbutler/*")? {
+        let reference = reference?;
+        if let (Some(name), Some(oid)) = (reference.name(), reference.target()) {
+            snapshot_refs
+                .gitbutler
+                .insert(name.to_string(), oid.to_string());
+            referenced_oids.push(oid);
+        }
+    }
+    // Branches without a ref, e.g. unapplied ones, are restored from `virtual_branches.toml`
+    referenced_oids.extend(
+        vb_state
+            .list_branches()?
+            .into_iter()
+            .map(|branch| git2::Oid::from(branch.head)),
+    );
+
+    let referenced_commits = referenced_oids
+        .into_iter()
+        .unique()
+        .filter_map(|oid| repo.find_commit(oid).ok())
+        .collect();
+    let blob = repo.blob(toml::to_string(&snapshot_refs)?.as_bytes())?;
+    Ok((blob, referenced_commits))
+}
+
+/// Returns `None` for snapshots that were taken before refs were recorded.
+fn read_refs_blob(
+    snapshot_tree: &git2::Tree,
+    repo: &git2::Repository,
+) -> Result<Option<SnapshotRefs>> {
+    let Some(refs_tree_entry) = snapshot_tree.get_name("refs.toml") else {
+        return Ok(None);
+    };
+    let refs_blob = refs_tree_entry
+        .to_object(repo)?
+        .into_blob()
+        .map_err(|_| anyhow!("failed to convert refs tree entry to blob"))?;
+    let snapshot_refs = toml::from_str(std::str::from_utf8(refs_blob.content())?)?;
+    Ok(Some(snapshot_refs))
+}
+
+fn restore_refs(snapshot_refs: &SnapshotRefs, repo: &git2::Repository) -> Result<()> {
+    let log_message = "restored from snapshot";
+
+    let stale_refs = repo
+        .references_glob("refs/gitbutler/*")?
+        .filter_map(|reference| reference.ok()?.name().map(ToString::to_string))
+        .filter(|name| !snapshot_refs.gitbutler.contains_key(name))
+        .collect_vec();
+    for name in stale_refs {
+        repo.find_reference(&name)?.delete()?;
+    }
+    for (name, sha) in &snapshot_refs.gitbutler {
+        repo.reference(name, git2::Oid::from_str(sha)?, true, log_message)?;
+    }
+
+    if let Some(sha) = &snapshot_refs.integration {
+        repo.reference(
+            INTEGRATION_REF,
+            git2::Oid::from_str(sha)?,
+            true,
+            log_message,
+        )?;
+    }
+    Ok(())
+}
+
+/// The sha of the snapshot that the given snapshot restored, if it is a restoring snapshot.
+/// The details of the snapshot `commit`, or `None` if it isn't a snapshot, as told by its trailers.
+fn snapshot_details(commit: &git2::Commit) -> Option<SnapshotDetails> {
+    SnapshotDetails::from_str(commit.message()?).ok()
+}
+
+fn restored_from(commit: &git2::Commit) -> Option<String> {
+    snapshot_details(commit)?
+        .trailers
+        .into_iter()
+        .find(|trailer| trailer.key == RESTORED_FROM_TRAILER)
+        .map(|trailer| trailer.value)
+}
+
+/// Fails unless `path` is a relative path that names something in the working directory of the repository
+/// at `repo_path`, outside of `.git`, such that restoring it can't touch anything else.
+fn ensure_in_workdir(repo_path: &Path, path: &Path) -> Result<()> {
+    let mut names_something = false;
+    for component in path.components() {
+        match component {
+            Component::Normal(name) if name == ".git" => {
+                bail!("cannot restore {}: it is part of .git", path.display())
+            }
+            Component::Normal(_) => names_something = true,
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => bail!(
+                "cannot restore {}: only relative paths without `..` can be restored",
+                path.display()
+            ),
+        }
+    }
+    if !names_something {
+        bail!(
+            "cannot restore '{}': it doesn't name anything in the working directory",
+            path.display()
+        );
+    }
+
+    // A symlinked directory along the way could still lead out of the working directory
+    let workdir = repo_path.canonicalize()?;
+    let full_path = repo_path.join(path);
+    let existing_parent = full_path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(repo_path);
+    if !existing_parent.canonicalize()?.starts_with(workdir) {
+        bail!(
+            "cannot restore {}: it is outside of the working directory",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Deletes the files at or below `path` that git reports as new, i.e. not part of `HEAD`, along with directories
+/// that are left empty. Taking a snapshot adds all files to the index, so files that are only staged count as new, too.
+/// Files in `HEAD` and ignored files are kept.
+fn remove_untracked(repo: &git2::Repository, repo_path: &Path, path: &Path) -> Result<()> {
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false)
+        .pathspec(path);
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        if !entry
+            .status()
+            .intersects(git2::Status::WT_NEW | git2::Status::INDEX_NEW)
+        {
+            continue;
+        }
+        if let Some(untracked) = entry.path() {
+            fs::remove_file(repo_path.join(untracked))?;
+        }
+    }
+
+    let full_path = repo_path.join(path);
+    if fs::symlink_metadata(&full_path).is_ok_and(|metadata| metadata.is_dir()) {
+        remove_empty_dirs(&full_path)?;
+    }
+    Ok(())
+}
+
+/// Removes `dir` and the directories below it that don't contain any files.
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+        }
+    }
+    if fs::read_dir(dir)?.next().is_none() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+fn strip_workdir_prefix(path: PathBuf) -> PathBuf {
+    match path.strip_prefix("workdir") {
+        Ok(path) => path.to_path_buf(),
+        Err(_) => path,
+    }
+}
+
 fn restore_conflicts_tree(
     snapshot_tree: &git2::Tree,
     repo: &git2::Repository,
@@ -417,9 +964,9 @@ fn get_exclude_list(repo: &git2::Repository) -> Result<String> {
 
 #[cfg(test)]
 mod tests {
-    use std::{io::Write, path::PathBuf};
+    use std::io::Write;
 
-    use crate::virtual_branches::Branch;
+    use crate::{snapshots::retention::SnapshotRetention, virtual_branches::Branch};
 
     use super::*;
     use tempfile::tempdir;
@@ -594,4 +1141,560 @@ mod tests {
         assert!(!&conflicts_path.exists());
         assert!(!&base_merge_parent_path.exists());
     }
+
+    #[test]
+    fn test_diff_and_restore_paths() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("1.txt"), "test\n").unwrap();
+        std::fs::write(dir.path().join("2.txt"), "test\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&PathBuf::from("1.txt")).unwrap();
+        index.add_path(&PathBuf::from("2.txt")).unwrap();
+        let oid = index.write_tree().unwrap();
+        let signature = git2::Signature::now("Your Name", "your.email@example.com").unwrap();
+        let initial_commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "initial commit",
+                &repo.find_tree(oid).unwrap(),
+                &[],
+            )
+            .unwrap();
+        repo.branch(
+            "gitbutler/integration",
+            &repo.find_commit(initial_commit).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        let project = Project {
+            path: dir.path().to_path_buf(),
+            enable_snapshots: Some(true),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(project.gb_dir()).unwrap();
+        let vb_state = project.virtual_branches();
+        vb_state
+            .set_default_target(crate::virtual_branches::target::Target {
+                branch: crate::git::RemoteRefname::new("origin", "main"),
+                remote_url: Default::default(),
+                sha: initial_commit.into(),
+                push_remote_name: None,
+            })
+            .unwrap();
+
+        let first_snapshot = project
+            .create_snapshot(SnapshotDetails::new(OperationType::CreateCommit))
+            .unwrap()
+            .unwrap();
+
+        // Modify file 1, remove file 2, create file 3 and a branch
+        std::fs::write(dir.path().join("1.txt"), "TEST\n").unwrap();
+        std::fs::remove_file(dir.path().join("2.txt")).unwrap();
+        std::fs::write(dir.path().join("3.txt"), "something_new\n").unwrap();
+        let id = crate::id::Id::from_str("9acb2a3b-cddf-47d7-b531-a7798978c237").unwrap();
+        vb_state
+            .set_branch(Branch {
+                id,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let second_snapshot = project
+            .create_snapshot(SnapshotDetails::new(OperationType::CreateBranch))
+            .unwrap()
+            .unwrap();
+
+        let diff = project
+            .diff_snapshots(first_snapshot.clone(), second_snapshot)
+            .unwrap();
+        assert_eq!(
+            diff.files.keys().cloned().sorted().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("1.txt"),
+                PathBuf::from("2.txt"),
+                PathBuf::from("3.txt")
+            ]
+        );
+        let file_1 = &diff.files[&PathBuf::from("1.txt")];
+        assert_eq!(file_1.new_path, Some(PathBuf::from("1.txt")));
+        assert_eq!(file_1.hunks.len(), 1);
+        assert!(file_1.hunks[0].diff_lines.ends_with(b"-test\n+TEST\n"));
+        let vb_diff = diff.virtual_branches.unwrap();
+        assert!(vb_diff.hunks[0]
+            .diff_lines
+            .to_string()
+            .contains("9acb2a3b-cddf-47d7-b531-a7798978c237"));
+
+        // Restore file 1 and drop file 3, leaving everything else as is
+        project
+            .restore_snapshot_paths(
+                first_snapshot,
+                &[PathBuf::from("1.txt"), PathBuf::from("3.txt")],
+            )
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "test\n"
+        );
+        assert!(!dir.path().join("2.txt").exists());
+        assert!(!dir.path().join("3.txt").exists());
+        // The branch state is untouched
+        assert!(vb_state.get_branch(&id).is_ok());
+    }
+
+    /// A project in `dir` with `1.txt` committed and set as default target, and a snapshot of that state.
+    fn project_with_snapshot(dir: &Path) -> (Project, String) {
+        let project = project_with_target(dir);
+        let snapshot = project
+            .create_snapshot(SnapshotDetails::new(OperationType::CreateCommit))
+            .unwrap()
+            .unwrap();
+        (project, snapshot)
+    }
+
+    /// A project in `dir` with `1.txt` committed on the integration branch and set as default target.
+    fn project_with_target(dir: &Path) -> Project {
+        let repo = git2::Repository::init(dir).unwrap();
+        std::fs::write(dir.join("1.txt"), "test\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&PathBuf::from("1.txt")).unwrap();
+        let oid = index.write_tree().unwrap();
+        let signature = git2::Signature::now("Your Name", "your.email@example.com").unwrap();
+        let initial_commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "initial commit",
+                &repo.find_tree(oid).unwrap(),
+                &[],
+            )
+            .unwrap();
+        // like in a project GitButler manages, work happens on the integration branch
+        repo.branch(
+            "gitbutler/integration",
+            &repo.find_commit(initial_commit).unwrap(),
+            false,
+        )
+        .unwrap();
+        repo.set_head("refs/heads/gitbutler/integration").unwrap();
+
+        let project = Project {
+            path: dir.to_path_buf(),
+            enable_snapshots: Some(true),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(project.gb_dir()).unwrap();
+        project
+            .virtual_branches()
+            .set_default_target(crate::virtual_branches::target::Target {
+                branch: crate::git::RemoteRefname::new("origin", "main"),
+                remote_url: Default::default(),
+                sha: initial_commit.into(),
+                push_remote_name: None,
+            })
+            .unwrap();
+        project
+    }
+
+    #[test]
+    fn test_restore_paths_outside_of_workdir_is_rejected() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("keep.txt"), "keep\n").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+        let (project, snapshot) = project_with_snapshot(dir.path());
+
+        for path in [
+            outside.path().to_path_buf(),
+            PathBuf::from("../outside"),
+            PathBuf::from("dir/../../outside"),
+            PathBuf::from(""),
+            PathBuf::from("."),
+            PathBuf::from("/"),
+            PathBuf::from(".git"),
+            PathBuf::from(".git/config"),
+            PathBuf::from("link/keep.txt"),
+        ] {
+            assert!(
+                project
+                    .restore_snapshot_paths(snapshot.clone(), &[path.clone()])
+                    .is_err(),
+                "{} must be rejected",
+                path.display()
+            );
+        }
+        // nothing is restored if any of the paths is rejected
+        std::fs::write(dir.path().join("1.txt"), "TEST\n").unwrap();
+        assert!(project
+            .restore_snapshot_paths(snapshot, &[PathBuf::from("1.txt"), PathBuf::from("..")])
+            .is_err());
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "TEST\n"
+        );
+        assert!(outside.path().join("keep.txt").exists());
+        assert!(dir.path().join(".git/config").exists());
+    }
+
+    #[test]
+    fn test_restore_paths_only_deletes_untracked_files() {
+        let dir = tempdir().unwrap();
+        let (project, snapshot) = project_with_snapshot(dir.path());
+
+        // an untracked directory, an ignored file within it and a committed file that the snapshot doesn't know about
+        std::fs::create_dir_all(dir.path().join("new/nested")).unwrap();
+        std::fs::write(dir.path().join("new/nested/file.txt"), "new\n").unwrap();
+        std::fs::write(dir.path().join("new/ignored.log"), "log\n").unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("empty/nested")).unwrap();
+        std::fs::write(dir.path().join("tracked.txt"), "tracked\n").unwrap();
+        let repo = git2::Repository::open(dir.path()).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("tracked.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = git2::Signature::now("Your Name", "your.email@example.com").unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "track",
+            &tree,
+            &[&head],
+        )
+        .unwrap();
+
+        project
+            .restore_snapshot_paths(
+                snapshot,
+                &[
+                    PathBuf::from("new"),
+                    PathBuf::from("empty"),
+                    PathBuf::from("tracked.txt"),
+                ],
+            )
+            .unwrap();
+
+        assert!(!dir.path().join("new/nested").exists());
+        assert!(!dir.path().join("empty").exists());
+        assert!(dir.path().join("new/ignored.log").exists());
+        assert!(dir.path().join("tracked.txt").exists());
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("1.txt"), "test\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&PathBuf::from("1.txt")).unwrap();
+        let oid = index.write_tree().unwrap();
+        let signature = git2::Signature::now("Your Name", "your.email@example.com").unwrap();
+        let initial_commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "initial commit",
+                &repo.find_tree(oid).unwrap(),
+                &[],
+            )
+            .unwrap();
+        repo.branch(
+            "gitbutler/integration",
+            &repo.find_commit(initial_commit).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        let project = Project {
+            path: dir.path().to_path_buf(),
+            enable_snapshots: Some(true),
+            snapshot_retention: SnapshotRetention {
+                keep_last: 1,
+                keep_hourly: 0,
+                keep_daily: 0,
+            },
+            ..Default::default()
+        };
+        std::fs::create_dir_all(project.gb_dir()).unwrap();
+        project
+            .virtual_branches()
+            .set_default_target(crate::virtual_branches::target::Target {
+                branch: crate::git::RemoteRefname::new("origin", "main"),
+                remote_url: Default::default(),
+                sha: initial_commit.into(),
+                push_remote_name: None,
+            })
+            .unwrap();
+
+        for (content, operation) in [
+            ("1", OperationType::FileChanges),
+            ("2", OperationType::CreateCommit),
+            ("3", OperationType::FileChanges),
+            ("4", OperationType::FileChanges),
+        ] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            project
+                .create_snapshot(SnapshotDetails::new(operation))
+                .unwrap();
+        }
+        assert_eq!(project.list_snapshots(10, None).unwrap().len(), 4);
+
+        assert!(project.should_compact_snapshots().unwrap());
+        assert_eq!(project.compact_snapshots().unwrap(), 2);
+        // compacting again has to wait for the next interval
+        assert!(!project.should_compact_snapshots().unwrap());
+
+        let snapshots = project.list_snapshots(10, None).unwrap();
+        let operations = snapshots
+            .iter()
+            .map(|snapshot| snapshot.details.as_ref().unwrap().operation.clone())
+            .collect_vec();
+        assert_eq!(
+            operations,
+            vec![OperationType::FileChanges, OperationType::CreateCommit]
+        );
+        // nothing left to drop
+        assert_eq!(project.compact_snapshots().unwrap(), 0);
+
+        // the kept snapshots still restore to their state
+        project.restore_snapshot(snapshots[1].id.clone()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn test_restore_refs() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("1.txt"), "test\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&PathBuf::from("1.txt")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Your Name", "your.email@example.com").unwrap();
+        let initial_commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "initial commit",
+                &tree,
+                &[],
+            )
+            .unwrap();
+        let initial_commit = repo.find_commit(initial_commit).unwrap();
+        repo.branch("gitbutler/integration", &initial_commit, false)
+            .unwrap();
+
+        // a commit that only a virtual branch ref points to
+        let branch_commit = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                "branch commit",
+                &tree,
+                &[&initial_commit],
+            )
+            .unwrap();
+        repo.reference("refs/gitbutler/my-branch", branch_commit, false, "")
+            .unwrap();
+
+        let project = Project {
+            path: dir.path().to_path_buf(),
+            enable_snapshots: Some(true),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(project.gb_dir()).unwrap();
+        project
+            .virtual_branches()
+            .set_default_target(crate::virtual_branches::target::Target {
+                branch: crate::git::RemoteRefname::new("origin", "main"),
+                remote_url: Default::default(),
+                sha: initial_commit.id().into(),
+                push_remote_name: None,
+            })
+            .unwrap();
+
+        let snapshot = project
+            .create_snapshot(SnapshotDetails::new(OperationType::CreateCommit))
+            .unwrap()
+            .unwrap();
+
+        // the snapshot keeps the branch commit reachable
+        let snapshot_commit = repo
+            .find_commit(git2::Oid::from_str(&snapshot).unwrap())
+            .unwrap();
+        assert_eq!(snapshot_commit.parent_id(0).unwrap(), initial_commit.id());
+        assert!(snapshot_commit.parent_ids().any(|id| id == branch_commit));
+
+        // destroy the ref and move the integration branch
+        repo.find_reference("refs/gitbutler/my-branch")
+            .unwrap()
+            .delete()
+            .unwrap();
+        repo.reference(
+            "refs/gitbutler/other-branch",
+            initial_commit.id(),
+            false,
+            "",
+        )
+        .unwrap();
+        repo.reference("refs/heads/gitbutler/integration", branch_commit, true, "")
+            .unwrap();
+        project
+            .create_snapshot(SnapshotDetails::new(OperationType::DeleteBranch))
+            .unwrap();
+        assert_eq!(project.list_snapshots(10, None).unwrap().len(), 2);
+
+        project.restore_snapshot(snapshot).unwrap();
+
+        assert_eq!(
+            repo.refname_to_id("refs/gitbutler/my-branch").unwrap(),
+            branch_commit
+        );
+        assert!(repo.find_reference("refs/gitbutler/other-branch").is_err());
+        assert_eq!(
+            repo.refname_to_id("refs/heads/gitbutler/integration")
+                .unwrap(),
+            initial_commit.id()
+        );
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("1.txt"), "0").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&PathBuf::from("1.txt")).unwrap();
+        let oid = index.write_tree().unwrap();
+        let signature = git2::Signature::now("Your Name", "your.email@example.com").unwrap();
+        let initial_commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "initial commit",
+                &repo.find_tree(oid).unwrap(),
+                &[],
+            )
+            .unwrap();
+        repo.branch(
+            "gitbutler/integration",
+            &repo.find_commit(initial_commit).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        let project = Project {
+            path: dir.path().to_path_buf(),
+            enable_snapshots: Some(true),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(project.gb_dir()).unwrap();
+        project
+            .virtual_branches()
+            .set_default_target(crate::virtual_branches::target::Target {
+                branch: crate::git::RemoteRefname::new("origin", "main"),
+                remote_url: Default::default(),
+                sha: initial_commit.into(),
+                push_remote_name: None,
+            })
+            .unwrap();
+
+        let read = || std::fs::read_to_string(dir.path().join("1.txt")).unwrap();
+
+        // nothing to undo before any operation
+        assert_eq!(project.undo().unwrap(), None);
+
+        // operations snapshot the state they start from
+        for content in ["1", "2"] {
+            project
+                .create_snapshot(SnapshotDetails::new(OperationType::CreateCommit))
+                .unwrap();
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+        }
+        // file changes after the last operation are not an operation of their own
+        project
+            .create_snapshot(SnapshotDetails::new(OperationType::FileChanges))
+            .unwrap();
+        std::fs::write(dir.path().join("1.txt"), "3").unwrap();
+
+        assert!(project.undo().unwrap().is_some());
+        assert_eq!(read(), "1");
+        assert_eq!(
+            project.list_snapshots(1, None).unwrap()[0]
+                .details
+                .as_ref()
+                .unwrap()
+                .operation,
+            OperationType::Undo
+        );
+        assert!(project.undo().unwrap().is_some());
+        assert_eq!(read(), "0");
+        // there is no operation before the first one
+        assert_eq!(project.undo().unwrap(), None);
+        assert_eq!(read(), "0");
+
+        assert!(project.redo().unwrap().is_some());
+        assert_eq!(read(), "1");
+        assert!(project.redo().unwrap().is_some());
+        assert_eq!(read(), "3");
+        assert_eq!(project.redo().unwrap(), None);
+
+        // undo again after redoing
+        assert!(project.undo().unwrap().is_some());
+        assert_eq!(read(), "1");
+
+        // a new operation drops what could be redone
+        std::fs::write(dir.path().join("1.txt"), "4").unwrap();
+        project
+            .create_snapshot(SnapshotDetails::new(OperationType::CreateBranch))
+            .unwrap();
+        std::fs::write(dir.path().join("1.txt"), "5").unwrap();
+        assert_eq!(project.redo().unwrap(), None);
+        assert!(project.undo().unwrap().is_some());
+        assert_eq!(read(), "4");
+    }
+
+    #[test]
+    fn test_undo_version_1_snapshots() {
+        let dir = tempdir().unwrap();
+        let project = project_with_target(dir.path());
+        let read = || std::fs::read_to_string(dir.path().join("1.txt")).unwrap();
+
+        // version 1 snapshots were taken after their operation, later ones before it
+        let after_operation =
+            SnapshotDetails::from_str("Old operation\n\nVersion: 1\nOperation: CreateCommit\n")
+                .unwrap();
+        for content in ["1", "2"] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            project.create_snapshot(after_operation.clone()).unwrap();
+        }
+        std::fs::write(dir.path().join("1.txt"), "3").unwrap();
+        project
+            .create_snapshot(SnapshotDetails::new(OperationType::CreateBranch))
+            .unwrap();
+        std::fs::write(dir.path().join("1.txt"), "4").unwrap();
+
+        assert!(project.undo().unwrap().is_some());
+        assert_eq!(read(), "3");
+        assert!(project.undo().unwrap().is_some());
+        assert_eq!(read(), "1");
+        // the state before the first operation wasn't recorded
+        assert_eq!(project.undo().unwrap(), None);
+        assert_eq!(read(), "1");
+    }
 }
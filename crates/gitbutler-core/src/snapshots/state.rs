@@ -12,6 +12,13 @@ use serde::{Deserialize, Serialize};
 pub struct Oplog {
     /// This is the sha of the last oplog commit
     pub head_sha: Option<String>,
+    /// The shas of the snapshots that redo restores, most recently undone last.
+    /// Cleared whenever a new operation is recorded.
+    #[serde(default)]
+    pub redo_stack: Vec<String>,
+    /// When the oplog was last compacted, in seconds since the epoch.
+    #[serde(default)]
+    pub compacted_at: Option<i64>,
 }
 
 pub struct OplogHandle {
@@ -44,6 +51,52 @@ impl OplogHandle {
         Ok(oplog.head_sha)
     }
 
+    /// Remembers the snapshot to restore when the undo that is about to happen is redone.
+    pub fn push_redo(&self, sha: String) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        oplog.redo_stack.push(sha);
+        self.write_file(&oplog)
+    }
+
+    /// Takes the snapshot to restore for the next redo, if anything was undone.
+    pub fn pop_redo(&self) -> Result<Option<String>> {
+        let mut oplog = self.read_file()?;
+        let sha = oplog.redo_stack.pop();
+        if sha.is_some() {
+            self.write_file(&oplog)?;
+        }
+        Ok(sha)
+    }
+
+    /// Gets the snapshots that redo restores, most recently undone last.
+    pub fn get_redo_stack(&self) -> Result<Vec<String>> {
+        let oplog = self.read_file()?;
+        Ok(oplog.redo_stack)
+    }
+
+    /// Replaces the snapshots that redo restores.
+    pub fn set_redo_stack(&self, redo_stack: Vec<String>) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        if oplog.redo_stack != redo_stack {
+            oplog.redo_stack = redo_stack;
+            self.write_file(&oplog)?;
+        }
+        Ok(())
+    }
+
+    /// Gets when the oplog was last compacted, in seconds since the epoch.
+    pub fn get_compacted_at(&self) -> Result<Option<i64>> {
+        let oplog = self.read_file()?;
+        Ok(oplog.compacted_at)
+    }
+
+    /// Remembers that the oplog was compacted at `seconds` since the epoch.
+    pub fn set_compacted_at(&self, seconds: i64) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        oplog.compacted_at = Some(seconds);
+        self.write_file(&oplog)
+    }
+
     /// Reads and parses the state file.
     ///
     /// If the file does not exist, it will be created.
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::git;
@@ -18,6 +20,9 @@ pub struct User {
     pub github_access_token: Option<String>,
     #[serde(default)]
     pub github_username: Option<String>,
+    /// Personal access tokens to authenticate with over https, by host.
+    #[serde(default)]
+    pub access_tokens: BTreeMap<String, String>,
 }
 
 impl TryFrom<User> for git::Signature<'_> {
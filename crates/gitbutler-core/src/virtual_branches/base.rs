@@ -1,6 +1,7 @@
-use std::{path::Path, time};
+use std::{collections::HashMap, path::Path, time};
 
 use anyhow::{Context, Result};
+use itertools::Itertools;
 use serde::Serialize;
 
 use super::{
@@ -25,8 +26,13 @@ pub struct BaseBranch {
     pub branch_name: String,
     pub remote_name: String,
     pub remote_url: String,
+    /// The forge hosting the remote, if it's known.
+    pub forge: Option<git::ForgeKind>,
+    /// The web page of the remote repository, if it has one.
+    pub web_url: Option<String>,
     pub push_remote_name: Option<String>,
     pub push_remote_url: String,
+    pub push_web_url: Option<String>,
     pub base_sha: git::Oid,
     pub current_sha: git::Oid,
     pub behind: usize,
@@ -197,7 +203,14 @@ pub fn set_base_branch(
         // if there are any commits on the head branch or uncommitted changes in the working directory, we need to
         // put them into a virtual branch
 
-        let wd_diff = diff::workdir(repo, &current_head_commit.id())?;
+        let wd_diff = diff::workdir(
+            repo,
+            &current_head_commit.id(),
+            &project_repository
+                .project()
+                .diff_settings
+                .keeping_whitespace(),
+        )?;
         if !wd_diff.is_empty() || current_head_commit.id() != target.sha {
             // assign ownership to the branch
             let ownership = wd_diff.iter().fold(
@@ -263,6 +276,8 @@ pub fn set_base_branch(
                 ownership,
                 order: 0,
                 selected_for_changes: None,
+                parent: None,
+                pull_request: None,
             };
 
             vb_state.set_branch(branch)?;
@@ -344,6 +359,17 @@ pub fn update_base_branch(
     user: Option<&users::User>,
     signing_key: Option<&keys::PrivateKey>,
 ) -> Result<(), errors::UpdateBaseBranchError> {
+    for branch in project_repository
+        .project()
+        .virtual_branches()
+        .list_branches()
+        .context("failed to read virtual branches")?
+    {
+        if super::rebase::is_rebasing(project_repository, &branch.id)? {
+            return Err(errors::UpdateBaseBranchError::RebaseInProgress(branch.id));
+        }
+    }
+
     if project_repository.is_resolving() {
         return Err(errors::UpdateBaseBranchError::Conflict(
             errors::ProjectConflict {
@@ -389,14 +415,72 @@ pub fn update_base_branch(
     let vb_state = project_repository.project().virtual_branches();
     let integration_commit = get_workspace_head(&vb_state, project_repository)?;
 
+    let applied_branches = vb_state
+        .list_branches()?
+        .into_iter()
+        .filter(|branch| branch.applied)
+        .collect::<Vec<_>>();
+    // the heads of the branches before updating them
+    let old_heads = applied_branches
+        .iter()
+        .map(|branch| (branch.id, branch.head))
+        .collect::<HashMap<_, _>>();
+
     // try to update every branch
     let updated_vbranches =
         super::get_status_by_branch(project_repository, Some(&integration_commit))?
             .0
             .into_iter()
-            .map(|(branch, _)| branch)
+            // the status leaves large files out of the tree of an applied branch, but their
+            // content has to survive the update
+            .map(|(mut branch, files)| -> Result<branch::Branch> {
+                if branch.applied {
+                    branch.tree = super::write_tree(project_repository, &branch.head, &files)?;
+                }
+                Ok(branch)
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            // stacked branches follow their parent, so parents are updated first
+            .sorted_by_key(|branch| super::stack_depth(&applied_branches, branch))
             .map(
                 |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
+                    // the parent of a stacked branch was updated before it
+                    let updated_parent = branch.parent.and_then(|parent_id| {
+                        old_heads
+                            .get(&parent_id)
+                            .map(|old_head| (parent_id, *old_head))
+                    });
+                    if let Some((parent_id, old_parent_head)) = updated_parent {
+                        match vb_state.get_branch(&parent_id) {
+                            Ok(parent) if parent.applied => {
+                                if !super::rebase_onto_parent(
+                                    project_repository,
+                                    &mut branch,
+                                    old_parent_head,
+                                    parent.head,
+                                    signing_key,
+                                )? {
+                                    // unapplied like any other branch that conflicts with the new target
+                                    branch.applied = false;
+                                }
+                                vb_state.set_branch(branch.clone())?;
+                                return Ok(Some(branch));
+                            }
+                            Ok(_) => {
+                                // the parent conflicts with the new target, and so does everything built on it
+                                branch.applied = false;
+                                vb_state.set_branch(branch.clone())?;
+                                return Ok(Some(branch));
+                            }
+                            Err(crate::reader::Error::NotFound) => {
+                                // the parent was integrated and removed, the branch is based on the target from now on
+                                branch.parent = None;
+                            }
+                            Err(error) => return Err(error.into()),
+                        }
+                    }
+
                     let branch_tree = repo.find_tree(branch.tree)?;
 
                     let branch_head_commit = repo.find_commit(branch.head).context(format!(
@@ -424,6 +508,10 @@ pub fn update_base_branch(
                                 &project_repository.git_repository,
                                 &branch_head_tree,
                                 &branch_tree,
+                                &project_repository
+                                    .project()
+                                    .diff_settings
+                                    .keeping_whitespace(),
                             )?;
                             if non_commited_files.is_empty() {
                                 // if there are no commited files, then the branch is fully merged
@@ -528,52 +616,22 @@ pub fn update_base_branch(
                     }
 
                     // branch was not pushed to upstream yet. attempt a rebase,
-                    let (_, committer) = project_repository.git_signatures(user)?;
-                    let mut rebase_options = git2::RebaseOptions::new();
-                    rebase_options.quiet(true);
-                    rebase_options.inmemory(true);
-                    let mut rebase = repo
-                        .rebase(
-                            Some(branch.head),
-                            Some(new_target_commit.id()),
-                            None,
-                            Some(&mut rebase_options),
-                        )
-                        .context("failed to rebase")?;
-
-                    let mut rebase_success = true;
-                    // check to see if these commits have already been pushed
-                    let mut last_rebase_head = branch.head;
-                    while rebase.next().is_some() {
-                        let index = rebase
-                            .inmemory_index()
-                            .context("failed to get inmemory index")?;
-                        if index.has_conflicts() {
-                            rebase_success = false;
-                            break;
-                        }
-
-                        if let Ok(commit_id) = rebase.commit(None, &committer.clone().into(), None)
-                        {
-                            last_rebase_head = commit_id.into();
-                        } else {
-                            rebase_success = false;
-                            break;
-                        }
-                    }
-
-                    if rebase_success {
+                    let rebased_head = super::rebase::rebase_onto(
+                        project_repository,
+                        branch.head,
+                        new_target_commit.id(),
+                        signing_key,
+                    )?;
+
+                    if let Some(rebased_head) = rebased_head {
                         // rebase worked out, rewrite the branch head
-                        rebase.finish(None).context("failed to finish rebase")?;
-                        branch.head = last_rebase_head;
+                        branch.head = rebased_head;
                         branch.tree = branch_merge_index_tree_oid;
                         vb_state.set_branch(branch.clone())?;
                         return Ok(Some(branch));
                     }
 
                     // rebase failed, do a merge commit
-                    rebase.abort().context("failed to abort rebase")?;
-
                     result_merge(branch)
                 },
             )
@@ -585,16 +643,29 @@ pub fn update_base_branch(
     // ok, now all the problematic branches have been unapplied
     // now we calculate and checkout new tree for the working directory
 
-    let final_tree = updated_vbranches
+    let applied_vbranches = updated_vbranches
         .iter()
         .filter(|branch| branch.applied)
-        .fold(new_target_commit.tree(), |final_tree, branch| {
-            let final_tree = final_tree?;
-            let branch_tree = repo.find_tree(branch.tree)?;
-            let mut merge_result = repo.merge_trees(&new_target_tree, &final_tree, &branch_tree)?;
-            let final_tree_oid = merge_result.write_tree_to(repo)?;
-            repo.find_tree(final_tree_oid)
-        })
+        .cloned()
+        .collect::<Vec<_>>();
+    let final_tree = applied_vbranches
+        .iter()
+        .fold(
+            new_target_commit.tree().map_err(Into::into),
+            |final_tree: Result<git::Tree>, branch| {
+                let final_tree = final_tree?;
+                let base_tree = super::stack_base_tree(
+                    project_repository,
+                    &applied_vbranches,
+                    branch,
+                    new_target_commit.id(),
+                )?;
+                let branch_tree = repo.find_tree(branch.tree)?;
+                let mut merge_result = repo.merge_trees(&base_tree, &final_tree, &branch_tree)?;
+                let final_tree_oid = merge_result.write_tree_to(repo)?;
+                Ok(repo.find_tree(final_tree_oid)?)
+            },
+        )
         .context("failed to calculate final tree")?;
 
     repo.checkout_tree(&final_tree)
@@ -653,12 +724,30 @@ pub fn target_to_base_branch(
         None => target.remote_url.clone(),
     };
 
+    let (forge, web_url) = match target.remote_url.parse::<git::Url>() {
+        Ok(remote_url) => (
+            project_repository
+                .config()
+                .forge_kind(&remote_url)
+                .context("failed to read forge config")?,
+            remote_url.web_url(),
+        ),
+        Err(_) => (None, None),
+    };
+    let push_web_url = push_remote_url
+        .parse::<git::Url>()
+        .ok()
+        .and_then(|url| url.web_url());
+
     let base = super::BaseBranch {
         branch_name: format!("{}/{}", target.branch.remote(), target.branch.branch()),
         remote_name: target.branch.remote().to_string(),
         remote_url: target.remote_url.clone(),
+        forge,
+        web_url,
         push_remote_name: target.push_remote_name.clone(),
         push_remote_url,
+        push_web_url,
         base_sha: target.sha,
         current_sha: oid,
         behind: upstream_commits.len(),
@@ -89,14 +89,24 @@ impl OwnershipClaim {
             return another.clone();
         }
 
-        let mut hunks = self
-            .hunks
-            .iter()
-            .filter(|hunk| !another.hunks.contains(hunk))
-            .cloned()
-            .collect::<Vec<Hunk>>();
-
-        for hunk in another.hunks {
+        let mut hunks = self.hunks.clone();
+        for mut hunk in another.hunks {
+            if !hunk.lines.is_empty() {
+                if hunks
+                    .iter()
+                    .any(|owned| owned.lines.is_empty() && owned.same_hunk(&hunk))
+                {
+                    // all of the hunk is owned already
+                    continue;
+                }
+                let owned_lines = hunks
+                    .iter()
+                    .filter(|owned| owned.same_hunk(&hunk))
+                    .flat_map(|owned| owned.lines.iter().copied());
+                let lines = hunk.lines.iter().copied().chain(owned_lines).collect();
+                hunk = hunk.with_lines(lines);
+            }
+            hunks.retain(|owned| !owned.same_hunk(&hunk));
             hunks.insert(0, hunk);
         }
 
@@ -133,11 +143,27 @@ impl OwnershipClaim {
             left = left
                 .iter()
                 .flat_map(|r: &Hunk| -> Vec<Hunk> {
-                    if r.eq(range) {
+                    if !r.same_hunk(range) {
+                        return vec![r.clone()];
+                    }
+                    if range.lines.is_empty() || r.lines == range.lines {
                         taken.push(r.clone());
+                        return vec![];
+                    }
+                    if r.lines.is_empty() {
+                        // all of a hunk is what's left of it after other claims for some of
+                        // its lines, so this keeps the rest
+                        return vec![r.clone()];
+                    }
+                    let (taken_lines, left_lines): (Vec<_>, Vec<_>) =
+                        r.lines.iter().partition(|line| range.lines.contains(line));
+                    if !taken_lines.is_empty() {
+                        taken.push(r.clone().with_lines(taken_lines));
+                    }
+                    if left_lines.is_empty() {
                         vec![]
                     } else {
-                        vec![r.clone()]
+                        vec![r.clone().with_lines(left_lines)]
                     }
                 })
                 .collect();
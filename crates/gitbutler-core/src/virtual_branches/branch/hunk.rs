@@ -1,4 +1,9 @@
-use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
+use std::{
+    fmt::Display,
+    hash::{Hash, Hasher},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
 use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
@@ -14,27 +19,36 @@ pub struct Hunk {
     pub start: u32,
     pub end: u32,
     pub locked_to: Vec<diff::HunkLock>,
+    /// The changed lines of the hunk this is about, or all of them if empty.
+    pub lines: Vec<diff::ChangedLine>,
 }
 
+/// The claim for all of the hunk `git2` found, even if `hunk` only has some of its changes.
 impl From<&diff::GitHunk> for Hunk {
     fn from(hunk: &diff::GitHunk) -> Self {
+        let hunk = hunk.whole();
         Hunk {
             start: hunk.new_start,
             end: hunk.new_start + hunk.new_lines,
             hash: Some(Hunk::hash_diff(&hunk.diff_lines)),
             timestamp_ms: None,
             locked_to: hunk.locked_to.to_vec(),
+            lines: vec![],
         }
     }
 }
 
 impl PartialEq for Hunk {
     fn eq(&self, other: &Self) -> bool {
-        if self.hash.is_some() && other.hash.is_some() {
-            self.hash == other.hash && self.start == other.start && self.end == other.end
-        } else {
-            self.start == other.start && self.end == other.end
-        }
+        self.same_hunk(other) && self.lines == other.lines
+    }
+}
+
+impl Hash for Hunk {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // equal hunks may differ in their range and the hash may be missing on either side of
+        // a comparison, so neither can go in here
+        self.lines.hash(state);
     }
 }
 
@@ -46,6 +60,7 @@ impl From<RangeInclusive<u32>> for Hunk {
             hash: None,
             timestamp_ms: None,
             locked_to: vec![],
+            lines: vec![],
         }
     }
 }
@@ -54,6 +69,11 @@ impl FromStr for Hunk {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (s, lines) = match s.strip_suffix(']').and_then(|s| s.split_once('[')) {
+            Some((s, raw_lines)) => (s, parse_lines(raw_lines)?),
+            None => (s, vec![]),
+        };
+
         let mut range = s.split('-');
         let start = if let Some(raw_start) = range.next() {
             raw_start
@@ -93,8 +113,20 @@ impl FromStr for Hunk {
             None
         };
 
-        Hunk::new(start, end, hash, timestamp_ms)
+        Hunk::new(start, end, hash, timestamp_ms).map(|hunk| hunk.with_lines(lines))
+    }
+}
+
+/// Parse changed lines written like `+4+5-3`.
+fn parse_lines(s: &str) -> Result<Vec<diff::ChangedLine>> {
+    let mut lines = vec![];
+    let mut rest = s;
+    while !rest.is_empty() {
+        let end = rest[1..].find(['+', '-']).map_or(rest.len(), |pos| pos + 1);
+        lines.push(rest[..end].parse()?);
+        rest = &rest[end..];
     }
+    Ok(lines)
 }
 
 impl Display for Hunk {
@@ -105,7 +137,15 @@ impl Display for Hunk {
             (Some(hash), None) => write!(f, "-{:x}", hash),
             (None, Some(timestamp_ms)) => write!(f, "--{}", timestamp_ms),
             (None, None) => Ok(()),
+        }?;
+        if !self.lines.is_empty() {
+            write!(f, "[")?;
+            for line in &self.lines {
+                write!(f, "{line}")?;
+            }
+            write!(f, "]")?;
         }
+        Ok(())
     }
 }
 
@@ -125,6 +165,7 @@ impl Hunk {
                 start,
                 end,
                 locked_to: vec![],
+                lines: vec![],
             })
         }
     }
@@ -134,6 +175,14 @@ impl Hunk {
         self
     }
 
+    /// Claim only `lines` of the hunk instead of all of its changes.
+    pub fn with_lines(mut self, mut lines: Vec<diff::ChangedLine>) -> Self {
+        lines.sort();
+        lines.dedup();
+        self.lines = lines;
+        self
+    }
+
     pub fn with_timestamp(mut self, timestamp_ms: u128) -> Self {
         self.timestamp_ms = Some(timestamp_ms);
         self
@@ -155,13 +204,52 @@ impl Hunk {
     }
 
     pub fn shallow_eq(&self, other: &diff::GitHunk) -> bool {
+        let other = other.whole();
         self.start == other.new_start && self.end == other.new_start + other.new_lines
     }
 
+    /// `true` if `other` is about the same hunk, no matter which of its lines either claims.
+    /// Hunks with the same changes are the same even if their ranges differ, as long as they
+    /// overlap, as the range depends on how many context lines the diff has.
+    pub fn same_hunk(&self, other: &Hunk) -> bool {
+        if self.hash.is_some() && other.hash.is_some() {
+            self.hash == other.hash && self.start <= other.end && other.start <= self.end
+        } else {
+            self.start == other.start && self.end == other.end
+        }
+    }
+
+    /// The part of `hunk` this claim is for, which is all of it unless only some of its lines
+    /// are claimed. `None` if this doesn't claim `hunk`, or none of its lines are in it.
+    /// `hunk` may itself have only some of the changes of the hunk this claim is about.
+    pub fn select(&self, hunk: &diff::GitHunk) -> Option<diff::GitHunk> {
+        if !self.same_hunk(&Hunk::from(hunk)) {
+            return None;
+        }
+        if self.lines.is_empty() {
+            return Some(hunk.clone());
+        }
+        hunk.filter_changes(|line| self.lines.contains(&line))
+    }
+
+    /// What's left of `hunk` without the part this claim is for, if anything.
+    pub fn deselect(&self, hunk: &diff::GitHunk) -> Option<diff::GitHunk> {
+        if !self.same_hunk(&Hunk::from(hunk)) {
+            return Some(hunk.clone());
+        }
+        if self.lines.is_empty() {
+            return None;
+        }
+        hunk.filter_changes(|line| !self.lines.contains(&line))
+    }
+
     /// Produce a hash from `diff` as hex-string, which is **assumed to have a one-line diff header**!
     /// `diff` can also be entirely empty, or not contain a diff header which is when it will just be hashed
     /// with [`Self::hash()`].
     ///
+    /// Only the added and removed lines are hashed, so the hash stays the same no matter how many
+    /// context lines surround them.
+    ///
     /// ### Notes on Persistence
     /// Note that there is danger in changing the hash function as this information is persisted
     /// in the virtual-branch toml file. Even if it can still be parsed or decoded,
@@ -174,6 +262,7 @@ impl Hunk {
         let mut ctx = md5::Context::new();
         diff.lines_with_terminator()
             .skip(1) // skip the first line which is the diff header.
+            .filter(|line| line.starts_with(b"+") || line.starts_with(b"-"))
             .for_each(|line| ctx.consume(line));
         ctx.compute()
     }
@@ -8,7 +8,7 @@ pub use hunk::{Hunk, HunkHash};
 pub use ownership::{reconcile_claims, BranchOwnershipClaims};
 use serde::{Deserialize, Serialize};
 
-use crate::{git, id::Id};
+use crate::{forge, git, id::Id};
 
 pub type BranchId = Id<Branch>;
 
@@ -45,6 +45,13 @@ pub struct Branch {
     // is Some(timestamp), the branch is considered a default destination for new changes.
     // if more than one branch is selected, the branch with the highest timestamp wins.
     pub selected_for_changes: Option<i64>,
+    /// The branch this branch is stacked on, if any. A stacked branch builds on the head of its parent
+    /// rather than on the default target, and is rebased whenever its parent is.
+    #[serde(default)]
+    pub parent: Option<BranchId>,
+    /// The pull request opened for the upstream, as last seen on the forge.
+    #[serde(default)]
+    pub pull_request: Option<forge::PullRequest>,
 }
 
 fn serialize_u128<S>(x: &u128, s: S) -> Result<S::Ok, S::Error>
@@ -86,6 +93,8 @@ pub struct BranchCreateRequest {
     pub ownership: Option<BranchOwnershipClaims>,
     pub order: Option<usize>,
     pub selected_for_changes: Option<bool>,
+    // the branch to stack the new branch on
+    pub parent: Option<BranchId>,
 }
 
 impl Branch {
@@ -225,6 +234,8 @@ impl Branch {
             ownership,
             order,
             selected_for_changes,
+            parent: None,
+            pull_request: None,
         })
     }
 }
@@ -167,13 +167,25 @@ pub fn reconcile_claims(
     for outcome in claim_outcomes.clone() {
         for claim in outcome.updated_branch.ownership.claims {
             for hunk in claim.hunks {
-                if !seen.insert(format!(
+                let hunk_key = format!(
                     "{}-{}-{}",
                     claim.file_path.to_str().unwrap_or_default(),
                     hunk.start,
                     hunk.end
-                )) {
-                    return Err(anyhow::anyhow!("inconsistent ownership claims"));
+                );
+                // a hunk can be claimed as a whole once, and each of its lines once more
+                let keys = if hunk.lines.is_empty() {
+                    vec![hunk_key]
+                } else {
+                    hunk.lines
+                        .iter()
+                        .map(|line| format!("{hunk_key}{line}"))
+                        .collect()
+                };
+                for key in keys {
+                    if !seen.insert(key) {
+                        return Err(anyhow::anyhow!("inconsistent ownership claims"));
+                    }
                 }
             }
         }
@@ -1,11 +1,15 @@
 use crate::{
     error::Error,
     snapshots::{
-        entry::{OperationType, SnapshotDetails},
+        entry::{OperationType, SnapshotDetails, Trailer},
         snapshot::Oplog,
     },
 };
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
 
 use anyhow::Context;
 use tokio::{sync::Semaphore, task::JoinHandle};
@@ -13,11 +17,13 @@ use tokio::{sync::Semaphore, task::JoinHandle};
 use super::{
     branch::{BranchId, BranchOwnershipClaims},
     errors::{self, FetchFromTargetError},
+    rebase::RebaseOutcome,
     target, target_to_base_branch, BaseBranch, RemoteBranchFile, VirtualBranchesHandle,
 };
 use crate::{
     askpass::AskpassBroker,
-    git, keys, project_repository,
+    forge, git, keys,
+    project_repository::{self, remote_backend::OnProgress},
     projects::{self, ProjectId},
     users,
 };
@@ -28,6 +34,9 @@ pub struct Controller {
     users: users::Controller,
     keys: keys::Controller,
     helper: git::credentials::Helper,
+    /// Where the progress of fetches and pushes goes, if anywhere.
+    on_progress: Option<OnProgress>,
+    forge_provider: forge::ForgeProvider,
 
     by_project_id: Arc<tokio::sync::Mutex<HashMap<ProjectId, ControllerInner>>>,
 }
@@ -46,16 +55,38 @@ impl Controller {
             users,
             keys,
             helper,
+            on_progress: None,
+            forge_provider: Arc::new(forge::for_remote),
         }
     }
 
+    /// Report the progress of fetches and pushes to `on_progress` while they run.
+    #[must_use]
+    pub fn with_progress(mut self, on_progress: OnProgress) -> Self {
+        self.on_progress = Some(on_progress);
+        self
+    }
+
+    /// Look up the forges hosting remotes with `forge_provider` instead of [`forge::for_remote()`].
+    pub fn with_forge_provider(mut self, forge_provider: forge::ForgeProvider) -> Self {
+        self.forge_provider = forge_provider;
+        self
+    }
+
     async fn inner(&self, project_id: &ProjectId) -> ControllerInner {
         self.by_project_id
             .lock()
             .await
             .entry(*project_id)
             .or_insert_with(|| {
-                ControllerInner::new(&self.projects, &self.users, &self.keys, &self.helper)
+                ControllerInner::new(
+                    &self.projects,
+                    &self.users,
+                    &self.keys,
+                    &self.helper,
+                    self.on_progress.as_ref(),
+                    &self.forge_provider,
+                )
             })
             .clone()
     }
@@ -243,7 +274,7 @@ impl Controller {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         ownership: &BranchOwnershipClaims,
-    ) -> Result<git::Oid, Error> {
+    ) -> Result<RebaseOutcome, Error> {
         self.inner(project_id)
             .await
             .amend(project_id, branch_id, commit_oid, ownership)
@@ -275,7 +306,7 @@ impl Controller {
         project_id: &ProjectId,
         branch_id: &BranchId,
         commit_oid: git::Oid,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         self.inner(project_id)
             .await
             .undo_commit(project_id, branch_id, commit_oid)
@@ -288,7 +319,7 @@ impl Controller {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         offset: i32,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         self.inner(project_id)
             .await
             .insert_blank_commit(project_id, branch_id, commit_oid, offset)
@@ -301,13 +332,35 @@ impl Controller {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         offset: i32,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         self.inner(project_id)
             .await
             .reorder_commit(project_id, branch_id, commit_oid, offset)
             .await
     }
 
+    pub async fn continue_rebase(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<RebaseOutcome, Error> {
+        self.inner(project_id)
+            .await
+            .continue_rebase(project_id, branch_id)
+            .await
+    }
+
+    pub async fn abort_rebase(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .abort_rebase(project_id, branch_id)
+            .await
+    }
+
     pub async fn reset_virtual_branch(
         &self,
         project_id: &ProjectId,
@@ -337,10 +390,25 @@ impl Controller {
         branch_id: &BranchId,
         with_force: bool,
         askpass: Option<(AskpassBroker, Option<BranchId>)>,
-    ) -> Result<(), Error> {
+    ) -> Result<super::PushReport, Error> {
+        let options = super::PushOptions {
+            with_force,
+            ..Default::default()
+        };
+        self.push_virtual_branch_with_options(project_id, branch_id, &options, askpass)
+            .await
+    }
+
+    pub async fn push_virtual_branch_with_options(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        options: &super::PushOptions,
+        askpass: Option<(AskpassBroker, Option<BranchId>)>,
+    ) -> Result<super::PushReport, Error> {
         self.inner(project_id)
             .await
-            .push_virtual_branch(project_id, branch_id, with_force, askpass)
+            .push_virtual_branch(project_id, branch_id, options, askpass)
             .await
     }
 
@@ -359,10 +427,11 @@ impl Controller {
     pub async fn list_remote_branches(
         &self,
         project_id: &ProjectId,
+        filter: &super::RemoteBranchFilter,
     ) -> Result<Vec<super::RemoteBranch>, Error> {
         self.inner(project_id)
             .await
-            .list_remote_branches(project_id)
+            .list_remote_branches(project_id, filter)
     }
 
     pub async fn get_remote_branch_data(
@@ -380,7 +449,7 @@ impl Controller {
         project_id: &ProjectId,
         branch_id: &BranchId,
         commit_oid: git::Oid,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         self.inner(project_id)
             .await
             .squash(project_id, branch_id, commit_oid)
@@ -393,7 +462,7 @@ impl Controller {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         message: &str,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         self.inner(project_id)
             .await
             .update_commit_message(project_id, branch_id, commit_oid, message)
@@ -411,6 +480,17 @@ impl Controller {
             .await
     }
 
+    pub async fn fetch_remotes(
+        &self,
+        project_id: &ProjectId,
+        askpass: Option<(AskpassBroker, String)>,
+    ) -> Result<BTreeMap<String, project_repository::RemoteError>, Error> {
+        self.inner(project_id)
+            .await
+            .fetch_remotes(project_id, askpass)
+            .await
+    }
+
     pub async fn move_commit(
         &self,
         project_id: &ProjectId,
@@ -422,6 +502,41 @@ impl Controller {
             .move_commit(project_id, target_branch_id, commit_oid)
             .await
     }
+
+    pub async fn create_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        pull_request: &forge::NewPullRequest,
+    ) -> Result<forge::PullRequest, Error> {
+        self.inner(project_id)
+            .await
+            .create_pull_request(project_id, branch_id, pull_request)
+            .await
+    }
+
+    pub async fn update_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        update: &forge::PullRequestUpdate,
+    ) -> Result<forge::PullRequest, Error> {
+        self.inner(project_id)
+            .await
+            .update_pull_request(project_id, branch_id, update)
+            .await
+    }
+
+    /// Look up the pull requests of all pushed branches on their forge, so listing the branches shows them.
+    pub async fn refresh_pull_requests(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<HashMap<BranchId, anyhow::Error>, Error> {
+        self.inner(project_id)
+            .await
+            .refresh_pull_requests(project_id)
+            .await
+    }
 }
 
 #[derive(Clone)]
@@ -432,6 +547,8 @@ struct ControllerInner {
     users: users::Controller,
     keys: keys::Controller,
     helper: git::credentials::Helper,
+    on_progress: Option<OnProgress>,
+    forge_provider: forge::ForgeProvider,
 }
 
 impl ControllerInner {
@@ -440,6 +557,8 @@ impl ControllerInner {
         users: &users::Controller,
         keys: &keys::Controller,
         helper: &git::credentials::Helper,
+        on_progress: Option<&OnProgress>,
+        forge_provider: &forge::ForgeProvider,
     ) -> Self {
         Self {
             semaphore: Arc::new(Semaphore::new(1)),
@@ -447,6 +566,8 @@ impl ControllerInner {
             users: users.clone(),
             keys: keys.clone(),
             helper: helper.clone(),
+            on_progress: on_progress.cloned(),
+            forge_provider: forge_provider.clone(),
         }
     }
 
@@ -460,19 +581,13 @@ impl ControllerInner {
     ) -> Result<git::Oid, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-
-            let result = super::commit(
+        let details = SnapshotDetails::new(OperationType::CreateCommit)
+            .with_trailers([Trailer::new("branch_id", branch_id)])
+            .with_trailers(ownership.into_iter().flat_map(ownership_trailers));
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::commit(
                 project_repository,
                 branch_id,
                 message,
@@ -481,12 +596,7 @@ impl ControllerInner {
                 user,
                 run_hooks,
             )
-            .map_err(Into::into);
-
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::CreateCommit));
-            result
+            .map_err(Into::into)
         })
     }
 
@@ -531,12 +641,10 @@ impl ControllerInner {
     ) -> Result<BranchId, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let branch_id = super::create_virtual_branch(project_repository, create)?.id;
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::CreateBranch));
-            Ok(branch_id)
+        let details = SnapshotDetails::new(OperationType::CreateBranch)
+            .with_trailers(create.name.iter().map(|name| Trailer::new("name", name)));
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            Ok(super::create_virtual_branch(project_repository, create)?.id)
         })
     }
 
@@ -547,27 +655,16 @@ impl ControllerInner {
     ) -> Result<BranchId, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-            let result = super::create_virtual_branch_from_branch(
+        let details = SnapshotDetails::new(OperationType::CreateBranch)
+            .with_trailers([Trailer::new("branch", branch)]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+            Ok(super::create_virtual_branch_from_branch(
                 project_repository,
                 branch,
                 signing_key.as_ref(),
                 user,
-            )?;
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::CreateBranch));
-            Ok(result)
+            )?)
         })
     }
 
@@ -587,8 +684,12 @@ impl ControllerInner {
     ) -> Result<Vec<RemoteBranchFile>, Error> {
         let project = self.projects.get(project_id)?;
         let project_repository = project_repository::Repository::open(&project)?;
-        super::list_remote_commit_files(&project_repository.git_repository, commit_oid)
-            .map_err(Into::into)
+        super::list_remote_commit_files(
+            &project_repository.git_repository,
+            commit_oid,
+            &project.diff_settings,
+        )
+        .map_err(Into::into)
     }
 
     pub fn set_base_branch(
@@ -598,11 +699,18 @@ impl ControllerInner {
     ) -> Result<super::BaseBranch, Error> {
         let project = self.projects.get(project_id)?;
         let project_repository = project_repository::Repository::open(&project)?;
-        let result = super::set_base_branch(&project_repository, target_branch)?;
-        let _ = project_repository
-            .project()
-            .create_snapshot(SnapshotDetails::new(OperationType::SetBaseBranch));
-        Ok(result)
+        // there is no workspace to snapshot before the first target is set, or while it's left
+        // for another branch, which setting the same target goes back from
+        if default_target(&project.gb_dir())?.is_some()
+            && super::integration::verify_branch(&project_repository).is_ok()
+        {
+            let details = SnapshotDetails::new(OperationType::SetBaseBranch)
+                .with_trailers([Trailer::new("branch", target_branch)]);
+            return self.with_snapshot(project_id, details, |project_repository, _| {
+                Ok(super::set_base_branch(project_repository, target_branch)?)
+            });
+        }
+        Ok(super::set_base_branch(&project_repository, target_branch)?)
     }
 
     pub fn set_target_push_remote(
@@ -610,10 +718,14 @@ impl ControllerInner {
         project_id: &ProjectId,
         push_remote: &str,
     ) -> Result<(), Error> {
-        let project = self.projects.get(project_id)?;
-        let project_repository = project_repository::Repository::open(&project)?;
-        super::set_target_push_remote(&project_repository, push_remote)?;
-        Ok(())
+        let details = SnapshotDetails::new(OperationType::SetPushRemote)
+            .with_trailers([Trailer::new("remote", push_remote)]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            Ok(super::set_target_push_remote(
+                project_repository,
+                push_remote,
+            )?)
+        })
     }
 
     pub async fn merge_virtual_branch_upstream(
@@ -623,53 +735,30 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-
-            let result = super::merge_virtual_branch_upstream(
+        let details = SnapshotDetails::new(OperationType::MergeUpstream)
+            .with_trailers([Trailer::new("branch_id", branch_id)]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::merge_virtual_branch_upstream(
                 project_repository,
                 branch_id,
                 signing_key.as_ref(),
                 user,
             )
-            .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::MergeUpstream));
-            result
+            .map_err(Into::into)
         })
     }
 
     pub async fn update_base_branch(&self, project_id: &ProjectId) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-
-            let result = super::update_base_branch(project_repository, user, signing_key.as_ref())
-                .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::UpdateWorkspaceBase));
-            result
+        let details = SnapshotDetails::new(OperationType::UpdateWorkspaceBase);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::update_base_branch(project_repository, user, signing_key.as_ref())
+                .map_err(Into::into)
         })
     }
 
@@ -680,24 +769,32 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let details = if branch_update.ownership.is_some() {
-                SnapshotDetails::new(OperationType::MoveHunk)
-            } else if branch_update.name.is_some() {
-                SnapshotDetails::new(OperationType::UpdateBranchName)
-            } else if branch_update.notes.is_some() {
-                SnapshotDetails::new(OperationType::UpdateBranchNotes)
-            } else if branch_update.order.is_some() {
-                SnapshotDetails::new(OperationType::ReorderBranches)
-            } else if branch_update.selected_for_changes.is_some() {
-                SnapshotDetails::new(OperationType::SelectDefaultVirtualBranch)
-            } else if branch_update.upstream.is_some() {
-                SnapshotDetails::new(OperationType::UpdateBranchRemoteName)
-            } else {
-                SnapshotDetails::new(OperationType::GenericBranchUpdate)
-            };
+        let details = if branch_update.ownership.is_some() {
+            SnapshotDetails::new(OperationType::MoveHunk)
+        } else if branch_update.name.is_some() {
+            SnapshotDetails::new(OperationType::UpdateBranchName)
+        } else if branch_update.notes.is_some() {
+            SnapshotDetails::new(OperationType::UpdateBranchNotes)
+        } else if branch_update.order.is_some() {
+            SnapshotDetails::new(OperationType::ReorderBranches)
+        } else if branch_update.selected_for_changes.is_some() {
+            SnapshotDetails::new(OperationType::SelectDefaultVirtualBranch)
+        } else if branch_update.upstream.is_some() {
+            SnapshotDetails::new(OperationType::UpdateBranchRemoteName)
+        } else {
+            SnapshotDetails::new(OperationType::GenericBranchUpdate)
+        };
+        let details = details
+            .with_trailers([Trailer::new("branch_id", branch_update.id)])
+            .with_trailers(
+                branch_update
+                    .name
+                    .iter()
+                    .map(|name| Trailer::new("name", name)),
+            )
+            .with_trailers(branch_update.ownership.iter().flat_map(ownership_trailers));
+        self.with_snapshot(project_id, details, |project_repository, _| {
             super::update_branch(project_repository, branch_update)?;
-            let _ = project_repository.project().create_snapshot(details);
             Ok(())
         })
     }
@@ -709,11 +806,10 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
+        let details = SnapshotDetails::new(OperationType::DeleteBranch)
+            .with_trailers([Trailer::new("branch_id", branch_id)]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
             super::delete_branch(project_repository, branch_id)?;
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::DeleteBranch));
             Ok(())
         })
     }
@@ -725,25 +821,13 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-
-            let result =
-                super::apply_branch(project_repository, branch_id, signing_key.as_ref(), user)
-                    .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::ApplyBranch));
-            result
+        let details = SnapshotDetails::new(OperationType::ApplyBranch)
+            .with_trailers([Trailer::new("branch_id", branch_id)]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+
+            super::apply_branch(project_repository, branch_id, signing_key.as_ref(), user)
+                .map_err(Into::into)
         })
     }
 
@@ -754,13 +838,10 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::unapply_ownership(project_repository, ownership).map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::DiscardHunk));
-            result
+        let details = SnapshotDetails::new(OperationType::DiscardHunk)
+            .with_trailers(ownership_trailers(ownership));
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            super::unapply_ownership(project_repository, ownership).map_err(Into::into)
         })
     }
 
@@ -771,12 +852,10 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::reset_files(project_repository, ownership).map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::DiscardFile));
-            result
+        let details = SnapshotDetails::new(OperationType::DiscardFile)
+            .with_trailers(ownership.iter().map(|path| Trailer::new("file_path", path)));
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            super::reset_files(project_repository, ownership).map_err(Into::into)
         })
     }
 
@@ -786,16 +865,25 @@ impl ControllerInner {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         ownership: &BranchOwnershipClaims,
-    ) -> Result<git::Oid, Error> {
+    ) -> Result<RebaseOutcome, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::amend(project_repository, branch_id, commit_oid, ownership)
-                .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::AmendCommit));
-            result
+        let details = SnapshotDetails::new(OperationType::AmendCommit)
+            .with_trailers([
+                Trailer::new("branch_id", branch_id),
+                Trailer::new("commit_id", commit_oid),
+            ])
+            .with_trailers(ownership_trailers(ownership));
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::amend(
+                project_repository,
+                branch_id,
+                commit_oid,
+                ownership,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
         })
     }
 
@@ -809,19 +897,24 @@ impl ControllerInner {
     ) -> Result<git::Oid, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::move_commit_file(
+        let details = SnapshotDetails::new(OperationType::MoveCommitFile)
+            .with_trailers([
+                Trailer::new("branch_id", branch_id),
+                Trailer::new("from_commit_id", from_commit_oid),
+                Trailer::new("to_commit_id", to_commit_oid),
+            ])
+            .with_trailers(ownership_trailers(ownership));
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::move_commit_file(
                 project_repository,
                 branch_id,
                 from_commit_oid,
                 to_commit_oid,
                 ownership,
+                signing_key.as_ref(),
             )
-            .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::MoveCommitFile));
-            result
+            .map_err(Into::into)
         })
     }
 
@@ -830,16 +923,22 @@ impl ControllerInner {
         project_id: &ProjectId,
         branch_id: &BranchId,
         commit_oid: git::Oid,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::undo_commit(project_repository, branch_id, commit_oid).map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::UndoCommit));
-            result
+        let details = SnapshotDetails::new(OperationType::UndoCommit).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("commit_id", commit_oid),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::undo_commit(
+                project_repository,
+                branch_id,
+                commit_oid,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
         })
     }
 
@@ -849,17 +948,25 @@ impl ControllerInner {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         offset: i32,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let result =
-                super::insert_blank_commit(project_repository, branch_id, commit_oid, user, offset)
-                    .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::InsertBlankCommit));
-            result
+        let details = SnapshotDetails::new(OperationType::InsertBlankCommit).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("commit_id", commit_oid),
+            Trailer::new("offset", offset),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::insert_blank_commit(
+                project_repository,
+                branch_id,
+                commit_oid,
+                user,
+                offset,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
         })
     }
 
@@ -869,16 +976,61 @@ impl ControllerInner {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         offset: i32,
+    ) -> Result<RebaseOutcome, Error> {
+        let _permit = self.semaphore.acquire().await;
+
+        let details = SnapshotDetails::new(OperationType::ReorderCommit).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("commit_id", commit_oid),
+            Trailer::new("offset", offset),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::reorder_commit(
+                project_repository,
+                branch_id,
+                commit_oid,
+                offset,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn continue_rebase(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<RebaseOutcome, Error> {
+        let _permit = self.semaphore.acquire().await;
+
+        let details = SnapshotDetails::new(OperationType::ContinueRebase)
+            .with_trailers([Trailer::new("branch_id", branch_id)]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::rebase::continue_rebase(
+                project_repository,
+                branch_id,
+                user,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn abort_rebase(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::reorder_commit(project_repository, branch_id, commit_oid, offset)
-                .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::ReorderCommit));
-            result
+        let details = SnapshotDetails::new(OperationType::AbortRebase)
+            .with_trailers([Trailer::new("branch_id", branch_id)]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::rebase::abort_rebase(project_repository, branch_id, user, signing_key.as_ref())
+                .map_err(Into::into)
         })
     }
 
@@ -890,13 +1042,13 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::reset_branch(project_repository, branch_id, target_commit_oid)
-                .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::UndoCommit));
-            result
+        let details = SnapshotDetails::new(OperationType::ResetBranch).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("target_commit_id", target_commit_oid),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            super::reset_branch(project_repository, branch_id, target_commit_oid)
+                .map_err(Into::into)
         })
     }
 
@@ -907,14 +1059,12 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::unapply_branch(project_repository, branch_id)
+        let details = SnapshotDetails::new(OperationType::UnapplyBranch)
+            .with_trailers([Trailer::new("branch_id", branch_id)]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            super::unapply_branch(project_repository, branch_id)
                 .map(|_| ())
-                .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::UnapplyBranch));
-            result
+                .map_err(Into::into)
         })
     }
 
@@ -922,20 +1072,23 @@ impl ControllerInner {
         &self,
         project_id: &ProjectId,
         branch_id: &BranchId,
-        with_force: bool,
+        options: &super::PushOptions,
         askpass: Option<(AskpassBroker, Option<BranchId>)>,
-    ) -> Result<(), Error> {
+    ) -> Result<super::PushReport, Error> {
         let _permit = self.semaphore.acquire().await;
         let helper = self.helper.clone();
+        let on_progress = self.on_progress.clone();
         let project_id = *project_id;
         let branch_id = *branch_id;
+        let options = *options;
         self.with_verify_branch_async(&project_id, move |project_repository, _| {
             Ok(super::push(
                 project_repository,
                 &branch_id,
-                with_force,
+                &options,
                 &helper,
                 askpass,
+                on_progress.as_ref(),
             )?)
         })?
         .await
@@ -950,23 +1103,30 @@ impl ControllerInner {
     ) -> Result<Option<git::Oid>, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::cherry_pick(project_repository, branch_id, commit_oid).map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::CherryPick));
-            result
+        let details = SnapshotDetails::new(OperationType::CherryPick).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("commit_id", commit_oid),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::cherry_pick(
+                project_repository,
+                branch_id,
+                commit_oid,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
         })
     }
 
     pub fn list_remote_branches(
         &self,
         project_id: &ProjectId,
+        filter: &super::RemoteBranchFilter,
     ) -> Result<Vec<super::RemoteBranch>, Error> {
         let project = self.projects.get(project_id)?;
         let project_repository = project_repository::Repository::open(&project)?;
-        Ok(super::list_remote_branches(&project_repository)?)
+        Ok(super::list_remote_branches(&project_repository, filter)?)
     }
 
     pub fn get_remote_branch_data(
@@ -984,16 +1144,22 @@ impl ControllerInner {
         project_id: &ProjectId,
         branch_id: &BranchId,
         commit_oid: git::Oid,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::squash(project_repository, branch_id, commit_oid).map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::SquashCommit));
-            result
+        let details = SnapshotDetails::new(OperationType::SquashCommit).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("commit_id", commit_oid),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::squash(
+                project_repository,
+                branch_id,
+                commit_oid,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
         })
     }
 
@@ -1003,16 +1169,23 @@ impl ControllerInner {
         branch_id: &BranchId,
         commit_oid: git::Oid,
         message: &str,
-    ) -> Result<(), Error> {
+    ) -> Result<RebaseOutcome, Error> {
         let _permit = self.semaphore.acquire().await;
-        self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::update_commit_message(project_repository, branch_id, commit_oid, message)
-                    .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::UpdateCommitMessage));
-            result
+
+        let details = SnapshotDetails::new(OperationType::UpdateCommitMessage).with_trailers([
+            Trailer::new("branch_id", branch_id),
+            Trailer::new("commit_id", commit_oid),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, _| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::update_commit_message(
+                project_repository,
+                branch_id,
+                commit_oid,
+                message,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
         })
     }
 
@@ -1021,8 +1194,29 @@ impl ControllerInner {
         project_id: &ProjectId,
         askpass: Option<(AskpassBroker, String)>,
     ) -> Result<BaseBranch, Error> {
+        self.fetch_remotes(project_id, askpass).await?;
+
         let project = self.projects.get(project_id)?;
-        let mut project_repository = project_repository::Repository::open(&project)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        let default_target = default_target(&project_repository.project().gb_dir())
+            .context("failed to get default target")?
+            .context("default target was not set")?;
+
+        let base_branch = target_to_base_branch(&project_repository, &default_target)
+            .context("failed to convert target to base branch")?;
+
+        Ok(base_branch)
+    }
+
+    /// Fetches the remotes of the project and records the outcome of each.
+    /// Returns the errors of the remotes that failed to fetch, by remote name.
+    pub async fn fetch_remotes(
+        &self,
+        project_id: &ProjectId,
+        askpass: Option<(AskpassBroker, String)>,
+    ) -> Result<BTreeMap<String, project_repository::RemoteError>, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
 
         let default_target = default_target(&project_repository.project().gb_dir())
             .context("failed to get default target")?
@@ -1032,46 +1226,60 @@ impl ControllerInner {
                 },
             ))?;
 
-        let project_data_last_fetched = match project_repository
-            .fetch(
-                default_target.branch.remote(),
-                &self.helper,
-                askpass.clone(),
-            )
-            .map_err(errors::FetchFromTargetError::Remote)
-        {
-            Ok(()) => projects::FetchResult::Fetched {
-                timestamp: std::time::SystemTime::now(),
-            },
-            Err(error) => projects::FetchResult::Error {
-                timestamp: std::time::SystemTime::now(),
-                error: error.to_string(),
-            },
-        };
-
-        // if we have a push remote, let's fetch from this too
-        if let Some(push_remote) = &default_target.push_remote_name {
-            let _ = project_repository
-                .fetch(push_remote, &self.helper, askpass.clone())
-                .map_err(errors::FetchFromTargetError::Remote);
+        // the target's remote, and the push remote if we have one, are fetched along with the configured remotes
+        let target_remote = default_target.branch.remote();
+        let fetched = project_repository.fetch_remotes(
+            &[target_remote]
+                .into_iter()
+                .chain(default_target.push_remote_name.as_deref())
+                .collect::<Vec<_>>(),
+            &self.helper,
+            askpass,
+            self.on_progress.as_ref(),
+        )?;
+
+        let timestamp = std::time::SystemTime::now();
+        let mut fetch_results = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+        for (remote, result) in fetched {
+            let fetch_result = match result {
+                Ok(()) => projects::FetchResult::Fetched { timestamp },
+                Err(error) => {
+                    let fetch_result = projects::FetchResult::Error {
+                        timestamp,
+                        error: error.to_string(),
+                    };
+                    errors.insert(remote.clone(), error);
+                    fetch_result
+                }
+            };
+            fetch_results.insert(remote, fetch_result);
         }
-
-        let updated_project = self
-            .projects
+        let project_data_last_fetched = fetch_results
+            .get(target_remote)
+            .cloned()
+            .context("target remote was not fetched")?;
+
+        // results of remotes that were removed since they were last fetched are dropped
+        let remotes = project_repository
+            .git_repository
+            .remotes()
+            .context("failed to list remotes")?;
+        let mut remotes_last_fetched = project.remotes_last_fetch.clone();
+        remotes_last_fetched.retain(|remote, _| remotes.contains(remote));
+        remotes_last_fetched.extend(fetch_results);
+
+        self.projects
             .update(&projects::UpdateRequest {
                 id: *project_id,
                 project_data_last_fetched: Some(project_data_last_fetched),
+                remotes_last_fetched: Some(remotes_last_fetched),
                 ..Default::default()
             })
             .await
             .context("failed to update project")?;
 
-        project_repository.set_project(&updated_project);
-
-        let base_branch = target_to_base_branch(&project_repository, &default_target)
-            .context("failed to convert target to base branch")?;
-
-        Ok(base_branch)
+        Ok(errors)
     }
 
     pub async fn move_commit(
@@ -1082,34 +1290,178 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-            let result = super::move_commit(
+        let details = SnapshotDetails::new(OperationType::MoveCommit).with_trailers([
+            Trailer::new("branch_id", target_branch_id),
+            Trailer::new("commit_id", commit_oid),
+        ]);
+        self.with_snapshot(project_id, details, |project_repository, user| {
+            let signing_key = self.signing_key(project_repository)?;
+            super::move_commit(
                 project_repository,
                 target_branch_id,
                 commit_oid,
                 user,
                 signing_key.as_ref(),
             )
-            .map_err(Into::into);
-            let _ = project_repository
-                .project()
-                .create_snapshot(SnapshotDetails::new(OperationType::MoveCommit));
-            result
+            .map_err(Into::into)
         })
     }
+
+    pub async fn create_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        pull_request: &forge::NewPullRequest,
+    ) -> Result<forge::PullRequest, Error> {
+        let target = self
+            .pull_request_target(project_id, branch_id)?
+            .context("branch has not been pushed to a supported forge")?;
+        let created = target
+            .forge
+            .create_pull_request(&target.head, &target.base, pull_request)
+            .await
+            .context("failed to create pull request")?;
+        self.set_pull_request(project_id, branch_id, Some(created.clone()))
+            .await?;
+        Ok(created)
+    }
+
+    pub async fn update_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        update: &forge::PullRequestUpdate,
+    ) -> Result<forge::PullRequest, Error> {
+        let target = self
+            .pull_request_target(project_id, branch_id)?
+            .context("branch has not been pushed to a supported forge")?;
+        let project = self.projects.get(project_id)?;
+        let known_number = VirtualBranchesHandle::new(project.gb_dir())
+            .get_branch(branch_id)
+            .context("failed to read branch")?
+            .pull_request
+            .map(|pull_request| pull_request.number);
+        let number = match known_number {
+            Some(number) => number,
+            None => {
+                target
+                    .forge
+                    .pull_request(&target.head)
+                    .await
+                    .context("failed to get pull request")?
+                    .context("branch has no pull request")?
+                    .number
+            }
+        };
+        let updated = target
+            .forge
+            .update_pull_request(number, update)
+            .await
+            .context("failed to update pull request")?;
+        self.set_pull_request(project_id, branch_id, Some(updated.clone()))
+            .await?;
+        Ok(updated)
+    }
+
+    /// Looks up the pull requests of all pushed branches. A branch whose pull request can't be looked up
+    /// keeps the one last seen, and the error is returned along with those of the other branches.
+    pub async fn refresh_pull_requests(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<HashMap<BranchId, anyhow::Error>, Error> {
+        let project = self.projects.get(project_id)?;
+        let branch_ids = VirtualBranchesHandle::new(project.gb_dir())
+            .list_branches()
+            .context("failed to list branches")?
+            .into_iter()
+            .filter(|branch| branch.upstream.is_some())
+            .map(|branch| branch.id)
+            .collect::<Vec<_>>();
+
+        let mut errors = HashMap::new();
+        for branch_id in branch_ids {
+            let pull_request = match self.pull_request_target(project_id, &branch_id) {
+                Ok(Some(target)) => target
+                    .forge
+                    .pull_request(&target.head)
+                    .await
+                    .context("failed to get pull request"),
+                Ok(None) => continue,
+                // there is nothing to refresh before the user logs in
+                Err(error)
+                    if matches!(
+                        error.downcast_ref::<forge::Error>(),
+                        Some(forge::Error::NotAuthenticated(_))
+                    ) =>
+                {
+                    continue
+                }
+                Err(error) => Err(error.into()),
+            };
+            match pull_request {
+                Ok(pull_request) => {
+                    self.set_pull_request(project_id, &branch_id, pull_request)
+                        .await?;
+                }
+                Err(error) => {
+                    tracing::warn!(%project_id, %branch_id, ?error, "failed to refresh pull request");
+                    errors.insert(branch_id, error);
+                }
+            }
+        }
+        Ok(errors)
+    }
+
+    /// Records `pull_request` as the one last seen for the branch.
+    async fn set_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        pull_request: Option<forge::PullRequest>,
+    ) -> Result<(), Error> {
+        let _permit = self.semaphore.acquire().await;
+        let project = self.projects.get(project_id)?;
+        let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+        let mut branch = vb_state
+            .get_branch(branch_id)
+            .context("failed to read branch")?;
+        if branch.pull_request != pull_request {
+            branch.pull_request = pull_request;
+            vb_state
+                .set_branch(branch)
+                .context("failed to write branch")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the pull request of a branch goes.
+struct PullRequestTarget {
+    forge: Box<dyn forge::Forge>,
+    head: forge::Head,
+    /// The branch the pull request merges into.
+    base: String,
 }
 
 impl ControllerInner {
+    /// The key to sign commits with, if the project is configured to sign them.
+    fn signing_key(
+        &self,
+        project_repository: &project_repository::Repository,
+    ) -> Result<Option<keys::PrivateKey>, Error> {
+        let signing_key = project_repository
+            .config()
+            .sign_commits()
+            .context("failed to get sign commits option")?
+            .then(|| {
+                self.keys
+                    .get_or_create()
+                    .context("failed to get private key")
+            })
+            .transpose()?;
+        Ok(signing_key)
+    }
+
     fn with_verify_branch<T>(
         &self,
         project_id: &ProjectId,
@@ -1122,6 +1474,95 @@ impl ControllerInner {
         action(&project_repository, user.as_ref())
     }
 
+    /// Where the pull request of `branch_id` goes, or `None` if the branch wasn't pushed to a forge
+    /// we can talk to.
+    ///
+    /// Pull requests are opened against the repository of the target's remote, from the branch's
+    /// upstream, which may be in a fork. Stacked branches merge into the upstream of their parent.
+    fn pull_request_target(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Option<PullRequestTarget>, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+        let branch = vb_state
+            .get_branch(branch_id)
+            .context("failed to read branch")?;
+        let Some(upstream) = branch.upstream else {
+            return Ok(None);
+        };
+        let Some(default_target) = default_target(&project.gb_dir())? else {
+            return Ok(None);
+        };
+
+        let remote_url = |name: &str| -> Result<Option<git::Url>, Error> {
+            Ok(project_repository
+                .git_repository
+                .find_remote(name)
+                .with_context(|| format!("failed to find remote {name}"))?
+                .url()
+                .with_context(|| format!("failed to get url of remote {name}"))?)
+        };
+        let (Some(target_url), Some(upstream_url)) = (
+            remote_url(default_target.branch.remote())?,
+            remote_url(upstream.remote())?,
+        ) else {
+            return Ok(None);
+        };
+        let forge_kind = project_repository
+            .config()
+            .forge_kind(&target_url)
+            .context("failed to read forge config")?;
+        let user = self.users.get_user()?;
+        let (Some(forge), Some(upstream_repo)) = (
+            (self.forge_provider)(&target_url, forge_kind, user.as_ref())
+                .map_err(Error::from_err)?,
+            forge::Repo::from_url(&upstream_url),
+        ) else {
+            return Ok(None);
+        };
+
+        let parent_upstream = branch
+            .parent
+            .map(|parent| vb_state.get_branch(&parent))
+            .transpose()
+            .context("failed to read parent branch")?
+            .and_then(|parent| parent.upstream);
+        let base = parent_upstream
+            .as_ref()
+            .unwrap_or(&default_target.branch)
+            .branch()
+            .to_owned();
+
+        Ok(Some(PullRequestTarget {
+            forge,
+            head: forge::Head {
+                owner: upstream_repo.owner,
+                branch: upstream.branch().to_owned(),
+            },
+            base,
+        }))
+    }
+
+    /// Like [`Self::with_verify_branch`], but records a snapshot described by `details` before running `action`,
+    /// so that the operation can be undone.
+    fn with_snapshot<T>(
+        &self,
+        project_id: &ProjectId,
+        details: SnapshotDetails,
+        action: impl FnOnce(&project_repository::Repository, Option<&users::User>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.with_verify_branch(project_id, |project_repository, user| {
+            // failing to record a snapshot only means the operation can't be undone
+            if let Err(error) = project_repository.project().create_snapshot(details) {
+                tracing::warn!(%project_id, ?error, "failed to create snapshot");
+            }
+            action(project_repository, user)
+        })
+    }
+
     fn with_verify_branch_async<T: Send + 'static>(
         &self,
         project_id: &ProjectId,
@@ -1139,6 +1580,14 @@ impl ControllerInner {
     }
 }
 
+/// One trailer per claimed file, holding its path.
+fn ownership_trailers(ownership: &BranchOwnershipClaims) -> impl Iterator<Item = Trailer> + '_ {
+    ownership
+        .claims
+        .iter()
+        .map(|claim| Trailer::new("file_path", claim.file_path.display()))
+}
+
 fn default_target(base_path: &Path) -> anyhow::Result<Option<target::Target>> {
     let vb_state = VirtualBranchesHandle::new(base_path);
     match vb_state.get_default_target() {
@@ -22,11 +22,13 @@ pub enum VirtualBranchError {
     #[error("commit failed")]
     CommitFailed,
     #[error("rebase failed")]
-    RebaseFailed,
+    RebaseFailed(#[source] anyhow::Error),
     #[error("force push not allowed")]
     ForcePushNotAllowed(ForcePushNotAllowed),
     #[error("branch has no commits")]
     BranchHasNoCommits,
+    #[error("branch {0} is being rebased")]
+    RebaseInProgress(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -46,7 +48,7 @@ impl ErrorWithContext for VirtualBranchError {
             VirtualBranchError::CommitFailed => {
                 error::Context::new_static(Code::Branches, "commit failed")
             }
-            VirtualBranchError::RebaseFailed => {
+            VirtualBranchError::RebaseFailed(_) => {
                 error::Context::new_static(Code::Branches, "rebase failed")
             }
             VirtualBranchError::BranchHasNoCommits => error::Context::new_static(
@@ -54,6 +56,10 @@ impl ErrorWithContext for VirtualBranchError {
                 "Branch has no commits - there is nothing to amend to",
             ),
             VirtualBranchError::ForcePushNotAllowed(ctx) => ctx.to_context(),
+            VirtualBranchError::RebaseInProgress(branch_id) => error::Context::new(
+                Code::Branches,
+                format!("branch {branch_id} is being rebased, continue or abort the rebase first"),
+            ),
             VirtualBranchError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
     }
@@ -218,6 +224,8 @@ impl ErrorWithContext for ListVirtualBranchesError {
 pub enum CreateVirtualBranchError {
     #[error("project")]
     DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -226,6 +234,7 @@ impl ErrorWithContext for CreateVirtualBranchError {
     fn context(&self) -> Option<Context> {
         match self {
             CreateVirtualBranchError::DefaultTargetNotSet(ctx) => ctx.to_context().into(),
+            CreateVirtualBranchError::BranchNotFound(ctx) => ctx.to_context().into(),
             CreateVirtualBranchError::Other(error) => error.custom_context_or_root_cause().into(),
         }
     }
@@ -265,6 +274,8 @@ pub enum CommitError {
     CommitHookRejected(String),
     #[error("commit msg hook rejected")]
     CommitMsgHookRejected(String),
+    #[error("branch {0} is being rebased")]
+    RebaseInProgress(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -281,6 +292,10 @@ impl ErrorWithContext for CommitError {
             CommitError::CommitMsgHookRejected(error) => {
                 error::Context::new(Code::CommitMsgHook, error)
             }
+            CommitError::RebaseInProgress(branch_id) => error::Context::new(
+                Code::Branches,
+                format!("branch {branch_id} is being rebased, continue or abort the rebase first"),
+            ),
             CommitError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
     }
@@ -292,6 +307,12 @@ pub enum PushError {
     DefaultTargetNotSet(DefaultTargetNotSet),
     #[error("branch not found")]
     BranchNotFound(BranchNotFound),
+    #[error("pre-push hook rejected")]
+    PrePushHookRejected(String),
+    #[error("remote branch {0} changed since it was last pushed")]
+    LeaseExpired(git::RemoteRefname),
+    #[error("branch {0} is being rebased")]
+    RebaseInProgress(BranchId),
     #[error(transparent)]
     Remote(#[from] project_repository::RemoteError),
     #[error(transparent)]
@@ -303,6 +324,17 @@ impl ErrorWithContext for PushError {
         Some(match self {
             PushError::DefaultTargetNotSet(ctx) => ctx.to_context(),
             PushError::BranchNotFound(ctx) => ctx.to_context(),
+            PushError::PrePushHookRejected(error) => {
+                error::Context::new(Code::PrePushHook, error)
+            }
+            PushError::LeaseExpired(remote_branch) => error::Context::new(
+                Code::ProjectGitPush,
+                format!("{remote_branch} has changed since it was last pushed, fetch it before pushing again"),
+            ),
+            PushError::RebaseInProgress(branch_id) => error::Context::new(
+                Code::Branches,
+                format!("branch {branch_id} is being rebased, continue or abort the rebase first"),
+            ),
             PushError::Remote(error) => return error.context(),
             PushError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
@@ -433,6 +465,32 @@ impl ErrorWithContext for SquashError {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum RebaseError {
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("branch {0} is not being rebased")]
+    NotRebasing(BranchId),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for RebaseError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            RebaseError::BranchNotFound(ctx) => ctx.to_context(),
+            RebaseError::NotRebasing(branch_id) => error::Context::new(
+                Code::Branches,
+                format!("branch {branch_id} is not being rebased"),
+            ),
+            RebaseError::Conflict(ctx) => ctx.to_context(),
+            RebaseError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum FetchFromTargetError {
     #[error("default target not set")]
@@ -522,6 +580,8 @@ pub enum UpdateBaseBranchError {
     Conflict(ProjectConflict),
     #[error("no default target set")]
     DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("branch {0} is being rebased")]
+    RebaseInProgress(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -531,6 +591,10 @@ impl ErrorWithContext for UpdateBaseBranchError {
         Some(match self {
             UpdateBaseBranchError::Conflict(ctx) => ctx.to_context(),
             UpdateBaseBranchError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            UpdateBaseBranchError::RebaseInProgress(branch_id) => error::Context::new(
+                Code::Branches,
+                format!("branch {branch_id} is being rebased, continue or abort the rebase first"),
+            ),
             UpdateBaseBranchError::Other(error) => {
                 return error.custom_context_or_root_cause().into()
             }
@@ -550,6 +614,8 @@ pub enum MoveCommitError {
     BranchNotFound(BranchNotFound),
     #[error("commit not found")]
     CommitNotFound(git::Oid),
+    #[error("branch {0} is being rebased")]
+    RebaseInProgress(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -567,6 +633,10 @@ impl ErrorWithContext for MoveCommitError {
             MoveCommitError::CommitNotFound(oid) => {
                 error::Context::new(Code::Branches, format!("Commit {} not found", oid))
             }
+            MoveCommitError::RebaseInProgress(branch_id) => error::Context::new(
+                Code::Branches,
+                format!("branch {branch_id} is being rebased, continue or abort the rebase first"),
+            ),
             MoveCommitError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
     }
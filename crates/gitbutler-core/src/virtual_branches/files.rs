@@ -4,7 +4,10 @@ use anyhow::{Context, Result};
 use serde::Serialize;
 
 use super::errors;
-use crate::git::{self, diff};
+use crate::{
+    git::{self, diff},
+    projects::DiffSettings,
+};
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,6 +20,7 @@ pub struct RemoteBranchFile {
 pub fn list_remote_commit_files(
     repository: &git::Repository,
     commit_oid: git::Oid,
+    settings: &DiffSettings,
 ) -> Result<Vec<RemoteBranchFile>, errors::ListRemoteCommitFilesError> {
     let commit = match repository.find_commit(commit_oid) {
         Ok(commit) => Ok(commit),
@@ -33,7 +37,7 @@ pub fn list_remote_commit_files(
     let parent = commit.parent(0).context("failed to get parent commit")?;
     let commit_tree = commit.tree().context("failed to get commit tree")?;
     let parent_tree = parent.tree().context("failed to get parent tree")?;
-    let diff_files = diff::trees(repository, &parent_tree, &commit_tree)?;
+    let diff_files = diff::trees(repository, &parent_tree, &commit_tree, settings)?;
 
     Ok(diff_files
         .into_iter()
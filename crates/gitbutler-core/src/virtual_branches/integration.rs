@@ -20,7 +20,7 @@ const WORKSPACE_HEAD: &str = "Workspace Head";
 const GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME: &str = "GitButler";
 const GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL: &str = "gitbutler@gitbutler.com";
 
-fn get_committer<'a>() -> Result<git::Signature<'a>> {
+pub(crate) fn get_committer<'a>() -> Result<git::Signature<'a>> {
     Ok(git::Signature::now(
         GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
         GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
@@ -42,21 +42,28 @@ pub fn get_workspace_head(
     let vb_state = project_repository.project().virtual_branches();
 
     let all_virtual_branches = vb_state.list_branches()?;
-    let applied_virtual_branches = all_virtual_branches
-        .iter()
+    let mut applied_virtual_branches = all_virtual_branches
+        .into_iter()
         .filter(|branch| branch.applied)
         .collect::<Vec<_>>();
+    // Stacked branches are merged after the branch they are stacked on.
+    super::sort_by_stack(&mut applied_virtual_branches, |branch| branch);
 
     let target_commit = repo.find_commit(target.sha)?;
-    let target_tree = target_commit.tree()?;
     let mut workspace_tree = target_commit.tree()?;
 
     // Merge applied branches into one `workspace_tree`.
     for branch in &applied_virtual_branches {
         let branch_head = repo.find_commit(branch.head)?;
         let branch_tree = branch_head.tree()?;
+        let base_tree = super::stack_base_tree(
+            project_repository,
+            &applied_virtual_branches,
+            branch,
+            target.sha,
+        )?;
 
-        if let Ok(mut result) = repo.merge_trees(&target_tree, &workspace_tree, &branch_tree) {
+        if let Ok(mut result) = repo.merge_trees(&base_tree, &workspace_tree, &branch_tree) {
             if !result.has_conflicts() {
                 let final_tree_oid = result.write_tree_to(repo)?;
                 workspace_tree = repo.find_tree(final_tree_oid)?;
@@ -19,6 +19,8 @@ pub use controller::Controller;
 mod r#virtual;
 pub use r#virtual::*;
 
+pub mod rebase;
+
 mod remote;
 pub use remote::*;
 
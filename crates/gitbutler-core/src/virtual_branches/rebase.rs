@@ -0,0 +1,629 @@
+//! A cherry-pick based rebase of the commits of a virtual branch that can stop on conflicts.
+//!
+//! When a commit can't be picked cleanly, its conflicts are checked out into the working
+//! directory and marked with [`conflicts::mark`], and the progress of the rebase is kept in
+//! `virtual_branches.toml`. Once all conflicts are resolved the rebase can be continued with
+//! [`continue_rebase`], or it can be undone entirely with [`abort_rebase`].
+//!
+//! While a rebase is stopped, the original head and the uncommitted changes of the branch are
+//! kept reachable through `refs/gitbutler-rebase/<branch id>`, so `git gc` leaves them alone.
+//! The other applied branches are unapplied for as long as the rebase is stopped, and no other
+//! operation may rewrite the history of the branch until it is continued or aborted.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use bstr::ByteSlice;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    branch::{Branch, BranchId},
+    errors, r#virtual as vb,
+};
+use crate::{
+    git, keys,
+    project_repository::{self, conflicts},
+    reader, users,
+};
+
+/// The progress of a rebase that stopped on a conflicting commit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RebaseState {
+    /// The head of the branch before the rebase started.
+    pub original_head: git::Oid,
+    /// The tree of the branch including its uncommitted changes when the rebase started.
+    pub wip_tree: git::Oid,
+    /// The last commit that was rebased, and the one `conflicting` is picked onto.
+    pub onto: git::Oid,
+    /// The commit that could not be picked cleanly.
+    pub conflicting: git::Oid,
+    /// The commits that still need to be picked after `conflicting`, oldest first.
+    pub remaining: Vec<git::Oid>,
+    /// The branches that were unapplied to make room for resolving conflicts, and that are
+    /// applied again once the rebase is over.
+    #[serde(default)]
+    pub unapplied: Vec<BranchId>,
+}
+
+/// How a rebase of a virtual branch ended.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RebaseOutcome {
+    /// All commits were rebased and the branch head now points at the given commit.
+    Completed(git::Oid),
+    /// A commit conflicted. The conflicts are checked out and need to be resolved before
+    /// the rebase can be continued.
+    Stopped,
+}
+
+/// The result of picking a list of commits one after another.
+pub(crate) enum Picked {
+    /// All commits were picked, the last one being the given commit.
+    All(git::Oid),
+    /// Picking `conflicting` onto `onto` produced conflicts.
+    Conflict {
+        onto: git::Oid,
+        conflicting: git::Oid,
+        remaining: Vec<git::Oid>,
+    },
+}
+
+/// Cherry-picks `ids_to_pick` (oldest first) onto `onto`, stopping at the first commit that
+/// conflicts. Unlike a libgit2 based rebase, this also picks empty commits.
+///
+/// The picked commits keep their authorship, and are signed with `signing_key` if given.
+pub(crate) fn pick_commits(
+    project_repository: &project_repository::Repository,
+    onto: git::Oid,
+    ids_to_pick: &[git::Oid],
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Picked> {
+    let repo = &project_repository.git_repository;
+    let mut head = repo
+        .find_commit(onto)
+        .context("failed to find new commit")?;
+
+    for (idx, id) in ids_to_pick.iter().enumerate() {
+        let to_rebase = repo
+            .find_commit(*id)
+            .context("failed to read commit to rebase")?;
+
+        let mut cherrypick_index = repo
+            .cherry_pick(&head, &to_rebase)
+            .context("failed to cherry pick")?;
+
+        if cherrypick_index.has_conflicts() {
+            return Ok(Picked::Conflict {
+                onto: head.id(),
+                conflicting: *id,
+                remaining: ids_to_pick[idx + 1..].to_vec(),
+            });
+        }
+
+        let merge_tree_oid = cherrypick_index
+            .write_tree_to(repo)
+            .context("failed to write merge tree")?;
+        let merge_tree = repo
+            .find_tree(merge_tree_oid)
+            .context("failed to find merge tree")?;
+
+        let commit_oid = project_repository
+            .recommit(
+                &to_rebase,
+                &to_rebase.message().to_str_lossy(),
+                &merge_tree,
+                &[&head],
+                signing_key,
+            )
+            .context("failed to create commit")?;
+
+        head = repo
+            .find_commit(commit_oid)
+            .context("failed to find commit")?;
+    }
+
+    Ok(Picked::All(head.id()))
+}
+
+/// Picks the commits of `head` that aren't part of `onto` onto it, like `git rebase` would,
+/// and returns the new head, or `None` if a commit conflicts. Merge commits are dropped.
+pub(crate) fn rebase_onto(
+    project_repository: &project_repository::Repository,
+    head: git::Oid,
+    onto: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Option<git::Oid>> {
+    let repo = &project_repository.git_repository;
+    let mut ids_to_pick = Vec::new();
+    for id in project_repository.l(head, project_repository::LogUntil::Commit(onto))? {
+        let commit = repo.find_commit(id).context("failed to find commit")?;
+        if commit.parent_count() <= 1 {
+            ids_to_pick.push(id);
+        }
+    }
+    ids_to_pick.reverse();
+
+    match pick_commits(project_repository, onto, &ids_to_pick, signing_key)? {
+        Picked::All(new_head) => Ok(Some(new_head)),
+        Picked::Conflict { .. } => Ok(None),
+    }
+}
+
+/// Rebases `ids_to_rebase` (newest first, as returned by [`project_repository::Repository::l`])
+/// onto `target_commit_oid` and makes the result the new head of `branch`.
+///
+/// If a commit conflicts, all other applied branches are unapplied, the conflicts are
+/// checked out and `branch` is marked as being rebased. This is only possible for applied
+/// branches, for others the rebase fails.
+pub fn rebase_branch(
+    project_repository: &project_repository::Repository,
+    branch: &mut Branch,
+    target_commit_oid: git::Oid,
+    ids_to_rebase: &[git::Oid],
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<RebaseOutcome> {
+    let vb_state = project_repository.project().virtual_branches();
+    if vb_state
+        .get_branch_rebase(&branch.id)
+        .context("failed to read rebase state")?
+        .is_some()
+    {
+        bail!("branch {} is already being rebased", branch.name);
+    }
+
+    let ids_to_pick = ids_to_rebase.iter().rev().copied().collect::<Vec<_>>();
+    match pick_commits(
+        project_repository,
+        target_commit_oid,
+        &ids_to_pick,
+        signing_key,
+    )? {
+        Picked::All(new_head) => {
+            let old_head = branch.head;
+            branch.head = new_head;
+            vb_state
+                .set_branch(branch.clone())
+                .context("failed to write branch")?;
+            vb::rebase_stacked_branches(project_repository, branch, old_head, signing_key)?;
+
+            super::integration::update_gitbutler_integration(&vb_state, project_repository)
+                .context("failed to update gitbutler integration")?;
+
+            Ok(RebaseOutcome::Completed(new_head))
+        }
+        Picked::Conflict {
+            onto,
+            conflicting,
+            remaining,
+        } => {
+            if !branch.applied {
+                bail!("failed to rebase: {} conflicts with {}", conflicting, onto);
+            }
+
+            let (wip_tree, unapplied) = stash_workspace(project_repository, branch)?;
+            let state = RebaseState {
+                original_head: branch.head,
+                wip_tree,
+                onto,
+                conflicting,
+                remaining,
+                unapplied,
+            };
+            keep_reachable(project_repository, &branch.id, &state)?;
+            stop(project_repository, branch, state)?;
+
+            Ok(RebaseOutcome::Stopped)
+        }
+    }
+}
+
+/// Commits the resolved changes of the conflicting commit and picks the remaining ones.
+///
+/// The rebase may stop again if another commit conflicts. Once it completes, the uncommitted
+/// changes the branch had when the rebase started are brought back into the working directory,
+/// and the branches that were unapplied for the rebase are applied again.
+pub fn continue_rebase(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<RebaseOutcome, errors::RebaseError> {
+    let vb_state = project_repository.project().virtual_branches();
+    let (mut branch, state) = get_rebasing_branch(project_repository, branch_id)?;
+
+    let integration_commit_id =
+        super::integration::get_workspace_head(&vb_state, project_repository)?;
+    let (statuses, _) = vb::get_status_by_branch(project_repository, Some(&integration_commit_id))
+        .context("failed to get status by branch")?;
+    let files = statuses
+        .into_iter()
+        .find(|(b, _)| b.id == *branch_id)
+        .map(|(_, files)| files)
+        .unwrap_or_default();
+
+    vb::update_conflict_markers(project_repository, &files)?;
+
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::RebaseError::Conflict(errors::ProjectConflict {
+            project_id: project_repository.project().id,
+        }));
+    }
+
+    let repo = &project_repository.git_repository;
+    let resolved_tree_oid = vb::write_tree_onto_commit(project_repository, state.onto, files)?;
+    let resolved_tree = repo
+        .find_tree(resolved_tree_oid)
+        .context("failed to find resolved tree")?;
+    let onto = repo
+        .find_commit(state.onto)
+        .context("failed to find commit to rebase onto")?;
+    let conflicting = repo
+        .find_commit(state.conflicting)
+        .context("failed to find conflicting commit")?;
+
+    let resolved_oid = project_repository
+        .recommit(
+            &conflicting,
+            &conflicting.message().to_str_lossy(),
+            &resolved_tree,
+            &[&onto],
+            signing_key,
+        )
+        .context("failed to commit resolved changes")?;
+
+    if conflicts::is_resolving(project_repository) {
+        conflicts::clear(project_repository).context("failed to clear conflicts")?;
+    }
+
+    match pick_commits(
+        project_repository,
+        resolved_oid,
+        &state.remaining,
+        signing_key,
+    )? {
+        Picked::All(new_head) => {
+            finish(
+                project_repository,
+                &mut branch,
+                &state,
+                new_head,
+                signing_key,
+            )?;
+            reapply(project_repository, &state.unapplied, user, signing_key);
+            Ok(RebaseOutcome::Completed(new_head))
+        }
+        Picked::Conflict {
+            onto,
+            conflicting,
+            remaining,
+        } => {
+            stop(
+                project_repository,
+                &mut branch,
+                RebaseState {
+                    onto,
+                    conflicting,
+                    remaining,
+                    ..state
+                },
+            )?;
+            Ok(RebaseOutcome::Stopped)
+        }
+    }
+}
+
+/// Puts the branch and the working directory back into the state they had before the rebase
+/// started, applying the branches that were unapplied for the rebase again.
+pub fn abort_rebase(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<(), errors::RebaseError> {
+    let vb_state = project_repository.project().virtual_branches();
+    let (mut branch, state) = get_rebasing_branch(project_repository, branch_id)?;
+
+    let repo = &project_repository.git_repository;
+    let wip_tree = repo
+        .find_tree(state.wip_tree)
+        .context("failed to find wip tree")?;
+    repo.checkout_tree(&wip_tree)
+        .force()
+        .remove_untracked()
+        .checkout()
+        .context("failed to checkout tree")?;
+
+    if conflicts::is_resolving(project_repository) {
+        conflicts::clear(project_repository).context("failed to clear conflicts")?;
+    }
+
+    branch.head = state.original_head;
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+    vb_state
+        .remove_branch_rebase(&branch.id)
+        .context("failed to remove rebase state")?;
+    release(project_repository, &branch.id)?;
+
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    reapply(project_repository, &state.unapplied, user, signing_key);
+
+    Ok(())
+}
+
+/// Returns whether `branch_id` is in the middle of a rebase, which keeps other operations
+/// from rewriting its history.
+pub(crate) fn is_rebasing(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<bool> {
+    Ok(project_repository
+        .project()
+        .virtual_branches()
+        .get_branch_rebase(branch_id)
+        .context("failed to read rebase state")?
+        .is_some())
+}
+
+fn get_rebasing_branch(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<(Branch, RebaseState), errors::RebaseError> {
+    let vb_state = project_repository.project().virtual_branches();
+
+    let branch = vb_state
+        .get_branch(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::RebaseError::BranchNotFound(errors::BranchNotFound {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::RebaseError::Other(error.into()),
+        })?;
+
+    let state = vb_state
+        .get_branch_rebase(branch_id)
+        .context("failed to read rebase state")?
+        .ok_or(errors::RebaseError::NotRebasing(*branch_id))?;
+
+    Ok((branch, state))
+}
+
+/// Unapplies all other branches and returns the tree of `branch` with its uncommitted changes,
+/// so the working directory can be used for resolving conflicts, along with the unapplied branches.
+fn stash_workspace(
+    project_repository: &project_repository::Repository,
+    branch: &Branch,
+) -> Result<(git::Oid, Vec<BranchId>)> {
+    let vb_state = project_repository.project().virtual_branches();
+    let default_target = vb_state
+        .get_default_target()
+        .context("failed to read default target")?;
+
+    let applied_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.applied)
+        .collect::<Vec<_>>();
+
+    let integration_commit_id =
+        super::integration::get_workspace_head(&vb_state, project_repository)?;
+
+    let (applied_statuses, _) = vb::get_applied_status(
+        project_repository,
+        &integration_commit_id,
+        &default_target.sha,
+        applied_branches,
+    )?;
+
+    let branch_files = applied_statuses
+        .iter()
+        .find(|(b, _)| b.id == branch.id)
+        .map(|(_, files)| files)
+        .context("branch status not found")?;
+    let wip_tree = vb::write_tree(project_repository, &branch.head, branch_files)?;
+
+    let unapplied = applied_statuses
+        .iter()
+        .map(|(b, _)| b.id)
+        .filter(|id| *id != branch.id)
+        .collect::<Vec<_>>();
+    for id in &unapplied {
+        vb::unapply_branch(project_repository, id).context("failed to unapply branch")?;
+    }
+
+    Ok((wip_tree, unapplied))
+}
+
+/// Applies the branches that were unapplied for the rebase again. Branches that can't be
+/// applied, for instance because they conflict with the rebased branch, stay unapplied.
+fn reapply(
+    project_repository: &project_repository::Repository,
+    branch_ids: &[BranchId],
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) {
+    for branch_id in branch_ids {
+        if let Err(error) = vb::apply_branch(project_repository, branch_id, signing_key, user) {
+            tracing::warn!(%branch_id, ?error, "failed to apply branch after rebase");
+        }
+    }
+}
+
+/// Checks out the conflicts of picking `state.conflicting` onto `state.onto` and records `state`.
+fn stop(
+    project_repository: &project_repository::Repository,
+    branch: &mut Branch,
+    state: RebaseState,
+) -> Result<()> {
+    let vb_state = project_repository.project().virtual_branches();
+    let repo = &project_repository.git_repository;
+
+    let onto = repo
+        .find_commit(state.onto)
+        .context("failed to find commit to rebase onto")?;
+    let conflicting = repo
+        .find_commit(state.conflicting)
+        .context("failed to find conflicting commit")?;
+    let mut cherrypick_index = repo
+        .cherry_pick(&onto, &conflicting)
+        .context("failed to cherry pick")?;
+
+    let onto_tree = onto.tree().context("failed to find tree")?;
+    let paths = checkout_with_conflicts(project_repository, &onto_tree, &mut cherrypick_index)?;
+    conflicts::mark(project_repository, paths, Some(state.onto))?;
+
+    branch.head = state.onto;
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+    vb_state
+        .set_branch_rebase(branch.id, state)
+        .context("failed to write rebase state")?;
+
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(())
+}
+
+/// Makes `new_head` the head of `branch` and merges the uncommitted changes it had when the
+/// rebase started back into the working directory.
+fn finish(
+    project_repository: &project_repository::Repository,
+    branch: &mut Branch,
+    state: &RebaseState,
+    new_head: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<()> {
+    let vb_state = project_repository.project().virtual_branches();
+    let repo = &project_repository.git_repository;
+
+    let original_tree = repo
+        .find_commit(state.original_head)
+        .context("failed to find original head")?
+        .tree()
+        .context("failed to find tree")?;
+    let new_tree = repo
+        .find_commit(new_head)
+        .context("failed to find new head")?
+        .tree()
+        .context("failed to find tree")?;
+    let wip_tree = repo
+        .find_tree(state.wip_tree)
+        .context("failed to find wip tree")?;
+
+    let mut merge_index = repo
+        .merge_trees(&original_tree, &new_tree, &wip_tree)
+        .context("failed to merge uncommitted changes")?;
+    let paths = checkout_with_conflicts(project_repository, &new_tree, &mut merge_index)?;
+    // the conflicts are part of the uncommitted changes, and resolved by committing them
+    conflicts::mark(project_repository, paths, None)?;
+
+    branch.head = new_head;
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+    vb_state
+        .remove_branch_rebase(&branch.id)
+        .context("failed to remove rebase state")?;
+    release(project_repository, &branch.id)?;
+    vb::rebase_stacked_branches(project_repository, branch, state.original_head, signing_key)?;
+
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(())
+}
+
+fn keep_refname(branch_id: &BranchId) -> git::Refname {
+    git::Refname::Other(format!("refs/gitbutler-rebase/{branch_id}"))
+}
+
+/// Points the keep ref of the branch at a commit of `state.wip_tree` on top of `state.original_head`,
+/// which makes both reachable for as long as the rebase is stopped.
+fn keep_reachable(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    state: &RebaseState,
+) -> Result<()> {
+    let repo = &project_repository.git_repository;
+    let wip_tree = repo
+        .find_tree(state.wip_tree)
+        .context("failed to find wip tree")?;
+    let original_head = repo
+        .find_commit(state.original_head)
+        .context("failed to find original head")?;
+    let committer = super::integration::get_committer()?;
+    let keep_oid = repo
+        .commit(
+            None,
+            &committer,
+            &committer,
+            "GitButler rebase in progress",
+            &wip_tree,
+            &[&original_head],
+        )
+        .context("failed to commit wip tree")?;
+    repo.reference(&keep_refname(branch_id), keep_oid, true, "rebase stopped")
+        .context("failed to write rebase ref")?;
+    Ok(())
+}
+
+/// Removes the keep ref of the branch once its rebase is over.
+fn release(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<()> {
+    match project_repository
+        .git_repository
+        .find_reference(&keep_refname(branch_id))
+    {
+        Ok(mut reference) => reference.delete().context("failed to delete rebase ref"),
+        Err(git::Error::NotFound(_)) => Ok(()),
+        Err(error) => Err(error).context("failed to find rebase ref"),
+    }
+}
+
+/// Replaces the working directory with `base_tree` and checks out `index` on top of it,
+/// writing conflict markers into conflicting files. Returns the paths of those files.
+fn checkout_with_conflicts(
+    project_repository: &project_repository::Repository,
+    base_tree: &git::Tree,
+    index: &mut git::Index,
+) -> Result<Vec<String>> {
+    let repo = &project_repository.git_repository;
+
+    repo.checkout_tree(base_tree)
+        .force()
+        .remove_untracked()
+        .checkout()
+        .context("failed to checkout tree")?;
+    repo.checkout_index(index)
+        .allow_conflicts()
+        .conflict_style_merge()
+        .force()
+        .checkout()
+        .context("failed to checkout conflicts")?;
+
+    let mut paths = Vec::new();
+    if index.has_conflicts() {
+        let conflicts = index.conflicts().context("failed to get conflicts")?;
+        for path in conflicts.flatten() {
+            // files deleted on one side only have an entry on the other
+            if let Some(entry) = path.our.or(path.their) {
+                let path = std::str::from_utf8(&entry.path)
+                    .context("failed to convert path")?
+                    .to_string();
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
@@ -1,10 +1,14 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Context, Result};
 use bstr::BString;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use super::{errors, target, Author, VirtualBranchesHandle};
+use super::{errors, target, Author, BranchId, VirtualBranchesHandle};
 use crate::{
     git,
     project_repository::{self, LogUntil},
@@ -27,6 +31,29 @@ pub struct RemoteBranch {
     pub upstream: Option<git::RemoteRefname>,
     pub last_commit_timestamp_ms: Option<u128>,
     pub last_commit_author: Option<String>,
+    /// The number of commits the branch has that the fetched target branch doesn't.
+    /// Like `behind` and `is_merged`, this is only known when listing remote branches.
+    pub ahead: u32,
+    /// The number of commits the fetched target branch has that the branch doesn't.
+    pub behind: u32,
+    /// Whether all the work of the branch made it into the target branch, even if it was squashed or rebased.
+    pub is_merged: bool,
+    /// The virtual branch that pushes to this branch, if any.
+    pub virtual_branch_id: Option<BranchId>,
+}
+
+/// Which remote branches to list. Branches are kept if they match all the criteria that are set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteBranchFilter {
+    /// Only list branches of this remote.
+    pub remote: Option<String>,
+    /// Only list branches whose last commit was authored by someone whose name contains this, ignoring case.
+    pub author: Option<String>,
+    /// Only list branches with a commit in this many days.
+    pub max_age_days: Option<u64>,
+    /// Only list branches that are merged into the target, or only those that are not.
+    pub merged: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq)]
@@ -51,6 +78,7 @@ pub struct RemoteCommit {
 
 pub fn list_remote_branches(
     project_repository: &project_repository::Repository,
+    filter: &RemoteBranchFilter,
 ) -> Result<Vec<RemoteBranch>, errors::ListRemoteBranchesError> {
     let default_target = default_target(&project_repository.project().gb_dir())
         .context("failed to get default target")?
@@ -60,19 +88,91 @@ pub fn list_remote_branches(
             })
         })?;
 
-    let remote_branches = project_repository
+    let virtual_branch_ids = VirtualBranchesHandle::new(project_repository.project().gb_dir())
+        .list_branches()
+        .context("failed to list virtual branches")?
+        .into_iter()
+        .filter_map(|branch| Some((git::Refname::from(branch.upstream?), branch.id)))
+        .collect::<HashMap<_, _>>();
+    let min_timestamp_ms = filter.max_age_days.map(|days| {
+        let max_age = Duration::from_secs(days * 24 * 60 * 60);
+        SystemTime::now()
+            .checked_sub(max_age)
+            .unwrap_or(UNIX_EPOCH)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    });
+    let author = filter.author.as_deref().map(str::to_lowercase);
+    // the target as last fetched, which may be ahead of the commit the workspace is based on
+    let target_head = project_repository
+        .git_repository
+        .find_branch(&default_target.branch.clone().into())
+        .context("failed to find target branch")?
+        .peel_to_commit()
+        .context("failed to peel target branch to commit")?
+        .id();
+
+    let mut remote_branches = Vec::new();
+    for (branch, _) in project_repository
         .git_repository
         .branches(Some(git2::BranchType::Remote))
         .context("failed to list remote branches")?
         .flatten()
-        .map(|(branch, _)| branch)
-        .map(|branch| branch_to_remote_branch(&branch))
-        .collect::<Result<Vec<_>>>()
-        .context("failed to convert branches")?
-        .into_iter()
-        .flatten()
-        .filter(|branch| branch.name.branch() != Some(default_target.branch.branch()))
-        .collect::<Vec<_>>();
+    {
+        // the cheap criteria go first, so only the branches that pass them are checked for being merged
+        let Ok(git::Refname::Remote(name)) = git::Refname::try_from(&branch) else {
+            continue;
+        };
+        if name.branch() == default_target.branch.branch()
+            || filter
+                .remote
+                .as_deref()
+                .map_or(false, |remote| name.remote() != remote)
+        {
+            continue;
+        }
+        let Some(mut remote_branch) =
+            branch_to_remote_branch(&branch).context("failed to convert branch")?
+        else {
+            continue;
+        };
+        let too_old = min_timestamp_ms.map_or(false, |min_timestamp_ms| {
+            remote_branch
+                .last_commit_timestamp_ms
+                .map_or(true, |timestamp_ms| timestamp_ms < min_timestamp_ms)
+        });
+        let other_author = author.as_ref().map_or(false, |author| {
+            remote_branch
+                .last_commit_author
+                .as_ref()
+                .map_or(true, |name| !name.to_lowercase().contains(author))
+        });
+        if too_old || other_author {
+            continue;
+        }
+        remote_branch.is_merged = is_remote_branch_merged(
+            project_repository,
+            &default_target,
+            target_head,
+            remote_branch.sha,
+        )
+        .context("failed to check if branch is merged")?;
+        if filter
+            .merged
+            .map_or(false, |merged| merged != remote_branch.is_merged)
+        {
+            continue;
+        }
+        remote_branch.ahead = project_repository
+            .distance(remote_branch.sha, target_head)
+            .context("failed to get ahead count")?;
+        remote_branch.behind = project_repository
+            .distance(target_head, remote_branch.sha)
+            .context("failed to get behind count")?;
+        remote_branch.virtual_branch_id = virtual_branch_ids.get(&remote_branch.name).copied();
+        remote_branches.push(remote_branch);
+    }
 
     Ok(remote_branches)
 }
@@ -110,6 +210,8 @@ pub fn get_branch_data(
         })
 }
 
+/// Describe `branch`. How it relates to the target and which virtual branch it belongs to is left
+/// for the caller to fill in.
 pub fn branch_to_remote_branch(branch: &git::Branch) -> Result<Option<RemoteBranch>> {
     let commit = match branch.peel_to_commit() {
         Ok(c) => c,
@@ -141,11 +243,34 @@ pub fn branch_to_remote_branch(branch: &git::Branch) -> Result<Option<RemoteBran
                     .map(|t: u128| t * 1000)
                     .ok(),
                 last_commit_author: commit.author().name().map(std::string::ToString::to_string),
+                ahead: 0,
+                behind: 0,
+                is_merged: false,
+                virtual_branch_id: None,
             })
         })
         .transpose()
 }
 
+/// A remote branch is merged if its head is contained in the target branch, or if its changes
+/// were integrated in another way, like by squashing or rebasing them.
+fn is_remote_branch_merged(
+    project_repository: &project_repository::Repository,
+    target: &target::Target,
+    target_head: git::Oid,
+    head: git::Oid,
+) -> Result<bool> {
+    if project_repository
+        .git_repository
+        .merge_base(target_head, head)?
+        == head
+    {
+        return Ok(true);
+    }
+    let commit = project_repository.git_repository.find_commit(head)?;
+    super::is_commit_integrated(project_repository, target, &commit)
+}
+
 pub fn branch_to_remote_branch_data(
     project_repository: &project_repository::Repository,
     branch: &git::Branch,
@@ -7,7 +7,7 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use super::{target::Target, Branch};
+use super::{rebase::RebaseState, target::Target, Branch};
 use crate::virtual_branches::BranchId;
 
 /// The state of virtual branches data, as persisted in a TOML file.
@@ -19,6 +19,9 @@ pub struct VirtualBranches {
     pub branch_targets: HashMap<BranchId, Target>,
     /// The current state of the virtual branches
     pub branches: HashMap<BranchId, Branch>,
+    /// The virtual branches that are in the middle of a rebase that stopped on a conflict
+    #[serde(default)]
+    pub branch_rebases: HashMap<BranchId, RebaseState>,
 }
 /// A handle to the state of virtual branches.
 ///
@@ -122,6 +125,37 @@ impl VirtualBranchesHandle {
             .ok_or(crate::reader::Error::NotFound)
     }
 
+    /// Records that the given virtual branch is in the middle of a rebase.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn set_branch_rebase(&self, id: BranchId, rebase: RebaseState) -> anyhow::Result<()> {
+        let mut virtual_branches = self.read_file()?;
+        virtual_branches.branch_rebases.insert(id, rebase);
+        self.write_file(&virtual_branches)?;
+        Ok(())
+    }
+
+    /// Gets the state of the rebase the given virtual branch is in the middle of, if any.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn get_branch_rebase(
+        &self,
+        id: &BranchId,
+    ) -> Result<Option<RebaseState>, crate::reader::Error> {
+        let virtual_branches = self.read_file()?;
+        Ok(virtual_branches.branch_rebases.get(id).cloned())
+    }
+
+    /// Forgets about the rebase of the given virtual branch.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn remove_branch_rebase(&self, id: &BranchId) -> anyhow::Result<()> {
+        let mut virtual_branches = self.read_file()?;
+        virtual_branches.branch_rebases.remove(id);
+        self.write_file(&virtual_branches)?;
+        Ok(())
+    }
+
     /// Lists all virtual branches.
     ///
     /// Errors if the file cannot be read or written.
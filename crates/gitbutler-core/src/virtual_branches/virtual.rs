@@ -22,20 +22,21 @@ use super::{
     branch::{
         self, Branch, BranchCreateRequest, BranchId, BranchOwnershipClaims, Hunk, OwnershipClaim,
     },
-    branch_to_remote_branch, errors, target, RemoteBranch, VirtualBranchesHandle,
+    branch_to_remote_branch, errors, rebase, target, RemoteBranch, VirtualBranchesHandle,
 };
 use crate::git::diff::{diff_files_into_hunks, trees, FileDiff};
 use crate::virtual_branches::branch::HunkHash;
 use crate::{
     askpass::AskpassBroker,
     dedup::{dedup, dedup_fmt},
+    forge,
     git::{
         self,
         diff::{self},
         Commit, Refname, RemoteRefname,
     },
     keys,
-    project_repository::{self, conflicts, LogUntil},
+    project_repository::{self, conflicts, remote_backend::OnProgress, LogUntil},
     reader, users,
 };
 use crate::{error::Error, git::diff::GitHunk};
@@ -61,6 +62,7 @@ pub struct VirtualBranch {
     pub commits: Vec<VirtualBranchCommit>,
     pub requires_force: bool, // does this branch require a force push to the upstream?
     pub conflicted: bool, // is this branch currently in a conflicted state (only for the workspace)
+    pub rebasing: bool,   // is this branch in the middle of a rebase that stopped on a conflict?
     pub order: usize,     // the order in which this branch should be displayed in the UI
     pub upstream: Option<RemoteBranch>, // the upstream branch where this branch pushes to, if any
     pub upstream_name: Option<String>, // the upstream branch where this branch will push to on next push
@@ -69,6 +71,8 @@ pub struct VirtualBranch {
     pub updated_at: u128,
     pub selected_for_changes: bool,
     pub head: git::Oid,
+    pub parent: Option<BranchId>, // the branch this branch is stacked on, if any
+    pub pull_request: Option<forge::PullRequest>, // the pull request opened for the upstream, as last seen on the forge
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
@@ -120,6 +124,8 @@ pub struct VirtualBranchFile {
     pub conflicted: bool,
     pub binary: bool,
     pub large: bool,
+    /// The commits the submodule at `path` moves between, if it is one.
+    pub submodule: Option<diff::SubmoduleChange>,
 }
 
 // this struct is a mapping to the view `Hunk` type in Typescript
@@ -147,6 +153,7 @@ pub struct VirtualBranchHunk {
     pub locked: bool,
     pub locked_to: Option<Box<[diff::HunkLock]>>,
     pub change_type: diff::ChangeType,
+    pub submodule: Option<diff::SubmoduleChange>,
 }
 
 /// Lifecycle
@@ -160,20 +167,24 @@ impl VirtualBranchHunk {
         hunk: GitHunk,
         mtimes: &mut MTimeCache,
     ) -> Self {
-        let hash = Hunk::hash_diff(&hunk.diff_lines);
+        // a hunk with only some of the changes of another is known by the claim for that one
+        let whole = hunk.whole();
+        let hash = Hunk::hash_diff(&whole.diff_lines);
+        let (start, new_lines) = (whole.new_start, whole.new_lines);
         Self {
-            id: Self::gen_id(hunk.new_start, hunk.new_lines),
+            id: Self::gen_id(start, new_lines),
             modified_at: mtimes.mtime_by_path(project_path.join(&file_path)),
             file_path,
             diff: hunk.diff_lines,
             old_start: hunk.old_start,
-            start: hunk.new_start,
-            end: hunk.new_start + hunk.new_lines,
+            start,
+            end: start + new_lines,
             binary: hunk.binary,
             hash,
             locked: hunk.locked_to.len() > 0,
             locked_to: Some(hunk.locked_to),
             change_type: hunk.change_type,
+            submodule: hunk.submodule,
         }
     }
 }
@@ -366,48 +377,19 @@ pub fn apply_branch(
             vb_state.set_branch(branch.clone())?;
         } else {
             // branch was not pushed to upstream yet. attempt a rebase,
-            let (_, committer) = project_repository.git_signatures(user)?;
-            let mut rebase_options = git2::RebaseOptions::new();
-            rebase_options.quiet(true);
-            rebase_options.inmemory(true);
-            let mut rebase = repo
-                .rebase(
-                    Some(branch.head),
-                    Some(target_commit.id()),
-                    None,
-                    Some(&mut rebase_options),
-                )
-                .context("failed to rebase")?;
-
-            let mut rebase_success = true;
-            // check to see if these commits have already been pushed
-            let mut last_rebase_head = branch.head;
-            while rebase.next().is_some() {
-                let index = rebase
-                    .inmemory_index()
-                    .context("failed to get inmemory index")?;
-                if index.has_conflicts() {
-                    rebase_success = false;
-                    break;
-                }
-
-                if let Ok(commit_id) = rebase.commit(None, &committer.clone().into(), None) {
-                    last_rebase_head = commit_id.into();
-                } else {
-                    rebase_success = false;
-                    break;
-                }
-            }
+            let rebased_head = rebase::rebase_onto(
+                project_repository,
+                branch.head,
+                target_commit.id(),
+                signing_key,
+            )?;
 
-            if rebase_success {
+            if let Some(rebased_head) = rebased_head {
                 // rebase worked out, rewrite the branch head
-                rebase.finish(None).context("failed to finish rebase")?;
-                branch.head = last_rebase_head;
+                branch.head = rebased_head;
                 branch.tree = merged_branch_tree_oid;
             } else {
                 // rebase failed, do a merge commit
-                rebase.abort().context("failed to abort rebase")?;
-
                 // get tree from merge_tree_oid
                 let merge_tree = repo
                     .find_tree(merged_branch_tree_oid)
@@ -511,46 +493,38 @@ pub fn unapply_ownership(
     )
     .context("failed to get status by branch")?;
 
-    let hunks_to_unapply = applied_statuses
-        .iter()
-        .map(
-            |(_branch, branch_files)| -> Result<Vec<(PathBuf, &diff::GitHunk)>> {
-                let mut hunks_to_unapply = Vec::new();
-                for (path, hunks) in branch_files {
+    // what's left of the changes of each branch once the claimed ones are gone, which may be
+    // some lines of a hunk
+    let remaining_statuses = applied_statuses
+        .into_iter()
+        .map(|(branch, branch_files)| {
+            let branch_files = branch_files
+                .into_iter()
+                .filter_map(|(path, hunks)| {
                     let ownership_hunks: Vec<&Hunk> = ownership
                         .claims
                         .iter()
-                        .filter(|o| o.file_path == *path)
+                        .filter(|o| o.file_path == path)
                         .flat_map(|f| &f.hunks)
                         .collect();
-                    for hunk in hunks {
-                        if ownership_hunks.contains(&&Hunk::from(hunk)) {
-                            hunks_to_unapply.push((path.clone(), hunk));
-                        }
-                    }
-                }
-
-                hunks_to_unapply.sort_by(|a, b| a.1.old_start.cmp(&b.1.old_start));
-
-                Ok(hunks_to_unapply)
-            },
-        )
-        .collect::<Result<Vec<_>>>()?
-        .into_iter()
-        .flatten()
+                    let hunks = hunks
+                        .into_iter()
+                        .filter_map(|hunk| {
+                            let whole = Hunk::from(&hunk);
+                            ownership_hunks
+                                .iter()
+                                .filter(|owned| owned.same_hunk(&whole))
+                                .try_fold(hunk, |hunk, owned| owned.deselect(&hunk))
+                        })
+                        .collect::<Vec<_>>();
+                    // a file without changes is left as the workspace has it
+                    (!hunks.is_empty()).then_some((path, hunks))
+                })
+                .collect::<BranchStatus>();
+            (branch, branch_files)
+        })
         .collect::<Vec<_>>();
 
-    let mut diff = HashMap::new();
-    for h in hunks_to_unapply {
-        if let Some(reversed_hunk) = diff::reverse_hunk(h.1) {
-            diff.entry(h.0).or_insert_with(Vec::new).push(reversed_hunk);
-        } else {
-            return Err(errors::UnapplyOwnershipError::Other(anyhow::anyhow!(
-                "failed to reverse hunk"
-            )));
-        }
-    }
-
     let repo = &project_repository.git_repository;
 
     let target_commit = repo
@@ -558,7 +532,7 @@ pub fn unapply_ownership(
         .context("failed to find target commit")?;
 
     let base_tree = target_commit.tree().context("failed to get target tree")?;
-    let final_tree = applied_statuses.into_iter().fold(
+    let final_tree = remaining_statuses.into_iter().fold(
         target_commit.tree().context("failed to get target tree"),
         |final_tree, status| {
             let final_tree = final_tree?;
@@ -571,11 +545,6 @@ pub fn unapply_ownership(
         },
     )?;
 
-    let final_tree_oid = write_tree_onto_tree(project_repository, &final_tree, diff)?;
-    let final_tree = repo
-        .find_tree(final_tree_oid)
-        .context("failed to find tree")?;
-
     repo.checkout_tree(&final_tree)
         .force()
         .remove_untracked()
@@ -629,6 +598,16 @@ pub fn unapply_branch(
 ) -> Result<Option<branch::Branch>, errors::UnapplyBranchError> {
     let vb_state = project_repository.project().virtual_branches();
 
+    // branches stacked on this one contain its commits, so they leave the workspace with it
+    for child in vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.applied && b.parent == Some(*branch_id))
+    {
+        unapply_branch(project_repository, &child.id)?;
+    }
+
     let mut target_branch = vb_state
         .get_branch(branch_id)
         .map_err(|error| match error {
@@ -645,6 +624,19 @@ pub fn unapply_branch(
         return Ok(Some(target_branch));
     }
 
+    // the rebase state can't be kept once the branch leaves the workspace
+    if vb_state
+        .get_branch_rebase(branch_id)
+        .context("failed to read rebase state")?
+        .is_some()
+    {
+        rebase::abort_rebase(project_repository, branch_id, None, None)
+            .context("failed to abort rebase")?;
+        target_branch = vb_state
+            .get_branch(branch_id)
+            .context("failed to read branch")?;
+    }
+
     let Some(default_target) = vb_state
         .try_get_default_target()
         .context("failed to get default target")?
@@ -719,27 +711,36 @@ pub fn unapply_branch(
             .find_commit(default_target.sha)
             .context("failed to find target commit")?;
 
-        // ok, update the wd with the union of the rest of the branches
-        let base_tree = target_commit.tree().context("failed to get target tree")?;
-
         // go through the other applied branches and merge them into the final tree
         // then check that out into the working directory
-        let final_tree = applied_statuses
+        let mut remaining_statuses = applied_statuses
             .into_iter()
             .filter(|(branch, _)| &branch.id != branch_id)
-            .fold(
-                target_commit.tree().context("failed to get target tree"),
-                |final_tree, status| {
-                    let final_tree = final_tree?;
-                    let branch = status.0;
-                    let tree_oid = write_tree(project_repository, &branch.head, status.1)?;
-                    let branch_tree = repo.find_tree(tree_oid)?;
-                    let mut result = repo.merge_trees(&base_tree, &final_tree, &branch_tree)?;
-                    let final_tree_oid = result.write_tree_to(repo)?;
-                    repo.find_tree(final_tree_oid)
-                        .context("failed to find tree")
-                },
-            )?;
+            .collect::<Vec<_>>();
+        sort_by_stack(&mut remaining_statuses, |(branch, _)| branch);
+        let remaining_branches = remaining_statuses
+            .iter()
+            .map(|(branch, _)| branch.clone())
+            .collect::<Vec<_>>();
+        let final_tree = remaining_statuses.into_iter().fold(
+            target_commit.tree().context("failed to get target tree"),
+            |final_tree, (branch, files)| {
+                let final_tree = final_tree?;
+                // ok, update the wd with the union of the rest of the branches
+                let base_tree = stack_base_tree(
+                    project_repository,
+                    &remaining_branches,
+                    &branch,
+                    default_target.sha,
+                )?;
+                let tree_oid = write_tree(project_repository, &branch.head, files)?;
+                let branch_tree = repo.find_tree(tree_oid)?;
+                let mut result = repo.merge_trees(&base_tree, &final_tree, &branch_tree)?;
+                let final_tree_oid = result.write_tree_to(repo)?;
+                repo.find_tree(final_tree_oid)
+                    .context("failed to find tree")
+            },
+        )?;
 
         ensure_selected_for_changes(&vb_state).context("failed to ensure selected for changes")?;
 
@@ -802,6 +803,9 @@ pub fn list_virtual_branches(
 
     let (statuses, skipped_files) =
         get_status_by_branch(project_repository, Some(&integration_commit.id()))?;
+    let all_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?;
     let max_selected_for_changes = statuses
         .iter()
         .filter_map(|(branch, _)| branch.selected_for_changes)
@@ -854,9 +858,15 @@ pub fn list_virtual_branches(
         let mut is_integrated = false;
         let mut is_remote = false;
 
-        // find all commits on head that are not on target.sha
+        // find all commits on head that are not on target.sha, or on the branch it is stacked on
+        let base = stack_base(
+            project_repository,
+            &all_branches,
+            &branch,
+            default_target.sha,
+        )?;
         let commits = project_repository
-            .log(branch.head, LogUntil::Commit(default_target.sha))
+            .log(branch.head, LogUntil::Commit(base))
             .context(format!("failed to get log for branch {}", branch.name))?
             .iter()
             .map(|commit| {
@@ -898,7 +908,11 @@ pub fn list_virtual_branches(
         let upstream = upstream_branch
             .map(|upstream_branch| branch_to_remote_branch(&upstream_branch))
             .transpose()?
-            .flatten();
+            .flatten()
+            .map(|upstream| RemoteBranch {
+                virtual_branch_id: Some(branch.id),
+                ..upstream
+            });
 
         let mut files = diffs_into_virtual_files(project_repository, files);
 
@@ -933,11 +947,17 @@ pub fn list_virtual_branches(
                 .upstream
                 .and_then(|r| Refname::from(r).branch().map(Into::into)),
             conflicted: conflicts::is_resolving(project_repository),
+            rebasing: vb_state
+                .get_branch_rebase(&branch.id)
+                .context("failed to read rebase state")?
+                .is_some(),
             base_current,
             ownership: branch.ownership,
             updated_at: branch.updated_timestamp_ms,
             selected_for_changes: branch.selected_for_changes == Some(max_selected_for_changes),
             head: branch.head,
+            parent: branch.parent,
+            pull_request: branch.pull_request,
         };
         branches.push(branch);
     }
@@ -1013,6 +1033,10 @@ fn list_virtual_commit_files(
         &project_repository.git_repository,
         &parent_tree,
         &commit_tree,
+        &project_repository
+            .project()
+            .diff_settings
+            .keeping_whitespace(),
     )?;
     let hunks_by_filepath = virtual_hunks_by_file_diffs(&project_repository.project().path, diff);
     Ok(virtual_hunks_into_virtual_files(
@@ -1069,19 +1093,36 @@ pub fn create_virtual_branch(
         ));
     };
 
+    let mut all_virtual_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?;
+    all_virtual_branches.sort_by_key(|branch| branch.order);
+
+    // a stacked branch starts out at the head of its parent
+    let head = match create.parent {
+        Some(parent_id) => {
+            all_virtual_branches
+                .iter()
+                .find(|branch| branch.id == parent_id)
+                .ok_or(errors::CreateVirtualBranchError::BranchNotFound(
+                    errors::BranchNotFound {
+                        project_id: project_repository.project().id,
+                        branch_id: parent_id,
+                    },
+                ))?
+                .head
+        }
+        None => default_target.sha,
+    };
+
     let commit = project_repository
         .git_repository
-        .find_commit(default_target.sha)
-        .context("failed to find default target commit")?;
+        .find_commit(head)
+        .context("failed to find branch head commit")?;
 
     let tree = commit
         .tree()
-        .context("failed to find defaut target commit tree")?;
-
-    let mut all_virtual_branches = vb_state
-        .list_branches()
-        .context("failed to read virtual branches")?;
-    all_virtual_branches.sort_by_key(|branch| branch.order);
+        .context("failed to find branch head commit tree")?;
 
     let order = create
         .order
@@ -1141,12 +1182,14 @@ pub fn create_virtual_branch(
         upstream: None,
         upstream_head: None,
         tree: tree.id(),
-        head: default_target.sha,
+        head,
         created_timestamp_ms: now,
         updated_timestamp_ms: now,
         ownership: BranchOwnershipClaims::default(),
         order,
         selected_for_changes,
+        parent: create.parent,
+        pull_request: None,
     };
 
     if let Some(ownership) = &create.ownership {
@@ -1288,43 +1331,15 @@ pub fn merge_virtual_branch_upstream(
 
         if *project_repository.project().ok_with_force_push {
             // attempt a rebase
-            let (_, committer) = project_repository.git_signatures(user)?;
-            let mut rebase_options = git2::RebaseOptions::new();
-            rebase_options.quiet(true);
-            rebase_options.inmemory(true);
-            let mut rebase = repo
-                .rebase(
-                    Some(branch.head),
-                    Some(upstream_commit.id()),
-                    None,
-                    Some(&mut rebase_options),
-                )
-                .context("failed to rebase")?;
-
-            let mut rebase_success = true;
-            // check to see if these commits have already been pushed
-            let mut last_rebase_head = upstream_commit.id();
-            while rebase.next().is_some() {
-                let index = rebase
-                    .inmemory_index()
-                    .context("failed to get inmemory index")?;
-                if index.has_conflicts() {
-                    rebase_success = false;
-                    break;
-                }
-
-                if let Ok(commit_id) = rebase.commit(None, &committer.clone().into(), None) {
-                    last_rebase_head = commit_id.into();
-                } else {
-                    rebase_success = false;
-                    break;
-                }
-            }
+            let rebased_head = rebase::rebase_onto(
+                project_repository,
+                branch.head,
+                upstream_commit.id(),
+                signing_key,
+            )?;
 
-            if rebase_success {
+            if let Some(rebased_head) = rebased_head {
                 // rebase worked out, rewrite the branch head
-                rebase.finish(None).context("failed to finish rebase")?;
-
                 project_repository
                     .git_repository
                     .checkout_tree(&merge_tree)
@@ -1332,15 +1347,17 @@ pub fn merge_virtual_branch_upstream(
                     .checkout()
                     .context("failed to checkout tree")?;
 
-                branch.head = last_rebase_head;
+                let old_head = branch.head;
+                branch.head = rebased_head;
                 branch.tree = merge_tree_oid;
+                // the remote commits are part of the branch now, so they won't be lost to the next forced push
+                branch.upstream_head = Some(upstream_commit.id());
                 vb_state.set_branch(branch.clone())?;
+                rebase_stacked_branches(project_repository, &branch, old_head, signing_key)?;
                 super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
 
                 return Ok(());
             }
-
-            rebase.abort().context("failed to abort rebase")?;
         }
 
         let head_commit = repo
@@ -1368,9 +1385,12 @@ pub fn merge_virtual_branch_upstream(
             .context("failed to checkout tree")?;
 
         // write the branch data
+        let old_head = branch.head;
         branch.head = new_branch_head;
         branch.tree = merge_tree_oid;
+        branch.upstream_head = Some(upstream_commit.id());
         vb_state.set_branch(branch.clone())?;
+        rebase_stacked_branches(project_repository, &branch, old_head, signing_key)?;
     }
 
     super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
@@ -1496,6 +1516,19 @@ pub fn delete_branch(
         .remove_branch(branch.id)
         .context("Failed to remove branch")?;
 
+    // branches stacked on the deleted branch keep its commits and move down the stack
+    for mut child in vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.parent == Some(branch.id))
+    {
+        child.parent = branch.parent;
+        vb_state
+            .set_branch(child)
+            .context("failed to write branch")?;
+    }
+
     project_repository.delete_branch_reference(&branch)?;
 
     ensure_selected_for_changes(&vb_state).context("failed to ensure selected for changes")?;
@@ -1697,7 +1730,15 @@ fn get_non_applied_status(
                 .tree()
                 .context("failed to find target tree")?;
 
-            let diff = diff::trees(&project_repository.git_repository, &head_tree, &branch_tree)?;
+            let diff = diff::trees(
+                &project_repository.git_repository,
+                &head_tree,
+                &branch_tree,
+                &project_repository
+                    .project()
+                    .diff_settings
+                    .keeping_whitespace(),
+            )?;
 
             Ok((branch, diff::diff_files_into_hunks(diff).collect()))
         })
@@ -1706,18 +1747,50 @@ fn get_non_applied_status(
 
 // Returns branches and their associated file changes, in addition to a list
 // of skipped files.
-fn get_applied_status(
+pub(crate) fn get_applied_status(
     project_repository: &project_repository::Repository,
     integration_commit: &git::Oid,
     target_sha: &git::Oid,
     mut virtual_branches: Vec<branch::Branch>,
 ) -> Result<(AppliedStatuses, Vec<diff::FileDiff>)> {
-    let base_file_diffs = diff::workdir(&project_repository.git_repository, integration_commit)
-        .context("failed to diff workdir")?;
+    let mut base_file_diffs = diff::workdir(
+        &project_repository.git_repository,
+        integration_commit,
+        &project_repository
+            .project()
+            .diff_settings
+            .keeping_whitespace(),
+    )
+    .context("failed to diff workdir")?;
 
     let mut skipped_files: Vec<diff::FileDiff> = Vec::new();
-    for file_diff in base_file_diffs.values() {
-        if file_diff.skipped {
+    for (path, file_diff) in &mut base_file_diffs {
+        if !file_diff.skipped {
+            continue;
+        }
+        let claimed = virtual_branches.iter().any(|branch| {
+            branch
+                .ownership
+                .claims
+                .iter()
+                .any(|claim| &claim.file_path == path)
+        });
+        if claimed {
+            // a skipped file has no hunks, so only a branch that claimed it explicitly
+            // gets all of it
+            let repo = &project_repository.git_repository;
+            let change_type = if repo
+                .find_commit(*integration_commit)?
+                .tree()?
+                .get_path(path)
+                .is_ok()
+            {
+                diff::ChangeType::Modified
+            } else {
+                diff::ChangeType::Added
+            };
+            file_diff.hunks = vec![diff::GitHunk::worktree_marker(change_type)];
+        } else {
             skipped_files.push(file_diff.clone());
         }
     }
@@ -1735,8 +1808,9 @@ fn get_applied_status(
     }
 
     let mut commit_to_branch = HashMap::new();
-    for branch in &mut virtual_branches {
-        for commit in project_repository.log(branch.head, LogUntil::Commit(*target_sha))? {
+    for branch in &virtual_branches {
+        let base = stack_base(project_repository, &virtual_branches, branch, *target_sha)?;
+        for commit in project_repository.log(branch.head, LogUntil::Commit(base))? {
             commit_to_branch.insert(commit.id(), branch.id);
         }
     }
@@ -1781,7 +1855,7 @@ fn get_applied_status(
         for (path, hunks) in base_diffs.clone().into_iter() {
             for hunk in hunks {
                 let blame = project_repository.git_repository.blame(
-                    &path,
+                    hunk.old_path.as_ref().unwrap_or(&path),
                     hunk.old_start,
                     (hunk.old_start + hunk.old_lines).saturating_sub(1),
                     target_sha,
@@ -1821,6 +1895,53 @@ fn get_applied_status(
         }
     }
 
+    // The lines of hunks that branches claim one by one, which the first branch to claim a line
+    // gets. A branch claiming all of a hunk only gets what's left of it.
+    let mut claimed_lines =
+        HashMap::<(PathBuf, u32, u32), Vec<(BranchId, Vec<diff::ChangedLine>)>>::new();
+    for branch in &virtual_branches {
+        for claim in &branch.ownership.claims {
+            for hunk in claim.hunks.iter().filter(|hunk| !hunk.lines.is_empty()) {
+                let Some(git_diff_hunk) = claimed_git_hunk(&base_diffs, &claim.file_path, hunk)
+                else {
+                    continue;
+                };
+                if locked_hunk_map.contains_key(&Hunk::hash_diff(&git_diff_hunk.diff_lines)) {
+                    continue;
+                }
+                let owners = claimed_lines
+                    .entry((
+                        claim.file_path.clone(),
+                        git_diff_hunk.new_start,
+                        git_diff_hunk.new_start + git_diff_hunk.new_lines,
+                    ))
+                    .or_default();
+                let lines = hunk
+                    .lines
+                    .iter()
+                    .filter(|line| !owners.iter().any(|(_, owned)| owned.contains(line)))
+                    .copied()
+                    .collect();
+                owners.push((branch.id, lines));
+            }
+        }
+    }
+    let is_claimed_line = |path: &PathBuf, hunk: &diff::GitHunk, line: diff::ChangedLine| {
+        claimed_lines
+            .get(&(
+                path.clone(),
+                hunk.new_start,
+                hunk.new_start + hunk.new_lines,
+            ))
+            .map_or(false, |owners| {
+                owners.iter().any(|(_, owned)| owned.contains(&line))
+            })
+    };
+
+    // branches claiming a hunk as a whole take it out of `base_diffs`, but other branches may
+    // still have claimed lines of it
+    let all_diffs = base_diffs.clone();
+
     for branch in &mut virtual_branches {
         if !branch.applied {
             bail!("branch {} is not applied", branch.name);
@@ -1830,10 +1951,38 @@ fn get_applied_status(
         let new_claims = old_claims
             .iter()
             .filter_map(|claim| {
-                let git_diff_hunks = match base_diffs.get_mut(&claim.file_path) {
-                    None => return None,
-                    Some(hunks) => hunks,
-                };
+                if !base_diffs.contains_key(&claim.file_path) {
+                    // the branch keeps all changes of a file it owned across a rename
+                    let renamed_to = base_diffs.iter().find_map(|(path, hunks)| {
+                        hunks
+                            .iter()
+                            .any(|hunk| {
+                                hunk.change_type == diff::ChangeType::Renamed
+                                    && hunk.old_path.as_ref() == Some(&claim.file_path)
+                            })
+                            .then(|| path.clone())
+                    })?;
+                    let git_diff_hunks = base_diffs.remove(&renamed_to)?;
+                    let mtime = mtimes.mtime_by_path(renamed_to.as_path());
+                    let claimed_hunks = git_diff_hunks
+                        .iter()
+                        .map(|hunk| {
+                            Hunk::from(hunk)
+                                .with_timestamp(mtime)
+                                .with_hash(Hunk::hash_diff(&hunk.diff_lines))
+                        })
+                        .collect();
+                    diffs_by_branch
+                        .entry(branch.id)
+                        .or_default()
+                        .insert(renamed_to.clone(), git_diff_hunks);
+                    return Some(OwnershipClaim {
+                        file_path: renamed_to,
+                        hunks: claimed_hunks,
+                    });
+                }
+
+                let git_diff_hunks = base_diffs.get_mut(&claim.file_path)?;
 
                 let mtime = mtimes.mtime_by_path(claim.file_path.as_path());
 
@@ -1841,43 +1990,97 @@ fn get_applied_status(
                     .hunks
                     .iter()
                     .filter_map(|claimed_hunk| {
+                        if !claimed_hunk.lines.is_empty() {
+                            // lines can only be claimed while their hunk stays as it is
+                            let git_diff_hunk =
+                                claimed_git_hunk(&all_diffs, &claim.file_path, claimed_hunk)?;
+                            let hash = Hunk::hash_diff(&git_diff_hunk.diff_lines);
+                            if locked_hunk_map.contains_key(&hash) {
+                                return None; // Defer allocation to unclaimed hunks processing
+                            }
+                            let lines = claimed_lines
+                                .get(&(
+                                    claim.file_path.clone(),
+                                    git_diff_hunk.new_start,
+                                    git_diff_hunk.new_start + git_diff_hunk.new_lines,
+                                ))?
+                                .iter()
+                                .find(|(branch_id, _)| *branch_id == branch.id)?
+                                .1
+                                .clone();
+                            let part =
+                                git_diff_hunk.filter_changes(|line| lines.contains(&line))?;
+                            diffs_by_branch
+                                .entry(branch.id)
+                                .or_default()
+                                .entry(claim.file_path.clone())
+                                .or_default()
+                                .push(part);
+                            let timestamp = claimed_hunk.timestamp_ms().unwrap_or(mtime);
+                            // the range follows the hunk in case it has more or less context now
+                            return Some(
+                                Hunk {
+                                    start: git_diff_hunk.new_start,
+                                    end: git_diff_hunk.new_start + git_diff_hunk.new_lines,
+                                    ..claimed_hunk.clone()
+                                }
+                                .with_lines(lines)
+                                .with_timestamp(timestamp)
+                                .with_hash(hash),
+                            );
+                        }
+
                         // if any of the current hunks intersects with the owned hunk, we want to keep it
                         for (i, git_diff_hunk) in git_diff_hunks.iter().enumerate() {
                             let hash = Hunk::hash_diff(&git_diff_hunk.diff_lines);
                             if locked_hunk_map.contains_key(&hash) {
                                 return None; // Defer allocation to unclaimed hunks processing
                             }
+                            // whatever other branches didn't claim line by line
+                            let rest_of = |git_diff_hunk: &diff::GitHunk| {
+                                git_diff_hunk.filter_changes(|line| {
+                                    !is_claimed_line(&claim.file_path, git_diff_hunk, line)
+                                })
+                            };
                             if claimed_hunk.eq(&Hunk::from(git_diff_hunk)) {
                                 let timestamp = claimed_hunk.timestamp_ms().unwrap_or(mtime);
+                                let git_diff_hunk = git_diff_hunks.remove(i);
+                                let rest = rest_of(&git_diff_hunk)?;
                                 diffs_by_branch
                                     .entry(branch.id)
                                     .or_default()
                                     .entry(claim.file_path.clone())
                                     .or_default()
-                                    .push(git_diff_hunk.clone());
-
-                                git_diff_hunks.remove(i);
+                                    .push(rest);
+                                // the range follows the hunk in case it has more or less context
                                 return Some(
-                                    claimed_hunk
-                                        .clone()
-                                        .with_timestamp(timestamp)
-                                        .with_hash(hash),
+                                    Hunk {
+                                        start: git_diff_hunk.new_start,
+                                        end: git_diff_hunk.new_start + git_diff_hunk.new_lines,
+                                        ..claimed_hunk.clone()
+                                    }
+                                    .with_timestamp(timestamp)
+                                    .with_hash(hash),
                                 );
                             } else if claimed_hunk.intersects(git_diff_hunk) {
+                                let git_diff_hunk = git_diff_hunks.remove(i);
+                                let rest = rest_of(&git_diff_hunk)?;
                                 diffs_by_branch
                                     .entry(branch.id)
                                     .or_default()
                                     .entry(claim.file_path.clone())
                                     .or_default()
-                                    .push(git_diff_hunk.clone());
+                                    .push(rest);
                                 let updated_hunk = Hunk {
                                     start: git_diff_hunk.new_start,
                                     end: git_diff_hunk.new_start + git_diff_hunk.new_lines,
                                     timestamp_ms: Some(mtime),
                                     hash: Some(hash),
                                     locked_to: git_diff_hunk.locked_to.to_vec(),
+                                    // the lines moved along with the hunk, so a selection
+                                    // of them can't be trusted anymore
+                                    lines: vec![],
                                 };
-                                git_diff_hunks.remove(i);
                                 return Some(updated_hunk);
                             }
                         }
@@ -1916,6 +2119,15 @@ fn get_applied_status(
         for hunk in hunks {
             let hash = Hunk::hash_diff(&hunk.diff_lines);
             let locked_to = locked_hunk_map.get(&hash);
+            let hunk = if locked_to.is_some() {
+                hunk
+            } else {
+                // the lines branches claimed one by one went to them already
+                match hunk.filter_changes(|line| !is_claimed_line(&filepath, &hunk, line)) {
+                    Some(rest) => rest,
+                    None => continue,
+                }
+            };
 
             let vbranch_pos = if let Some(locks) = locked_to {
                 let first_lock = &locks[0];
@@ -1930,7 +2142,6 @@ fn get_applied_status(
                 default_vbranch_pos
             };
 
-            let hash = Hunk::hash_diff(&hunk.diff_lines);
             let mut new_hunk = Hunk::from(&hunk)
                 .with_timestamp(mtimes.mtime_by_path(filepath.as_path()))
                 .with_hash(hash);
@@ -1943,7 +2154,7 @@ fn get_applied_status(
                 file_path: filepath.clone(),
                 hunks: vec![Hunk::from(&hunk)
                     .with_timestamp(mtimes.mtime_by_path(filepath.as_path()))
-                    .with_hash(Hunk::hash_diff(&hunk.diff_lines))],
+                    .with_hash(hash)],
             });
 
             let hunk = match locked_to {
@@ -1977,7 +2188,12 @@ fn get_applied_status(
     if !project_repository.is_resolving() {
         let vb_state = project_repository.project().virtual_branches();
         for (vbranch, files) in &mut hunks_by_branch {
-            vbranch.tree = write_tree(project_repository, &vbranch.head, files)?;
+            // files too large to diff are only stored once a tree of them is needed, not on
+            // every status
+            let diffed_files = files
+                .iter()
+                .filter(|(_, hunks)| !hunks.iter().any(diff::GitHunk::is_worktree_marker));
+            vbranch.tree = write_tree(project_repository, &vbranch.head, diffed_files)?;
             vb_state
                 .set_branch(vbranch.clone())
                 .context(format!("failed to write virtual branch {}", vbranch.name))?;
@@ -1987,6 +2203,18 @@ fn get_applied_status(
     Ok((hunks_by_branch, skipped_files))
 }
 
+/// The hunk in `diffs` that `claimed_hunk` of the file at `path` is about, if it's still there.
+fn claimed_git_hunk<'a>(
+    diffs: &'a HashMap<PathBuf, Vec<diff::GitHunk>>,
+    path: &PathBuf,
+    claimed_hunk: &Hunk,
+) -> Option<&'a diff::GitHunk> {
+    diffs
+        .get(path)?
+        .iter()
+        .find(|git_diff_hunk| claimed_hunk.same_hunk(&Hunk::from(*git_diff_hunk)))
+}
+
 /// NOTE: There is no use returning an iterator here as this acts like the final product.
 fn virtual_hunks_into_virtual_files(
     project_repository: &project_repository::Repository,
@@ -1999,6 +2227,7 @@ fn virtual_hunks_into_virtual_files(
             let conflicted =
                 conflicts::is_conflicting(project_repository, Some(&id)).unwrap_or(false);
             let binary = hunks.iter().any(|h| h.binary);
+            let submodule = hunks.iter().find_map(|h| h.submodule);
             let modified_at = hunks.iter().map(|h| h.modified_at).max().unwrap_or(0);
             debug_assert!(hunks.iter().all(|hunk| hunk.file_path == path));
             VirtualBranchFile {
@@ -2009,6 +2238,7 @@ fn virtual_hunks_into_virtual_files(
                 large: false,
                 modified_at,
                 conflicted,
+                submodule,
             }
         })
         .collect::<Vec<_>>()
@@ -2074,6 +2304,10 @@ pub fn reset_branch(
         repo,
         &repo.find_commit(updated_head)?.tree()?,
         &repo.find_commit(old_head)?.tree()?,
+        &project_repository
+            .project()
+            .diff_settings
+            .keeping_whitespace(),
     )?;
 
     // Assign the new hunks to the branch we're working on.
@@ -2148,9 +2382,25 @@ pub fn write_tree_onto_tree(
         let hunks = hunks.borrow();
         let full_path = project_repository.path().join(rel_path);
 
-        let is_submodule = full_path.is_dir()
-            && hunks.len() == 1
-            && hunks[0].diff_lines.contains_str(b"Subproject commit");
+        // renamed or copied files start out as the file they came from
+        let origin = hunks.iter().find_map(|hunk| hunk.old_path.as_ref());
+        let is_renamed = hunks
+            .iter()
+            .any(|hunk| hunk.change_type == diff::ChangeType::Renamed);
+        if let Some(old_path) = origin.filter(|old_path| is_renamed && *old_path != rel_path) {
+            if base_tree.get_path(old_path).is_ok() {
+                builder.remove(old_path);
+            }
+        }
+
+        if let Some(submodule) = hunks.iter().find_map(|hunk| hunk.submodule) {
+            // a submodule is a commit of another repository, not content on disk
+            match submodule.new_id {
+                Some(commit_id) => builder.upsert(rel_path, commit_id, git::FileMode::Commit),
+                None => builder.remove(rel_path),
+            }
+            continue;
+        }
 
         // if file exists
         if full_path.exists() {
@@ -2205,60 +2455,52 @@ pub fn write_tree_onto_tree(
                         .as_bytes(),
                 )?;
                 builder.upsert(rel_path, blob_oid, filemode);
-            } else if let Ok(tree_entry) = base_tree.get_path(rel_path) {
-                if hunks.len() == 1 && hunks[0].binary {
+            } else if hunks.len() == 1 && hunks[0].binary {
+                let new_blob_oid = if hunks[0].is_worktree_marker() {
+                    // a file too large to diff only goes into the object database now
+                    git_repository
+                        .blob_path(&full_path)
+                        .context(format!("failed to create blob from path {:?}", &full_path))?
+                } else {
+                    // the whole content is a blob already, which may differ from the file on
+                    // disk if a filter like `git lfs` turned it into a pointer
                     let new_blob_oid = &hunks[0].diff_lines;
                     // convert string to Oid
-                    let new_blob_oid = new_blob_oid
+                    new_blob_oid
                         .to_str()
                         .expect("hex-string")
                         .parse()
-                        .context("failed to diff as oid")?;
-                    builder.upsert(rel_path, new_blob_oid, filemode);
-                } else {
-                    // blob from tree_entry
-                    let blob = tree_entry
-                        .to_object(git_repository)
-                        .unwrap()
-                        .peel_to_blob()
-                        .context("failed to get blob")?;
-
-                    let blob_contents = blob.content();
-
-                    let mut hunks = hunks.iter().collect::<Vec<_>>();
-                    hunks.sort_by_key(|hunk| hunk.new_start);
-                    let mut all_diffs = BString::default();
-                    for hunk in hunks {
-                        all_diffs.push_str(&hunk.diff_lines);
-                    }
-
-                    let patch = Patch::from_bytes(&all_diffs)?;
-                    let blob_contents = apply(blob_contents, &patch).context(format!(
-                        "failed to apply\n{}\nonto:\n{}",
-                        all_diffs.as_bstr(),
-                        blob_contents.as_bstr()
-                    ))?;
+                        .context("failed to diff as oid")?
+                };
+                builder.upsert(rel_path, new_blob_oid, filemode);
+            } else if let Ok(tree_entry) = base_tree.get_path(rel_path) {
+                // blob from tree_entry
+                let blob = tree_entry
+                    .to_object(git_repository)
+                    .unwrap()
+                    .peel_to_blob()
+                    .context("failed to get blob")?;
 
-                    // create a blob
-                    let new_blob_oid = git_repository.blob(&blob_contents)?;
-                    // upsert into the builder
-                    builder.upsert(rel_path, new_blob_oid, filemode);
-                }
-            } else if is_submodule {
-                let mut blob_contents = BString::default();
-
-                let mut hunks = hunks.iter().collect::<Vec<_>>();
-                hunks.sort_by_key(|hunk| hunk.new_start);
-                for hunk in hunks {
-                    let patch = Patch::from_bytes(&hunk.diff_lines)?;
-                    blob_contents = apply(&blob_contents, &patch)
-                        .context(format!("failed to apply {}", &hunk.diff_lines))?;
-                }
+                let blob_contents = apply_hunks(blob.content(), hunks)?;
 
                 // create a blob
-                let new_blob_oid = git_repository.blob(blob_contents.as_bytes())?;
+                let new_blob_oid = git_repository.blob(&blob_contents)?;
                 // upsert into the builder
                 builder.upsert(rel_path, new_blob_oid, filemode);
+            } else if hunks.iter().any(diff::GitHunk::is_partial) {
+                // only some lines of the new file go in, so the file on disk has too many
+                let origin_contents = match origin.and_then(|path| base_tree.get_path(path).ok()) {
+                    Some(entry) => entry
+                        .to_object(git_repository)?
+                        .peel_to_blob()
+                        .context("failed to get blob")?
+                        .content()
+                        .to_vec(),
+                    None => vec![],
+                };
+                let blob_contents = apply_hunks(&origin_contents, hunks)?;
+                let new_blob_oid = git_repository.blob(&blob_contents)?;
+                builder.upsert(rel_path, new_blob_oid, filemode);
             } else {
                 // create a git blob from a file on disk
                 let blob_oid = git_repository
@@ -2266,9 +2508,25 @@ pub fn write_tree_onto_tree(
                     .context(format!("failed to create blob from path {:?}", &full_path))?;
                 builder.upsert(rel_path, blob_oid, filemode);
             }
-        } else if base_tree.get_path(rel_path).is_ok() {
-            // remove file from index if it exists in the base tree
-            builder.remove(rel_path);
+        } else if let Ok(tree_entry) = base_tree.get_path(rel_path) {
+            if hunks.iter().any(diff::GitHunk::is_partial) {
+                // only some lines of the deleted file go away, so it has to stay
+                let blob = tree_entry
+                    .to_object(git_repository)?
+                    .peel_to_blob()
+                    .context("failed to get blob")?;
+                let blob_contents = apply_hunks(blob.content(), hunks)?;
+                let new_blob_oid = git_repository.blob(&blob_contents)?;
+                let filemode = if tree_entry.filemode() & 0o111 != 0 {
+                    git::FileMode::BlobExecutable
+                } else {
+                    git::FileMode::Blob
+                };
+                builder.upsert(rel_path, new_blob_oid, filemode);
+            } else {
+                // remove file from index if it exists in the base tree
+                builder.remove(rel_path);
+            }
         }
     }
 
@@ -2278,6 +2536,23 @@ pub fn write_tree_onto_tree(
     Ok(tree_oid)
 }
 
+/// Apply all `hunks` of a file onto `blob_contents`, in the order they appear in the file.
+fn apply_hunks(blob_contents: &[u8], hunks: &[diff::GitHunk]) -> Result<BString> {
+    let mut hunks = hunks.iter().collect::<Vec<_>>();
+    hunks.sort_by_key(|hunk| hunk.new_start);
+    let mut all_diffs = BString::default();
+    for hunk in hunks {
+        all_diffs.push_str(&hunk.diff_lines);
+    }
+
+    let patch = Patch::from_bytes(&all_diffs)?;
+    apply(blob_contents, &patch).context(format!(
+        "failed to apply\n{}\nonto:\n{}",
+        all_diffs.as_bstr(),
+        blob_contents.as_bstr()
+    ))
+}
+
 fn _print_tree(repo: &git2::Repository, tree: &git2::Tree) -> Result<()> {
     println!("tree id: {}", tree.id());
     for entry in tree {
@@ -2350,6 +2625,10 @@ pub fn commit(
             })
         })?;
 
+    if rebase::is_rebasing(project_repository, branch_id)? {
+        return Err(errors::CommitError::RebaseInProgress(*branch_id));
+    }
+
     update_conflict_markers(project_repository, &files)?;
 
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
@@ -2362,17 +2641,12 @@ pub fn commit(
         let files = files.into_iter().filter_map(|(filepath, hunks)| {
             let hunks = hunks
                 .into_iter()
-                .filter(|hunk| {
+                .filter_map(|hunk| {
                     ownership
                         .claims
                         .iter()
                         .find(|f| f.file_path.eq(&filepath))
-                        .map_or(false, |f| {
-                            f.hunks.iter().any(|h| {
-                                h.start == hunk.new_start
-                                    && h.end == hunk.new_start + hunk.new_lines
-                            })
-                        })
+                        .and_then(|f| f.hunks.iter().find_map(|h| h.select(&hunk)))
                 })
                 .collect::<Vec<_>>();
             if hunks.is_empty() {
@@ -2424,11 +2698,13 @@ pub fn commit(
     }
 
     let vb_state = project_repository.project().virtual_branches();
+    let old_head = branch.head;
     branch.tree = tree_oid;
     branch.head = commit_oid;
     vb_state
         .set_branch(branch.clone())
         .context("failed to write branch")?;
+    rebase_stacked_branches(project_repository, branch, old_head, signing_key)?;
 
     super::integration::update_gitbutler_integration(&vb_state, project_repository)
         .context("failed to update gitbutler integration")?;
@@ -2436,16 +2712,48 @@ pub fn commit(
     Ok(commit_oid)
 }
 
+/// A ref that [`push`] creates or updates on the remote.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushedRef {
+    pub branch_id: BranchId,
+    pub remote_ref: git::RemoteRefname,
+    // the head of the remote branch before the push, none if the push creates it
+    pub old_head: Option<git::Oid>,
+    pub new_head: git::Oid,
+}
+
+/// What [`push`] did, or would have done in a dry run.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushReport {
+    pub refs: Vec<PushedRef>,
+    // the output of the pre-push hooks that ran
+    pub hook_output: Vec<String>,
+}
+
+/// How [`push`] pushes a branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PushOptions {
+    /// Allow the push to overwrite the upstream branch, as long as nobody else moved it since we last pushed it.
+    pub with_force: bool,
+    /// Run the `pre-push` hook and report what would be pushed, without pushing anything.
+    pub dry_run: bool,
+}
+
+/// Pushes the branch, after the branches it is stacked on. Like `git push`, the `pre-push` hook runs once
+/// per remote with all the refs that are about to be pushed to it, and any of them being rejected stops the push.
 pub fn push(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
-    with_force: bool,
+    options: &PushOptions,
     credentials: &git::credentials::Helper,
     askpass: Option<(AskpassBroker, Option<BranchId>)>,
-) -> Result<(), errors::PushError> {
+    on_progress: Option<&OnProgress>,
+) -> Result<PushReport, errors::PushError> {
     let vb_state = project_repository.project().virtual_branches();
 
-    let mut vbranch = vb_state
+    let vbranch = vb_state
         .get_branch(branch_id)
         .map_err(|error| match error {
             reader::Error::NotFound => errors::PushError::BranchNotFound(errors::BranchNotFound {
@@ -2455,74 +2763,182 @@ pub fn push(
             error => errors::PushError::Other(error.into()),
         })?;
 
-    let remote_branch = if let Some(upstream_branch) = &vbranch.upstream {
-        upstream_branch.clone()
-    } else {
-        let Some(default_target) = vb_state
-            .try_get_default_target()
-            .context("failed to get default target")?
-        else {
-            return Err(errors::PushError::DefaultTargetNotSet(
-                errors::DefaultTargetNotSet {
-                    project_id: project_repository.project().id,
-                },
-            ));
-        };
+    // the branches a stacked branch builds on are pushed first, so that they can be reviewed in order
+    let mut vbranches = vec![vbranch];
+    while let Some(parent) = vbranches[0]
+        .parent
+        .and_then(|parent_id| vb_state.get_branch(&parent_id).ok())
+    {
+        if parent.upstream_head == Some(parent.head) || vbranches.iter().any(|b| b.id == parent.id)
+        {
+            break;
+        }
+        vbranches.insert(0, parent);
+    }
 
-        let upstream_remote = match default_target.push_remote_name {
-            Some(remote) => remote.clone(),
-            None => default_target.branch.remote().to_owned(),
-        };
+    for vbranch in &vbranches {
+        if rebase::is_rebasing(project_repository, &vbranch.id)? {
+            return Err(errors::PushError::RebaseInProgress(vbranch.id));
+        }
+    }
 
-        let remote_branch = format!(
-            "refs/remotes/{}/{}",
-            upstream_remote,
-            normalize_branch_name(&vbranch.name)
-        )
-        .parse::<git::RemoteRefname>()
-        .context("failed to parse remote branch name")?;
+    let mut report = PushReport::default();
+    for vbranch in &vbranches {
+        let remote_ref = push_remote_ref(project_repository, vbranch)?;
+        let old_head = match project_repository
+            .git_repository
+            .find_reference(&remote_ref.clone().into())
+        {
+            Ok(reference) => reference.target(),
+            Err(git::Error::NotFound(_)) => None,
+            Err(error) => return Err(errors::PushError::Other(error.into())),
+        };
+        report.refs.push(PushedRef {
+            branch_id: vbranch.id,
+            remote_ref,
+            old_head,
+            new_head: vbranch.head,
+        });
+    }
 
-        let remote_branches = project_repository.git_remote_branches()?;
-        let existing_branches = remote_branches
+    let mut remotes = Vec::<&str>::new();
+    for pushed in &report.refs {
+        if !remotes.contains(&pushed.remote_ref.remote()) {
+            remotes.push(pushed.remote_ref.remote());
+        }
+    }
+    for &remote in &remotes {
+        let remote_url = project_repository
+            .git_repository
+            .find_remote(remote)
+            .and_then(|remote| remote.url())
+            .context("failed to get remote url")?
+            .map(|url| url.to_string())
+            .unwrap_or_default();
+        let updates = vbranches
             .iter()
-            .map(RemoteRefname::branch)
-            .map(str::to_lowercase) // git is weird about case sensitivity here, assume not case sensitive
+            .zip(&report.refs)
+            .filter(|(_, pushed)| pushed.remote_ref.remote() == remote)
+            .map(|(vbranch, pushed)| git::RefUpdate {
+                local_ref: git::VirtualRefname::from(vbranch).to_string(),
+                local_oid: pushed.new_head,
+                remote_ref: format!("refs/heads/{}", pushed.remote_ref.branch()),
+                remote_oid: pushed.old_head.unwrap_or_default(),
+            })
             .collect::<Vec<_>>();
+        if let Some(hook_output) = project_repository
+            .git_repository
+            .run_hook_pre_push(remote, &remote_url, &updates)
+            .context("failed to run hook")?
+        {
+            let output = [hook_output.stdout, hook_output.stderr].concat();
+            if !hook_output.success {
+                return Err(errors::PushError::PrePushHookRejected(output));
+            }
+            if !output.is_empty() {
+                report.hook_output.push(output);
+            }
+        }
+    }
 
-        remote_branch.with_branch(&dedup_fmt(
-            &existing_branches
-                .iter()
-                .map(String::as_str)
-                .collect::<Vec<_>>(),
-            remote_branch.branch(),
-            "-",
-        ))
+    if options.dry_run {
+        return Ok(report);
+    }
+
+    for (mut vbranch, pushed) in vbranches.into_iter().zip(&report.refs) {
+        // a forced push must not drop commits that others pushed since we last did, and may only
+        // overwrite a remote branch we pushed ourselves
+        project_repository
+            .push(
+                &vbranch.head,
+                &pushed.remote_ref,
+                options.with_force,
+                vbranch.upstream_head,
+                credentials,
+                None,
+                askpass.clone(),
+                on_progress,
+            )
+            .map_err(|error| match error {
+                project_repository::RemoteError::LeaseExpired(remote_branch) => {
+                    errors::PushError::LeaseExpired(remote_branch)
+                }
+                error => errors::PushError::Remote(error),
+            })?;
+
+        vbranch.upstream = Some(pushed.remote_ref.clone());
+        vbranch.upstream_head = Some(vbranch.head);
+        vb_state
+            .set_branch(vbranch)
+            .context("failed to write target branch after push")?;
+    }
+
+    for &remote in &remotes {
+        project_repository.fetch(
+            remote,
+            credentials,
+            askpass
+                .clone()
+                .map(|(broker, _)| (broker, "modal".to_string())),
+            on_progress,
+        )?;
+    }
+
+    Ok(report)
+}
+
+/// Returns the remote branch `vbranch` is pushed to, which is a new one named after it if it wasn't pushed before.
+fn push_remote_ref(
+    project_repository: &project_repository::Repository,
+    vbranch: &branch::Branch,
+) -> Result<git::RemoteRefname, errors::PushError> {
+    if let Some(upstream_branch) = &vbranch.upstream {
+        return Ok(upstream_branch.clone());
+    }
+
+    let vb_state = project_repository.project().virtual_branches();
+    let Some(default_target) = vb_state
+        .try_get_default_target()
+        .context("failed to get default target")?
+    else {
+        return Err(errors::PushError::DefaultTargetNotSet(
+            errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            },
+        ));
     };
 
-    project_repository.push(
-        &vbranch.head,
-        &remote_branch,
-        with_force,
-        credentials,
-        None,
-        askpass.clone(),
-    )?;
+    let upstream_remote = match default_target.push_remote_name {
+        Some(remote) => remote.clone(),
+        None => default_target.branch.remote().to_owned(),
+    };
 
-    vbranch.upstream = Some(remote_branch.clone());
-    vbranch.upstream_head = Some(vbranch.head);
-    vb_state
-        .set_branch(vbranch.clone())
-        .context("failed to write target branch after push")?;
-    project_repository.fetch(
-        remote_branch.remote(),
-        credentials,
-        askpass.map(|(broker, _)| (broker, "modal".to_string())),
-    )?;
+    let remote_branch = format!(
+        "refs/remotes/{}/{}",
+        upstream_remote,
+        normalize_branch_name(&vbranch.name)
+    )
+    .parse::<git::RemoteRefname>()
+    .context("failed to parse remote branch name")?;
 
-    Ok(())
+    let remote_branches = project_repository.git_remote_branches()?;
+    let existing_branches = remote_branches
+        .iter()
+        .map(RemoteRefname::branch)
+        .map(str::to_lowercase) // git is weird about case sensitivity here, assume not case sensitive
+        .collect::<Vec<_>>();
+
+    Ok(remote_branch.with_branch(&dedup_fmt(
+        &existing_branches
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>(),
+        remote_branch.branch(),
+        "-",
+    )))
 }
 
-fn is_commit_integrated(
+pub(crate) fn is_commit_integrated(
     project_repository: &project_repository::Repository,
     target: &target::Target,
     commit: &git::Commit,
@@ -2735,6 +3151,7 @@ pub fn move_commit_file(
     from_commit_oid: git::Oid,
     to_commit_oid: git::Oid,
     target_ownership: &BranchOwnershipClaims,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<git::Oid, errors::VirtualBranchError> {
     let vb_state = project_repository.project().virtual_branches();
 
@@ -2769,8 +3186,15 @@ pub fn move_commit_file(
     )?;
 
     // get a list of all the diffs across all the virtual branches
-    let base_file_diffs = diff::workdir(&project_repository.git_repository, &default_target.sha)
-        .context("failed to diff workdir")?;
+    let base_file_diffs = diff::workdir(
+        &project_repository.git_repository,
+        &default_target.sha,
+        &project_repository
+            .project()
+            .diff_settings
+            .keeping_whitespace(),
+    )
+    .context("failed to diff workdir")?;
 
     // filter base_file_diffs to HashMap<filepath, Vec<GitHunk>> only for hunks in target_ownership
     // this is essentially the group of patches that we're "moving"
@@ -2784,13 +3208,12 @@ pub fn move_commit_file(
                     hunks
                         .hunks
                         .iter()
-                        .filter(|hunk| {
-                            file_ownership.hunks.iter().any(|owned_hunk| {
-                                owned_hunk.start == hunk.new_start
-                                    && owned_hunk.end == hunk.new_start + hunk.new_lines
-                            })
+                        .filter_map(|hunk| {
+                            file_ownership
+                                .hunks
+                                .iter()
+                                .find_map(|owned_hunk| owned_hunk.select(hunk))
                         })
-                        .cloned()
                         .collect::<Vec<_>>()
                 })
                 .unwrap_or_default();
@@ -2833,27 +3256,33 @@ pub fn move_commit_file(
             &project_repository.git_repository,
             &from_parent_tree,
             &from_tree,
+            &project_repository
+                .project()
+                .diff_settings
+                .keeping_whitespace(),
         )
         .context("failed to diff trees")?;
 
-        // filter from_commit_diffs to HashMap<filepath, Vec<GitHunk>> only for hunks NOT in target_ownership
-        // this is the patch parts we're keeping
+        // filter from_commit_diffs to HashMap<filepath, Vec<GitHunk>> only for hunks NOT in target_ownership,
+        // or the lines of them that aren't. this is the patch parts we're keeping
         let diffs_to_keep = from_commit_diffs
             .iter()
             .filter_map(|(filepath, file_diff)| {
                 let hunks = file_diff
                     .hunks
                     .iter()
-                    .filter(|hunk| {
-                        !target_ownership.claims.iter().any(|file_ownership| {
-                            file_ownership.file_path.eq(filepath)
-                                && file_ownership.hunks.iter().any(|owned_hunk| {
-                                    owned_hunk.start == hunk.new_start
-                                        && owned_hunk.end == hunk.new_start + hunk.new_lines
-                                })
-                        })
+                    .filter_map(|hunk| {
+                        target_ownership
+                            .claims
+                            .iter()
+                            .filter(|file_ownership| file_ownership.file_path.eq(filepath))
+                            .flat_map(|file_ownership| &file_ownership.hunks)
+                            .find(|owned_hunk| owned_hunk.same_hunk(&Hunk::from(hunk)))
+                            .map_or_else(
+                                || Some(hunk.clone()),
+                                |owned_hunk| owned_hunk.deselect(hunk),
+                            )
                     })
-                    .cloned()
                     .collect::<Vec<_>>();
                 if hunks.is_empty() {
                     None
@@ -2871,14 +3300,13 @@ pub fn move_commit_file(
         let new_from_tree = &repo
             .find_tree(new_from_tree_oid)
             .map_err(|_error| errors::VirtualBranchError::GitObjectNotFound(new_from_tree_oid))?;
-        let new_from_commit_oid = repo
-            .commit(
-                None,
-                &from_commit.author(),
-                &from_commit.committer(),
+        let new_from_commit_oid = project_repository
+            .recommit(
+                &from_commit,
                 &from_commit.message().to_str_lossy(),
                 new_from_tree,
                 &[&from_parent],
+                signing_key,
             )
             .map_err(|_error| errors::VirtualBranchError::CommitFailed)?;
 
@@ -2888,11 +3316,15 @@ pub fn move_commit_file(
             new_from_commit_oid,
             from_commit_oid,
             target_branch.head,
+            signing_key,
         ) {
             Ok(Some(new_head)) => new_head,
-            _ => {
-                return Err(errors::VirtualBranchError::RebaseFailed);
+            Ok(None) => {
+                return Err(errors::VirtualBranchError::RebaseFailed(anyhow!(
+                    "commits above {from_commit_oid} conflict with the moved changes"
+                )));
             }
+            Err(error) => return Err(errors::VirtualBranchError::RebaseFailed(error)),
         };
 
         // ok, now we need to identify which the new "to" commit is in the rebased history
@@ -2950,14 +3382,12 @@ pub fn move_commit_file(
         .parents()
         .context("failed to find head commit parents")?;
     let commit_oid = project_repository
-        .git_repository
-        .commit(
-            None,
-            &amend_commit.author(),
-            &amend_commit.committer(),
+        .recommit(
+            &amend_commit,
             &amend_commit.message().to_str_lossy(),
             &new_tree,
             &parents.iter().collect::<Vec<_>>(),
+            signing_key,
         )
         .context("failed to create commit")?;
 
@@ -2965,8 +3395,10 @@ pub fn move_commit_file(
 
     // if there are no upstream commits (the "to" commit was the branch head), then we're done
     if upstream_commits.is_empty() {
+        let old_head = target_branch.head;
         target_branch.head = commit_oid;
         vb_state.set_branch(target_branch.clone())?;
+        rebase_stacked_branches(project_repository, &target_branch, old_head, signing_key)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
         return Ok(commit_oid);
     }
@@ -2978,28 +3410,39 @@ pub fn move_commit_file(
         commit_oid,
         amend_commit.id(),
         last_commit,
+        signing_key,
     )?;
 
     // if that rebase worked, update the branch head and the gitbutler integration
     if let Some(new_head) = new_head {
+        let old_head = target_branch.head;
         target_branch.head = new_head;
         vb_state.set_branch(target_branch.clone())?;
+        rebase_stacked_branches(project_repository, &target_branch, old_head, signing_key)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
         Ok(commit_oid)
     } else {
-        Err(errors::VirtualBranchError::RebaseFailed)
+        Err(errors::VirtualBranchError::RebaseFailed(anyhow!(
+            "commits above {} conflict with the moved changes",
+            amend_commit.id()
+        )))
     }
 }
 
 // takes a list of file ownership and a commit oid and rewrites that commit to
 // add the file changes. The branch is then rebased onto the new commit
-// and the respective branch head is updated
+// and the respective branch head is updated, unless the rebase stops on a conflict
 pub fn amend(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
     target_ownership: &BranchOwnershipClaims,
-) -> Result<git::Oid, errors::VirtualBranchError> {
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<rebase::RebaseOutcome, errors::VirtualBranchError> {
+    if rebase::is_rebasing(project_repository, branch_id)? {
+        return Err(errors::VirtualBranchError::RebaseInProgress(*branch_id));
+    }
+
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
         return Err(errors::VirtualBranchError::Conflict(
             errors::ProjectConflict {
@@ -3102,13 +3545,12 @@ pub fn amend(
                 .map(|hunks| {
                     hunks
                         .iter()
-                        .filter(|hunk| {
-                            file_ownership.hunks.iter().any(|owned_hunk| {
-                                owned_hunk.start == hunk.new_start
-                                    && owned_hunk.end == hunk.new_start + hunk.new_lines
-                            })
+                        .filter_map(|hunk| {
+                            file_ownership
+                                .hunks
+                                .iter()
+                                .find_map(|owned_hunk| owned_hunk.select(hunk))
                         })
-                        .cloned()
                         .collect::<Vec<_>>()
                 })
                 .unwrap_or_default();
@@ -3138,14 +3580,12 @@ pub fn amend(
         .context("failed to find head commit parents")?;
 
     let commit_oid = project_repository
-        .git_repository
-        .commit(
-            None,
-            &amend_commit.author(),
-            &amend_commit.committer(),
+        .recommit(
+            &amend_commit,
             &amend_commit.message().to_str_lossy(),
             &new_tree,
             &parents.iter().collect::<Vec<_>>(),
+            signing_key,
         )
         .context("failed to create commit")?;
 
@@ -3156,29 +3596,22 @@ pub fn amend(
     )?;
     // if there are no upstream commits, we're done
     if upstream_commits.is_empty() {
+        let old_head = target_branch.head;
         target_branch.head = commit_oid;
         vb_state.set_branch(target_branch.clone())?;
+        rebase_stacked_branches(project_repository, target_branch, old_head, signing_key)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
-        return Ok(commit_oid);
+        return Ok(rebase::RebaseOutcome::Completed(commit_oid));
     }
 
-    let last_commit = upstream_commits.first().cloned().unwrap();
-
-    let new_head = cherry_rebase(
+    rebase::rebase_branch(
         project_repository,
+        target_branch,
         commit_oid,
-        amend_commit.id(),
-        last_commit,
-    )?;
-
-    if let Some(new_head) = new_head {
-        target_branch.head = new_head;
-        vb_state.set_branch(target_branch.clone())?;
-        super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
-        Ok(commit_oid)
-    } else {
-        Err(errors::VirtualBranchError::RebaseFailed)
-    }
+        &upstream_commits,
+        signing_key,
+    )
+    .map_err(errors::VirtualBranchError::RebaseFailed)
 }
 
 // move a given commit in a branch up one or down one
@@ -3190,7 +3623,8 @@ pub fn reorder_commit(
     branch_id: &BranchId,
     commit_oid: git::Oid,
     offset: i32,
-) -> Result<(), errors::VirtualBranchError> {
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<rebase::RebaseOutcome, errors::VirtualBranchError> {
     let vb_state = project_repository.project().virtual_branches();
 
     let Some(default_target) = vb_state
@@ -3228,7 +3662,7 @@ pub fn reorder_commit(
         // move commit up
         if branch.head == commit_oid {
             // can't move the head commit up
-            return Ok(());
+            return Ok(rebase::RebaseOutcome::Completed(branch.head));
         }
 
         // get a list of the commits to rebase
@@ -3240,25 +3674,19 @@ pub fn reorder_commit(
         ids_to_rebase.push(commit_oid);
         ids_to_rebase.push(last_oid);
 
-        match cherry_rebase_group(project_repository, parent_oid, &mut ids_to_rebase) {
-            Ok(Some(new_head)) => {
-                branch.head = new_head;
-                vb_state
-                    .set_branch(branch.clone())
-                    .context("failed to write branch")?;
-
-                super::integration::update_gitbutler_integration(&vb_state, project_repository)
-                    .context("failed to update gitbutler integration")?;
-            }
-            _ => {
-                return Err(errors::VirtualBranchError::RebaseFailed);
-            }
-        }
+        rebase::rebase_branch(
+            project_repository,
+            &mut branch,
+            parent_oid,
+            &ids_to_rebase,
+            signing_key,
+        )
+        .map_err(errors::VirtualBranchError::RebaseFailed)
     } else {
         //  move commit down
         if default_target.sha == parent_oid {
             // can't move the commit down past the target
-            return Ok(());
+            return Ok(rebase::RebaseOutcome::Completed(branch.head));
         }
 
         let target = parent.parent(0).context("failed to find target")?;
@@ -3272,23 +3700,15 @@ pub fn reorder_commit(
         ids_to_rebase.push(parent_oid);
         ids_to_rebase.push(commit_oid);
 
-        match cherry_rebase_group(project_repository, target_oid, &mut ids_to_rebase) {
-            Ok(Some(new_head)) => {
-                branch.head = new_head;
-                vb_state
-                    .set_branch(branch.clone())
-                    .context("failed to write branch")?;
-
-                super::integration::update_gitbutler_integration(&vb_state, project_repository)
-                    .context("failed to update gitbutler integration")?;
-            }
-            _ => {
-                return Err(errors::VirtualBranchError::RebaseFailed);
-            }
-        }
+        rebase::rebase_branch(
+            project_repository,
+            &mut branch,
+            target_oid,
+            &ids_to_rebase,
+            signing_key,
+        )
+        .map_err(errors::VirtualBranchError::RebaseFailed)
     }
-
-    Ok(())
 }
 
 // create and insert a blank commit (no tree change) either above or below a commit
@@ -3300,7 +3720,12 @@ pub fn insert_blank_commit(
     commit_oid: git::Oid,
     user: Option<&users::User>,
     offset: i32,
-) -> Result<(), errors::VirtualBranchError> {
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<rebase::RebaseOutcome, errors::VirtualBranchError> {
+    if rebase::is_rebasing(project_repository, branch_id)? {
+        return Err(errors::VirtualBranchError::RebaseInProgress(*branch_id));
+    }
+
     let vb_state = project_repository.project().virtual_branches();
 
     let mut branch = match vb_state.get_branch(branch_id) {
@@ -3325,40 +3750,35 @@ pub fn insert_blank_commit(
     }
 
     let commit_tree = commit.tree().unwrap();
-    let blank_commit_oid = project_repository.commit(user, "", &commit_tree, &[&commit], None)?;
+    let blank_commit_oid =
+        project_repository.commit(user, "", &commit_tree, &[&commit], signing_key)?;
 
     if commit.id() == branch.head && offset < 0 {
         // inserting before the first commit
+        let old_head = branch.head;
         branch.head = blank_commit_oid;
         vb_state
             .set_branch(branch.clone())
             .context("failed to write branch")?;
+        rebase_stacked_branches(project_repository, &branch, old_head, signing_key)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)
             .context("failed to update gitbutler integration")?;
+        Ok(rebase::RebaseOutcome::Completed(blank_commit_oid))
     } else {
         // rebase all commits above it onto the new commit
-        match cherry_rebase(
+        let ids_to_rebase = project_repository.l(
+            branch.head,
+            project_repository::LogUntil::Commit(commit.id()),
+        )?;
+        rebase::rebase_branch(
             project_repository,
+            &mut branch,
             blank_commit_oid,
-            commit.id(),
-            branch.head,
-        ) {
-            Ok(Some(new_head)) => {
-                branch.head = new_head;
-                vb_state
-                    .set_branch(branch.clone())
-                    .context("failed to write branch")?;
-
-                super::integration::update_gitbutler_integration(&vb_state, project_repository)
-                    .context("failed to update gitbutler integration")?;
-            }
-            _ => {
-                return Err(errors::VirtualBranchError::RebaseFailed);
-            }
-        }
+            &ids_to_rebase,
+            signing_key,
+        )
+        .map_err(errors::VirtualBranchError::RebaseFailed)
     }
-
-    Ok(())
 }
 
 // remove a commit in a branch by rebasing all commits _except_ for it onto it's parent
@@ -3367,7 +3787,8 @@ pub fn undo_commit(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
-) -> Result<(), errors::VirtualBranchError> {
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<rebase::RebaseOutcome, errors::VirtualBranchError> {
     let vb_state = project_repository.project().virtual_branches();
 
     let mut branch = match vb_state.get_branch(branch_id) {
@@ -3386,41 +3807,35 @@ pub fn undo_commit(
         .find_commit(commit_oid)
         .context("failed to find commit")?;
 
-    let new_commit_oid;
+    let parent_commit_oid = commit.parent(0).context("failed to find parent")?.id();
 
     if branch.head == commit_oid {
         // if commit is the head, just set head to the parent
-        new_commit_oid = commit.parent(0).context("failed to find parent")?.id();
-    } else {
-        // if commit is not the head, rebase all commits above it onto it's parent
-        let parent_commit_oid = commit.parent(0).context("failed to find parent")?.id();
-
-        match cherry_rebase(
-            project_repository,
-            parent_commit_oid,
-            commit_oid,
-            branch.head,
-        ) {
-            Ok(Some(new_head)) => {
-                new_commit_oid = new_head;
-            }
-            _ => {
-                return Err(errors::VirtualBranchError::RebaseFailed);
-            }
-        }
-    }
-
-    if new_commit_oid != commit_oid {
-        branch.head = new_commit_oid;
+        let old_head = branch.head;
+        branch.head = parent_commit_oid;
         vb_state
             .set_branch(branch.clone())
             .context("failed to write branch")?;
+        rebase_stacked_branches(project_repository, &branch, old_head, signing_key)?;
 
         super::integration::update_gitbutler_integration(&vb_state, project_repository)
             .context("failed to update gitbutler integration")?;
+        Ok(rebase::RebaseOutcome::Completed(parent_commit_oid))
+    } else {
+        // if commit is not the head, rebase all commits above it onto it's parent
+        let ids_to_rebase = project_repository.l(
+            branch.head,
+            project_repository::LogUntil::Commit(commit_oid),
+        )?;
+        rebase::rebase_branch(
+            project_repository,
+            &mut branch,
+            parent_commit_oid,
+            &ids_to_rebase,
+            signing_key,
+        )
+        .map_err(errors::VirtualBranchError::RebaseFailed)
     }
-
-    Ok(())
 }
 
 // cherry-pick based rebase, which handles empty commits
@@ -3431,6 +3846,7 @@ pub fn cherry_rebase(
     target_commit_oid: git::Oid,
     start_commit_oid: git::Oid,
     end_commit_oid: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<Option<git::Oid>, anyhow::Error> {
     // get a list of the commits to rebase
     let mut ids_to_rebase = project_repository.l(
@@ -3442,131 +3858,188 @@ pub fn cherry_rebase(
         return Ok(None);
     }
 
-    let new_head_id =
-        cherry_rebase_group(project_repository, target_commit_oid, &mut ids_to_rebase)?;
+    let new_head_id = cherry_rebase_group(
+        project_repository,
+        target_commit_oid,
+        &mut ids_to_rebase,
+        signing_key,
+    )?;
 
     Ok(new_head_id)
 }
 
-// takes a vector of commit oids and rebases them onto a target commit and returns the
-// new head commit oid if it's successful
-// the difference between this and a libgit2 based rebase is that this will successfully
-// rebase empty commits (two commits with identical trees)
-fn cherry_rebase_group(
+/// Returns the commit that the commits of `branch` start from. That is the head of the branch it is stacked on,
+/// or the default target if it isn't stacked or its parent's head isn't part of its history anymore.
+pub(crate) fn stack_base(
     project_repository: &project_repository::Repository,
-    target_commit_oid: git::Oid,
-    ids_to_rebase: &mut [git::Oid],
-) -> Result<Option<git::Oid>, anyhow::Error> {
-    ids_to_rebase.reverse();
-    // now, rebase unchanged commits onto the new commit
-    let commits_to_rebase = ids_to_rebase
-        .iter()
-        .map(|oid| project_repository.git_repository.find_commit(*oid))
-        .collect::<Result<Vec<_>, _>>()
-        .context("failed to read commits to rebase")?;
-
-    let new_head_id = commits_to_rebase
-        .into_iter()
-        .fold(
-            project_repository
-                .git_repository
-                .find_commit(target_commit_oid)
-                .context("failed to find new commit"),
-            |head, to_rebase| {
-                let head = head?;
+    branches: &[branch::Branch],
+    branch: &branch::Branch,
+    target_sha: git::Oid,
+) -> Result<git::Oid> {
+    let Some(parent) = branch
+        .parent
+        .and_then(|parent_id| branches.iter().find(|b| b.id == parent_id))
+    else {
+        return Ok(target_sha);
+    };
+    let merge_base = project_repository
+        .git_repository
+        .merge_base(parent.head, branch.head)
+        .context("failed to find merge base")?;
+    Ok(if merge_base == parent.head {
+        parent.head
+    } else {
+        target_sha
+    })
+}
 
-                let mut cherrypick_index = project_repository
-                    .git_repository
-                    .cherry_pick(&head, &to_rebase)
-                    .context("failed to cherry pick")?;
+/// Sorts `items` such that every branch comes after the branch it is stacked on.
+pub(crate) fn sort_by_stack<T>(items: &mut [T], branch: impl Fn(&T) -> &branch::Branch) {
+    let branches = items
+        .iter()
+        .map(|item| branch(item).clone())
+        .collect::<Vec<_>>();
+    items.sort_by_key(|item| stack_depth(&branches, branch(item)));
+}
 
-                if cherrypick_index.has_conflicts() {
-                    bail!("failed to rebase");
-                }
+/// Returns the number of `branches` that `branch` is stacked on, directly or through other branches.
+pub(crate) fn stack_depth(branches: &[branch::Branch], branch: &branch::Branch) -> usize {
+    let mut depth = 0;
+    let mut parent = branch.parent;
+    while let Some(parent_id) = parent {
+        // a cycle would go on forever
+        if depth > branches.len() {
+            break;
+        }
+        depth += 1;
+        parent = branches
+            .iter()
+            .find(|b| b.id == parent_id)
+            .and_then(|b| b.parent);
+    }
+    depth
+}
 
-                let merge_tree_oid = cherrypick_index
-                    .write_tree_to(&project_repository.git_repository)
-                    .context("failed to write merge tree")?;
+/// Returns the tree to merge `branch` against when combining it with the other `applied` branches,
+/// which have to be sorted with [`sort_by_stack`]. For a stacked branch, this is the tree of its parent's head
+/// so that the changes it shares with its parent aren't merged twice.
+pub(crate) fn stack_base_tree<'repo>(
+    project_repository: &'repo project_repository::Repository,
+    applied: &[branch::Branch],
+    branch: &branch::Branch,
+    target_sha: git::Oid,
+) -> Result<git::Tree<'repo>> {
+    let base = stack_base(project_repository, applied, branch, target_sha)?;
+    let tree = project_repository
+        .git_repository
+        .find_commit(base)
+        .and_then(|commit| commit.tree())
+        .context(format!("failed to find tree of {}", base))?;
+    Ok(tree)
+}
 
-                let merge_tree = project_repository
-                    .git_repository
-                    .find_tree(merge_tree_oid)
-                    .context("failed to find merge tree")?;
+/// Moves the commits and uncommitted changes of the stacked `branch` from `old_parent_head` onto `new_parent_head`.
+///
+/// Returns `false` and leaves `branch` as it is if they conflict with the new parent head.
+pub(crate) fn rebase_onto_parent(
+    project_repository: &project_repository::Repository,
+    branch: &mut branch::Branch,
+    old_parent_head: git::Oid,
+    new_parent_head: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<bool> {
+    let repo = &project_repository.git_repository;
 
-                let commit_oid = project_repository
-                    .git_repository
-                    .commit(
-                        None,
-                        &to_rebase.author(),
-                        &to_rebase.committer(),
-                        &to_rebase.message().to_str_lossy(),
-                        &merge_tree,
-                        &[&head],
-                    )
-                    .context("failed to create commit")?;
+    let mut ids_to_rebase = project_repository.l(
+        branch.head,
+        project_repository::LogUntil::Commit(old_parent_head),
+    )?;
+    ids_to_rebase.reverse();
+    let new_head = match rebase::pick_commits(
+        project_repository,
+        new_parent_head,
+        &ids_to_rebase,
+        signing_key,
+    )? {
+        rebase::Picked::All(new_head) => new_head,
+        rebase::Picked::Conflict { .. } => return Ok(false),
+    };
 
-                project_repository
-                    .git_repository
-                    .find_commit(commit_oid)
-                    .context("failed to find commit")
-            },
-        )?
-        .id();
+    let old_head_tree = repo.find_commit(branch.head)?.tree()?;
+    let new_head_tree = repo.find_commit(new_head)?.tree()?;
+    let branch_tree = repo.find_tree(branch.tree)?;
+    let mut merge_index = repo
+        .merge_trees(&old_head_tree, &branch_tree, &new_head_tree)
+        .context(format!("failed to merge trees for branch {}", branch.id))?;
+    if merge_index.has_conflicts() {
+        return Ok(false);
+    }
 
-    Ok(Some(new_head_id))
+    branch.tree = merge_index.write_tree_to(repo)?;
+    branch.head = new_head;
+    Ok(true)
 }
 
-// runs a simple libgit2 based in-memory rebase on a commit range onto a target commit
-// possibly not used in favor of cherry_rebase
-pub fn simple_rebase(
+/// Rebases the branches stacked on `parent`, and the branches stacked on those, after the head of `parent` moved from `old_head`.
+/// Applied branches that conflict with their new parent head are unapplied, and keep their commits.
+pub(crate) fn rebase_stacked_branches(
     project_repository: &project_repository::Repository,
-    target_commit_oid: git::Oid,
-    start_commit_oid: git::Oid,
-    end_commit_oid: git::Oid,
-) -> Result<Option<git::Oid>, anyhow::Error> {
-    let repo = &project_repository.git_repository;
-    let (_, committer) = project_repository.git_signatures(None)?;
-
-    let mut rebase_options = git2::RebaseOptions::new();
-    rebase_options.quiet(true);
-    rebase_options.inmemory(true);
-    let mut rebase = repo
-        .rebase(
-            Some(end_commit_oid),
-            Some(start_commit_oid),
-            Some(target_commit_oid),
-            Some(&mut rebase_options),
-        )
-        .context("failed to rebase")?;
-
-    let mut rebase_success = true;
-    // check to see if these commits have already been pushed
-    let mut last_rebase_head = target_commit_oid;
-    while rebase.next().is_some() {
-        let index = rebase
-            .inmemory_index()
-            .context("failed to get inmemory index")?;
-        if index.has_conflicts() {
-            rebase_success = false;
-            break;
-        }
+    parent: &branch::Branch,
+    old_head: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<()> {
+    if parent.head == old_head {
+        return Ok(());
+    }
 
-        if let Ok(commit_id) = rebase.commit(None, &committer.clone().into(), None) {
-            last_rebase_head = commit_id.into();
-        } else {
-            rebase_success = false;
-            break;
+    let vb_state = project_repository.project().virtual_branches();
+    for mut child in vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.parent == Some(parent.id))
+    {
+        let old_child_head = child.head;
+        if !rebase_onto_parent(
+            project_repository,
+            &mut child,
+            old_head,
+            parent.head,
+            signing_key,
+        )? {
+            tracing::warn!(branch = %child.id, "stacked branch conflicts with its parent");
+            if child.applied {
+                unapply_branch(project_repository, &child.id)
+                    .context("failed to unapply stacked branch")?;
+            }
+            continue;
         }
+        vb_state
+            .set_branch(child.clone())
+            .context("failed to write branch")?;
+        rebase_stacked_branches(project_repository, &child, old_child_head, signing_key)?;
     }
+    Ok(())
+}
 
-    if rebase_success {
-        // rebase worked out, rewrite the branch head
-        rebase.finish(None).context("failed to finish rebase")?;
-        Ok(Some(last_rebase_head))
-    } else {
-        // rebase failed, do a merge commit
-        rebase.abort().context("failed to abort rebase")?;
-        Ok(None)
+// takes a vector of commit oids and rebases them onto a target commit and returns the
+// new head commit oid if it's successful
+// unlike `rebase::rebase_branch`, this fails if any of the commits conflict
+fn cherry_rebase_group(
+    project_repository: &project_repository::Repository,
+    target_commit_oid: git::Oid,
+    ids_to_rebase: &mut [git::Oid],
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Option<git::Oid>, anyhow::Error> {
+    ids_to_rebase.reverse();
+    match rebase::pick_commits(
+        project_repository,
+        target_commit_oid,
+        ids_to_rebase,
+        signing_key,
+    )? {
+        rebase::Picked::All(new_head_id) => Ok(Some(new_head_id)),
+        rebase::Picked::Conflict { .. } => bail!("failed to rebase"),
     }
 }
 
@@ -3574,6 +4047,7 @@ pub fn cherry_pick(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     target_commit_oid: git::Oid,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<Option<git::Oid>, errors::CherryPickError> {
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
         return Err(errors::CherryPickError::Conflict(errors::ProjectConflict {
@@ -3717,14 +4191,12 @@ pub fn cherry_pick(
             .context("failed to find branch head commit")?;
 
         let commit_oid = project_repository
-            .git_repository
-            .commit(
-                None,
-                &target_commit.author(),
-                &target_commit.committer(),
+            .recommit(
+                &target_commit,
                 &target_commit.message().to_str_lossy(),
                 &merge_tree,
                 &[&branch_head_commit],
+                signing_key,
             )
             .context("failed to create commit")?;
 
@@ -3738,10 +4210,12 @@ pub fn cherry_pick(
             .context("failed to checkout final tree")?;
 
         // update branch status
+        let old_head = branch.head;
         branch.head = commit_oid;
         vb_state
             .set_branch(branch.clone())
             .context("failed to write branch")?;
+        rebase_stacked_branches(project_repository, &branch, old_head, signing_key)?;
 
         Some(commit_oid)
     };
@@ -3757,7 +4231,8 @@ pub fn squash(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
-) -> Result<(), errors::SquashError> {
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<rebase::RebaseOutcome, errors::SquashError> {
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
         return Err(errors::SquashError::Conflict(errors::ProjectConflict {
             project_id: project_repository.project().id,
@@ -3841,11 +4316,8 @@ pub fn squash(
         .context("failed to find head commit parents")?;
 
     let new_commit_oid = project_repository
-        .git_repository
-        .commit(
-            None,
-            &commit_to_squash.author(),
-            &commit_to_squash.committer(),
+        .recommit(
+            &commit_to_squash,
             &format!(
                 "{}\n{}",
                 parent_commit.message(),
@@ -3853,6 +4325,7 @@ pub fn squash(
             ),
             &commit_to_squash.tree().context("failed to find tree")?,
             &parents.iter().collect::<Vec<_>>(),
+            signing_key,
         )
         .context("failed to commit")?;
 
@@ -3863,22 +4336,16 @@ pub fn squash(
         ids.first().copied()
     }
     .ok_or(errors::SquashError::CommitNotFound(commit_oid))?;
-    let mut ids_to_rebase = ids_to_rebase.to_vec();
-
-    match cherry_rebase_group(project_repository, new_commit_oid, &mut ids_to_rebase) {
-        Ok(Some(new_head_id)) => {
-            // save new branch head
-            branch.head = new_head_id;
-            vb_state
-                .set_branch(branch.clone())
-                .context("failed to write branch")?;
 
-            super::integration::update_gitbutler_integration(&vb_state, project_repository)
-                .context("failed to update gitbutler integration")?;
-            Ok(())
-        }
-        _ => Err(errors::SquashError::Other(anyhow!("rebase error"))),
-    }
+    rebase::rebase_branch(
+        project_repository,
+        &mut branch,
+        new_commit_oid,
+        ids_to_rebase,
+        signing_key,
+    )
+    .context("rebase error")
+    .map_err(Into::into)
 }
 
 // changes a commit message for commit_oid, rebases everything above it, updates branch head if successful
@@ -3887,7 +4354,8 @@ pub fn update_commit_message(
     branch_id: &BranchId,
     commit_oid: git::Oid,
     message: &str,
-) -> Result<(), errors::UpdateCommitMessageError> {
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<rebase::RebaseOutcome, errors::UpdateCommitMessageError> {
     if message.is_empty() {
         return Err(errors::UpdateCommitMessageError::EmptyMessage);
     }
@@ -3964,14 +4432,12 @@ pub fn update_commit_message(
         .context("failed to find head commit parents")?;
 
     let new_commit_oid = project_repository
-        .git_repository
-        .commit(
-            None,
-            &target_commit.author(),
-            &target_commit.committer(),
+        .recommit(
+            &target_commit,
             message,
             &target_commit.tree().context("failed to find tree")?,
             &parents.iter().collect::<Vec<_>>(),
+            signing_key,
         )
         .context("failed to commit")?;
 
@@ -3982,24 +4448,16 @@ pub fn update_commit_message(
         ids.first().copied()
     }
     .ok_or(errors::UpdateCommitMessageError::CommitNotFound(commit_oid))?;
-    let mut ids_to_rebase = ids_to_rebase.to_vec();
 
-    match cherry_rebase_group(project_repository, new_commit_oid, &mut ids_to_rebase) {
-        Ok(Some(new_head_id)) => {
-            // save new branch head
-            branch.head = new_head_id;
-            vb_state
-                .set_branch(branch.clone())
-                .context("failed to write branch")?;
-
-            super::integration::update_gitbutler_integration(&vb_state, project_repository)
-                .context("failed to update gitbutler integration")?;
-            Ok(())
-        }
-        _ => Err(errors::UpdateCommitMessageError::Other(anyhow!(
-            "rebase error"
-        ))),
-    }
+    rebase::rebase_branch(
+        project_repository,
+        &mut branch,
+        new_commit_oid,
+        ids_to_rebase,
+        signing_key,
+    )
+    .context("rebase error")
+    .map_err(Into::into)
 }
 
 /// moves commit from the branch it's in to the top of the target branch
@@ -4010,6 +4468,18 @@ pub fn move_commit(
     user: Option<&users::User>,
     signing_key: Option<&keys::PrivateKey>,
 ) -> Result<(), errors::MoveCommitError> {
+    // the commit is moved off an applied branch onto another, so none of them may be rebasing
+    for branch in project_repository
+        .project()
+        .virtual_branches()
+        .list_branches()
+        .context("failed to read virtual branches")?
+    {
+        if branch.applied && rebase::is_rebasing(project_repository, &branch.id)? {
+            return Err(errors::MoveCommitError::RebaseInProgress(branch.id));
+        }
+    }
+
     if project_repository.is_resolving() {
         return Err(errors::MoveCommitError::Conflicted(
             errors::ProjectConflict {
@@ -4081,6 +4551,10 @@ pub fn move_commit(
         &project_repository.git_repository,
         &source_branch_head_parent_tree,
         &source_branch_head_tree,
+        &project_repository
+            .project()
+            .diff_settings
+            .keeping_whitespace(),
     )?;
 
     let branch_head_diff: HashMap<_, _> = diff::diff_files_into_hunks(branch_head_diff).collect();
@@ -4121,8 +4595,10 @@ pub fn move_commit(
 
     // reset the source branch to the parent commit
     {
+        let old_head = source_branch.head;
         source_branch.head = source_branch_head_parent.id();
         vb_state.set_branch(source_branch.clone())?;
+        rebase_stacked_branches(project_repository, source_branch, old_head, signing_key)?;
     }
 
     // move the commit to destination branch target branch
@@ -4168,8 +4644,15 @@ pub fn move_commit(
             )
             .context("failed to commit")?;
 
+        let old_head = destination_branch.head;
         destination_branch.head = new_destination_head_oid;
         vb_state.set_branch(destination_branch.clone())?;
+        rebase_stacked_branches(
+            project_repository,
+            &destination_branch,
+            old_head,
+            signing_key,
+        )?;
     }
 
     super::integration::update_gitbutler_integration(&vb_state, project_repository)
@@ -4273,6 +4756,10 @@ pub fn create_virtual_branch_from_branch(
         &project_repository.git_repository,
         &merge_base_tree,
         &head_commit_tree,
+        &project_repository
+            .project()
+            .diff_settings
+            .keeping_whitespace(),
     )
     .context("failed to diff trees")?;
 
@@ -4312,6 +4799,8 @@ pub fn create_virtual_branch_from_branch(
         ownership,
         order,
         selected_for_changes,
+        parent: None,
+        pull_request: None,
     };
 
     vb_state
@@ -4399,7 +4888,7 @@ pub fn apply<S: AsRef<[u8]>>(base_image: S, patch: &Patch<'_, [u8]>) -> Result<B
 // Goes through a set of changes and checks if conflicts are present. If no conflicts
 // are present in a file it will be resolved, meaning it will be removed from the
 // conflicts file.
-fn update_conflict_markers(
+pub(crate) fn update_conflict_markers(
     project_repository: &project_repository::Repository,
     files: &HashMap<PathBuf, Vec<GitHunk>>,
 ) -> Result<()> {
@@ -7,6 +7,7 @@ mod suite {
 mod database;
 mod deltas;
 mod error;
+mod forge;
 mod gb_repository;
 mod git;
 mod keys;
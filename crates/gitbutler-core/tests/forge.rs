@@ -0,0 +1,503 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use gitbutler_core::{
+    forge::{
+        self, ChecksStatus, Forge, GitHub, Head, NewPullRequest, PullRequestState,
+        PullRequestUpdate, Repo, ReviewState,
+    },
+    git::ForgeKind,
+};
+
+/// A request as the mock server received it.
+#[derive(Debug, Clone)]
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A route of the mock server: requests with `method` whose path starts with `prefix` get
+/// `status` and `body` as response.
+type Route = (&'static str, &'static str, u16, String);
+
+/// An HTTP server on a local port that answers each request with the first matching route.
+struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        thread::spawn({
+            let requests = Arc::clone(&requests);
+            move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let request = read_request(&stream);
+                    let (status, body) = routes
+                        .iter()
+                        .find(|(method, prefix, ..)| {
+                            request.method == *method && request.path.starts_with(prefix)
+                        })
+                        .map_or((404, r#"{"message":"Not Found"}"#.to_owned()), |route| {
+                            (route.2, route.3.clone())
+                        });
+                    requests.lock().unwrap().push(request);
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+                }
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn github(&self) -> GitHub {
+        GitHub::new(
+            Repo {
+                owner: "owner".into(),
+                name: "repo".into(),
+            },
+            "token",
+        )
+        .with_api_url(&self.url)
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Request {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut request_line = request_line.split(' ');
+    let method = request_line.next().unwrap().to_owned();
+    let path = request_line.next().unwrap().to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let Some((key, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.push((key.to_owned(), value.trim().to_owned()));
+    }
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .map_or(0, |(_, len)| len.parse().unwrap());
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8(body).unwrap(),
+    }
+}
+
+fn pull_request_json(number: u64, state: &str, merged: bool) -> String {
+    format!(
+        r#"{{"number":{number},"title":"title","html_url":"https://github.com/owner/repo/pull/{number}","state":"{state}","draft":false,"merged_at":{},"head":{{"sha":"abc"}}}}"#,
+        if merged {
+            r#""2024-01-01T00:00:00Z""#
+        } else {
+            "null"
+        }
+    )
+}
+
+/// The routes of a pull request without reviews and checks, found by its head.
+fn found_pull_request(number: u64, state: &str, merged: bool) -> Vec<Route> {
+    vec![
+        (
+            "GET",
+            "/repos/owner/repo/pulls?",
+            200,
+            format!("[{}]", pull_request_json(number, state, merged)),
+        ),
+        ("GET", "/repos/owner/repo/pulls/", 200, "[]".into()),
+        (
+            "GET",
+            "/repos/owner/repo/commits/abc/check-runs",
+            200,
+            r#"{"total_count":0,"check_runs":[]}"#.into(),
+        ),
+    ]
+}
+
+fn head() -> Head {
+    Head {
+        owner: "fork".into(),
+        branch: "feature".into(),
+    }
+}
+
+#[tokio::test]
+async fn create_pull_request() {
+    let server = MockServer::start(vec![
+        (
+            "POST",
+            "/repos/owner/repo/pulls",
+            201,
+            pull_request_json(7, "open", false),
+        ),
+        ("GET", "/repos/owner/repo/pulls/7/reviews", 200, "[]".into()),
+        (
+            "GET",
+            "/repos/owner/repo/commits/abc/check-runs",
+            200,
+            r#"{"total_count":0,"check_runs":[]}"#.into(),
+        ),
+    ]);
+
+    let pull_request = server
+        .github()
+        .create_pull_request(
+            &head(),
+            "main",
+            &NewPullRequest {
+                title: "title".into(),
+                body: Some("body".into()),
+                draft: true,
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(pull_request.number, 7);
+    assert_eq!(pull_request.url, "https://github.com/owner/repo/pull/7");
+    assert_eq!(pull_request.state, PullRequestState::Open);
+    assert_eq!(pull_request.review, ReviewState::Pending);
+    assert_eq!(pull_request.checks, ChecksStatus::None);
+
+    let requests = server.requests();
+    let paths = requests
+        .iter()
+        .map(|request| (request.method.as_str(), request.path.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            ("POST", "/repos/owner/repo/pulls"),
+            ("GET", "/repos/owner/repo/pulls/7/reviews?per_page=100"),
+            (
+                "GET",
+                "/repos/owner/repo/commits/abc/check-runs?per_page=100"
+            ),
+        ]
+    );
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["head"], "fork:feature");
+    assert_eq!(body["base"], "main");
+    assert_eq!(body["title"], "title");
+    assert_eq!(body["body"], "body");
+    assert_eq!(body["draft"], true);
+}
+
+#[tokio::test]
+async fn requests_are_authenticated_and_versioned() {
+    let server = MockServer::start(found_pull_request(3, "open", false));
+
+    server.github().pull_request(&head()).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    for request in requests {
+        assert_eq!(request.header("authorization"), Some("Bearer token"));
+        assert_eq!(
+            request.header("accept"),
+            Some("application/vnd.github+json")
+        );
+        assert_eq!(request.header("x-github-api-version"), Some("2022-11-28"));
+    }
+}
+
+#[tokio::test]
+async fn update_pull_request() {
+    let server = MockServer::start(vec![
+        (
+            "PATCH",
+            "/repos/owner/repo/pulls/7",
+            200,
+            pull_request_json(7, "open", false),
+        ),
+        ("GET", "/repos/owner/repo/pulls/7/reviews", 200, "[]".into()),
+        (
+            "GET",
+            "/repos/owner/repo/commits/abc/check-runs",
+            200,
+            r#"{"total_count":0,"check_runs":[]}"#.into(),
+        ),
+    ]);
+
+    server
+        .github()
+        .update_pull_request(
+            7,
+            &PullRequestUpdate {
+                title: Some("new title".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "PATCH");
+    assert_eq!(requests[0].path, "/repos/owner/repo/pulls/7");
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body, serde_json::json!({ "title": "new title" }));
+}
+
+#[tokio::test]
+async fn pull_request_with_reviews_and_checks() {
+    let server = MockServer::start(vec![
+        (
+            "GET",
+            "/repos/owner/repo/pulls/3/reviews",
+            200,
+            r#"[
+            {"state":"CHANGES_REQUESTED","user":{"login":"a"}},
+            {"state":"COMMENTED","user":{"login":"b"}},
+            {"state":"APPROVED","user":{"login":"a"}}
+        ]"#
+            .into(),
+        ),
+        (
+            "GET",
+            "/repos/owner/repo/pulls?",
+            200,
+            format!("[{}]", pull_request_json(3, "open", false)),
+        ),
+        (
+            "GET",
+            "/repos/owner/repo/commits/abc/check-runs",
+            200,
+            r#"{"total_count":2,"check_runs":[
+                {"status":"completed","conclusion":"success"},
+                {"status":"in_progress","conclusion":null}
+            ]}"#
+            .into(),
+        ),
+    ]);
+
+    let pull_request = server
+        .github()
+        .pull_request(&head())
+        .await
+        .unwrap()
+        .expect("there is a pull request");
+
+    assert_eq!(pull_request.number, 3);
+    assert_eq!(pull_request.review, ReviewState::Approved);
+    assert_eq!(pull_request.checks, ChecksStatus::Pending);
+
+    let request = &server.requests()[0];
+    assert!(request.path.starts_with("/repos/owner/repo/pulls?"));
+    assert!(request.path.contains("head=fork%3Afeature"));
+    assert!(request.path.contains("state=all"));
+}
+
+#[tokio::test]
+async fn failed_checks_win() {
+    let server = MockServer::start(vec![
+        (
+            "GET",
+            "/repos/owner/repo/pulls/3/reviews",
+            200,
+            r#"[
+            {"state":"APPROVED","user":{"login":"a"}},
+            {"state":"CHANGES_REQUESTED","user":{"login":"b"}}
+        ]"#
+            .into(),
+        ),
+        (
+            "GET",
+            "/repos/owner/repo/pulls?",
+            200,
+            format!("[{}]", pull_request_json(3, "open", false)),
+        ),
+        (
+            "GET",
+            "/repos/owner/repo/commits/abc/check-runs",
+            200,
+            r#"{"total_count":2,"check_runs":[
+                {"status":"completed","conclusion":"failure"},
+                {"status":"in_progress","conclusion":null}
+            ]}"#
+            .into(),
+        ),
+    ]);
+
+    let pull_request = server
+        .github()
+        .pull_request(&head())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(pull_request.review, ReviewState::ChangesRequested);
+    assert_eq!(pull_request.checks, ChecksStatus::Failure);
+}
+
+#[tokio::test]
+async fn merged_pull_request() {
+    let server = MockServer::start(found_pull_request(3, "closed", true));
+
+    let pull_request = server
+        .github()
+        .pull_request(&head())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(pull_request.state, PullRequestState::Merged);
+}
+
+#[tokio::test]
+async fn closed_pull_request() {
+    let server = MockServer::start(found_pull_request(3, "closed", false));
+
+    let pull_request = server
+        .github()
+        .pull_request(&head())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(pull_request.state, PullRequestState::Closed);
+}
+
+#[tokio::test]
+async fn no_pull_request() {
+    let server = MockServer::start(vec![("GET", "/repos/owner/repo/pulls?", 200, "[]".into())]);
+
+    assert!(server
+        .github()
+        .pull_request(&head())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn api_errors_carry_the_message() {
+    let server = MockServer::start(vec![(
+        "POST",
+        "/repos/owner/repo/pulls",
+        422,
+        r#"{"message":"Validation Failed"}"#.into(),
+    )]);
+
+    let err = server
+        .github()
+        .create_pull_request(&head(), "main", &NewPullRequest::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        forge::Error::Api { status: 422, ref message } if message == "Validation Failed"
+    ));
+}
+
+#[tokio::test]
+async fn api_errors_without_a_message() {
+    let server = MockServer::start(vec![("GET", "/repos/owner/repo/pulls?", 502, "".into())]);
+
+    let err = server.github().pull_request(&head()).await.unwrap_err();
+
+    assert!(matches!(err, forge::Error::Api { status: 502, .. }));
+}
+
+#[tokio::test]
+async fn other_forges_are_unsupported() {
+    let url = "https://gitlab.com/owner/repo.git".parse().unwrap();
+    let forge = forge::for_remote(&url, Some(ForgeKind::GitLab), None)
+        .unwrap()
+        .unwrap();
+
+    let error = forge
+        .create_pull_request(&head(), "main", &NewPullRequest::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        forge::Error::Unsupported(ForgeKind::GitLab)
+    ));
+    assert!(forge.pull_request(&head()).await.unwrap().is_none());
+}
+
+#[test]
+fn github_needs_a_token() {
+    let url = "https://github.com/owner/repo.git".parse().unwrap();
+    assert!(matches!(
+        forge::for_remote(&url, Some(ForgeKind::GitHub), None),
+        Err(forge::Error::NotAuthenticated(ForgeKind::GitHub))
+    ));
+}
+
+#[test]
+fn remotes_of_unknown_forges_have_none() {
+    let url = "https://example.com/owner/repo.git".parse().unwrap();
+    assert!(matches!(forge::for_remote(&url, None, None), Ok(None)));
+}
+
+mod repo {
+    use gitbutler_core::forge::Repo;
+
+    fn repo(url: &str) -> Option<(String, String)> {
+        Repo::from_url(&url.parse().unwrap()).map(|repo| (repo.owner, repo.name))
+    }
+
+    #[test]
+    fn from_https_url() {
+        assert_eq!(
+            repo("https://github.com/owner/repo.git"),
+            Some(("owner".into(), "repo".into()))
+        );
+        assert_eq!(
+            repo("https://github.com/owner/repo"),
+            Some(("owner".into(), "repo".into()))
+        );
+    }
+
+    #[test]
+    fn from_scp_like_url() {
+        assert_eq!(
+            repo("git@github.com:owner/repo.git"),
+            Some(("owner".into(), "repo".into()))
+        );
+    }
+
+    #[test]
+    fn needs_an_owner() {
+        assert_eq!(repo("https://github.com/repo.git"), None);
+    }
+}
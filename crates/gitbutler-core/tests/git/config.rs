@@ -32,3 +32,68 @@ pub fn get_bool_none() {
     let config = repo.config().unwrap();
     assert_eq!(config.get_bool("test.key").unwrap(), None);
 }
+
+mod forge_kind {
+    use gitbutler_core::{git, project_repository};
+    use gitbutler_testsupport::test_repository;
+
+    fn url(url: &str) -> git::Url {
+        url.parse().unwrap()
+    }
+
+    #[test]
+    fn detected_from_host() {
+        let (repo, _tmp) = test_repository();
+        let config = project_repository::Config::from(&repo);
+        assert_eq!(
+            config
+                .forge_kind(&url("git@gitlab.com:owner/repo.git"))
+                .unwrap(),
+            Some(git::ForgeKind::GitLab)
+        );
+        assert_eq!(
+            config
+                .forge_kind(&url("https://git.example.com/owner/repo.git"))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn configured_for_self_hosted_host() {
+        let (repo, _tmp) = test_repository();
+        repo.config()
+            .unwrap()
+            .set_str("gitbutler.git.example.com.forge", "forgejo")
+            .unwrap();
+        let config = project_repository::Config::from(&repo);
+        assert_eq!(
+            config
+                .forge_kind(&url("https://git.example.com/owner/repo.git"))
+                .unwrap(),
+            Some(git::ForgeKind::Gitea)
+        );
+        assert_eq!(
+            config
+                .forge_kind(&url("https://other.example.com/owner/repo.git"))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_configuration_falls_back_to_detection() {
+        let (repo, _tmp) = test_repository();
+        repo.config()
+            .unwrap()
+            .set_str("gitbutler.gitlab.example.com.forge", "sourcehut")
+            .unwrap();
+        let config = project_repository::Config::from(&repo);
+        assert_eq!(
+            config
+                .forge_kind(&url("https://gitlab.example.com/owner/repo.git"))
+                .unwrap(),
+            Some(git::ForgeKind::GitLab)
+        );
+    }
+}
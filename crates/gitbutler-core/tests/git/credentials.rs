@@ -11,6 +11,7 @@ use gitbutler_testsupport::{temp_dir, test_repository};
 struct TestCase<'a> {
     remote_url: &'a str,
     github_access_token: Option<&'a str>,
+    access_tokens: &'a [(&'a str, &'a str)],
     preferred_key: projects::AuthKey,
     home_dir: Option<PathBuf>,
 }
@@ -22,6 +23,11 @@ impl TestCase<'_> {
         let users = users::Controller::from_path(local_app_data.path());
         let user = users::User {
             github_access_token: self.github_access_token.map(ToString::to_string),
+            access_tokens: self
+                .access_tokens
+                .iter()
+                .map(|(host, token)| (host.to_string(), token.to_string()))
+                .collect(),
             ..Default::default()
         };
         users.set_user(&user).unwrap();
@@ -111,6 +117,88 @@ mod not_github {
         }
     }
 
+    mod with_access_token {
+        use super::*;
+
+        #[test]
+        fn https() {
+            let test_case = TestCase {
+                remote_url: "https://gitlab.com/test-gitbutler/test.git",
+                access_tokens: &[("gitlab.com", "token")],
+                ..Default::default()
+            };
+            let flow = test_case.run();
+            assert_eq!(flow.len(), 1);
+            assert_eq!(
+                flow[0].0,
+                "https://gitlab.com/test-gitbutler/test.git".to_string(),
+            );
+            assert_eq!(
+                flow[0].1,
+                vec![Credential::Https(HttpsCredential::AccessToken(
+                    "token".to_string()
+                ))]
+            );
+        }
+
+        #[test]
+        fn ssh() {
+            let test_case = TestCase {
+                remote_url: "git@gitlab.com:test-gitbutler/test.git",
+                access_tokens: &[("gitlab.com", "token")],
+                ..Default::default()
+            };
+            let flow = test_case.run();
+            assert_eq!(flow.len(), 1);
+            assert_eq!(
+                flow[0].0,
+                "https://gitlab.com/test-gitbutler/test.git".to_string(),
+            );
+            assert_eq!(
+                flow[0].1,
+                vec![Credential::Https(HttpsCredential::AccessToken(
+                    "token".to_string()
+                ))]
+            );
+        }
+
+        #[test]
+        fn other_host() {
+            let test_case = TestCase {
+                remote_url: "git@gitlab.com:test-gitbutler/test.git",
+                access_tokens: &[("gitea.com", "token")],
+                ..Default::default()
+            };
+            let flow = test_case.run();
+            assert_eq!(flow.len(), 1);
+            assert_eq!(flow[0].1.len(), 1);
+            assert!(matches!(
+                flow[0].1[0],
+                Credential::Ssh(SshCredential::GitButlerKey(_))
+            ));
+        }
+    }
+
+    mod with_ssh_agent {
+        use super::*;
+
+        #[test]
+        fn https() {
+            let test_case = TestCase {
+                remote_url: "https://gitlab.com/test-gitbutler/test.git",
+                preferred_key: projects::AuthKey::SshAgent,
+                ..Default::default()
+            };
+            let flow = test_case.run();
+            assert_eq!(flow.len(), 1);
+            assert_eq!(
+                flow[0].0,
+                "git@gitlab.com:test-gitbutler/test.git".to_string(),
+            );
+            assert_eq!(flow[0].1, vec![Credential::Ssh(SshCredential::Agent)]);
+        }
+    }
+
     mod with_github_token {
         use super::*;
 
@@ -454,3 +454,183 @@ fn gitbutler_file() {
         .unwrap();
     assert_eq!(pid, project_repository.project().id);
 }
+
+mod renames {
+    use std::{fs, path::Path};
+
+    use bstr::ByteSlice;
+    use gitbutler_core::git::diff::{self, ChangeType};
+    use gitbutler_testsupport::{commit_all, test_repository};
+
+    const CONTENT: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+
+    #[test]
+    fn workdir_rename_with_modification() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let base = commit_all(&repository);
+
+        fs::remove_file(tmp.path().join("a.txt")).unwrap();
+        fs::write(tmp.path().join("b.txt"), CONTENT.replace("five", "FIVE")).unwrap();
+
+        let diff = diff::workdir(&repository, &base, &Default::default()).unwrap();
+        assert_eq!(
+            diff.len(),
+            1,
+            "the move is one change, not a delete and an add"
+        );
+        let file = &diff[Path::new("b.txt")];
+        assert_eq!(file.old_path.as_deref(), Some(Path::new("a.txt")));
+        let similarity = file.similarity.unwrap();
+        assert_eq!(similarity.change_type, ChangeType::Renamed);
+        assert!((50..100).contains(&similarity.score));
+
+        assert_eq!(file.hunks.len(), 1);
+        let hunk = &file.hunks[0];
+        assert_eq!(hunk.change_type, ChangeType::Renamed);
+        assert_eq!(hunk.old_path.as_deref(), Some(Path::new("a.txt")));
+        assert!(hunk.diff_lines.contains_str("-five"));
+        assert!(hunk.diff_lines.contains_str("+FIVE"));
+    }
+
+    #[test]
+    fn trees_rename_with_modification() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let old = commit_all(&repository);
+
+        fs::remove_file(tmp.path().join("a.txt")).unwrap();
+        fs::write(tmp.path().join("b.txt"), CONTENT.replace("ten", "TEN")).unwrap();
+        let new = commit_all(&repository);
+
+        let diff = diff::trees(
+            &repository,
+            &repository.find_commit(old).unwrap().tree().unwrap(),
+            &repository.find_commit(new).unwrap().tree().unwrap(),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(diff.len(), 1);
+        let file = &diff[Path::new("b.txt")];
+        assert_eq!(file.old_path.as_deref(), Some(Path::new("a.txt")));
+        assert_eq!(
+            file.similarity.map(|similarity| similarity.change_type),
+            Some(ChangeType::Renamed)
+        );
+        assert!(file.hunks[0].diff_lines.contains_str("+TEN"));
+    }
+
+    #[test]
+    fn pure_rename_has_a_hunk() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let base = commit_all(&repository);
+
+        fs::rename(tmp.path().join("a.txt"), tmp.path().join("b.txt")).unwrap();
+
+        let diff = diff::workdir(&repository, &base, &Default::default()).unwrap();
+        let file = &diff[Path::new("b.txt")];
+        assert_eq!(file.similarity.unwrap().score, 100);
+        assert_eq!(file.hunks.len(), 1);
+        assert_eq!(file.hunks[0].change_type, ChangeType::Renamed);
+        assert_eq!(file.hunks[0].old_path.as_deref(), Some(Path::new("a.txt")));
+    }
+}
+
+mod settings {
+    use std::{fs, path::Path};
+
+    use gitbutler_core::{
+        git::diff,
+        projects::{DiffSettings, Whitespace},
+        virtual_branches::branch::Hunk,
+    };
+    use gitbutler_testsupport::{commit_all, test_repository};
+
+    const CONTENT: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+
+    #[test]
+    fn context_lines_decide_whether_hunks_merge() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let base = commit_all(&repository);
+
+        let content = CONTENT.replace("two", "TWO").replace("nine", "NINE");
+        fs::write(tmp.path().join("a.txt"), content).unwrap();
+
+        let diff = diff::workdir(&repository, &base, &DiffSettings::default()).unwrap();
+        assert_eq!(diff[Path::new("a.txt")].hunks.len(), 1);
+
+        let settings = DiffSettings {
+            context_lines: 1,
+            ..Default::default()
+        };
+        let diff = diff::workdir(&repository, &base, &settings).unwrap();
+        assert_eq!(diff[Path::new("a.txt")].hunks.len(), 2);
+    }
+
+    #[test]
+    fn hunks_keep_their_hash_with_more_or_less_context() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let base = commit_all(&repository);
+
+        fs::write(tmp.path().join("a.txt"), CONTENT.replace("five", "FIVE")).unwrap();
+
+        let hunk_with_context = |context_lines| {
+            let settings = DiffSettings {
+                context_lines,
+                ..Default::default()
+            };
+            let diff = diff::workdir(&repository, &base, &settings).unwrap();
+            Hunk::from(&diff[Path::new("a.txt")].hunks[0])
+        };
+        let (wide, narrow) = (hunk_with_context(3), hunk_with_context(1));
+        assert_ne!((wide.start, wide.end), (narrow.start, narrow.end));
+        assert_eq!(wide.hash, narrow.hash);
+        assert!(wide.same_hunk(&narrow));
+    }
+
+    #[test]
+    fn diffs_without_context_are_rejected() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let base = commit_all(&repository);
+
+        let settings = DiffSettings {
+            context_lines: 0,
+            ..Default::default()
+        };
+        diff::workdir(&repository, &base, &settings).unwrap_err();
+    }
+
+    #[test]
+    fn whitespace_is_ignored_in_tree_diffs_only() {
+        let (repository, tmp) = test_repository();
+        fs::write(tmp.path().join("a.txt"), CONTENT).unwrap();
+        let old = commit_all(&repository);
+
+        fs::write(tmp.path().join("a.txt"), CONTENT.replace("five", "five  ")).unwrap();
+        let settings = DiffSettings {
+            whitespace: Whitespace::IgnoreAtEol,
+            ..Default::default()
+        };
+
+        diff::workdir(&repository, &old, &settings)
+            .expect_err("the changes that get committed can't leave out whitespace");
+        let diff = diff::workdir(&repository, &old, &settings.keeping_whitespace()).unwrap();
+        assert_eq!(diff[Path::new("a.txt")].hunks.len(), 1);
+
+        let new = commit_all(&repository);
+        let diff = diff::trees(
+            &repository,
+            &repository.find_commit(old).unwrap().tree().unwrap(),
+            &repository.find_commit(new).unwrap().tree().unwrap(),
+            &settings,
+        )
+        .unwrap();
+        assert!(diff
+            .get(Path::new("a.txt"))
+            .map_or(true, |file| file.hunks.is_empty()));
+    }
+}
@@ -130,3 +130,39 @@ mod delete {
         assert!(!project.path.join(".gitbutler.json").exists());
     }
 }
+
+mod update {
+    use gitbutler_core::projects::{
+        DiffSettings, UpdateError, UpdateRequest, UpdateValidationError,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn diffs_without_context_are_rejected() {
+        let (controller, _tmp) = new();
+        let repository = gitbutler_testsupport::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        let result = controller
+            .update(&UpdateRequest {
+                id: project.id,
+                diff_settings: Some(DiffSettings {
+                    context_lines: 0,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(UpdateError::Validation(
+                UpdateValidationError::NoDiffContext
+            ))
+        ));
+        assert_eq!(
+            controller.get(&project.id).unwrap().diff_settings,
+            DiffSettings::default()
+        );
+    }
+}
@@ -1,7 +1,4 @@
-use gitbutler_core::{
-    id::Id,
-    virtual_branches::{Branch, VirtualBranch},
-};
+use bstr::ByteSlice;
 
 use super::*;
 
@@ -279,6 +276,287 @@ async fn should_double_lock() {
     assert_eq!(locks[1].commit_id, commit_2);
 }
 
+#[tokio::test]
+async fn should_commit_selected_lines_of_a_hunk() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let mut lines = gen_file(repository, "file.txt", 10);
+    commit_and_push_initial(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    // two edits close enough to end up in the same hunk
+    lines[2] = "change 1".to_string();
+    lines[4] = "change 2".to_string();
+    write_file(repository, "file.txt", &lines);
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    let hunk = &branch.files[0].hunks[0];
+
+    // only the first edit, which replaces the third line
+    let ownership: branch::BranchOwnershipClaims =
+        format!("file.txt:{}-{}[-3+3]", hunk.start, hunk.end)
+            .parse()
+            .unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit", Some(&ownership), false)
+        .await
+        .unwrap();
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert_eq!(branch.commits.len(), 1);
+    let committed = &branch.commits[0].files[0].hunks[0].diff;
+    assert!(committed.contains_str("+change 1"));
+    assert!(!committed.contains_str("change 2"));
+
+    assert_eq!(branch.files.len(), 1);
+    let uncommitted = &branch.files[0].hunks[0].diff;
+    assert!(uncommitted.contains_str("+change 2"));
+    assert!(!uncommitted.contains_str("+change 1"));
+}
+
+#[tokio::test]
+async fn should_split_a_hunk_between_branches_claiming_its_lines() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let mut lines = gen_file(repository, "file.txt", 10);
+    commit_and_push_initial(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_1_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    lines[2] = "change 1".to_string();
+    lines[4] = "change 2".to_string();
+    write_file(repository, "file.txt", &lines);
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    assert_eq!(branch_1.files[0].hunks.len(), 1);
+    let hunk = &branch_1.files[0].hunks[0];
+    let (start, end) = (hunk.start, hunk.end);
+
+    let branch_2_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    // the second edit, which replaces the fifth line, moves to the other branch
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: branch_2_id,
+                ownership: Some(format!("file.txt:{start}-{end}[-5+5]").parse().unwrap()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    assert_eq!(branch_1.files.len(), 1);
+    let diff = &branch_1.files[0].hunks[0].diff;
+    assert!(diff.contains_str("+change 1"));
+    assert!(!diff.contains_str("change 2"));
+
+    let branch_2 = get_virtual_branch(controller, project_id, branch_2_id).await;
+    assert_eq!(branch_2.files.len(), 1);
+    let diff = &branch_2.files[0].hunks[0].diff;
+    assert!(diff.contains_str("+change 2"));
+    assert!(!diff.contains_str("change 1"));
+
+    // each branch commits only its own lines
+    controller
+        .create_commit(project_id, &branch_1_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    let committed = &branch_1.commits[0].files[0].hunks[0].diff;
+    assert!(committed.contains_str("+change 1"));
+    assert!(!committed.contains_str("change 2"));
+
+    // the commit is in the context of what's left, which locks it to the committing branch
+    assert_eq!(branch_1.files.len(), 1);
+    let hunk = &branch_1.files[0].hunks[0];
+    assert!(hunk.diff.contains_str("+change 2"));
+    assert_eq!(
+        hunk.locked_to.as_ref().unwrap()[0].commit_id,
+        branch_1.commits[0].id
+    );
+}
+
+#[tokio::test]
+async fn should_keep_line_claims_when_diff_context_changes() {
+    let Test {
+        project_id,
+        controller,
+        projects,
+        repository,
+        ..
+    } = &Test::default();
+
+    let mut lines = gen_file(repository, "file.txt", 10);
+    commit_and_push_initial(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_1_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    lines[2] = "change 1".to_string();
+    lines[4] = "change 2".to_string();
+    write_file(repository, "file.txt", &lines);
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    let hunk = &branch_1.files[0].hunks[0];
+    let (start, end, hash) = (hunk.start, hunk.end, hunk.hash);
+
+    let branch_2_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: branch_2_id,
+                ownership: Some(
+                    format!("file.txt:{start}-{end}-{hash:x}[-5+5]")
+                        .parse()
+                        .unwrap(),
+                ),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    // less context makes for a smaller hunk with the same changes
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            diff_settings: Some(projects::DiffSettings {
+                context_lines: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    assert_eq!(branch_1.files.len(), 1);
+    let hunk = &branch_1.files[0].hunks[0];
+    assert_ne!((hunk.start, hunk.end), (start, end));
+    assert!(hunk.diff.contains_str("+change 1"));
+    assert!(!hunk.diff.contains_str("change 2"));
+
+    let branch_2 = get_virtual_branch(controller, project_id, branch_2_id).await;
+    assert_eq!(branch_2.files.len(), 1);
+    let diff = &branch_2.files[0].hunks[0].diff;
+    assert!(diff.contains_str("+change 2"));
+    assert!(!diff.contains_str("change 1"));
+}
+
+#[tokio::test]
+async fn should_unapply_only_the_claimed_lines_of_a_hunk() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let mut lines = gen_file(repository, "file.txt", 10);
+    commit_and_push_initial(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_1_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    lines[2] = "change 1".to_string();
+    lines[4] = "change 2".to_string();
+    write_file(repository, "file.txt", &lines);
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    let hunk = &branch_1.files[0].hunks[0];
+    let (start, end) = (hunk.start, hunk.end);
+
+    let branch_2_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: branch_2_id,
+                ownership: Some(format!("file.txt:{start}-{end}[-5+5]").parse().unwrap()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    controller
+        .unapply_ownership(
+            project_id,
+            &format!("file.txt:{start}-{end}[-5+5]").parse().unwrap(),
+        )
+        .await
+        .unwrap();
+
+    lines[4] = "line 4".to_string();
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        lines.join("\n")
+    );
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    assert_eq!(branch_1.files.len(), 1);
+    assert!(branch_1.files[0].hunks[0].diff.contains_str("+change 1"));
+
+    let branch_2 = get_virtual_branch(controller, project_id, branch_2_id).await;
+    assert!(branch_2.files.is_empty());
+}
+
 fn write_file(repository: &TestProject, path: &str, lines: &[String]) {
     fs::write(repository.path().join(path), lines.join("\n")).unwrap()
 }
@@ -293,18 +571,3 @@ fn commit_and_push_initial(repository: &TestProject) {
     repository.commit_all("initial commit");
     repository.push();
 }
-
-async fn get_virtual_branch(
-    controller: &Controller,
-    project_id: &ProjectId,
-    branch_id: Id<Branch>,
-) -> VirtualBranch {
-    controller
-        .list_virtual_branches(project_id)
-        .await
-        .unwrap()
-        .0
-        .into_iter()
-        .find(|b| b.id == branch_id)
-        .unwrap()
-}
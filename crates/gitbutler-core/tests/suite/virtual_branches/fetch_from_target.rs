@@ -1,3 +1,6 @@
+use gitbutler_core::{git::credentials::Credential, project_repository};
+use gitbutler_testsupport::credentials::FakeCredentialProvider;
+
 use super::*;
 
 #[tokio::test]
@@ -44,3 +47,316 @@ async fn should_update_last_fetched() {
         after_second_fetch.unwrap().last_fetched_ms
     );
 }
+
+#[tokio::test]
+async fn fetches_all_remotes() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+
+    {
+        let repo = git2::Repository::open(repository.path()).unwrap();
+        let origin_url = repo
+            .find_remote("origin")
+            .unwrap()
+            .url()
+            .unwrap()
+            .to_owned();
+        repo.remote("fork", &origin_url).unwrap();
+        repo.remote("broken", "/does/not/exist").unwrap();
+    }
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            fetch_settings: Some(projects::FetchSettings {
+                remotes: None,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    controller
+        .fetch_from_target(project_id, None)
+        .await
+        .unwrap();
+
+    let project = projects.get(project_id).unwrap();
+    assert_eq!(
+        project.remotes_last_fetch.keys().collect::<Vec<_>>(),
+        vec!["broken", "fork", "origin"]
+    );
+    assert!(matches!(
+        project.remotes_last_fetch["origin"],
+        projects::FetchResult::Fetched { .. }
+    ));
+    assert!(matches!(
+        project.remotes_last_fetch["fork"],
+        projects::FetchResult::Fetched { .. }
+    ));
+    assert!(matches!(
+        project.remotes_last_fetch["broken"],
+        projects::FetchResult::Error { .. }
+    ));
+
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    assert!(repo.find_reference("refs/remotes/fork/master").is_ok());
+
+    // a removed remote is forgotten with the next fetch
+    repo.remote_delete("broken").unwrap();
+    controller
+        .fetch_from_target(project_id, None)
+        .await
+        .unwrap();
+
+    let project = projects.get(project_id).unwrap();
+    assert_eq!(
+        project.remotes_last_fetch.keys().collect::<Vec<_>>(),
+        vec!["fork", "origin"]
+    );
+}
+
+#[tokio::test]
+async fn fetches_only_target_remote_by_default() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+
+    {
+        let repo = git2::Repository::open(repository.path()).unwrap();
+        repo.remote("broken", "/does/not/exist").unwrap();
+    }
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    controller
+        .fetch_from_target(project_id, None)
+        .await
+        .unwrap();
+
+    let project = projects.get(project_id).unwrap();
+    assert_eq!(
+        project.remotes_last_fetch.keys().collect::<Vec<_>>(),
+        vec!["origin"]
+    );
+}
+
+#[tokio::test]
+async fn fetches_with_credential_providers() {
+    let provider = FakeCredentialProvider::new(vec![Credential::Noop]);
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::with_credential_providers(vec![Arc::new(provider.clone())]);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            preferred_key: Some(projects::AuthKey::Default),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    controller
+        .fetch_from_target(project_id, None)
+        .await
+        .unwrap();
+
+    let project = projects.get(project_id).unwrap();
+    assert!(matches!(
+        project.remotes_last_fetch["origin"],
+        projects::FetchResult::Fetched { .. }
+    ));
+
+    let origin_url = git2::Repository::open(repository.path())
+        .unwrap()
+        .find_remote("origin")
+        .unwrap()
+        .url()
+        .unwrap()
+        .to_owned();
+    assert_eq!(
+        provider.requested_urls(),
+        vec![origin_url.parse::<git::Url>().unwrap().to_string()]
+    );
+}
+
+#[tokio::test]
+async fn fetch_with_each_remote_backend() {
+    for remote_backend in [
+        projects::RemoteBackendKind::Git2,
+        projects::RemoteBackendKind::GitCli,
+        projects::RemoteBackendKind::Gix,
+    ] {
+        let Test {
+            project_id,
+            controller,
+            projects,
+            ..
+        } = &Test::default();
+
+        projects
+            .update(&projects::UpdateRequest {
+                id: *project_id,
+                remote_backend: Some(remote_backend),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        controller
+            .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let fetch = controller
+            .fetch_from_target(project_id, None)
+            .await
+            .unwrap();
+        assert!(fetch.last_fetched_ms.is_some(), "{remote_backend:?}");
+        let project = projects.get(project_id).unwrap();
+        assert!(
+            matches!(
+                project.remotes_last_fetch.get("origin"),
+                Some(projects::FetchResult::Fetched { .. })
+            ),
+            "{remote_backend:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn prune_with_each_remote_backend() {
+    for remote_backend in [
+        projects::RemoteBackendKind::Git2,
+        projects::RemoteBackendKind::GitCli,
+        projects::RemoteBackendKind::Gix,
+    ] {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            projects,
+            ..
+        } = &Test::default();
+
+        projects
+            .update(&projects::UpdateRequest {
+                id: *project_id,
+                remote_backend: Some(remote_backend),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        controller
+            .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let repo = git2::Repository::open(repository.path()).unwrap();
+        let mut origin = repo.find_remote("origin").unwrap();
+        origin
+            .push(&["refs/remotes/origin/master:refs/heads/gone"], None)
+            .unwrap();
+        controller
+            .fetch_from_target(project_id, None)
+            .await
+            .unwrap();
+        assert!(
+            repo.find_reference("refs/remotes/origin/gone").is_ok(),
+            "{remote_backend:?}"
+        );
+
+        origin.push(&[":refs/heads/gone"], None).unwrap();
+        controller
+            .fetch_from_target(project_id, None)
+            .await
+            .unwrap();
+        assert!(
+            repo.find_reference("refs/remotes/origin/gone").is_err(),
+            "{remote_backend:?}"
+        );
+        assert!(
+            repo.find_reference("refs/remotes/origin/master").is_ok(),
+            "{remote_backend:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn unreachable_remotes_fail_with_network_errors() {
+    for remote_backend in [
+        projects::RemoteBackendKind::GitCli,
+        projects::RemoteBackendKind::Gix,
+    ] {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            projects,
+            ..
+        } = &Test::default();
+
+        {
+            let repo = git2::Repository::open(repository.path()).unwrap();
+            // nothing listens on port 1
+            repo.remote("offline", "http://127.0.0.1:1/repo.git")
+                .unwrap();
+        }
+
+        controller
+            .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+        projects
+            .update(&projects::UpdateRequest {
+                id: *project_id,
+                remote_backend: Some(remote_backend),
+                fetch_settings: Some(projects::FetchSettings {
+                    remotes: None,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let errors = controller.fetch_remotes(project_id, None).await.unwrap();
+        assert_eq!(
+            errors.keys().collect::<Vec<_>>(),
+            vec!["offline"],
+            "{remote_backend:?}"
+        );
+        assert!(
+            matches!(errors["offline"], project_repository::RemoteError::Network),
+            "{remote_backend:?}: {:?}",
+            errors["offline"]
+        );
+    }
+}
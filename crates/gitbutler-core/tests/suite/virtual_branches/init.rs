@@ -202,6 +202,13 @@ async fn submodule() {
 
     let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
     assert_eq!(branches.len(), 1);
-    assert_eq!(branches[0].files.len(), 1);
-    assert_eq!(branches[0].files[0].hunks.len(), 1);
+    // `.gitmodules` and the submodule itself
+    assert_eq!(branches[0].files.len(), 2);
+    assert!(branches[0].files.iter().all(|file| file.hunks.len() == 1));
+    let submodule = branches[0]
+        .files
+        .iter()
+        .find(|file| file.path == path::Path::new("submodule"))
+        .unwrap();
+    assert!(submodule.submodule.is_some());
 }
@@ -0,0 +1,241 @@
+use super::*;
+
+/// What the `git lfs` stand-in stores in git instead of the content of a file.
+const POINTER: &str = "version lfs-stand-in\n";
+
+#[tokio::test]
+async fn lfs_files_are_committed_as_pointers() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    track_with_lfs_stand_in(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.bin"), "large content").unwrap();
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert_eq!(branch.files.len(), 1);
+    assert!(branch.files[0].binary);
+
+    controller
+        .create_commit(project_id, &branch_id, "add file", None, false)
+        .await
+        .unwrap();
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    // the file on disk still cleans into the committed pointer
+    assert!(branch.files.is_empty());
+    assert_eq!(
+        committed_content(repository, branch.commits[0].id, "file.bin"),
+        POINTER
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.bin")).unwrap(),
+        "large content"
+    );
+}
+
+#[tokio::test]
+async fn lfs_files_are_smudged_when_applied() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    track_with_lfs_stand_in(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.bin"), "large content").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "add file", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .unapply_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+    assert!(!repository.path().join("file.bin").exists());
+
+    controller
+        .apply_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.bin")).unwrap(),
+        "large content"
+    );
+}
+
+#[tokio::test]
+async fn lfs_files_are_cleaned_once_per_change() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    track_with_lfs_stand_in(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.bin"), "large content").unwrap();
+    controller.list_virtual_branches(project_id).await.unwrap();
+    controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(clean_count(repository), 1);
+
+    fs::write(repository.path().join("file.bin"), "more large content").unwrap();
+    controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(clean_count(repository), 2);
+}
+
+#[tokio::test]
+async fn large_files_are_skipped_until_claimed() {
+    let Test {
+        project_id,
+        controller,
+        projects,
+        repository,
+        ..
+    } = &Test::default();
+
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            diff_settings: Some(projects::DiffSettings {
+                large_file_limit_bytes: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("large.txt"), "more than ten bytes").unwrap();
+
+    let (branches, skipped_files) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches[0].files.is_empty());
+    assert_eq!(skipped_files.len(), 1);
+    assert_eq!(
+        skipped_files[0].new_path.as_deref(),
+        Some(path::Path::new("large.txt"))
+    );
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: branch_id,
+                ownership: Some("large.txt:0-0".parse().unwrap()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let (branches, skipped_files) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(skipped_files.is_empty());
+    assert_eq!(branches[0].files.len(), 1);
+    assert_eq!(branches[0].files[0].path, path::PathBuf::from("large.txt"));
+    // the content is only stored once it's committed
+    let blob_id = git2::Oid::hash_object(git2::ObjectType::Blob, b"more than ten bytes").unwrap();
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    assert!(!repo.odb().unwrap().exists(blob_id));
+
+    controller
+        .create_commit(project_id, &branch_id, "add large file", None, false)
+        .await
+        .unwrap();
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert!(branch.files.is_empty());
+    assert_eq!(
+        committed_content(repository, branch.commits[0].id, "large.txt"),
+        "more than ten bytes"
+    );
+}
+
+/// Track `*.bin` with a filter that keeps the content of a single file in `.git/lfs-object`
+/// and puts a pointer in its place, like `git lfs` does. Each time it cleans a file, it adds a line
+/// to `.git/lfs-cleaned`.
+fn track_with_lfs_stand_in(repository: &TestProject) {
+    fs::write(
+        repository.path().join(".gitattributes"),
+        "*.bin filter=lfs\n",
+    )
+    .unwrap();
+    repository.commit_all("track binaries with lfs");
+    repository.push();
+
+    let mut config = git2::Repository::open(repository.path())
+        .unwrap()
+        .config()
+        .unwrap();
+    config
+        .set_str(
+            "filter.lfs.clean",
+            "cat > .git/lfs-object && echo >> .git/lfs-cleaned && printf 'version lfs-stand-in\\n'",
+        )
+        .unwrap();
+    config
+        .set_str("filter.lfs.smudge", "cat .git/lfs-object")
+        .unwrap();
+}
+
+fn clean_count(repository: &TestProject) -> usize {
+    fs::read_to_string(repository.path().join(".git/lfs-cleaned"))
+        .unwrap_or_default()
+        .lines()
+        .count()
+}
+
+fn committed_content(repository: &TestProject, commit_id: git::Oid, path: &str) -> String {
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    let tree = repo.find_commit(commit_id.into()).unwrap().tree().unwrap();
+    let blob = tree
+        .get_path(path::Path::new(path))
+        .unwrap()
+        .to_object(&repo)
+        .unwrap()
+        .peel_to_blob()
+        .unwrap();
+    String::from_utf8(blob.content().to_vec()).unwrap()
+}
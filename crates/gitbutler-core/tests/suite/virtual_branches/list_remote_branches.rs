@@ -0,0 +1,211 @@
+use gitbutler_core::virtual_branches::RemoteBranchFilter;
+
+use super::*;
+
+/// Pushes a branch `name` with a commit changing `file`, on top of master.
+fn push_branch(repository: &TestProject, name: &str, file: &str) {
+    let branch_name: git::LocalRefname = format!("refs/heads/{name}").parse().unwrap();
+    repository.checkout(&branch_name);
+    fs::write(repository.path().join(file), name).unwrap();
+    repository.commit_all(name);
+    repository.push_branch(&branch_name);
+    repository.checkout(&"refs/heads/master".parse().unwrap());
+}
+
+async fn list(
+    controller: &Controller,
+    project_id: &ProjectId,
+    filter: RemoteBranchFilter,
+) -> Vec<String> {
+    let mut names = controller
+        .list_remote_branches(project_id, &filter)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|branch| branch.name.to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn ahead_behind_and_merged() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    push_branch(repository, "feature", "feature.txt");
+    push_branch(repository, "merged", "merged.txt");
+    repository.merge(&"refs/heads/merged".parse().unwrap());
+    repository.fetch();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branches = controller
+        .list_remote_branches(project_id, &RemoteBranchFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(branches.len(), 2);
+
+    let feature = branches
+        .iter()
+        .find(|branch| branch.name.to_string() == "refs/remotes/origin/feature")
+        .unwrap();
+    assert_eq!(feature.ahead, 1);
+    assert_eq!(feature.behind, 2);
+    assert!(!feature.is_merged);
+    assert_eq!(feature.virtual_branch_id, None);
+
+    let merged = branches
+        .iter()
+        .find(|branch| branch.name.to_string() == "refs/remotes/origin/merged")
+        .unwrap();
+    assert_eq!(merged.ahead, 0);
+    assert!(merged.is_merged);
+}
+
+#[tokio::test]
+async fn filters() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    push_branch(repository, "feature", "feature.txt");
+    push_branch(repository, "merged", "merged.txt");
+    repository.merge(&"refs/heads/merged".parse().unwrap());
+    repository.fetch();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        list(
+            controller,
+            project_id,
+            RemoteBranchFilter {
+                merged: Some(false),
+                ..Default::default()
+            }
+        )
+        .await,
+        vec!["refs/remotes/origin/feature"]
+    );
+    assert_eq!(
+        list(
+            controller,
+            project_id,
+            RemoteBranchFilter {
+                merged: Some(true),
+                ..Default::default()
+            }
+        )
+        .await,
+        vec!["refs/remotes/origin/merged"]
+    );
+    assert!(list(
+        controller,
+        project_id,
+        RemoteBranchFilter {
+            remote: Some("upstream".into()),
+            ..Default::default()
+        }
+    )
+    .await
+    .is_empty());
+    assert_eq!(
+        list(
+            controller,
+            project_id,
+            RemoteBranchFilter {
+                remote: Some("origin".into()),
+                author: Some("TEST".into()),
+                max_age_days: Some(1),
+                ..Default::default()
+            }
+        )
+        .await,
+        vec!["refs/remotes/origin/feature", "refs/remotes/origin/merged"]
+    );
+    assert!(list(
+        controller,
+        project_id,
+        RemoteBranchFilter {
+            author: Some("somebody else".into()),
+            ..Default::default()
+        }
+    )
+    .await
+    .is_empty());
+}
+
+#[tokio::test]
+async fn virtual_branch_id() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    push_branch(repository, "feature", "feature.txt");
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch_from_branch(
+            project_id,
+            &"refs/remotes/origin/feature".parse().unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let branches = controller
+        .list_remote_branches(project_id, &RemoteBranchFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].virtual_branch_id, Some(branch_id));
+}
+
+#[tokio::test]
+async fn ahead_behind_of_fetched_target() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    push_branch(repository, "feature", "feature.txt");
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    // the target moves on without the workspace being updated to it
+    push_branch(repository, "master", "upstream.txt");
+    repository.fetch();
+
+    let branches = controller
+        .list_remote_branches(project_id, &RemoteBranchFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].ahead, 1);
+    assert_eq!(branches[0].behind, 1);
+}
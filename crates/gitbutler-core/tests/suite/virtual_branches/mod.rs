@@ -1,10 +1,10 @@
-use std::{fs, path, str::FromStr};
+use std::{fs, path, str::FromStr, sync::Arc};
 
 use gitbutler_core::{
     git, keys,
     projects::{self, ProjectId},
     users,
-    virtual_branches::{branch, errors, Controller},
+    virtual_branches::{branch, errors, Controller, VirtualBranch},
 };
 use tempfile::TempDir;
 
@@ -28,11 +28,26 @@ impl Drop for Test {
 
 impl Default for Test {
     fn default() -> Self {
+        Self::with_helper(|data_dir| git::credentials::Helper::from_path(data_dir))
+    }
+}
+
+impl Test {
+    /// Like the default, but authenticates with remotes using only `providers`.
+    fn with_credential_providers(
+        providers: Vec<Arc<dyn git::credentials::CredentialProvider>>,
+    ) -> Self {
+        Self::with_helper(|data_dir| {
+            git::credentials::Helper::from_path(data_dir).with_providers(providers)
+        })
+    }
+
+    fn with_helper(helper: impl FnOnce(&path::Path) -> git::credentials::Helper) -> Self {
         let data_dir = paths::data_dir();
         let keys = keys::Controller::from_path(data_dir.path());
         let projects = projects::Controller::from_path(data_dir.path());
         let users = users::Controller::from_path(data_dir.path());
-        let helper = git::credentials::Helper::from_path(data_dir.path());
+        let helper = helper(data_dir.path());
 
         let test_project = TestProject::default();
         let project = projects
@@ -58,20 +73,43 @@ mod delete_virtual_branch;
 mod fetch_from_target;
 mod init;
 mod insert_blank_commit;
+mod large_files;
+mod list_remote_branches;
 mod move_commit_file;
 mod move_commit_to_vbranch;
+mod oplog;
+mod pull_requests;
+#[cfg(unix)]
+mod push;
+mod rebase;
 mod references;
 mod reorder_commit;
 mod reset_virtual_branch;
 mod selected_for_changes;
 mod set_base_branch;
 mod squash;
+mod stacked_branches;
+mod submodules;
 mod unapply;
 mod unapply_ownership;
 mod undo_commit;
 mod update_base_branch;
 mod update_commit_message;
 mod upstream;
+async fn get_virtual_branch(
+    controller: &Controller,
+    project_id: &ProjectId,
+    branch_id: branch::BranchId,
+) -> VirtualBranch {
+    controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap()
+}
 
 #[tokio::test]
 async fn resolve_conflict_flow() {
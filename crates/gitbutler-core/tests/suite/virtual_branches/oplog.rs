@@ -0,0 +1,136 @@
+use gitbutler_core::snapshots::{entry::OperationType, snapshot::Oplog};
+
+use super::*;
+
+async fn enable_snapshots(projects: &projects::Controller, project_id: &ProjectId) {
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            enable_snapshots: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn records_operations_with_trailers() {
+    let Test {
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    enable_snapshots(projects, project_id).await;
+
+    let branch_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                name: Some("name".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    controller
+        .delete_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+
+    let project = projects.get(project_id).unwrap();
+    let snapshots = project.list_snapshots(10, None).unwrap();
+    let details = snapshots
+        .iter()
+        .map(|snapshot| snapshot.details.clone().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(details.len(), 2);
+
+    assert_eq!(details[0].operation, OperationType::DeleteBranch);
+    assert_eq!(details[0].trailers[0].key, "branch_id");
+    assert_eq!(details[0].trailers[0].value, branch_id.to_string());
+
+    assert_eq!(details[1].operation, OperationType::CreateBranch);
+    assert_eq!(details[1].trailers[0].key, "name");
+    assert_eq!(details[1].trailers[0].value, "name");
+}
+
+#[tokio::test]
+async fn records_changes_to_the_target() {
+    let Test {
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+    enable_snapshots(projects, project_id).await;
+
+    // there is no workspace to snapshot yet
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    controller
+        .set_target_push_remote(project_id, "origin")
+        .await
+        .unwrap();
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let project = projects.get(project_id).unwrap();
+    let snapshots = project.list_snapshots(10, None).unwrap();
+    let details = snapshots
+        .iter()
+        .map(|snapshot| snapshot.details.clone().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(details.len(), 2);
+
+    assert_eq!(details[0].operation, OperationType::SetBaseBranch);
+    assert_eq!(details[0].trailers[0].key, "branch");
+    assert_eq!(details[0].trailers[0].value, "refs/remotes/origin/master");
+
+    assert_eq!(details[1].operation, OperationType::SetPushRemote);
+    assert_eq!(details[1].trailers[0].key, "remote");
+    assert_eq!(details[1].trailers[0].value, "origin");
+}
+
+#[tokio::test]
+async fn undo_restores_state_before_operation() {
+    let Test {
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    enable_snapshots(projects, project_id).await;
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    controller
+        .delete_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches.is_empty());
+
+    let project = projects.get(project_id).unwrap();
+    assert!(project.undo().unwrap().is_some());
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].id, branch_id);
+}
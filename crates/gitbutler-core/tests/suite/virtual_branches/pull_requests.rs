@@ -0,0 +1,139 @@
+use gitbutler_core::forge::{NewPullRequest, PullRequestUpdate};
+use gitbutler_testsupport::forge::FakeForge;
+
+use super::*;
+
+/// A controller reading everything from the data dir of `test` anew, like after a restart.
+fn restarted(test: &Test, forge: &FakeForge) -> Controller {
+    let data_dir = test.data_dir.as_ref().unwrap().path();
+    Controller::new(
+        test.projects.clone(),
+        users::Controller::from_path(data_dir),
+        keys::Controller::from_path(data_dir),
+        git::credentials::Helper::from_path(data_dir),
+    )
+    .with_forge_provider(forge.provider())
+}
+
+/// Creates a branch with a commit and pushes it.
+async fn pushed_branch(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+    file: &str,
+) -> branch::BranchId {
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join(file), "content").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+    branch_id
+}
+
+fn upstream_name(
+    branches: &[gitbutler_core::virtual_branches::VirtualBranch],
+    id: &branch::BranchId,
+) -> String {
+    branches
+        .iter()
+        .find(|branch| branch.id == *id)
+        .and_then(|branch| branch.upstream_name.clone())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn pull_requests_are_kept_across_restarts() {
+    let test = Test::default();
+    let forge = FakeForge::default();
+    let controller = restarted(&test, &forge);
+    let project_id = &test.project_id;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    let branch_id = pushed_branch(&test.repository, project_id, &controller, "file.txt").await;
+
+    let created = controller
+        .create_pull_request(
+            project_id,
+            &branch_id,
+            &NewPullRequest {
+                title: "title".into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let controller = restarted(&test, &forge);
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches[0].pull_request.as_ref(), Some(&created));
+
+    // the number is remembered too, so the same pull request gets updated
+    let updated = controller
+        .update_pull_request(
+            project_id,
+            &branch_id,
+            &PullRequestUpdate {
+                title: Some("new title".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.number, created.number);
+    assert_eq!(updated.title, "new title");
+}
+
+#[tokio::test]
+async fn refreshing_continues_past_failing_branches() {
+    let test = Test::default();
+    let forge = FakeForge::default();
+    let controller = restarted(&test, &forge);
+    let project_id = &test.project_id;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    let failing_id = pushed_branch(&test.repository, project_id, &controller, "one.txt").await;
+    let working_id = pushed_branch(&test.repository, project_id, &controller, "two.txt").await;
+    for branch_id in [&failing_id, &working_id] {
+        controller
+            .create_pull_request(project_id, branch_id, &NewPullRequest::default())
+            .await
+            .unwrap();
+    }
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    forge.fail_for(&upstream_name(&branches, &failing_id));
+    // the pull request of the other branch was closed and deleted in the meantime
+    forge.set_pull_request(&upstream_name(&branches, &working_id), None);
+
+    let errors = controller.refresh_pull_requests(project_id).await.unwrap();
+    assert_eq!(errors.keys().collect::<Vec<_>>(), vec![&failing_id]);
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let pull_request = |id: &branch::BranchId| {
+        branches
+            .iter()
+            .find(|branch| branch.id == *id)
+            .unwrap()
+            .pull_request
+            .clone()
+    };
+    assert!(
+        pull_request(&failing_id).is_some(),
+        "keeps the pull request it last saw"
+    );
+    assert!(pull_request(&working_id).is_none());
+}
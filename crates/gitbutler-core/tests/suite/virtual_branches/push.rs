@@ -0,0 +1,392 @@
+use std::os::unix::fs::PermissionsExt;
+
+use gitbutler_core::{project_repository, virtual_branches::PushOptions};
+use gitbutler_testsupport::credentials::FakeCredentialProvider;
+
+use super::*;
+
+fn write_pre_push_hook(repository: &TestProject, script: &str) {
+    let hook = repository.path().join(".git/hooks/pre-push");
+    fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    fs::write(&hook, script).unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+async fn commit_to_new_branch(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+) -> branch::BranchId {
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+    branch_id
+}
+
+#[tokio::test]
+async fn pre_push_hook_receives_ref_updates() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    write_pre_push_hook(repository, "#!/bin/sh\necho \"$1\"\ncat\n");
+
+    let report = controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let branch = &branches[0];
+    assert_eq!(report.refs.len(), 1);
+    assert_eq!(report.refs[0].old_head, None);
+    assert_eq!(report.refs[0].new_head, branch.head);
+    assert_eq!(
+        report.hook_output,
+        vec![format!(
+            "origin\nrefs/gitbutler/{name} {head} refs/heads/{name} {zero}\n",
+            name = report.refs[0].remote_ref.branch(),
+            head = branch.head,
+            zero = git::Oid::default(),
+        )]
+    );
+    assert!(branch.upstream.is_some());
+}
+
+#[tokio::test]
+async fn rejected_by_pre_push_hook() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    write_pre_push_hook(repository, "#!/bin/sh\necho rejected\nexit 1\n");
+
+    assert!(matches!(
+        controller
+            .push_virtual_branch(project_id, &branch_id, false, None)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::PushError::PrePushHookRejected(output)) if output == "rejected\n"
+    ));
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches[0].upstream.is_none());
+}
+
+#[tokio::test]
+async fn dry_run_runs_pre_push_hook_without_pushing() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    write_pre_push_hook(repository, "#!/bin/sh\necho checked\n");
+
+    let report = controller
+        .push_virtual_branch_with_options(
+            project_id,
+            &branch_id,
+            &PushOptions {
+                dry_run: true,
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(report.refs.len(), 1);
+    assert_eq!(report.refs[0].branch_id, branch_id);
+    assert_eq!(report.refs[0].new_head, branches[0].head);
+    assert_eq!(report.hook_output, vec!["checked\n".to_string()]);
+    assert!(branches[0].upstream.is_none());
+}
+
+#[tokio::test]
+async fn pre_push_hook_runs_once_for_a_stack() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let parent_id = commit_to_new_branch(repository, project_id, controller).await;
+    let child_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                parent: Some(parent_id),
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "more content").unwrap();
+    controller
+        .create_commit(project_id, &child_id, "child", None, false)
+        .await
+        .unwrap();
+    write_pre_push_hook(repository, "#!/bin/sh\necho run\ncat\n");
+
+    let report = controller
+        .push_virtual_branch(project_id, &child_id, false, None)
+        .await
+        .unwrap();
+
+    assert_eq!(report.refs.len(), 2);
+    assert_eq!(report.refs[0].branch_id, parent_id);
+    assert_eq!(report.refs[1].branch_id, child_id);
+    assert_eq!(report.hook_output.len(), 1);
+    let lines = report.hook_output[0].lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "run");
+    assert!(lines[1].contains(&report.refs[0].new_head.to_string()));
+    assert!(lines[2].contains(&report.refs[1].new_head.to_string()));
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches.iter().all(|branch| branch.upstream.is_some()));
+}
+
+#[tokio::test]
+async fn pre_push_hook_from_hooks_path() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    let hook = repository.path().join("custom-hooks/pre-push");
+    fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    fs::write(&hook, "#!/bin/sh\necho custom\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    git2::Repository::open(repository.path())
+        .unwrap()
+        .config()
+        .unwrap()
+        .set_str("core.hooksPath", "custom-hooks")
+        .unwrap();
+
+    let report = controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    assert_eq!(report.hook_output, vec!["custom\n".to_string()]);
+}
+
+#[tokio::test]
+async fn force_push_with_unchanged_remote() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file2.txt"), "content2").unwrap();
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let to_amend: branch::BranchOwnershipClaims = "file2.txt:1-2".parse().unwrap();
+    controller
+        .amend(project_id, &branch_id, branches[0].commits[0].id, &to_amend)
+        .await
+        .unwrap();
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, true, None)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches[0].upstream.as_ref().unwrap().sha, branches[0].head);
+}
+
+#[tokio::test]
+async fn force_push_after_remote_changed() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    let report = controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+    let remote_ref = report.refs[0].remote_ref.clone();
+
+    // someone else moves the remote branch
+    {
+        let repo = git2::Repository::open(repository.path()).unwrap();
+        let base = repo.revparse_single("origin/master").unwrap().id();
+        repo.find_remote("origin")
+            .unwrap()
+            .push(
+                &[format!("+{base}:refs/heads/{}", remote_ref.branch())],
+                None,
+            )
+            .unwrap();
+    }
+
+    assert!(matches!(
+        controller
+            .push_virtual_branch(project_id, &branch_id, true, None)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::PushError::LeaseExpired(refname)) if *refname == remote_ref
+    ));
+}
+
+#[tokio::test]
+async fn force_push_over_a_branch_pushed_by_others() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+    let report = controller
+        .push_virtual_branch_with_options(
+            project_id,
+            &branch_id,
+            &PushOptions {
+                with_force: false,
+                dry_run: true,
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    let remote_ref = report.refs[0].remote_ref.clone();
+
+    // someone else pushed a branch of the same name before we ever did
+    {
+        let repo = git2::Repository::open(repository.path()).unwrap();
+        let base = repo.revparse_single("origin/master").unwrap().id();
+        repo.find_remote("origin")
+            .unwrap()
+            .push(
+                &[format!("{base}:refs/heads/{}", remote_ref.branch())],
+                None,
+            )
+            .unwrap();
+    }
+    repository.fetch();
+
+    assert!(matches!(
+        controller
+            .push_virtual_branch(project_id, &branch_id, true, None)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::PushError::LeaseExpired(refname)) if *refname == remote_ref
+    ));
+}
+
+#[tokio::test]
+async fn push_without_credentials() {
+    let provider = FakeCredentialProvider::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::with_credential_providers(vec![Arc::new(provider.clone())]);
+
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            preferred_key: Some(projects::AuthKey::Default),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+
+    assert!(matches!(
+        controller
+            .push_virtual_branch(project_id, &branch_id, false, None)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::PushError::Remote(
+            project_repository::RemoteError::Auth
+        ))
+    ));
+    assert_eq!(provider.requested_urls().len(), 1);
+}
+
+#[tokio::test]
+async fn push_with_each_remote_backend() {
+    for remote_backend in [
+        projects::RemoteBackendKind::Git2,
+        projects::RemoteBackendKind::GitCli,
+        projects::RemoteBackendKind::Gix,
+    ] {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            projects,
+            ..
+        } = &Test::default();
+
+        projects
+            .update(&projects::UpdateRequest {
+                id: *project_id,
+                remote_backend: Some(remote_backend),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let branch_id = commit_to_new_branch(repository, project_id, controller).await;
+        controller
+            .push_virtual_branch(project_id, &branch_id, false, None)
+            .await
+            .unwrap();
+
+        let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+        assert!(branches[0].upstream.is_some(), "{remote_backend:?}");
+    }
+}
@@ -0,0 +1,431 @@
+use gitbutler_core::virtual_branches::{rebase::RebaseOutcome, BranchId};
+
+use super::*;
+
+/// Creates three commits that all rewrite `file.txt`, so moving the last one down conflicts.
+async fn conflicting_commits(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+) -> (BranchId, git::Oid) {
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "zero\n").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit zero", None, false)
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "one\n").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit one", None, false)
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "two\n").unwrap();
+    let commit2_id = controller
+        .create_commit(project_id, &branch_id, "commit two", None, false)
+        .await
+        .unwrap();
+
+    (branch_id, commit2_id)
+}
+
+async fn get_branch(
+    project_id: &ProjectId,
+    controller: &Controller,
+    branch_id: BranchId,
+) -> VirtualBranch {
+    controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap()
+}
+
+/// Where the ref that keeps the objects of a stopped rebase of `branch_id` points to, if it exists.
+fn rebase_ref(repository: &TestProject, branch_id: BranchId) -> Option<git2::Oid> {
+    git2::Repository::open(repository.path())
+        .unwrap()
+        .refname_to_id(&format!("refs/gitbutler-rebase/{branch_id}"))
+        .ok()
+}
+
+#[tokio::test]
+async fn reorder_stops_on_conflict_and_continues() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let (branch_id, commit2_id) = conflicting_commits(repository, project_id, controller).await;
+
+    let outcome = controller
+        .reorder_commit(project_id, &branch_id, commit2_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(outcome, RebaseOutcome::Stopped);
+
+    {
+        // the rebase stopped at the moved commit
+        let branch = get_branch(project_id, controller, branch_id).await;
+        assert!(branch.rebasing);
+        assert!(branch.conflicted);
+        assert_eq!(branch.commits.len(), 1);
+        assert_eq!(branch.commits[0].description, "commit zero");
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+            "<<<<<<< ours\nzero\n=======\ntwo\n>>>>>>> theirs\n"
+        );
+    }
+
+    {
+        // can't continue with conflicts
+        assert!(matches!(
+            controller
+                .continue_rebase(project_id, &branch_id)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::RebaseError::Conflict(_))
+        ));
+    }
+
+    {
+        // can't commit while rebasing
+        fs::write(repository.path().join("file.txt"), "two\n").unwrap();
+        assert!(matches!(
+            controller
+                .create_commit(project_id, &branch_id, "commit", None, false)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::CommitError::RebaseInProgress(_))
+        ));
+    }
+
+    {
+        // the next commit conflicts as well
+        let outcome = controller
+            .continue_rebase(project_id, &branch_id)
+            .await
+            .unwrap();
+        assert_eq!(outcome, RebaseOutcome::Stopped);
+
+        let branch = get_branch(project_id, controller, branch_id).await;
+        assert!(branch.rebasing);
+        assert_eq!(branch.commits.len(), 2);
+        assert_eq!(branch.commits[0].description, "commit two");
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+            "<<<<<<< ours\ntwo\n=======\none\n>>>>>>> theirs\n"
+        );
+    }
+
+    {
+        fs::write(repository.path().join("file.txt"), "one\n").unwrap();
+        let outcome = controller
+            .continue_rebase(project_id, &branch_id)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, RebaseOutcome::Completed(_)));
+
+        let branch = get_branch(project_id, controller, branch_id).await;
+        assert!(!branch.rebasing);
+        assert!(!branch.conflicted);
+        assert!(branch.files.is_empty());
+        assert!(rebase_ref(repository, branch_id).is_none());
+
+        let descriptions = branch
+            .commits
+            .iter()
+            .map(|c| c.description.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptions,
+            vec!["commit one", "commit two", "commit zero"]
+        );
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+            "one\n"
+        );
+    }
+}
+
+#[tokio::test]
+async fn abort_restores_branch() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let (branch_id, commit2_id) = conflicting_commits(repository, project_id, controller).await;
+
+    // uncommitted changes survive the rebase
+    fs::write(repository.path().join("other.txt"), "wip\n").unwrap();
+
+    let outcome = controller
+        .reorder_commit(project_id, &branch_id, commit2_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(outcome, RebaseOutcome::Stopped);
+    assert!(get_branch(project_id, controller, branch_id).await.rebasing);
+    assert!(!repository.path().join("other.txt").exists());
+
+    // the original head and the uncommitted changes are safe from `git gc`
+    let kept = rebase_ref(repository, branch_id).unwrap();
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    let kept = repo.find_commit(kept).unwrap();
+    assert_eq!(kept.parent_id(0).unwrap(), commit2_id.into());
+    assert!(kept.tree().unwrap().get_name("other.txt").is_some());
+
+    controller
+        .abort_rebase(project_id, &branch_id)
+        .await
+        .unwrap();
+
+    let branch = get_branch(project_id, controller, branch_id).await;
+    assert!(!branch.rebasing);
+    assert!(!branch.conflicted);
+    assert!(rebase_ref(repository, branch_id).is_none());
+    assert_eq!(branch.head, commit2_id);
+    assert_eq!(branch.commits.len(), 3);
+    assert_eq!(branch.files.len(), 1);
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "two\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("other.txt")).unwrap(),
+        "wip\n"
+    );
+}
+
+#[tokio::test]
+async fn continue_without_rebase() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let (branch_id, _) = conflicting_commits(repository, project_id, controller).await;
+
+    assert!(matches!(
+        controller
+            .continue_rebase(project_id, &branch_id)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::RebaseError::NotRebasing(_))
+    ));
+}
+
+/// Creates another branch, selected for changes, with uncommitted changes to `other.txt`.
+async fn other_branch(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+) -> BranchId {
+    let branch_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("other.txt"), "other\n").unwrap();
+    branch_id
+}
+
+#[tokio::test]
+async fn abort_applies_other_branches_again() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let (branch_id, commit2_id) = conflicting_commits(repository, project_id, controller).await;
+    let other_branch_id = other_branch(repository, project_id, controller).await;
+
+    let outcome = controller
+        .reorder_commit(project_id, &branch_id, commit2_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(outcome, RebaseOutcome::Stopped);
+    assert!(
+        !get_branch(project_id, controller, other_branch_id)
+            .await
+            .active
+    );
+    assert!(!repository.path().join("other.txt").exists());
+
+    controller
+        .abort_rebase(project_id, &branch_id)
+        .await
+        .unwrap();
+
+    let other = get_branch(project_id, controller, other_branch_id).await;
+    assert!(other.active);
+    assert_eq!(other.files.len(), 1);
+    assert_eq!(
+        fs::read_to_string(repository.path().join("other.txt")).unwrap(),
+        "other\n"
+    );
+}
+
+#[tokio::test]
+async fn finishing_applies_other_branches_again() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let (branch_id, commit2_id) = conflicting_commits(repository, project_id, controller).await;
+    let other_branch_id = other_branch(repository, project_id, controller).await;
+
+    let outcome = controller
+        .reorder_commit(project_id, &branch_id, commit2_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(outcome, RebaseOutcome::Stopped);
+
+    fs::write(repository.path().join("file.txt"), "two\n").unwrap();
+    let outcome = controller
+        .continue_rebase(project_id, &branch_id)
+        .await
+        .unwrap();
+    assert_eq!(outcome, RebaseOutcome::Stopped);
+    assert!(
+        !get_branch(project_id, controller, other_branch_id)
+            .await
+            .active
+    );
+
+    fs::write(repository.path().join("file.txt"), "one\n").unwrap();
+    let outcome = controller
+        .continue_rebase(project_id, &branch_id)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, RebaseOutcome::Completed(_)));
+
+    let other = get_branch(project_id, controller, other_branch_id).await;
+    assert!(other.active);
+    assert_eq!(other.files.len(), 1);
+    assert_eq!(
+        fs::read_to_string(repository.path().join("other.txt")).unwrap(),
+        "other\n"
+    );
+}
+
+#[tokio::test]
+async fn history_is_kept_while_rebasing() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let (branch_id, commit2_id) = conflicting_commits(repository, project_id, controller).await;
+
+    let outcome = controller
+        .reorder_commit(project_id, &branch_id, commit2_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(outcome, RebaseOutcome::Stopped);
+    let head = get_branch(project_id, controller, branch_id).await.head;
+
+    {
+        let to_amend: branch::BranchOwnershipClaims = "file.txt:1-2".parse().unwrap();
+        assert!(matches!(
+            controller
+                .amend(project_id, &branch_id, head, &to_amend)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::VirtualBranchError::RebaseInProgress(_))
+        ));
+    }
+
+    {
+        assert!(matches!(
+            controller
+                .insert_blank_commit(project_id, &branch_id, head, 1)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::VirtualBranchError::RebaseInProgress(_))
+        ));
+    }
+
+    {
+        let target_branch_id = controller
+            .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        assert!(matches!(
+            controller
+                .move_commit(project_id, &target_branch_id, head)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::MoveCommitError::RebaseInProgress(_))
+        ));
+    }
+
+    {
+        assert!(matches!(
+            controller
+                .update_base_branch(project_id)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::UpdateBaseBranchError::RebaseInProgress(_))
+        ));
+    }
+
+    {
+        assert!(matches!(
+            controller
+                .push_virtual_branch(project_id, &branch_id, false, None)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::PushError::RebaseInProgress(_))
+        ));
+    }
+
+    // nothing changed
+    assert_eq!(
+        get_branch(project_id, controller, branch_id).await.head,
+        head
+    );
+}
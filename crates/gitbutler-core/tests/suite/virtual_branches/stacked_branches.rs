@@ -0,0 +1,237 @@
+use super::*;
+
+async fn create_stack(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+) -> (branch::BranchId, branch::BranchId) {
+    let parent_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "parent\n").unwrap();
+    controller
+        .create_commit(project_id, &parent_id, "parent", None, false)
+        .await
+        .unwrap();
+
+    let child_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                parent: Some(parent_id),
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "parent\nchild\n").unwrap();
+    controller
+        .create_commit(project_id, &child_id, "child", None, false)
+        .await
+        .unwrap();
+
+    (parent_id, child_id)
+}
+
+#[tokio::test]
+async fn child_lists_only_its_own_commits() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = create_stack(repository, project_id, controller).await;
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.parent, None);
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(parent.commits[0].description, "parent");
+
+    assert_eq!(child.parent, Some(parent_id));
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "parent\nchild\n"
+    );
+}
+
+#[tokio::test]
+async fn child_follows_parent_when_base_is_updated() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    // make sure we have an undiscovered commit in the remote branch
+    {
+        let first_commit_oid = repository.commit_all("first");
+        fs::write(repository.path().join("other.txt"), "second").unwrap();
+        repository.commit_all("second");
+        repository.push();
+        repository.reset_hard(Some(first_commit_oid));
+    }
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = create_stack(repository, project_id, controller).await;
+
+    controller.update_base_branch(project_id).await.unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert!(parent.active);
+    assert!(child.active);
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "parent\nchild\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("other.txt")).unwrap(),
+        "second"
+    );
+}
+
+#[tokio::test]
+async fn child_follows_parent_when_its_history_is_rewritten() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = create_stack(repository, project_id, controller).await;
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent_head = branches.iter().find(|b| b.id == parent_id).unwrap().head;
+    controller
+        .update_commit_message(project_id, &parent_id, parent_head, "parent updated")
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_ne!(parent.head, parent_head);
+    assert_eq!(parent.commits[0].description, "parent updated");
+    assert!(child.active);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn unapplying_parent_unapplies_child() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = create_stack(repository, project_id, controller).await;
+
+    controller
+        .unapply_virtual_branch(project_id, &parent_id)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches.iter().all(|b| !b.active));
+    assert!(branches.iter().any(|b| b.id == child_id));
+    assert!(!repository.path().join("file.txt").exists());
+}
+
+#[tokio::test]
+async fn deleting_parent_restacks_child() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = create_stack(repository, project_id, controller).await;
+
+    controller
+        .delete_virtual_branch(project_id, &parent_id)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+    assert_eq!(child.parent, None);
+    assert_eq!(child.commits.len(), 2);
+}
+
+#[tokio::test]
+async fn unknown_parent() {
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let parent_id = branch::BranchId::generate();
+    assert!(matches!(
+        controller
+            .create_virtual_branch(
+                project_id,
+                &branch::BranchCreateRequest {
+                    parent: Some(parent_id),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::CreateVirtualBranchError::BranchNotFound(_))
+    ));
+}
@@ -0,0 +1,332 @@
+use gitbutler_core::{git::diff::SubmoduleChange, virtual_branches::VirtualBranchFile};
+
+use super::*;
+
+#[tokio::test]
+async fn added_submodule_is_committed_as_gitlink() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    let upstream = TestProject::default();
+    add_submodule(repository, &upstream);
+    let head = submodule_head(repository);
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    let file = submodule_file(&branch);
+    assert_eq!(
+        file.submodule,
+        Some(SubmoduleChange {
+            old_id: None,
+            new_id: Some(head),
+        })
+    );
+
+    controller
+        .create_commit(project_id, &branch_id, "add submodule", None, false)
+        .await
+        .unwrap();
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert!(branch.files.is_empty());
+    assert_eq!(committed_gitlink(repository, branch.commits[0].id), head);
+}
+
+#[tokio::test]
+async fn moved_submodule_is_committed_as_gitlink() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let upstream = TestProject::default();
+    add_submodule(repository, &upstream);
+    repository.commit_all("add submodule");
+    repository.push();
+    let old_head = submodule_head(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    // changes inside of the submodule aren't ours to commit
+    fs::write(repository.path().join("submodule/file.txt"), "dirty").unwrap();
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert!(branch.files.is_empty());
+
+    let new_head = commit_in_submodule(repository);
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(
+        submodule_file(&branch).submodule,
+        Some(SubmoduleChange {
+            old_id: Some(old_head),
+            new_id: Some(new_head),
+        })
+    );
+
+    controller
+        .create_commit(project_id, &branch_id, "move submodule", None, false)
+        .await
+        .unwrap();
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert!(branch.files.is_empty());
+    assert_eq!(
+        committed_gitlink(repository, branch.commits[0].id),
+        new_head
+    );
+}
+
+#[tokio::test]
+async fn submodule_change_can_be_owned_by_another_branch() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let upstream = TestProject::default();
+    add_submodule(repository, &upstream);
+    repository.commit_all("add submodule");
+    repository.push();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_1_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    let branch_2_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    let new_head = commit_in_submodule(repository);
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    let hunk_id = &submodule_file(&branch_1).hunks[0].id;
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: branch_2_id,
+                ownership: Some(format!("submodule:{hunk_id}").parse().unwrap()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let branch_1 = get_virtual_branch(controller, project_id, branch_1_id).await;
+    assert!(branch_1.files.is_empty());
+
+    controller
+        .create_commit(project_id, &branch_2_id, "move submodule", None, false)
+        .await
+        .unwrap();
+
+    let branch_2 = get_virtual_branch(controller, project_id, branch_2_id).await;
+    assert_eq!(
+        committed_gitlink(repository, branch_2.commits[0].id),
+        new_head
+    );
+}
+
+#[tokio::test]
+async fn unapplying_moved_submodule_moves_it_back() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let upstream = TestProject::default();
+    add_submodule(repository, &upstream);
+    repository.commit_all("add submodule");
+    repository.push();
+    let old_head = submodule_head(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    let new_head = commit_in_submodule(repository);
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert_eq!(branch.files.len(), 1);
+
+    controller
+        .unapply_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+    assert_eq!(submodule_head(repository), old_head);
+
+    controller
+        .apply_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+    assert_eq!(submodule_head(repository), new_head);
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert_eq!(
+        submodule_file(&branch).submodule,
+        Some(SubmoduleChange {
+            old_id: Some(old_head),
+            new_id: Some(new_head),
+        })
+    );
+}
+
+#[tokio::test]
+async fn resetting_moved_submodule_moves_it_back() {
+    let Test {
+        project_id,
+        controller,
+        repository,
+        ..
+    } = &Test::default();
+
+    let upstream = TestProject::default();
+    add_submodule(repository, &upstream);
+    repository.commit_all("add submodule");
+    repository.push();
+    let old_head = submodule_head(repository);
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    commit_in_submodule(repository);
+
+    controller
+        .reset_files(project_id, &vec!["submodule".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(submodule_head(repository), old_head);
+
+    let branch = get_virtual_branch(controller, project_id, branch_id).await;
+    assert!(branch.files.is_empty());
+}
+
+#[tokio::test]
+async fn project_with_submodules_can_be_added() {
+    let Test {
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+
+    let repository = TestProject::default();
+    let upstream = TestProject::default();
+    add_submodule(&repository, &upstream);
+    repository.commit_all("add submodule");
+    repository.push();
+
+    let project = projects.add(repository.path()).unwrap();
+    assert_eq!(project.path, repository.path());
+
+    controller
+        .set_base_branch(&project.id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    // the submodule is neither a change nor skipped
+    let (branches, skipped_files) = controller.list_virtual_branches(&project.id).await.unwrap();
+    assert!(branches.iter().all(|branch| branch.files.is_empty()));
+    assert!(skipped_files.is_empty());
+}
+
+/// Add `upstream` as submodule at `submodule`, through a `file://` URL.
+fn add_submodule(repository: &TestProject, upstream: &TestProject) {
+    let url: git::Url = format!("file://{}", upstream.path().display())
+        .parse()
+        .unwrap();
+    repository.add_submodule(&url, path::Path::new("submodule"));
+}
+
+fn submodule_head(repository: &TestProject) -> git::Oid {
+    git2::Repository::open(repository.path().join("submodule"))
+        .unwrap()
+        .head()
+        .unwrap()
+        .target()
+        .unwrap()
+        .into()
+}
+
+/// Commit a change in the submodule, and return the new commit.
+fn commit_in_submodule(repository: &TestProject) -> git::Oid {
+    let repo = git2::Repository::open(repository.path().join("submodule")).unwrap();
+    fs::write(repository.path().join("submodule/file.txt"), "change").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(path::Path::new("file.txt")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    let signature = git2::Signature::now("test", "test@email.com").unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "change",
+        &tree,
+        &[&parent],
+    )
+    .unwrap()
+    .into()
+}
+
+fn submodule_file(branch: &VirtualBranch) -> &VirtualBranchFile {
+    branch
+        .files
+        .iter()
+        .find(|file| file.path == path::Path::new("submodule"))
+        .unwrap()
+}
+
+fn committed_gitlink(repository: &TestProject, commit_id: git::Oid) -> git::Oid {
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    let tree = repo.find_commit(commit_id.into()).unwrap().tree().unwrap();
+    let entry = tree.get_path(path::Path::new("submodule")).unwrap();
+    assert_eq!(entry.filemode(), i32::from(git2::FileMode::Commit));
+    entry.id().into()
+}
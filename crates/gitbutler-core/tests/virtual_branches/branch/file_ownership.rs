@@ -55,6 +55,17 @@ fn plus() {
         ),
         ("file.txt:1-10", "file.txt:1-10", "file.txt:1-10"),
         ("file.txt:1-10,3-15", "file.txt:1-10", "file.txt:1-10,3-15"),
+        (
+            "file.txt:1-10[+2]",
+            "file.txt:1-10[-3]",
+            "file.txt:1-10[+2-3]",
+        ),
+        ("file.txt:1-10", "file.txt:1-10[+2]", "file.txt:1-10"),
+        (
+            "file.txt:1-10[+2],3-5",
+            "file.txt:1-10",
+            "file.txt:1-10,3-5",
+        ),
     ]
     .into_iter()
     .map(|(a, b, expected)| {
@@ -107,6 +118,26 @@ fn minus() {
             "file.txt:1-10,15-17",
             (Some("file.txt:1-10,15-17"), Some("file.txt:11-15")),
         ),
+        (
+            "file.txt:1-10[+2-3]",
+            "file.txt:1-10[+2]",
+            (Some("file.txt:1-10[+2]"), Some("file.txt:1-10[-3]")),
+        ),
+        (
+            "file.txt:1-10[+2-3]",
+            "file.txt:1-10",
+            (Some("file.txt:1-10[+2-3]"), None),
+        ),
+        (
+            "file.txt:1-10",
+            "file.txt:1-10[+2]",
+            (None, Some("file.txt:1-10")),
+        ),
+        (
+            "file.txt:1-10[+2]",
+            "file.txt:1-10[+4]",
+            (None, Some("file.txt:1-10[+2]")),
+        ),
     ]
     .into_iter()
     .map(|(a, b, expected)| {
@@ -136,6 +167,8 @@ fn equal() {
         ("file.txt:1-10", "file.txt:1-11", false),
         ("file.txt:1-10,11-15", "file.txt:11-15,1-10", false),
         ("file.txt:1-10,11-15", "file.txt:1-10,11-15", true),
+        ("file.txt:1-10[+2]", "file.txt:1-10", false),
+        ("file.txt:1-10[+2]", "file.txt:1-10[+2]", true),
     ]
     .into_iter()
     .map(|(a, b, expected)| {
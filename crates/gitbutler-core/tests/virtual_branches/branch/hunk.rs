@@ -1,4 +1,7 @@
-use gitbutler_core::virtual_branches::branch::Hunk;
+use gitbutler_core::{
+    git::diff::{ChangeType, ChangedLine, GitHunk},
+    virtual_branches::branch::Hunk,
+};
 
 #[test]
 fn to_from_string() {
@@ -41,6 +44,121 @@ fn to_string_no_hash() {
     );
 }
 
+#[test]
+fn parse_with_lines() {
+    let hunk = "2-6--123[+3+4-2]".parse::<Hunk>().unwrap();
+    assert_eq!(hunk.timestamp_ms(), Some(123));
+    assert_eq!(
+        hunk.lines,
+        vec![
+            ChangedLine::Added(3),
+            ChangedLine::Added(4),
+            ChangedLine::Removed(2)
+        ]
+    );
+    assert_eq!(hunk.to_string(), "2-6--123[+3+4-2]");
+}
+
+#[test]
+fn parse_invalid_lines() {
+    "2-6[+3*4]".parse::<Hunk>().unwrap_err();
+    "2-6[3]".parse::<Hunk>().unwrap_err();
+}
+
+/// Two unrelated edits close enough for `git2` to put them into one hunk.
+fn two_edits() -> GitHunk {
+    GitHunk {
+        old_start: 1,
+        old_lines: 5,
+        new_start: 1,
+        new_lines: 5,
+        diff_lines: "@@ -1,5 +1,5 @@ fn main()\n a\n-b\n+B\n c\n-d\n+D\n e\n".into(),
+        binary: false,
+        locked_to: Box::new([]),
+        change_type: ChangeType::Modified,
+        selection: None,
+        old_path: None,
+        submodule: None,
+    }
+}
+
+#[test]
+fn select_lines() {
+    let hunk = "1-6[-2+2]".parse::<Hunk>().unwrap();
+    let selected = hunk.select(&two_edits()).unwrap();
+    assert!(selected.is_partial());
+    assert_eq!(selected.new_lines, 5);
+    assert_eq!(
+        selected.diff_lines,
+        "@@ -1,5 +1,5 @@ fn main()\n a\n-b\n+B\n c\n d\n e\n"
+    );
+
+    let rest = hunk.deselect(&two_edits()).unwrap();
+    assert!(rest.is_partial());
+    assert_eq!(
+        rest.diff_lines,
+        "@@ -1,5 +1,5 @@ fn main()\n a\n b\n c\n-d\n+D\n e\n"
+    );
+}
+
+#[test]
+fn select_only_an_addition() {
+    let hunk = "1-6[+4]".parse::<Hunk>().unwrap();
+    let selected = hunk.select(&two_edits()).unwrap();
+    assert_eq!(selected.new_lines, 6);
+    assert_eq!(
+        selected.diff_lines,
+        "@@ -1,5 +1,6 @@ fn main()\n a\n b\n c\n d\n+D\n e\n"
+    );
+}
+
+#[test]
+fn select_whole_hunk() {
+    let hunk = "1-6".parse::<Hunk>().unwrap();
+    assert_eq!(hunk.select(&two_edits()), Some(two_edits()));
+    assert_eq!(hunk.deselect(&two_edits()), None);
+
+    let all_lines = "1-6[-2+2-4+4]".parse::<Hunk>().unwrap();
+    assert_eq!(all_lines.select(&two_edits()), Some(two_edits()));
+    assert_eq!(all_lines.deselect(&two_edits()), None);
+}
+
+#[test]
+fn select_other_hunk() {
+    let hunk = "10-12[+11]".parse::<Hunk>().unwrap();
+    assert_eq!(hunk.select(&two_edits()), None);
+    assert_eq!(hunk.deselect(&two_edits()), Some(two_edits()));
+}
+
+#[test]
+fn select_from_what_another_claim_left() {
+    let first = "1-6[-2+2]".parse::<Hunk>().unwrap();
+    let rest = first.deselect(&two_edits()).unwrap();
+    assert!(first.select(&rest).is_none());
+
+    let second = "1-6[+4]".parse::<Hunk>().unwrap();
+    let selected = second.select(&rest).unwrap();
+    assert_eq!(
+        selected.diff_lines,
+        "@@ -1,5 +1,6 @@ fn main()\n a\n b\n c\n d\n+D\n e\n"
+    );
+    assert_eq!(
+        Hunk::from(&selected),
+        Hunk::from(&two_edits()),
+        "a part is still identified by all of its hunk"
+    );
+}
+
+#[test]
+fn equality_includes_lines() {
+    let whole = "1-6".parse::<Hunk>().unwrap();
+    let some_lines = "1-6[+4]".parse::<Hunk>().unwrap();
+    assert_ne!(whole, some_lines);
+    assert!(whole.same_hunk(&some_lines));
+    assert_eq!(some_lines, "1-6[+4]".parse::<Hunk>().unwrap());
+    assert!(!some_lines.same_hunk(&"1-7[+4]".parse::<Hunk>().unwrap()));
+}
+
 #[test]
 fn hash_diff_no_diff_header_is_normal_hash() {
     let actual = Hunk::hash_diff("a");
@@ -59,6 +177,14 @@ fn hash_diff_empty_is_fine() {
     )
 }
 
+#[test]
+fn hash_diff_leaves_out_context() {
+    let wide = Hunk::hash_diff("@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+    let narrow = Hunk::hash_diff("@@ -2,1 +2,1 @@\n-b\n+B\n");
+    assert_eq!(wide, narrow);
+    assert_ne!(wide, Hunk::hash_diff("@@ -2,1 +2,1 @@\n-b\n+C\n"));
+}
+
 #[test]
 fn hash_diff_content_hash() {
     let a_hash = Hunk::hash_diff("@@x\na");
@@ -91,9 +217,15 @@ fn eq() {
             format!("1-2-{a_hash:x}").parse::<Hunk>().unwrap(),
             true,
         ),
+        // the range of a hunk grows and shrinks with its context, its changed lines don't
         (
             format!("1-2-{a_hash:x}").parse::<Hunk>().unwrap(),
             format!("2-3-{a_hash:x}").parse::<Hunk>().unwrap(),
+            true,
+        ),
+        (
+            format!("1-2-{a_hash:x}").parse::<Hunk>().unwrap(),
+            format!("3-4-{a_hash:x}").parse::<Hunk>().unwrap(),
             false,
         ),
         (
@@ -19,6 +19,7 @@ fn reconcile_ownership_simple() {
                         hash: Some(Hunk::hash("1,3")),
                         timestamp_ms: None,
                         locked_to: vec![],
+                        lines: vec![],
                     },
                     Hunk {
                         start: 4,
@@ -26,6 +27,7 @@ fn reconcile_ownership_simple() {
                         hash: Some(Hunk::hash("4,6")),
                         timestamp_ms: None,
                         locked_to: vec![],
+                        lines: vec![],
                     },
                 ],
             }],
@@ -44,6 +46,7 @@ fn reconcile_ownership_simple() {
                     hash: Some(Hunk::hash("7,9")),
                     timestamp_ms: None,
                     locked_to: vec![],
+                    lines: vec![],
                 }],
             }],
         },
@@ -60,6 +63,7 @@ fn reconcile_ownership_simple() {
                 hash: Some(Hunk::hash("4,6")),
                 timestamp_ms: None,
                 locked_to: vec![],
+                lines: vec![],
             },
             Hunk {
                 start: 7,
@@ -67,6 +71,7 @@ fn reconcile_ownership_simple() {
                 hash: Some(Hunk::hash("9,7")),
                 timestamp_ms: None,
                 locked_to: vec![],
+                lines: vec![],
             },
         ],
     }];
@@ -86,6 +91,7 @@ fn reconcile_ownership_simple() {
                     hash: Some(Hunk::hash("1,3")),
                     timestamp_ms: None,
                     locked_to: vec![],
+                    lines: vec![],
                 },],
             }],
         }
@@ -103,6 +109,7 @@ fn reconcile_ownership_simple() {
                         hash: Some(Hunk::hash("4,6")),
                         timestamp_ms: None,
                         locked_to: vec![],
+                        lines: vec![],
                     },
                     Hunk {
                         start: 7,
@@ -110,6 +117,7 @@ fn reconcile_ownership_simple() {
                         hash: Some(Hunk::hash("9,7")),
                         timestamp_ms: None,
                         locked_to: vec![],
+                        lines: vec![],
                     },
                 ],
             }],
@@ -22,7 +22,7 @@ use gitbutler_core::{
         errors::CommitError,
         integration::verify_branch,
         is_remote_branch_mergeable, is_virtual_branch_mergeable, list_remote_branches,
-        merge_virtual_branch_upstream, unapply_ownership, update_branch,
+        merge_virtual_branch_upstream, unapply_ownership, update_branch, update_commit_message,
     },
 };
 use pretty_assertions::assert_eq;
@@ -137,6 +137,341 @@ fn signed_commit() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn signed_commits_stay_signed_when_rewritten() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    let mut config = project_repository
+        .git_repository
+        .config()
+        .with_context(|| "failed to get config")?;
+    config.set_str("gitbutler.signCommits", "true")?;
+    config.set_str("gitbutler.gitbutlerCommitter", "1")?;
+    let signing_key = suite.keys.get_or_create()?;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+    let first_commit_id = commit(
+        project_repository,
+        &branch1_id,
+        "first commit",
+        None,
+        Some(&signing_key),
+        None,
+        false,
+    )?;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\nline5\n",
+    )?;
+    commit(
+        project_repository,
+        &branch1_id,
+        "second commit",
+        None,
+        Some(&signing_key),
+        None,
+        false,
+    )?;
+
+    // rewording the first commit rebases the second one on top of it
+    update_commit_message(
+        project_repository,
+        &branch1_id,
+        first_commit_id,
+        "reworded first commit",
+        Some(&signing_key),
+    )?;
+
+    let (branches, _) = virtual_branches::list_virtual_branches(project_repository).unwrap();
+    let commits = &branches[0].commits;
+    assert_eq!(commits.len(), 2);
+    assert_eq!(commits[1].description, "reworded first commit");
+    for commit in commits {
+        let commit_obj = project_repository.git_repository.find_commit(commit.id)?;
+        assert!(commit_obj.raw_header().unwrap().contains("SSH SIGNATURE"));
+        // rewritten commits get the configured committer, not their author
+        assert_eq!(commit_obj.committer().name(), Some("GitButler"));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn signed_commit_with_configured_program() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    // a stand-in for gpg that signs with whatever key it is asked to use
+    let signer_dir = tempfile::tempdir()?;
+    let signer_path = signer_dir.path().join("fake-gpg");
+    std::fs::write(
+        &signer_path,
+        "#!/bin/sh\n\
+         cat > /dev/null\n\
+         echo '[GNUPG:] BEGIN_SIGNING' >&2\n\
+         echo '[GNUPG:] SIG_CREATED D 1 8 00 0 FAKE' >&2\n\
+         echo '-----BEGIN PGP SIGNATURE-----'\n\
+         echo \"fake signature by $3\"\n\
+         echo '-----END PGP SIGNATURE-----'\n",
+    )?;
+    std::fs::set_permissions(&signer_path, Permissions::from_mode(0o755))?;
+
+    let mut config = project_repository
+        .git_repository
+        .config()
+        .with_context(|| "failed to get config")?;
+    config.set_str("gitbutler.signCommits", "true")?;
+    config.set_str("gpg.format", "openpgp")?;
+    config.set_str("gpg.program", signer_path.to_str().unwrap())?;
+    config.set_str("user.signingkey", "test-key")?;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+    commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        Some(suite.keys.get_or_create()?).as_ref(),
+        None,
+        false,
+    )?;
+
+    let (branches, _) = virtual_branches::list_virtual_branches(project_repository).unwrap();
+    let commit_obj = project_repository
+        .git_repository
+        .find_commit(branches[0].commits[0].id)?;
+    let raw_header = commit_obj.raw_header().unwrap();
+    assert!(raw_header.contains("BEGIN PGP SIGNATURE"));
+    assert!(raw_header.contains("fake signature by test-key"));
+    assert!(!raw_header.contains("SSH SIGNATURE"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn signing_fails_when_program_does_not_create_signature() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    let signer_dir = tempfile::tempdir()?;
+    let signer_path = signer_dir.path().join("fake-gpg");
+    std::fs::write(
+        &signer_path,
+        "#!/bin/sh\ncat > /dev/null\necho 'no secret key' >&2\nexit 2\n",
+    )?;
+    std::fs::set_permissions(&signer_path, Permissions::from_mode(0o755))?;
+
+    let mut config = project_repository
+        .git_repository
+        .config()
+        .with_context(|| "failed to get config")?;
+    config.set_str("gitbutler.signCommits", "true")?;
+    config.set_str("gpg.program", signer_path.to_str().unwrap())?;
+    config.set_str("user.signingkey", "test-key")?;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+    let result = commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        Some(suite.keys.get_or_create()?).as_ref(),
+        None,
+        false,
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn ssh_signed_commit_with_literal_key() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    // a stand-in for `ssh-keygen -Y sign -n git -f <key file> [-U] <buffer file>`, which signs
+    // with the content of the key file
+    let signer_dir = tempfile::tempdir()?;
+    let signer_path = signer_dir.path().join("fake-ssh-keygen");
+    std::fs::write(
+        &signer_path,
+        "#!/bin/sh\n\
+         [ \"$1 $2 $3 $4 $5 $7\" = '-Y sign -n git -f -U' ] || exit 1\n\
+         echo '-----BEGIN SSH SIGNATURE-----' > \"$8.sig\"\n\
+         echo \"fake signature by $(cat \"$6\")\" >> \"$8.sig\"\n\
+         echo '-----END SSH SIGNATURE-----' >> \"$8.sig\"\n",
+    )?;
+    std::fs::set_permissions(&signer_path, Permissions::from_mode(0o755))?;
+
+    let mut config = project_repository
+        .git_repository
+        .config()
+        .with_context(|| "failed to get config")?;
+    config.set_str("gitbutler.signCommits", "true")?;
+    config.set_str("gpg.format", "ssh")?;
+    config.set_str("gpg.ssh.program", signer_path.to_str().unwrap())?;
+    config.set_str("user.signingkey", "key::ssh-ed25519 AAAAtest")?;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+    commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        Some(suite.keys.get_or_create()?).as_ref(),
+        None,
+        false,
+    )?;
+
+    let (branches, _) = virtual_branches::list_virtual_branches(project_repository).unwrap();
+    let commit_obj = project_repository
+        .git_repository
+        .find_commit(branches[0].commits[0].id)?;
+    let raw_header = commit_obj.raw_header().unwrap();
+    assert!(raw_header.contains("BEGIN SSH SIGNATURE"));
+    assert!(raw_header.contains("fake signature by ssh-ed25519 AAAAtest"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn x509_signed_commit_with_configured_program() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    // a stand-in for gpgsm, which takes the same arguments as gpg
+    let signer_dir = tempfile::tempdir()?;
+    let signer_path = signer_dir.path().join("fake-gpgsm");
+    std::fs::write(
+        &signer_path,
+        "#!/bin/sh\n\
+         cat > /dev/null\n\
+         echo '[GNUPG:] SIG_CREATED D 1 8 00 0 FAKE' >&2\n\
+         echo '-----BEGIN SIGNED MESSAGE-----'\n\
+         echo \"fake signature by $3\"\n\
+         echo '-----END SIGNED MESSAGE-----'\n",
+    )?;
+    std::fs::set_permissions(&signer_path, Permissions::from_mode(0o755))?;
+
+    let mut config = project_repository
+        .git_repository
+        .config()
+        .with_context(|| "failed to get config")?;
+    config.set_str("gitbutler.signCommits", "true")?;
+    config.set_str("gpg.format", "x509")?;
+    config.set_str("gpg.x509.program", signer_path.to_str().unwrap())?;
+    // only used for OpenPGP signatures
+    config.set_str("gpg.program", "/does/not/exist")?;
+    config.set_str("user.signingkey", "test-key")?;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+    commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        Some(suite.keys.get_or_create()?).as_ref(),
+        None,
+        false,
+    )?;
+
+    let (branches, _) = virtual_branches::list_virtual_branches(project_repository).unwrap();
+    let commit_obj = project_repository
+        .git_repository
+        .find_commit(branches[0].commits[0].id)?;
+    let raw_header = commit_obj.raw_header().unwrap();
+    assert!(raw_header.contains("BEGIN SIGNED MESSAGE"));
+    assert!(raw_header.contains("fake signature by test-key"));
+
+    Ok(())
+}
+
 #[test]
 fn track_binary_files() -> Result<()> {
     let suite = Suite::default();
@@ -1322,7 +1657,8 @@ fn detect_mergeable_branch() -> Result<()> {
     assert!(!branch2.active);
     assert!(is_virtual_branch_mergeable(project_repository, &branch2.id).unwrap());
 
-    let remotes = list_remote_branches(project_repository).expect("failed to list remotes");
+    let remotes = list_remote_branches(project_repository, &Default::default())
+        .expect("failed to list remotes");
     let _remote1 = &remotes
         .iter()
         .find(|b| b.name.to_string() == "refs/remotes/origin/remote_branch")
@@ -11,6 +11,10 @@ pub enum Error<BE: std::error::Error + core::fmt::Debug + Send + Sync + 'static>
     /// Usually returned by a push or fetch operation.
     #[error("a ref-spec was not found: {0}")]
     RefNotFound(String),
+    /// A push with a lease was rejected because the remote ref
+    /// no longer had the expected value.
+    #[error("the remote ref has changed since it was last seen: {0}")]
+    StaleRef(String),
     /// An authorized operation was attempted, but the authorization
     /// credentials were rejected by the remote (or further credentials
     /// were required).
@@ -19,6 +23,13 @@ pub enum Error<BE: std::error::Error + core::fmt::Debug + Send + Sync + 'static>
     /// more context.
     #[error("authorization failed: {0}")]
     AuthorizationFailed(BE),
+    /// The remote could not be reached, like when the network is down
+    /// or its host doesn't resolve.
+    ///
+    /// The inner error is the backend-specific error that may provide
+    /// more context.
+    #[error("failed to reach the remote: {0}")]
+    Unreachable(BE),
     /// An operation interacting with a remote by name failed to find
     /// the remote.
     #[error("no such remote: {0}")]
@@ -63,6 +63,25 @@ pub unsafe trait GitExecutor {
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error>;
 
+    /// Like [`Self::execute_raw`], but passes each line the command writes
+    /// to stderr to `on_stderr`. Lines are split at carriage returns as
+    /// well as newlines, as that is how Git redraws its progress output.
+    ///
+    /// Implementations should pass lines on as soon as they are written.
+    /// The default implementation can only pass them on once the command
+    /// has exited.
+    async fn execute_raw_with_stderr<P: AsRef<Path>, F: FnMut(&str)>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        mut on_stderr: F,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let output = self.execute_raw(args, cwd, envs).await?;
+        output.2.split(['\r', '\n']).for_each(&mut on_stderr);
+        Ok(output)
+    }
+
     /// Executes the given Git command with sane defaults.
     /// `git` is never passed as the first argument (arg 0).
     ///
@@ -73,6 +92,18 @@ pub unsafe trait GitExecutor {
         args: &[&str],
         cwd: P,
         envs: Option<HashMap<String, String>>,
+    ) -> Result<(usize, String, String), Self::Error> {
+        self.execute_with_stderr(args, cwd, envs, |_| {}).await
+    }
+
+    /// Like [`Self::execute`], but passes each line the command writes
+    /// to stderr to `on_stderr` (see [`Self::execute_raw_with_stderr`]).
+    async fn execute_with_stderr<P: AsRef<Path>, F: FnMut(&str)>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        on_stderr: F,
     ) -> Result<(usize, String, String), Self::Error> {
         let mut args = args.as_ref().to_vec();
 
@@ -88,7 +119,8 @@ pub unsafe trait GitExecutor {
         envs.insert("GIT_TERMINAL_PROMPT".into(), "0".into());
         envs.insert("LC_ALL".into(), "C".into()); // Force English. We need this for parsing output.
 
-        self.execute_raw(&args, cwd, Some(envs)).await
+        self.execute_raw_with_stderr(&args, cwd, Some(envs), on_stderr)
+            .await
     }
 
     /// Creates a named pipe server that is compatible with
@@ -5,8 +5,8 @@ mod unix;
 #[cfg(windows)]
 mod windows;
 
-use std::{collections::HashMap, path::Path};
-use tokio::process::Command;
+use std::{collections::HashMap, path::Path, process::Stdio};
+use tokio::{io::AsyncReadExt, process::Command};
 
 #[cfg(unix)]
 pub use self::unix::TokioAskpassServer;
@@ -27,6 +27,16 @@ unsafe impl super::GitExecutor for TokioExecutor {
         args: &[&str],
         cwd: P,
         envs: Option<HashMap<String, String>>,
+    ) -> Result<(usize, String, String), Self::Error> {
+        self.execute_raw_with_stderr(args, cwd, envs, |_| {}).await
+    }
+
+    async fn execute_raw_with_stderr<P: AsRef<Path>, F: FnMut(&str)>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        mut on_stderr: F,
     ) -> Result<(usize, String, String), Self::Error> {
         let git_exe = {
             #[cfg(unix)]
@@ -97,22 +107,61 @@ unsafe impl super::GitExecutor for TokioExecutor {
             }
         }
 
-        let output = cmd.output().await?;
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        let mut child = cmd.spawn()?;
+        let mut stdout_pipe = child.stdout.take().expect("stdout is piped");
+        let mut stderr_pipe = child.stderr.take().expect("stderr is piped");
+
+        let read_stdout = async {
+            let mut stdout = Vec::new();
+            stdout_pipe.read_to_end(&mut stdout).await?;
+            Ok::<_, std::io::Error>(stdout)
+        };
+        // stderr is read as it comes in, so that progress can be reported while git runs
+        let read_stderr = async {
+            let mut stderr = Vec::new();
+            let mut line = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stderr_pipe.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                for &byte in &buf[..n] {
+                    if byte == b'\n' || byte == b'\r' {
+                        on_stderr(&String::from_utf8_lossy(&line));
+                        line.clear();
+                    } else {
+                        line.push(byte);
+                    }
+                }
+                stderr.extend_from_slice(&buf[..n]);
+            }
+            if !line.is_empty() {
+                on_stderr(&String::from_utf8_lossy(&line));
+            }
+            Ok::<_, std::io::Error>(stderr)
+        };
+        let (stdout, stderr) = futures::try_join!(read_stdout, read_stderr)?;
+        let status = child.wait().await?;
 
         #[cfg(any(test, debug_assertions))]
         {
             eprintln!(
                 "\n\n GIT STDOUT:\n\n{}\n\nGIT STDERR:\n\n{}\n\nGIT EXIT CODE: {}\n",
-                String::from_utf8_lossy(&output.stdout),
-                String::from_utf8_lossy(&output.stderr),
-                output.status.code().unwrap_or(127) as usize
+                String::from_utf8_lossy(&stdout),
+                String::from_utf8_lossy(&stderr),
+                status.code().unwrap_or(127) as usize
             );
         }
 
         Ok((
-            output.status.code().unwrap_or(127) as usize,
-            String::from_utf8_lossy(&output.stdout).trim().into(),
-            String::from_utf8_lossy(&output.stderr).trim().into(),
+            status.code().unwrap_or(127) as usize,
+            String::from_utf8_lossy(&stdout).trim().into(),
+            String::from_utf8_lossy(&stderr).trim().into(),
         ))
     }
 
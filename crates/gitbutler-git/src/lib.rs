@@ -15,6 +15,7 @@ compile_error!("BUG: in production code this flag should not be set, nor do we r
 
 mod error;
 pub(crate) mod executor;
+mod progress;
 mod refspec;
 mod repository;
 
@@ -23,6 +24,7 @@ pub use self::executor::tokio;
 
 pub use self::{
     error::Error,
+    progress::{Progress, Stage},
     refspec::{Error as RefSpecError, RefSpec},
-    repository::{fetch, push},
+    repository::{fetch, push, Force},
 };
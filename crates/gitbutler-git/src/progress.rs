@@ -0,0 +1,147 @@
+/// A phase of a push or fetch that goes through objects one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum Stage {
+    /// Objects to send are being counted.
+    Counting,
+    /// Objects to send are being compressed.
+    Compressing,
+    /// Objects are being sent to the remote.
+    Writing,
+    /// Objects are being received from the remote.
+    Receiving,
+    /// Deltas of received objects are being resolved.
+    Resolving,
+}
+
+/// An update on how far a push or fetch got.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", rename_all = "camelCase")
+)]
+pub enum Progress {
+    /// `current` objects went through `stage`, out of `total` if that is known.
+    Objects {
+        /// The phase the objects went through.
+        stage: Stage,
+        /// How many objects went through so far.
+        current: usize,
+        /// How many objects there are in total, if known.
+        total: Option<usize>,
+    },
+    /// `transferred` bytes were sent or received so far.
+    Bytes {
+        /// The number of bytes transferred so far.
+        transferred: usize,
+    },
+    /// A ref was updated, or was rejected if `error` is set.
+    RefUpdate {
+        /// The ref, as git names it.
+        refname: String,
+        /// Why the update was rejected, if it was.
+        error: Option<String>,
+    },
+}
+
+impl Progress {
+    /// Parses a line of the `--progress` output git writes to stderr while pushing
+    /// or fetching. Lines that don't report progress yield nothing.
+    pub fn parse(line: &str) -> Vec<Progress> {
+        if let Some(update) = parse_ref_update(line) {
+            return vec![update];
+        }
+
+        let line = line.trim();
+        let line = line.strip_prefix("remote:").unwrap_or(line).trim_start();
+        let Some((label, counts)) = line.split_once(": ") else {
+            return vec![];
+        };
+        let stage = match label {
+            "Enumerating objects" | "Counting objects" => Stage::Counting,
+            "Compressing objects" => Stage::Compressing,
+            "Writing objects" => Stage::Writing,
+            "Receiving objects" => Stage::Receiving,
+            "Resolving deltas" => Stage::Resolving,
+            _ => return vec![],
+        };
+
+        // either `50% (1/2)[, 1.00 KiB | 1.00 MiB/s][, done.]` or `5[, done.]`
+        let mut progress = vec![];
+        let (current, total, rest) = match counts.split_once('(') {
+            Some((_percentage, counts)) => {
+                let Some((counts, rest)) = counts.split_once(')') else {
+                    return vec![];
+                };
+                let Some((current, total)) = counts.split_once('/') else {
+                    return vec![];
+                };
+                (current.trim().parse(), total.trim().parse().ok(), rest)
+            }
+            None => {
+                let current = counts.split(',').next().unwrap_or_default();
+                (current.trim().parse(), None, "")
+            }
+        };
+        if let Ok(current) = current {
+            progress.push(Progress::Objects {
+                stage,
+                current,
+                total,
+            });
+        }
+        if let Some(transferred) = rest
+            .trim_start_matches(", ")
+            .split(['|', ','])
+            .next()
+            .and_then(parse_size)
+        {
+            progress.push(Progress::Bytes { transferred });
+        }
+        progress
+    }
+}
+
+/// Parses a ref update of the summary git prints after pushing or fetching, like
+/// ` * [new branch]      main       -> origin/main` or ` ! [rejected]        main -> main (fetch first)`.
+fn parse_ref_update(line: &str) -> Option<Progress> {
+    let mut chars = line.chars();
+    let (' ', flag, ' ') = (chars.next()?, chars.next()?, chars.next()?) else {
+        return None;
+    };
+    if !" +-t*!=".contains(flag) {
+        return None;
+    }
+    let (_, to) = line.split_once(" -> ")?;
+    let to = to.trim();
+    let (refname, reason) = match to.split_once(" (") {
+        Some((refname, reason)) => (refname.trim(), reason.strip_suffix(')')),
+        None => (to, None),
+    };
+    if refname.is_empty() || refname.contains(' ') {
+        return None;
+    }
+    Some(Progress::RefUpdate {
+        refname: refname.to_owned(),
+        error: (flag == '!').then(|| reason.unwrap_or("rejected").to_owned()),
+    })
+}
+
+/// Parses sizes like `250 bytes` or `1.50 KiB`.
+fn parse_size(size: &str) -> Option<usize> {
+    let (amount, unit) = size.trim().split_once(' ')?;
+    let amount = amount.parse::<f64>().ok()?;
+    let factor = match unit {
+        "bytes" | "byte" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((amount * factor) as usize)
+}
@@ -1,5 +1,5 @@
 use super::executor::{AskpassServer, GitExecutor, Pid, Socket};
-use crate::RefSpec;
+use crate::{Progress, RefSpec};
 use futures::{select, FutureExt};
 use rand::Rng;
 use std::{collections::HashMap, path::Path, time::Duration};
@@ -57,13 +57,14 @@ pub type Error<E> = RepositoryError<
 >;
 
 #[cold]
-async fn execute_with_auth_harness<P, F, Fut, E, Extra>(
+async fn execute_with_auth_harness<P, F, Fut, E, Extra, S>(
     repo_path: P,
     executor: E,
     args: &[&str],
     envs: Option<HashMap<String, String>>,
     mut on_prompt: F,
     extra: Extra,
+    on_stderr: S,
 ) -> Result<(usize, String, String), Error<E>>
 where
     P: AsRef<Path>,
@@ -71,6 +72,7 @@ where
     F: FnMut(String, Extra) -> Fut,
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
+    S: FnMut(&str),
 {
     let path = std::env::current_exe().map_err(Error::<E>::NoSelfExe)?;
 
@@ -192,7 +194,7 @@ where
     let mut child_process = core::pin::pin! {
         async {
             executor
-                .execute(args, repo_path, Some(envs))
+                .execute_with_stderr(args, repo_path, Some(envs), on_stderr)
                 .await
                 .map_err(Error::<E>::Exec)
         }.fuse()
@@ -272,18 +274,29 @@ where
     }
 }
 
-/// Fetches the given refspec from the given remote in the repository
-/// at the given path. Any prompts for the user are passed to the asynchronous
+/// Fetches the given refspecs from the given remote in the repository
+/// at the given path. With `prune`, remote-tracking refs that no longer
+/// exist on the remote are removed, and with `tags`, all tags are fetched
+/// rather than only those pointing into the fetched history.
+/// The `config` key-value pairs are applied on top of git's own
+/// configuration for this invocation only, and `on_progress` is called
+/// as git reports progress.
+/// Any prompts for the user are passed to the asynchronous
 /// callback `on_prompt` which should return the user's response or `None` if the
 /// operation should be aborted, in which case an `Err` value is returned from this
 /// function.
-pub async fn fetch<P, F, Fut, E, Extra>(
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch<P, F, Fut, E, Extra, G>(
     repo_path: P,
     executor: E,
     remote: &str,
-    refspec: RefSpec,
+    refspecs: &[RefSpec],
+    prune: bool,
+    tags: bool,
+    config: &[(&str, &str)],
     on_prompt: F,
     extra: Extra,
+    mut on_progress: G,
 ) -> Result<(), crate::Error<Error<E>>>
 where
     P: AsRef<Path>,
@@ -291,16 +304,29 @@ where
     F: FnMut(String, Extra) -> Fut,
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
+    G: FnMut(Progress),
 {
-    let mut args = vec!["fetch", "--quiet", "--prune"];
+    let mut args = vec!["fetch", "--progress"];
+    args.push(if prune { "--prune" } else { "--no-prune" });
+    if tags {
+        args.push("--tags");
+    }
 
-    let refspec = refspec.to_string();
+    let refspecs = refspecs.iter().map(ToString::to_string).collect::<Vec<_>>();
 
     args.push(remote);
-    args.push(&refspec);
-
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(repo_path, executor, &args, None, on_prompt, extra).await?;
+    args.extend(refspecs.iter().map(String::as_str));
+
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        executor,
+        &args,
+        config_envs(config),
+        on_prompt,
+        extra,
+        |line| Progress::parse(line).into_iter().for_each(&mut on_progress),
+    )
+    .await?;
 
     if status == 0 {
         Ok(())
@@ -319,6 +345,13 @@ where
                 stdout,
                 stderr,
             }))?
+        } else if is_unreachable(&stderr) {
+            Err(crate::Error::Unreachable(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            }))?
         } else {
             Err(Error::<E>::Failed {
                 status,
@@ -330,18 +363,41 @@ where
     }
 }
 
-/// Pushes a refspec to the given remote in the repository at the given path.
+/// How a push may update a remote ref when the update is not a fast-forward.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Force {
+    /// Only fast-forward updates are allowed, unless the refspec says otherwise.
+    #[default]
+    No,
+    /// The remote ref is overwritten regardless of its current value.
+    Always,
+    /// The remote ref is only overwritten if it still points at `expected`,
+    /// or doesn't exist yet if `expected` is `None`.
+    WithLease {
+        /// The full name of the ref on the remote, e.g. `refs/heads/main`.
+        refname: String,
+        /// The object name the remote ref is expected to point at.
+        expected: Option<String>,
+    },
+}
+
+/// Pushes refspecs to the given remote in the repository at the given path.
+/// The `config` key-value pairs are applied on top of git's own configuration
+/// for this invocation only, and `on_progress` is called as git reports progress.
 /// Any prompts for the user are passed to the asynchronous callback `on_prompt`,
 /// which should return the user's response or `None` if the operation should be
 /// aborted, in which case an `Err` value is returned from this function.
-pub async fn push<P, F, Fut, E, Extra>(
+#[allow(clippy::too_many_arguments)]
+pub async fn push<P, F, Fut, E, Extra, G>(
     repo_path: P,
     executor: E,
     remote: &str,
-    refspec: RefSpec,
-    force: bool,
+    refspecs: &[RefSpec],
+    force: Force,
+    config: &[(&str, &str)],
     on_prompt: F,
     extra: Extra,
+    mut on_progress: G,
 ) -> Result<(), crate::Error<Error<E>>>
 where
     P: AsRef<Path>,
@@ -349,20 +405,38 @@ where
     F: FnMut(String, Extra) -> Fut,
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
+    G: FnMut(Progress),
 {
-    let mut args = vec!["push", "--quiet"];
+    let mut args = vec!["push", "--progress"];
 
-    let refspec = refspec.to_string();
+    let refspecs = refspecs.iter().map(ToString::to_string).collect::<Vec<_>>();
 
     args.push(remote);
-    args.push(&refspec);
-
-    if force {
-        args.push("--force");
+    args.extend(refspecs.iter().map(String::as_str));
+
+    let lease;
+    match &force {
+        Force::No => {}
+        Force::Always => args.push("--force"),
+        Force::WithLease { refname, expected } => {
+            lease = format!(
+                "--force-with-lease={refname}:{}",
+                expected.as_deref().unwrap_or_default()
+            );
+            args.push(&lease);
+        }
     }
 
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(repo_path, executor, &args, None, on_prompt, extra).await?;
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        executor,
+        &args,
+        config_envs(config),
+        on_prompt,
+        extra,
+        |line| Progress::parse(line).into_iter().for_each(&mut on_progress),
+    )
+    .await?;
 
     if status == 0 {
         Ok(())
@@ -374,6 +448,11 @@ where
             .map(|line| line.split_whitespace().last().unwrap_or_default())
         {
             Err(crate::Error::RefNotFound(refname.to_owned()))?
+        } else if let (Force::WithLease { refname, .. }, true) =
+            (&force, stderr.contains("(stale info)"))
+        {
+            // the remote ref moved since the lease was taken
+            Err(crate::Error::StaleRef(refname.clone()))?
         } else if stderr.to_lowercase().contains("permission denied") {
             Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
                 status,
@@ -381,6 +460,13 @@ where
                 stdout,
                 stderr,
             }))?
+        } else if is_unreachable(&stderr) {
+            Err(crate::Error::Unreachable(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            }))?
         } else {
             Err(Error::<E>::Failed {
                 status,
@@ -392,6 +478,38 @@ where
     }
 }
 
+/// Returns `true` if git's `stderr` says it couldn't connect to the remote.
+fn is_unreachable(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        // curl, for http(s) remotes
+        "could not resolve host",
+        "failed to connect to",
+        "couldn't connect to server",
+        // ssh
+        "connection refused",
+        "connection timed out",
+        "operation timed out",
+        "network is unreachable",
+    ]
+    .iter()
+    .any(|message| stderr.contains(message))
+}
+
+/// Returns the environment variables that make git use `config` on top of its own configuration.
+fn config_envs(config: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+    if config.is_empty() {
+        return None;
+    }
+    let mut envs = HashMap::new();
+    envs.insert("GIT_CONFIG_COUNT".into(), config.len().to_string());
+    for (idx, (key, value)) in config.iter().enumerate() {
+        envs.insert(format!("GIT_CONFIG_KEY_{idx}"), (*key).to_owned());
+        envs.insert(format!("GIT_CONFIG_VALUE_{idx}"), (*value).to_owned());
+    }
+    Some(envs)
+}
+
 async fn get_core_sshcommand<E: GitExecutor, P: AsRef<Path>>(
     executor: &E,
     cwd: P,
@@ -1 +1,2 @@
+mod progress;
 mod refspec;
@@ -0,0 +1,74 @@
+use gitbutler_git::{Progress, Stage};
+
+#[test]
+fn parse_objects_with_total() {
+    assert_eq!(
+        Progress::parse("Receiving objects:  33% (1/3)"),
+        vec![Progress::Objects {
+            stage: Stage::Receiving,
+            current: 1,
+            total: Some(3),
+        }]
+    );
+}
+
+#[test]
+fn parse_remote_objects_without_total() {
+    assert_eq!(
+        Progress::parse("remote: Enumerating objects: 5, done."),
+        vec![Progress::Objects {
+            stage: Stage::Counting,
+            current: 5,
+            total: None,
+        }]
+    );
+}
+
+#[test]
+fn parse_objects_with_bytes() {
+    assert_eq!(
+        Progress::parse("Writing objects: 100% (3/3), 1.50 KiB | 1.50 MiB/s, done."),
+        vec![
+            Progress::Objects {
+                stage: Stage::Writing,
+                current: 3,
+                total: Some(3),
+            },
+            Progress::Bytes { transferred: 1536 },
+        ]
+    );
+}
+
+#[test]
+fn parse_ref_updates() {
+    assert_eq!(
+        Progress::parse(" * [new branch]      main       -> origin/main"),
+        vec![Progress::RefUpdate {
+            refname: "origin/main".to_owned(),
+            error: None,
+        }]
+    );
+    assert_eq!(
+        Progress::parse(" + 1a2b3c4...5d6e7f8 main       -> origin/main  (forced update)"),
+        vec![Progress::RefUpdate {
+            refname: "origin/main".to_owned(),
+            error: None,
+        }]
+    );
+    assert_eq!(
+        Progress::parse(" ! [rejected]        main -> main (fetch first)"),
+        vec![Progress::RefUpdate {
+            refname: "main".to_owned(),
+            error: Some("fetch first".to_owned()),
+        }]
+    );
+}
+
+#[test]
+fn parse_other_output() {
+    assert!(Progress::parse("To /tmp/remote").is_empty());
+    assert!(
+        Progress::parse("remote: Total 3 (delta 0), reused 0 (delta 0), pack-reused 0").is_empty()
+    );
+    assert!(Progress::parse("").is_empty());
+}
@@ -108,7 +108,7 @@ impl App {
     ) -> Result<(), CoreError> {
         let project = self.projects.get(project_id)?;
         let project_repository = project_repository::Repository::open(&project)?;
-        Ok(project_repository.fetch(remote_name, credentials, askpass)?)
+        Ok(project_repository.fetch(remote_name, credentials, askpass, None)?)
     }
 
     pub fn git_index_size(&self, project_id: &ProjectId) -> Result<usize, CoreError> {
@@ -142,7 +142,12 @@ fn main() {
                         users_controller.clone(),
                         keys_controller.clone(),
                         git_credentials_controller.clone(),
-                    ));
+                    ).with_progress(std::sync::Arc::new({
+                        let handle = app_handle.clone();
+                        move |event| {
+                            handle.emit_all("git_progress", event).expect("tauri event emission doesn't fail in practice")
+                        }
+                    })));
 
                     let sessions_database_controller = gitbutler_core::sessions::database::Database::new(database_controller.clone());
                     app_handle.manage(sessions_database_controller.clone());
@@ -223,14 +228,21 @@ fn main() {
                     virtual_branches::commands::undo_commit,
                     virtual_branches::commands::insert_blank_commit,
                     virtual_branches::commands::reorder_commit,
+                    virtual_branches::commands::continue_rebase,
+                    virtual_branches::commands::abort_rebase,
                     virtual_branches::commands::update_commit_message,
                     virtual_branches::commands::list_remote_branches,
                     virtual_branches::commands::get_remote_branch_data,
                     virtual_branches::commands::squash_branch_commit,
                     virtual_branches::commands::fetch_from_target,
                     virtual_branches::commands::move_commit,
+                    virtual_branches::commands::create_pull_request,
+                    virtual_branches::commands::update_pull_request,
+                    virtual_branches::commands::refresh_pull_requests,
                     snapshots::list_snapshots,
                     snapshots::restore_snapshot,
+                    snapshots::undo,
+                    snapshots::redo,
                     menu::menu_item_set_enabled,
                     keys::commands::get_public_key,
                     github::commands::init_device_oauth,
@@ -29,11 +29,39 @@ pub async fn restore_snapshot(
     handle: tauri::AppHandle,
     project_id: ProjectId,
     sha: String,
-) -> Result<(), Error> {
+) -> Result<Option<String>, Error> {
     let project = handle
         .state::<projects::Controller>()
         .get(&project_id)
         .context("failed to get project")?;
-    project.restore_snapshot(sha)?;
-    Ok(())
+    let snapshot = project.restore_snapshot(sha)?;
+    Ok(snapshot)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn undo(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+) -> Result<Option<String>, Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    let snapshot = project.undo()?;
+    Ok(snapshot)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn redo(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+) -> Result<Option<String>, Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    let snapshot = project.redo()?;
+    Ok(snapshot)
 }
@@ -1,16 +1,20 @@
 pub mod commands {
+    use std::collections::HashMap;
+
     use crate::error::Error;
     use anyhow::Context;
     use gitbutler_core::{
         askpass::AskpassBroker,
         assets,
         error::Code,
-        git, projects,
+        forge, git, projects,
         projects::ProjectId,
         virtual_branches::{
             branch::{self, BranchId, BranchOwnershipClaims},
             controller::Controller,
-            BaseBranch, RemoteBranch, RemoteBranchData, RemoteBranchFile, VirtualBranches,
+            rebase::RebaseOutcome,
+            BaseBranch, RemoteBranch, RemoteBranchData, RemoteBranchFile, RemoteBranchFilter,
+            VirtualBranches,
         },
     };
     use tauri::{AppHandle, Manager};
@@ -362,13 +366,13 @@ pub mod commands {
         branch_id: BranchId,
         commit_oid: git::Oid,
         ownership: BranchOwnershipClaims,
-    ) -> Result<git::Oid, Error> {
-        let oid = handle
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
             .state::<Controller>()
             .amend(&project_id, &branch_id, commit_oid, &ownership)
             .await?;
         emit_vbranches(&handle, &project_id).await;
-        Ok(oid)
+        Ok(outcome)
     }
 
     #[tauri::command(async)]
@@ -402,13 +406,13 @@ pub mod commands {
         project_id: ProjectId,
         branch_id: BranchId,
         commit_oid: git::Oid,
-    ) -> Result<(), Error> {
-        handle
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
             .state::<Controller>()
             .undo_commit(&project_id, &branch_id, commit_oid)
             .await?;
         emit_vbranches(&handle, &project_id).await;
-        Ok(())
+        Ok(outcome)
     }
 
     #[tauri::command(async)]
@@ -419,13 +423,13 @@ pub mod commands {
         branch_id: BranchId,
         commit_oid: git::Oid,
         offset: i32,
-    ) -> Result<(), Error> {
-        handle
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
             .state::<Controller>()
             .insert_blank_commit(&project_id, &branch_id, commit_oid, offset)
             .await?;
         emit_vbranches(&handle, &project_id).await;
-        Ok(())
+        Ok(outcome)
     }
 
     #[tauri::command(async)]
@@ -436,10 +440,40 @@ pub mod commands {
         branch_id: BranchId,
         commit_oid: git::Oid,
         offset: i32,
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
+            .state::<Controller>()
+            .reorder_commit(&project_id, &branch_id, commit_oid, offset)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(outcome)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn continue_rebase(
+        handle: AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
+            .state::<Controller>()
+            .continue_rebase(&project_id, &branch_id)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(outcome)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn abort_rebase(
+        handle: AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
     ) -> Result<(), Error> {
         handle
             .state::<Controller>()
-            .reorder_commit(&project_id, &branch_id, commit_oid, offset)
+            .abort_rebase(&project_id, &branch_id)
             .await?;
         emit_vbranches(&handle, &project_id).await;
         Ok(())
@@ -450,10 +484,11 @@ pub mod commands {
     pub async fn list_remote_branches(
         handle: tauri::AppHandle,
         project_id: ProjectId,
+        filter: Option<RemoteBranchFilter>,
     ) -> Result<Vec<RemoteBranch>, Error> {
         let branches = handle
             .state::<Controller>()
-            .list_remote_branches(&project_id)
+            .list_remote_branches(&project_id, &filter.unwrap_or_default())
             .await?;
         Ok(branches)
     }
@@ -483,13 +518,13 @@ pub mod commands {
         project_id: ProjectId,
         branch_id: BranchId,
         target_commit_oid: git::Oid,
-    ) -> Result<(), Error> {
-        handle
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
             .state::<Controller>()
             .squash(&project_id, &branch_id, target_commit_oid)
             .await?;
         emit_vbranches(&handle, &project_id).await;
-        Ok(())
+        Ok(outcome)
     }
 
     #[tauri::command(async)]
@@ -538,13 +573,62 @@ pub mod commands {
         branch_id: BranchId,
         commit_oid: git::Oid,
         message: &str,
-    ) -> Result<(), Error> {
-        handle
+    ) -> Result<RebaseOutcome, Error> {
+        let outcome = handle
             .state::<Controller>()
             .update_commit_message(&project_id, &branch_id, commit_oid, message)
             .await?;
         emit_vbranches(&handle, &project_id).await;
-        Ok(())
+        Ok(outcome)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn create_pull_request(
+        handle: tauri::AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+        pull_request: forge::NewPullRequest,
+    ) -> Result<forge::PullRequest, Error> {
+        let pull_request = handle
+            .state::<Controller>()
+            .create_pull_request(&project_id, &branch_id, &pull_request)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(pull_request)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn update_pull_request(
+        handle: tauri::AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+        update: forge::PullRequestUpdate,
+    ) -> Result<forge::PullRequest, Error> {
+        let pull_request = handle
+            .state::<Controller>()
+            .update_pull_request(&project_id, &branch_id, &update)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(pull_request)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn refresh_pull_requests(
+        handle: tauri::AppHandle,
+        project_id: ProjectId,
+    ) -> Result<HashMap<BranchId, String>, Error> {
+        let errors = handle
+            .state::<Controller>()
+            .refresh_pull_requests(&project_id)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(errors
+            .into_iter()
+            .map(|(branch_id, error)| (branch_id, format!("{error:#}")))
+            .collect())
     }
 
     async fn emit_vbranches(handle: &AppHandle, project_id: &projects::ProjectId) {
@@ -52,6 +52,180 @@ pub mod virtual_branches {
     }
 }
 
+pub mod credentials {
+    use std::sync::{Arc, Mutex};
+
+    use gitbutler_core::{
+        git::{
+            self,
+            credentials::{Credential, CredentialProvider, Flow, HelpError},
+        },
+        project_repository,
+    };
+
+    /// A credential provider that offers fixed credentials for every remote, and remembers the urls
+    /// it was asked about.
+    #[derive(Clone, Default)]
+    pub struct FakeCredentialProvider {
+        credentials: Vec<Credential>,
+        requested_urls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl FakeCredentialProvider {
+        /// A provider that offers `credentials`, or nothing at all if they are empty.
+        pub fn new(credentials: Vec<Credential>) -> Self {
+            Self {
+                credentials,
+                ..Default::default()
+            }
+        }
+
+        pub fn requested_urls(&self) -> Vec<String> {
+            self.requested_urls.lock().unwrap().clone()
+        }
+    }
+
+    impl CredentialProvider for FakeCredentialProvider {
+        fn flow(
+            &self,
+            _project_repository: &project_repository::Repository,
+            remote_url: &git::Url,
+        ) -> Result<Flow, HelpError> {
+            self.requested_urls
+                .lock()
+                .unwrap()
+                .push(remote_url.to_string());
+            if self.credentials.is_empty() {
+                return Ok(vec![]);
+            }
+            Ok(vec![(remote_url.clone(), self.credentials.clone())])
+        }
+    }
+}
+
+pub mod forge {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use gitbutler_core::{
+        forge::{
+            ChecksStatus, Error, Forge, ForgeProvider, Head, NewPullRequest, PullRequest,
+            PullRequestState, PullRequestUpdate, ReviewState,
+        },
+        git, users,
+    };
+
+    /// A forge that keeps its pull requests in memory, by the branch they merge, and fails for
+    /// the branches it was told to.
+    #[derive(Clone, Default)]
+    pub struct FakeForge {
+        pull_requests: Arc<Mutex<HashMap<String, PullRequest>>>,
+        failing_branches: Arc<Mutex<HashSet<String>>>,
+    }
+
+    impl FakeForge {
+        /// A provider that finds this forge for every remote.
+        pub fn provider(&self) -> ForgeProvider {
+            let forge = self.clone();
+            Arc::new(
+                move |_url: &git::Url,
+                      _kind: Option<git::ForgeKind>,
+                      _user: Option<&users::User>|
+                      -> Result<Option<Box<dyn Forge>>, Error> {
+                    Ok(Some(Box::new(forge.clone())))
+                },
+            )
+        }
+
+        /// Make all requests about `branch` fail, like when the forge can't be reached.
+        pub fn fail_for(&self, branch: &str) {
+            self.failing_branches
+                .lock()
+                .unwrap()
+                .insert(branch.to_owned());
+        }
+
+        /// Change the pull request of `branch` behind our back, like somebody on the forge would.
+        pub fn set_pull_request(&self, branch: &str, pull_request: Option<PullRequest>) {
+            let mut pull_requests = self.pull_requests.lock().unwrap();
+            match pull_request {
+                Some(pull_request) => pull_requests.insert(branch.to_owned(), pull_request),
+                None => pull_requests.remove(branch),
+            };
+        }
+
+        fn check(&self, branch: &str) -> Result<(), Error> {
+            if self.failing_branches.lock().unwrap().contains(branch) {
+                return Err(Error::Api {
+                    status: 503,
+                    message: "Service Unavailable".to_owned(),
+                });
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Forge for FakeForge {
+        async fn create_pull_request(
+            &self,
+            head: &Head,
+            _base: &str,
+            pull_request: &NewPullRequest,
+        ) -> Result<PullRequest, Error> {
+            self.check(&head.branch)?;
+            let mut pull_requests = self.pull_requests.lock().unwrap();
+            let number = pull_requests.len() as u64 + 1;
+            let created = PullRequest {
+                number,
+                title: pull_request.title.clone(),
+                url: format!("https://forge.test/pulls/{number}"),
+                state: PullRequestState::Open,
+                draft: pull_request.draft,
+                review: ReviewState::Pending,
+                checks: ChecksStatus::None,
+            };
+            pull_requests.insert(head.branch.clone(), created.clone());
+            Ok(created)
+        }
+
+        async fn update_pull_request(
+            &self,
+            number: u64,
+            update: &PullRequestUpdate,
+        ) -> Result<PullRequest, Error> {
+            let mut pull_requests = self.pull_requests.lock().unwrap();
+            let Some((branch, pull_request)) = pull_requests
+                .iter_mut()
+                .find(|(_, pull_request)| pull_request.number == number)
+            else {
+                return Err(Error::Api {
+                    status: 404,
+                    message: "Not Found".to_owned(),
+                });
+            };
+            self.check(branch)?;
+            if let Some(title) = &update.title {
+                pull_request.title.clone_from(title);
+            }
+            Ok(pull_request.clone())
+        }
+
+        async fn pull_request(&self, head: &Head) -> Result<Option<PullRequest>, Error> {
+            self.check(&head.branch)?;
+            Ok(self
+                .pull_requests
+                .lock()
+                .unwrap()
+                .get(&head.branch)
+                .cloned())
+        }
+    }
+}
+
 pub fn init_opts() -> git2::RepositoryInitOptions {
     let mut opts = git2::RepositoryInitOptions::new();
     opts.initial_head("master");
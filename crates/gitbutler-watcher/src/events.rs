@@ -12,6 +12,7 @@ pub(super) enum InternalEvent {
     CalculateVirtualBranches(ProjectId),
     FetchGitbutlerData(ProjectId),
     PushGitbutlerData(ProjectId),
+    FetchProjectData(ProjectId),
 
     // From file monitor
     GitFilesChange(ProjectId, Vec<PathBuf>),
@@ -28,6 +29,7 @@ pub enum Action {
     CalculateVirtualBranches(ProjectId),
     FetchGitbutlerData(ProjectId),
     PushGitbutlerData(ProjectId),
+    FetchProjectData(ProjectId),
 }
 
 impl Action {
@@ -37,7 +39,8 @@ impl Action {
             Action::FetchGitbutlerData(project_id)
             | Action::Flush(project_id, _)
             | Action::CalculateVirtualBranches(project_id)
-            | Action::PushGitbutlerData(project_id) => *project_id,
+            | Action::PushGitbutlerData(project_id)
+            | Action::FetchProjectData(project_id) => *project_id,
         }
     }
 }
@@ -49,6 +52,7 @@ impl From<Action> for InternalEvent {
             Action::CalculateVirtualBranches(v) => InternalEvent::CalculateVirtualBranches(v),
             Action::FetchGitbutlerData(v) => InternalEvent::FetchGitbutlerData(v),
             Action::PushGitbutlerData(v) => InternalEvent::PushGitbutlerData(v),
+            Action::FetchProjectData(v) => InternalEvent::FetchProjectData(v),
         }
     }
 }
@@ -80,6 +84,7 @@ impl Display for InternalEvent {
             }
             InternalEvent::CalculateVirtualBranches(pid) => write!(f, "VirtualBranch({})", pid),
             InternalEvent::PushGitbutlerData(pid) => write!(f, "PushGitbutlerData({})", pid),
+            InternalEvent::FetchProjectData(pid) => write!(f, "FetchProjectData({})", pid),
         }
     }
 }
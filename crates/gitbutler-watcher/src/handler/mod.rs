@@ -94,6 +94,11 @@ impl Handler {
                 .await
                 .context("failed to fetch gitbutler data"),
 
+            events::InternalEvent::FetchProjectData(project_id) => self
+                .fetch_project_data(project_id)
+                .await
+                .context("failed to fetch project data"),
+
             events::InternalEvent::Flush(project_id, session) => self
                 .flush_session(project_id, &session)
                 .await
@@ -274,6 +279,67 @@ impl Handler {
         Ok(())
     }
 
+    /// How long to wait between background fetches of the project's remotes, or `None` if it is turned off.
+    pub(super) fn auto_fetch_interval(&self, project_id: ProjectId) -> Option<time::Duration> {
+        let project = self.projects.get(&project_id).ok()?;
+        project
+            .fetch_settings
+            .auto_fetch_interval_secs
+            .map(time::Duration::from_secs)
+    }
+
+    /// Fetches the remotes of the project like the user would, retrying with increasing delays while the network is down.
+    pub async fn fetch_project_data(&self, project_id: ProjectId) -> Result<()> {
+        let mut policy = backoff::ExponentialBackoffBuilder::new()
+            .with_max_elapsed_time(Some(time::Duration::from_secs(10 * 60)))
+            .build();
+        loop {
+            {
+                let project = self
+                    .projects
+                    .get(&project_id)
+                    .context("failed to get project")?;
+                let project_repository = project_repository::Repository::open(&project)
+                    .context("failed to open repository")?;
+                // the branch updates a fetch leads to would get in the way while conflicts are resolved
+                if project_repository.is_resolving() {
+                    tracing::debug!(%project_id, "not fetching while resolving conflicts");
+                    return Ok(());
+                }
+                if project
+                    .virtual_branches()
+                    .try_get_default_target()?
+                    .is_none()
+                {
+                    return Ok(());
+                }
+            }
+
+            // fetch failures are recorded rather than returned, so that each remote has its own result
+            let errors = self
+                .vbranch_controller
+                .fetch_remotes(&project_id, None)
+                .await?;
+            let network_failed = errors
+                .values()
+                .any(|error| matches!(error, project_repository::RemoteError::Network));
+            if !network_failed {
+                return Ok(());
+            }
+
+            match backoff::backoff::Backoff::next_backoff(&mut policy) {
+                Some(delay) => {
+                    tracing::warn!(%project_id, ?delay, will_retry = true, "failed to fetch project data");
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    tracing::error!(%project_id, will_retry = false, "failed to fetch project data");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
     #[instrument(skip(self, paths, project_id), fields(paths = paths.len()))]
     async fn recalculate_everything(
         &self,
@@ -303,6 +369,16 @@ impl Handler {
         let changed_lines = project.lines_since_snapshot()?;
         if changed_lines > project.snapshot_lines_threshold {
             project.create_snapshot(SnapshotDetails::new(OperationType::FileChanges))?;
+            // Keep file change snapshots from piling up, which is no reason to fail the snapshot
+            let compaction = project.should_compact_snapshots().and_then(|due| {
+                if due {
+                    project.compact_snapshots()?;
+                }
+                Ok(())
+            });
+            if let Err(err) = compaction {
+                tracing::warn!(%project_id, ?err, "failed to compact snapshots");
+            }
         }
         Ok(())
     }
@@ -168,13 +168,11 @@ fn push_all_refs(
         .collect();
 
     let all_refs: Vec<_> = all_refs.iter().map(String::as_str).collect();
-    let anything_pushed = project_repository.push_to_gitbutler_server(user, &all_refs)?;
-    if anything_pushed {
-        tracing::info!(
-            %project_id,
-            "refs pushed",
-        );
-    }
+    project_repository.push_to_gitbutler_server(user, &all_refs)?;
+    tracing::info!(
+        %project_id,
+        "refs pushed",
+    );
     Ok(())
 }
 
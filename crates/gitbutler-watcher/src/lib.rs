@@ -79,6 +79,7 @@ pub fn watch_in_background(
     handler.reindex(project_id)?;
 
     let cancellation_token = CancellationToken::new();
+    spawn_auto_fetch(handler.clone(), project_id, cancellation_token.clone());
     let handle = WatcherHandle {
         tx: events_out,
         project_id,
@@ -112,3 +113,48 @@ pub fn watch_in_background(
 
     Ok(handle)
 }
+
+/// How long to wait before looking at the auto-fetch settings again while auto-fetch is turned off.
+const AUTO_FETCH_RECHECK_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+/// Periodically fetch the project's remotes, at the interval configured for the project,
+/// until `cancellation_token` is cancelled.
+///
+/// The next interval starts once a fetch is done, so a fetch that keeps retrying while the network is down
+/// never runs alongside another one.
+fn spawn_auto_fetch(
+    handler: handler::Handler,
+    project_id: ProjectId,
+    cancellation_token: CancellationToken,
+) {
+    tokio::spawn(async move {
+        loop {
+            let interval = handler.auto_fetch_interval(project_id);
+            tokio::select! {
+                () = tokio::time::sleep(interval.unwrap_or(AUTO_FETCH_RECHECK_INTERVAL)) => {
+                    if interval.is_none() {
+                        continue;
+                    }
+                    // NOTE: like events, the fetch runs on a blocking thread as its future isn't `Send`.
+                    let fetch = task::spawn_blocking({
+                        let handler = handler.clone();
+                        move || futures::executor::block_on(handler.fetch_project_data(project_id))
+                    });
+                    tokio::select! {
+                        result = fetch => {
+                            if let Ok(Err(error)) = result {
+                                tracing::warn!(%project_id, ?error, "failed to fetch project data");
+                            }
+                        }
+                        () = cancellation_token.cancelled() => {
+                            break;
+                        }
+                    }
+                }
+                () = cancellation_token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    });
+}
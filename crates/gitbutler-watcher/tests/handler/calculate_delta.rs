@@ -97,6 +97,8 @@ fn new_test_branch() -> branch::Branch {
         ownership: branch::BranchOwnershipClaims::default(),
         order: TEST_INDEX.load(Ordering::Relaxed),
         selected_for_changes: None,
+        parent: None,
+        pull_request: None,
     }
 }
 
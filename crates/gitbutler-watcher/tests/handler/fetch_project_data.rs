@@ -0,0 +1,100 @@
+use std::{fs, time::Duration};
+
+use gitbutler_core::projects;
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case};
+
+use crate::handler::support::Fixture;
+
+#[tokio::test]
+async fn records_fetch_result() -> anyhow::Result<()> {
+    let mut fixture = Fixture::default();
+    let handler = fixture.new_handler();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &fixture.new_case();
+    set_test_target(project_repository)?;
+
+    handler.fetch_project_data(project.id).await?;
+
+    let project = fixture.projects.get(&project.id)?;
+    assert!(project.project_data_last_fetch.is_some());
+    assert!(project.remotes_last_fetch.contains_key("origin"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn retried_while_the_remote_is_unreachable() -> anyhow::Result<()> {
+    let mut fixture = Fixture::default();
+    let handler = fixture.new_handler();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &fixture.new_case();
+    set_test_target(project_repository)?;
+    // nothing listens on port 1
+    git2::Repository::open(&project.path)?
+        .remote_set_url("origin", "http://127.0.0.1:1/repo.git")?;
+    fixture
+        .projects
+        .update(&projects::UpdateRequest {
+            id: project.id,
+            remote_backend: Some(projects::RemoteBackendKind::GitCli),
+            ..Default::default()
+        })
+        .await?;
+
+    let fetch = tokio::time::timeout(
+        Duration::from_secs(2),
+        handler.fetch_project_data(project.id),
+    )
+    .await;
+    assert!(fetch.is_err(), "still retrying");
+
+    let project = fixture.projects.get(&project.id)?;
+    assert!(matches!(
+        project.project_data_last_fetch,
+        Some(projects::FetchResult::Error { .. })
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn skipped_without_target() -> anyhow::Result<()> {
+    let mut fixture = Fixture::default();
+    let handler = fixture.new_handler();
+    let Case { project, .. } = &fixture.new_case();
+
+    handler.fetch_project_data(project.id).await?;
+
+    let project = fixture.projects.get(&project.id)?;
+    assert!(project.project_data_last_fetch.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn skipped_while_resolving_conflicts() -> anyhow::Result<()> {
+    let mut fixture = Fixture::default();
+    let handler = fixture.new_handler();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &fixture.new_case();
+    set_test_target(project_repository)?;
+    fs::write(
+        project_repository
+            .git_repository
+            .path()
+            .join("base_merge_parent"),
+        "",
+    )?;
+
+    handler.fetch_project_data(project.id).await?;
+
+    let project = fixture.projects.get(&project.id)?;
+    assert!(project.project_data_last_fetch.is_none());
+    Ok(())
+}
@@ -97,5 +97,6 @@ fn test_remote_repository() -> anyhow::Result<(git2::Repository, TempDir)> {
 
 mod calculate_delta;
 mod fetch_gitbutler_data;
+mod fetch_project_data;
 mod git_file_change;
 mod push_project_to_gitbutler;